@@ -0,0 +1,861 @@
+//! Minimal D-Bus client: SASL `EXTERNAL` over a Unix socket, the
+//! little-endian wire format, and file descriptor passing. Enough to talk
+//! to UDisks2 without libdbus.
+
+use std::{
+    collections::VecDeque,
+    io::{self, Read, Write},
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+        unix::net::UnixStream,
+    },
+    time::Duration,
+};
+
+pub const METHOD_CALL: u8 = 1;
+pub const METHOD_RETURN: u8 = 2;
+pub const ERROR: u8 = 3;
+
+/// How long a call may wait, polkit dialogs included
+const CALL_TIMEOUT: Duration = Duration::from_secs(300);
+/// File descriptors accepted per read
+const MAX_FDS: usize = 16;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Byte(u8),
+    Bool(bool),
+    I16(i16),
+    U16(u16),
+    I32(i32),
+    U32(u32),
+    I64(i64),
+    U64(u64),
+    Double(f64),
+    Str(String),
+    Path(String),
+    Signature(String),
+    /// Index into the message's file descriptors
+    Fd(u32),
+    /// Element signature and elements
+    Array(String, Vec<Value>),
+    Struct(Vec<Value>),
+    DictEntry(Box<Value>, Box<Value>),
+    Variant(Box<Value>),
+}
+
+impl Value {
+    pub fn signature(&self) -> String {
+        match self {
+            Value::Byte(_) => "y".to_string(),
+            Value::Bool(_) => "b".to_string(),
+            Value::I16(_) => "n".to_string(),
+            Value::U16(_) => "q".to_string(),
+            Value::I32(_) => "i".to_string(),
+            Value::U32(_) => "u".to_string(),
+            Value::I64(_) => "x".to_string(),
+            Value::U64(_) => "t".to_string(),
+            Value::Double(_) => "d".to_string(),
+            Value::Str(_) => "s".to_string(),
+            Value::Path(_) => "o".to_string(),
+            Value::Signature(_) => "g".to_string(),
+            Value::Fd(_) => "h".to_string(),
+            Value::Array(elem, _) => format!("a{}", elem),
+            Value::Struct(fields) => format!(
+                "({})",
+                fields.iter().map(Value::signature).collect::<String>()
+            ),
+            Value::DictEntry(k, v) => format!("{{{}{}}}", k.signature(), v.signature()),
+            Value::Variant(_) => "v".to_string(),
+        }
+    }
+
+    /// The value inside a variant, or the value itself
+    pub fn inner(&self) -> &Value {
+        match self {
+            Value::Variant(v) => v.inner(),
+            v => v,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self.inner() {
+            Value::Str(s) | Value::Path(s) | Value::Signature(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self.inner() {
+            Value::Byte(n) => Some(n.into()),
+            Value::U16(n) => Some(n.into()),
+            Value::U32(n) => Some(n.into()),
+            Value::U64(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self.inner() {
+            Value::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self.inner() {
+            Value::Array(_, items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// An `ay` as bytes, with a trailing NUL (udisks paths) removed
+    pub fn as_bytes(&self) -> Option<Vec<u8>> {
+        let mut bytes = self
+            .as_array()?
+            .iter()
+            .map(|v| match v {
+                Value::Byte(b) => Some(*b),
+                _ => None,
+            })
+            .collect::<Option<Vec<u8>>>()?;
+        if bytes.last() == Some(&0) {
+            bytes.pop();
+        }
+        Some(bytes)
+    }
+
+    /// Look up a string or object path key in a dict, unwrapping variants
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.as_array()?.iter().find_map(|entry| match entry {
+            Value::DictEntry(k, v) if k.as_str() == Some(key) => Some(v.inner()),
+            _ => None,
+        })
+    }
+}
+
+/// An `a{sv}` from `(key, value)` pairs
+pub fn dict(entries: Vec<(&str, Value)>) -> Value {
+    Value::Array(
+        "{sv}".to_string(),
+        entries
+            .into_iter()
+            .map(|(k, v)| {
+                Value::DictEntry(
+                    Box::new(Value::Str(k.to_string())),
+                    Box::new(Value::Variant(Box::new(v))),
+                )
+            })
+            .collect(),
+    )
+}
+
+/// An `ay`, NUL-terminated the way udisks stores paths
+pub fn bytestring(s: &str) -> Value {
+    let mut bytes: Vec<Value> = s.bytes().map(Value::Byte).collect();
+    bytes.push(Value::Byte(0));
+    Value::Array("y".to_string(), bytes)
+}
+
+#[derive(Debug, Default)]
+pub struct Message {
+    pub kind: u8,
+    pub serial: u32,
+    pub reply_serial: Option<u32>,
+    pub path: Option<String>,
+    pub interface: Option<String>,
+    pub member: Option<String>,
+    pub error_name: Option<String>,
+    pub destination: Option<String>,
+    pub sender: Option<String>,
+    pub body: Vec<Value>,
+    pub fds: Vec<OwnedFd>,
+}
+
+impl Message {
+    pub fn call(
+        destination: &str,
+        path: &str,
+        interface: &str,
+        member: &str,
+        body: Vec<Value>,
+    ) -> Self {
+        Self {
+            kind: METHOD_CALL,
+            path: Some(path.to_string()),
+            interface: Some(interface.to_string()),
+            member: Some(member.to_string()),
+            destination: Some(destination.to_string()),
+            body,
+            ..Self::default()
+        }
+    }
+
+    pub fn reply(call: &Message, body: Vec<Value>) -> Self {
+        Self {
+            kind: METHOD_RETURN,
+            reply_serial: Some(call.serial),
+            destination: call.sender.clone(),
+            body,
+            ..Self::default()
+        }
+    }
+
+    pub fn error(call: &Message, name: &str, text: &str) -> Self {
+        Self {
+            kind: ERROR,
+            reply_serial: Some(call.serial),
+            error_name: Some(name.to_string()),
+            destination: call.sender.clone(),
+            body: vec![Value::Str(text.to_string())],
+            ..Self::default()
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        for value in &self.body {
+            put(&mut body, value);
+        }
+        let signature: String = self.body.iter().map(Value::signature).collect();
+        let field = |code: u8, value: Value| {
+            Value::Struct(vec![Value::Byte(code), Value::Variant(Box::new(value))])
+        };
+        let strings = [
+            (1, self.path.clone().map(Value::Path)),
+            (2, self.interface.clone().map(Value::Str)),
+            (3, self.member.clone().map(Value::Str)),
+            (4, self.error_name.clone().map(Value::Str)),
+            (6, self.destination.clone().map(Value::Str)),
+        ];
+        let mut fields: Vec<Value> = strings
+            .into_iter()
+            .filter_map(|(code, v)| v.map(|v| field(code, v)))
+            .collect();
+        if let Some(serial) = self.reply_serial {
+            fields.push(field(5, Value::U32(serial)));
+        }
+        if !signature.is_empty() {
+            fields.push(field(8, Value::Signature(signature)));
+        }
+        if !self.fds.is_empty() {
+            fields.push(field(9, Value::U32(self.fds.len() as u32)));
+        }
+        let mut out = vec![b'l', self.kind, 0, 1];
+        out.extend((body.len() as u32).to_le_bytes());
+        out.extend(self.serial.to_le_bytes());
+        put(&mut out, &Value::Array("(yv)".to_string(), fields));
+        pad(&mut out, 8);
+        out.extend(body);
+        out
+    }
+
+    fn decode(bytes: &[u8], fds: Vec<OwnedFd>) -> Result<Self, String> {
+        if bytes.first() != Some(&b'l') {
+            return Err("dbus: only little-endian messages are supported".to_string());
+        }
+        let mut reader = Reader { buf: bytes, pos: 1 };
+        let kind = reader.take(1)?[0];
+        reader.take(2)?;
+        let body_len = reader.u32()? as usize;
+        let serial = reader.u32()?;
+        let fields = reader.value("a(yv)")?;
+        reader.align(8);
+        let mut msg = Message {
+            kind,
+            serial,
+            fds,
+            ..Self::default()
+        };
+        let mut signature = String::new();
+        for field in fields.as_array().unwrap_or_default() {
+            let Value::Struct(parts) = field else {
+                continue;
+            };
+            let (Some(code), Some(value)) = (parts.first().and_then(Value::as_u64), parts.get(1))
+            else {
+                continue;
+            };
+            let text = value.as_str().map(str::to_string);
+            match code {
+                1 => msg.path = text,
+                2 => msg.interface = text,
+                3 => msg.member = text,
+                4 => msg.error_name = text,
+                5 => msg.reply_serial = value.as_u64().map(|n| n as u32),
+                6 => msg.destination = text,
+                7 => msg.sender = text,
+                8 => signature = text.unwrap_or_default(),
+                _ => {}
+            }
+        }
+        let body = bytes
+            .get(reader.pos..reader.pos + body_len)
+            .ok_or("dbus: truncated body")?;
+        let mut reader = Reader { buf: body, pos: 0 };
+        let mut rest = signature.as_str();
+        while !rest.is_empty() {
+            let len = type_len(rest)?;
+            msg.body.push(reader.value(&rest[..len])?);
+            rest = &rest[len..];
+        }
+        Ok(msg)
+    }
+}
+
+fn pad(buf: &mut Vec<u8>, align: usize) {
+    while !buf.len().is_multiple_of(align) {
+        buf.push(0);
+    }
+}
+
+fn alignment(signature: &str) -> usize {
+    match signature.as_bytes().first() {
+        Some(b'n' | b'q') => 2,
+        Some(b'b' | b'i' | b'u' | b'h' | b's' | b'o' | b'a') => 4,
+        Some(b'x' | b't' | b'd' | b'(' | b'{') => 8,
+        _ => 1,
+    }
+}
+
+/// Marshal `value`; offsets in `buf` count from an 8-aligned start
+fn put(buf: &mut Vec<u8>, value: &Value) {
+    pad(buf, alignment(&value.signature()));
+    match value {
+        Value::Byte(b) => buf.push(*b),
+        Value::Bool(b) => buf.extend(u32::from(*b).to_le_bytes()),
+        Value::I16(n) => buf.extend(n.to_le_bytes()),
+        Value::U16(n) => buf.extend(n.to_le_bytes()),
+        Value::I32(n) => buf.extend(n.to_le_bytes()),
+        Value::U32(n) | Value::Fd(n) => buf.extend(n.to_le_bytes()),
+        Value::I64(n) => buf.extend(n.to_le_bytes()),
+        Value::U64(n) => buf.extend(n.to_le_bytes()),
+        Value::Double(n) => buf.extend(n.to_le_bytes()),
+        Value::Str(s) | Value::Path(s) => {
+            buf.extend((s.len() as u32).to_le_bytes());
+            buf.extend(s.as_bytes());
+            buf.push(0);
+        }
+        Value::Signature(s) => {
+            buf.push(s.len() as u8);
+            buf.extend(s.as_bytes());
+            buf.push(0);
+        }
+        Value::Array(elem, items) => {
+            let len_at = buf.len();
+            buf.extend([0; 4]);
+            pad(buf, alignment(elem));
+            let start = buf.len();
+            for item in items {
+                put(buf, item);
+            }
+            let len = (buf.len() - start) as u32;
+            buf[len_at..len_at + 4].copy_from_slice(&len.to_le_bytes());
+        }
+        Value::Struct(fields) => fields.iter().for_each(|f| put(buf, f)),
+        Value::DictEntry(k, v) => {
+            put(buf, k);
+            put(buf, v);
+        }
+        Value::Variant(v) => {
+            put(buf, &Value::Signature(v.signature()));
+            put(buf, v);
+        }
+    }
+}
+
+/// Length of the first complete type in a signature
+fn type_len(signature: &str) -> Result<usize, String> {
+    let bytes = signature.as_bytes();
+    match bytes.first() {
+        Some(b'a') => Ok(1 + type_len(&signature[1..])?),
+        Some(&open @ (b'(' | b'{')) => {
+            let close = if open == b'(' { b')' } else { b'}' };
+            let mut depth = 0;
+            for (i, &c) in bytes.iter().enumerate() {
+                if c == open {
+                    depth += 1;
+                } else if c == close {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(i + 1);
+                    }
+                }
+            }
+            Err(format!("dbus: unbalanced signature `{}`", signature))
+        }
+        Some(_) => Ok(1),
+        None => Err("dbus: empty signature".to_string()),
+    }
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn align(&mut self, n: usize) {
+        self.pos = self.pos.div_ceil(n) * n;
+    }
+
+    fn take(&mut self, n: usize) -> Result<&[u8], String> {
+        let bytes = self
+            .buf
+            .get(self.pos..self.pos + n)
+            .ok_or("dbus: truncated message")?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        self.align(N);
+        Ok(self.take(N)?.try_into().expect("took N bytes"))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn string(&mut self, len: usize) -> Result<String, String> {
+        let s = String::from_utf8(self.take(len)?.to_vec()).map_err(|e| e.to_string())?;
+        self.take(1)?;
+        Ok(s)
+    }
+
+    fn value(&mut self, ty: &str) -> Result<Value, String> {
+        Ok(match ty.as_bytes().first().copied().unwrap_or(0) {
+            b'y' => Value::Byte(self.take(1)?[0]),
+            b'b' => Value::Bool(self.u32()? != 0),
+            b'n' => Value::I16(i16::from_le_bytes(self.array()?)),
+            b'q' => Value::U16(u16::from_le_bytes(self.array()?)),
+            b'i' => Value::I32(i32::from_le_bytes(self.array()?)),
+            b'u' => Value::U32(self.u32()?),
+            b'h' => Value::Fd(self.u32()?),
+            b'x' => Value::I64(i64::from_le_bytes(self.array()?)),
+            b't' => Value::U64(u64::from_le_bytes(self.array()?)),
+            b'd' => Value::Double(f64::from_le_bytes(self.array()?)),
+            b's' | b'o' => {
+                let len = self.u32()? as usize;
+                let s = self.string(len)?;
+                if ty.starts_with('o') {
+                    Value::Path(s)
+                } else {
+                    Value::Str(s)
+                }
+            }
+            b'g' => {
+                let len = self.take(1)?[0] as usize;
+                Value::Signature(self.string(len)?)
+            }
+            b'v' => {
+                let len = self.take(1)?[0] as usize;
+                let signature = self.string(len)?;
+                if type_len(&signature)? != signature.len() {
+                    return Err(format!("dbus: bad variant signature `{}`", signature));
+                }
+                Value::Variant(Box::new(self.value(&signature)?))
+            }
+            b'a' => {
+                let len = self.u32()? as usize;
+                let elem = &ty[1..];
+                self.align(alignment(elem));
+                let end = self.pos + len;
+                if end > self.buf.len() {
+                    return Err("dbus: truncated array".to_string());
+                }
+                let mut items = Vec::new();
+                while self.pos < end {
+                    items.push(self.value(elem)?);
+                }
+                Value::Array(elem.to_string(), items)
+            }
+            b'(' | b'{' => {
+                self.align(8);
+                let mut rest = &ty[1..ty.len() - 1];
+                let mut fields = Vec::new();
+                while !rest.is_empty() {
+                    let len = type_len(rest)?;
+                    fields.push(self.value(&rest[..len])?);
+                    rest = &rest[len..];
+                }
+                if ty.starts_with('{') {
+                    let mut kv = fields.into_iter();
+                    match (kv.next(), kv.next(), kv.next()) {
+                        (Some(k), Some(v), None) => Value::DictEntry(Box::new(k), Box::new(v)),
+                        _ => return Err(format!("dbus: bad dict entry `{}`", ty)),
+                    }
+                } else {
+                    Value::Struct(fields)
+                }
+            }
+            _ => return Err(format!("dbus: unsupported type `{}`", ty)),
+        })
+    }
+}
+
+/// An authenticated bus connection
+pub struct Connection {
+    stream: UnixStream,
+    buf: Vec<u8>,
+    fds: VecDeque<OwnedFd>,
+    serial: u32,
+    /// Messages that arrived while waiting for a reply
+    queue: VecDeque<Message>,
+}
+
+impl Connection {
+    /// The system bus, where udisks lives
+    pub fn system() -> Result<Self, String> {
+        let address = std::env::var("DBUS_SYSTEM_BUS_ADDRESS")
+            .unwrap_or_else(|_| "unix:path=/var/run/dbus/system_bus_socket".to_string());
+        Self::open(&address)
+    }
+
+    /// Connect to the first `unix:` address of a bus address list
+    pub fn open(address: &str) -> Result<Self, String> {
+        let stream = address
+            .split(';')
+            .find_map(|entry| {
+                let params = entry.strip_prefix("unix:")?;
+                params.split(',').find_map(|kv| match kv.split_once('=') {
+                    Some(("path", path)) => Some(UnixStream::connect(unescape(path))),
+                    Some(("abstract", name)) => Some(connect_abstract(&unescape(name))),
+                    _ => None,
+                })
+            })
+            .ok_or_else(|| format!("dbus: no unix socket in `{}`", address))?
+            .map_err(|e| format!("dbus: {}: {}", address, e))?;
+        stream
+            .set_read_timeout(Some(CALL_TIMEOUT))
+            .map_err(|e| e.to_string())?;
+        let mut conn = Self {
+            stream,
+            buf: Vec::new(),
+            fds: VecDeque::new(),
+            serial: 0,
+            queue: VecDeque::new(),
+        };
+        conn.authenticate()
+            .map_err(|e| format!("dbus: authentication failed: {}", e))?;
+        conn.call(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "Hello",
+            vec![],
+        )?;
+        Ok(conn)
+    }
+
+    fn authenticate(&mut self) -> io::Result<()> {
+        // SAFETY: geteuid cannot fail.
+        let uid = unsafe { libc::geteuid() }.to_string();
+        let hex: String = uid.bytes().map(|b| format!("{:02x}", b)).collect();
+        self.stream
+            .write_all(format!("\0AUTH EXTERNAL {}\r\n", hex).as_bytes())?;
+        if !self.auth_line()?.starts_with("OK ") {
+            return Err(io::Error::other("EXTERNAL rejected"));
+        }
+        self.stream.write_all(b"NEGOTIATE_UNIX_FD\r\n")?;
+        // Without fd passing udisks cannot hand over the device; calls say so later
+        let _ = self.auth_line()?;
+        self.stream.write_all(b"BEGIN\r\n")
+    }
+
+    fn auth_line(&mut self) -> io::Result<String> {
+        let mut line = Vec::new();
+        let mut byte = [0u8];
+        while !line.ends_with(b"\r\n") {
+            if self.stream.read(&mut byte)? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            line.push(byte[0]);
+        }
+        Ok(String::from_utf8_lossy(&line).trim_end().to_string())
+    }
+
+    /// Call a method and wait for its reply; an error reply becomes `Err`
+    pub fn call(
+        &mut self,
+        destination: &str,
+        path: &str,
+        interface: &str,
+        member: &str,
+        body: Vec<Value>,
+    ) -> Result<Message, String> {
+        let what = format!("{}.{} on {}", interface, member, path);
+        let reply = self.call_inner(destination, path, interface, member, body);
+        crate::log::outcome("dbus", what, &reply);
+        reply
+    }
+
+    fn call_inner(
+        &mut self,
+        destination: &str,
+        path: &str,
+        interface: &str,
+        member: &str,
+        body: Vec<Value>,
+    ) -> Result<Message, String> {
+        let serial = self.send(Message::call(destination, path, interface, member, body))?;
+        loop {
+            let msg = self.read_message()?;
+            if msg.reply_serial != Some(serial) {
+                self.queue.push_back(msg);
+                continue;
+            }
+            if msg.kind == ERROR {
+                let name = msg.error_name.unwrap_or_default();
+                return Err(match msg.body.first().and_then(Value::as_str) {
+                    Some(text) => format!("{}: {}", name, text),
+                    None => name,
+                });
+            }
+            return Ok(msg);
+        }
+    }
+
+    /// Send a message, with its file descriptors; returns its serial
+    pub fn send(&mut self, mut msg: Message) -> Result<u32, String> {
+        self.serial += 1;
+        msg.serial = self.serial;
+        let bytes = msg.encode();
+        let fds: Vec<RawFd> = msg.fds.iter().map(AsRawFd::as_raw_fd).collect();
+        send_with_fds(&self.stream, &bytes, &fds).map_err(|e| format!("dbus: send: {}", e))?;
+        Ok(msg.serial)
+    }
+
+    /// The next incoming message
+    pub fn receive(&mut self) -> Result<Message, String> {
+        match self.queue.pop_front() {
+            Some(msg) => Ok(msg),
+            None => self.read_message(),
+        }
+    }
+
+    fn read_message(&mut self) -> Result<Message, String> {
+        loop {
+            if self.buf.len() >= 16 {
+                let u32_at = |i: usize| {
+                    u32::from_le_bytes(self.buf[i..i + 4].try_into().expect("4 bytes")) as usize
+                };
+                let header = (16 + u32_at(12)).div_ceil(8) * 8;
+                let total = header + u32_at(4);
+                if self.buf.len() >= total {
+                    let bytes: Vec<u8> = self.buf.drain(..total).collect();
+                    let count = fd_count(&bytes).min(self.fds.len());
+                    let fds = self.fds.drain(..count).collect();
+                    return Message::decode(&bytes, fds);
+                }
+            }
+            let mut chunk = vec![0u8; 64 * 1024];
+            let n = recv_with_fds(&self.stream, &mut chunk, &mut self.fds)
+                .map_err(|e| format!("dbus: receive: {}", e))?;
+            if n == 0 {
+                return Err("dbus: connection closed".to_string());
+            }
+            self.buf.extend(&chunk[..n]);
+        }
+    }
+}
+
+/// The `UNIX_FDS` header field of a raw message, 0 if absent
+fn fd_count(bytes: &[u8]) -> usize {
+    let mut reader = Reader {
+        buf: bytes,
+        pos: 12,
+    };
+    let Ok(fields) = reader.value("a(yv)") else {
+        return 0;
+    };
+    fields
+        .as_array()
+        .unwrap_or_default()
+        .iter()
+        .find_map(|f| match f {
+            Value::Struct(parts) if parts.first().and_then(Value::as_u64) == Some(9) => {
+                parts.get(1).and_then(Value::as_u64)
+            }
+            _ => None,
+        })
+        .unwrap_or(0) as usize
+}
+
+/// Undo `%xx` escaping in an address value
+fn unescape(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = (bytes[i] == b'%')
+            .then(|| std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok())
+            .flatten()
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match hex {
+            Some(b) => {
+                out.push(b);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn connect_abstract(name: &str) -> io::Result<UnixStream> {
+    use std::os::linux::net::SocketAddrExt;
+    let addr = std::os::unix::net::SocketAddr::from_abstract_name(name.as_bytes())?;
+    UnixStream::connect_addr(&addr)
+}
+
+fn send_with_fds(stream: &UnixStream, bytes: &[u8], fds: &[RawFd]) -> io::Result<()> {
+    let mut iov = libc::iovec {
+        iov_base: bytes.as_ptr() as *mut libc::c_void,
+        iov_len: bytes.len(),
+    };
+    let fd_bytes = std::mem::size_of_val(fds) as u32;
+    // SAFETY: CMSG_SPACE only computes a size.
+    let mut control = vec![0u8; unsafe { libc::CMSG_SPACE(fd_bytes) } as usize];
+    // SAFETY: zeroed msghdr is a valid empty header.
+    let mut header: libc::msghdr = unsafe { std::mem::zeroed() };
+    header.msg_iov = &mut iov;
+    header.msg_iovlen = 1;
+    if !fds.is_empty() {
+        header.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        header.msg_controllen = control.len() as _;
+        // SAFETY: `control` has room for one header carrying `fds`, as sized above.
+        unsafe {
+            let cmsg = libc::CMSG_FIRSTHDR(&header);
+            (*cmsg).cmsg_level = libc::SOL_SOCKET;
+            (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(fd_bytes) as _;
+            std::ptr::copy_nonoverlapping(
+                fds.as_ptr() as *const u8,
+                libc::CMSG_DATA(cmsg),
+                fd_bytes as usize,
+            );
+        }
+    }
+    let mut sent = 0;
+    while sent < bytes.len() {
+        // SAFETY: `header` points at live buffers for the duration of the call.
+        let n = unsafe { libc::sendmsg(stream.as_raw_fd(), &header, libc::MSG_NOSIGNAL) };
+        if n < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        sent += n as usize;
+        // Descriptors go with the first chunk only
+        header.msg_control = std::ptr::null_mut();
+        header.msg_controllen = 0;
+        iov.iov_base = bytes[sent..].as_ptr() as *mut libc::c_void;
+        iov.iov_len = bytes.len() - sent;
+        header.msg_iov = &mut iov;
+    }
+    Ok(())
+}
+
+fn recv_with_fds(
+    stream: &UnixStream,
+    buf: &mut [u8],
+    fds: &mut VecDeque<OwnedFd>,
+) -> io::Result<usize> {
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+        iov_len: buf.len(),
+    };
+    let fd_bytes = (MAX_FDS * std::mem::size_of::<RawFd>()) as u32;
+    // SAFETY: CMSG_SPACE only computes a size.
+    let mut control = vec![0u8; unsafe { libc::CMSG_SPACE(fd_bytes) } as usize];
+    // SAFETY: zeroed msghdr is a valid empty header.
+    let mut header: libc::msghdr = unsafe { std::mem::zeroed() };
+    header.msg_iov = &mut iov;
+    header.msg_iovlen = 1;
+    header.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    header.msg_controllen = control.len() as _;
+    // SAFETY: `header` points at live buffers for the duration of the call.
+    let n = unsafe { libc::recvmsg(stream.as_raw_fd(), &mut header, libc::MSG_CMSG_CLOEXEC) };
+    if n < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: walks the control headers the kernel just filled in; each
+    // SCM_RIGHTS payload is an array of fresh descriptors we now own.
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(&header);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                let data = libc::CMSG_DATA(cmsg) as *const RawFd;
+                let count = ((*cmsg).cmsg_len as usize - libc::CMSG_LEN(0) as usize)
+                    / std::mem::size_of::<RawFd>();
+                for i in 0..count {
+                    fds.push_back(OwnedFd::from_raw_fd(data.add(i).read_unaligned()));
+                }
+            }
+            cmsg = libc::CMSG_NXTHDR(&header, cmsg);
+        }
+    }
+    Ok(n as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_round_trip_through_the_wire_format() {
+        let mut call = Message::call(
+            "org.freedesktop.UDisks2",
+            "/org/freedesktop/UDisks2/block_devices/sdb",
+            "org.freedesktop.UDisks2.Block",
+            "OpenDevice",
+            vec![
+                Value::Str("rw".to_string()),
+                dict(vec![("flags", Value::I32(0o200000))]),
+            ],
+        );
+        call.serial = 7;
+        call.sender = None;
+        let decoded = Message::decode(&call.encode(), Vec::new()).unwrap();
+        assert_eq!(decoded.serial, 7);
+        assert_eq!(decoded.member.as_deref(), Some("OpenDevice"));
+        assert_eq!(decoded.body, call.body);
+        assert_eq!(decoded.body[1].get("flags"), Some(&Value::I32(0o200000)));
+
+        let nested = Value::Array(
+            "{oa{sa{sv}}}".to_string(),
+            vec![Value::DictEntry(
+                Box::new(Value::Path("/a".to_string())),
+                Box::new(Value::Array(
+                    "{sa{sv}}".to_string(),
+                    vec![Value::DictEntry(
+                        Box::new(Value::Str("org.x".to_string())),
+                        Box::new(dict(vec![
+                            ("Size", Value::U64(1 << 33)),
+                            ("Device", bytestring("/dev/sdb")),
+                        ])),
+                    )],
+                )),
+            )],
+        );
+        let mut reply = Message::reply(&decoded, vec![nested.clone()]);
+        reply.serial = 8;
+        let back = Message::decode(&reply.encode(), Vec::new()).unwrap();
+        assert_eq!(back.reply_serial, Some(7));
+        assert_eq!(back.body, vec![nested]);
+        let props = back.body[0].get("/a").unwrap().get("org.x").unwrap();
+        assert_eq!(props.get("Size").and_then(Value::as_u64), Some(1 << 33));
+        assert_eq!(
+            props.get("Device").and_then(Value::as_bytes),
+            Some(b"/dev/sdb".to_vec())
+        );
+    }
+
+    #[test]
+    fn addresses_are_unescaped() {
+        assert_eq!(unescape("/run/user/1000/bus%2cx"), "/run/user/1000/bus,x");
+        assert_eq!(unescape("100%"), "100%");
+    }
+}
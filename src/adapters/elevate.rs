@@ -0,0 +1,99 @@
+//! Tools that run the privileged helper as root.
+
+use std::path::Path;
+use std::process::Command;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Elevator {
+    Sudo,
+    Doas,
+    /// polkit; shows a desktop dialog when an agent is running
+    Pkexec,
+    /// systemd's polkit-backed `run0`
+    Run0,
+}
+
+impl Elevator {
+    /// Tried in this order when the config leaves the choice to us
+    pub const ALL: [Elevator; 4] = [
+        Elevator::Sudo,
+        Elevator::Doas,
+        Elevator::Pkexec,
+        Elevator::Run0,
+    ];
+    pub const NAMES: [&'static str; 4] = ["sudo", "doas", "pkexec", "run0"];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Elevator::Sudo => "sudo",
+            Elevator::Doas => "doas",
+            Elevator::Pkexec => "pkexec",
+            Elevator::Run0 => "run0",
+        }
+    }
+
+    pub fn by_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|e| e.name() == name)
+    }
+
+    /// Asks for the password on the terminal rather than through a polkit agent
+    pub fn prompts_on_tty(self) -> bool {
+        matches!(self, Elevator::Sudo | Elevator::Doas)
+    }
+
+    /// `preferred` if it is installed, otherwise the first tool on `$PATH`
+    pub fn detect(preferred: Option<Self>) -> Option<Self> {
+        Self::pick(preferred, crate::adapters::platform::has_command)
+    }
+
+    fn pick(preferred: Option<Self>, installed: impl Fn(&str) -> bool) -> Option<Self> {
+        match preferred {
+            Some(e) => installed(e.name()).then_some(e),
+            None => Self::ALL.into_iter().find(|e| installed(e.name())),
+        }
+    }
+
+    /// `exe args...` run as root through this tool
+    pub fn command(self, exe: &Path, args: &[&str]) -> Command {
+        let mut cmd = Command::new(self.name());
+        match self {
+            Elevator::Sudo | Elevator::Doas => {
+                cmd.arg("--");
+            }
+            // Keep the helper's stdin/stdout as pipes instead of a new pty
+            Elevator::Run0 => {
+                cmd.arg("--pipe");
+            }
+            Elevator::Pkexec => {}
+        }
+        cmd.arg(exe).args(args);
+        cmd
+    }
+}
+
+impl std::fmt::Display for Elevator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn configured_tool_wins_only_when_installed() {
+        let installed = |name: &str| matches!(name, "doas" | "pkexec");
+        assert_eq!(Elevator::pick(None, installed), Some(Elevator::Doas));
+        assert_eq!(
+            Elevator::pick(Some(Elevator::Pkexec), installed),
+            Some(Elevator::Pkexec)
+        );
+        assert_eq!(Elevator::pick(Some(Elevator::Sudo), installed), None);
+
+        let cmd = Elevator::Doas.command(Path::new("/usr/bin/ferric"), &["helper"]);
+        assert_eq!(cmd.get_program(), "doas");
+        let args: Vec<_> = cmd.get_args().collect();
+        assert_eq!(args, ["--", "/usr/bin/ferric", "helper"]);
+    }
+}
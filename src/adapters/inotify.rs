@@ -0,0 +1,205 @@
+//! Minimal inotify(7) wrapper: one fd, directory watches, batched reads.
+
+use std::{
+    ffi::{CString, OsString},
+    io,
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        unix::ffi::{OsStrExt, OsStringExt},
+    },
+    path::Path,
+    time::Duration,
+};
+
+pub use libc::{
+    IN_CLOSE_WRITE, IN_CREATE, IN_DELETE, IN_DELETE_SELF, IN_IGNORED, IN_ISDIR, IN_MOVE_SELF,
+    IN_MOVED_FROM, IN_MOVED_TO, IN_Q_OVERFLOW,
+};
+
+/// Everything that can add, remove or finish an image in a directory
+pub const DIR_EVENTS: u32 = IN_CREATE
+    | IN_CLOSE_WRITE
+    | IN_MOVED_TO
+    | IN_MOVED_FROM
+    | IN_DELETE
+    | IN_DELETE_SELF
+    | IN_MOVE_SELF;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InotifyEvent {
+    pub wd: i32,
+    pub mask: u32,
+    /// Entry name inside the watched directory, if the event has one
+    pub name: Option<OsString>,
+}
+
+pub struct Inotify {
+    fd: OwnedFd,
+}
+
+impl Inotify {
+    pub fn new() -> io::Result<Self> {
+        // SAFETY: plain syscall; the returned fd is checked and owned below.
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC | libc::IN_NONBLOCK) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fd` is a fresh descriptor nothing else owns.
+        Ok(Self {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+        })
+    }
+
+    /// Watch a directory; returns its watch descriptor. Watching the same
+    /// directory twice returns the same descriptor.
+    pub fn add_watch(&self, path: &Path, mask: u32) -> io::Result<i32> {
+        let c_path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+        // SAFETY: `c_path` is NUL-terminated and outlives the call.
+        let wd = unsafe {
+            libc::inotify_add_watch(
+                self.fd.as_raw_fd(),
+                c_path.as_ptr(),
+                mask | libc::IN_ONLYDIR,
+            )
+        };
+        if wd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(wd)
+    }
+
+    /// Wait up to `timeout` for events; true if some are ready
+    pub fn wait(&self, timeout: Duration) -> io::Result<bool> {
+        let mut pfd = libc::pollfd {
+            fd: self.fd.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let ms = timeout.as_millis().min(i32::MAX as u128) as i32;
+        // SAFETY: one valid pollfd for the duration of the call.
+        let n = unsafe { libc::poll(&mut pfd, 1, ms) };
+        match n {
+            n if n < 0 => {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::Interrupted {
+                    Ok(false)
+                } else {
+                    Err(err)
+                }
+            }
+            0 => Ok(false),
+            _ => Ok(pfd.revents & libc::POLLIN != 0),
+        }
+    }
+
+    /// Drain the events that are ready without blocking
+    pub fn read_events(&self) -> io::Result<Vec<InotifyEvent>> {
+        let mut out = Vec::new();
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            // SAFETY: `buf` is valid for `buf.len()` writable bytes.
+            let n = unsafe {
+                libc::read(
+                    self.fd.as_raw_fd(),
+                    buf.as_mut_ptr() as *mut libc::c_void,
+                    buf.len(),
+                )
+            };
+            if n < 0 {
+                let err = io::Error::last_os_error();
+                return match err.kind() {
+                    io::ErrorKind::WouldBlock => Ok(out),
+                    io::ErrorKind::Interrupted => continue,
+                    _ => Err(err),
+                };
+            }
+            if n == 0 {
+                return Ok(out);
+            }
+            out.extend(parse_events(&buf[..n as usize]));
+        }
+    }
+}
+
+/// Decode a buffer of `struct inotify_event` records
+fn parse_events(buf: &[u8]) -> Vec<InotifyEvent> {
+    const HEADER: usize = std::mem::size_of::<libc::inotify_event>();
+    let mut out = Vec::new();
+    let mut off = 0;
+    while off + HEADER <= buf.len() {
+        let field = |at: usize| {
+            u32::from_ne_bytes([
+                buf[off + at],
+                buf[off + at + 1],
+                buf[off + at + 2],
+                buf[off + at + 3],
+            ])
+        };
+        // Layout: i32 wd, u32 mask, u32 cookie, u32 len, then `len` name bytes
+        let wd = field(0) as i32;
+        let mask = field(4);
+        let len = field(12) as usize;
+        let end = (off + HEADER + len).min(buf.len());
+        let raw = &buf[off + HEADER..end];
+        let name_len = raw.iter().position(|&b| b == 0).unwrap_or(raw.len());
+        let name = (name_len > 0).then(|| OsString::from_vec(raw[..name_len].to_vec()));
+        out.push(InotifyEvent { wd, mask, name });
+        off = end;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(wd: i32, mask: u32, name: &str, padded: usize) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&wd.to_ne_bytes());
+        out.extend_from_slice(&mask.to_ne_bytes());
+        out.extend_from_slice(&0u32.to_ne_bytes());
+        out.extend_from_slice(&(padded as u32).to_ne_bytes());
+        let mut raw = name.as_bytes().to_vec();
+        raw.resize(padded, 0);
+        out.extend_from_slice(&raw);
+        out
+    }
+
+    #[test]
+    fn parses_packed_records() {
+        let mut buf = record(1, IN_CREATE, "a.iso", 16);
+        buf.extend(record(2, IN_IGNORED, "", 0));
+        let events = parse_events(&buf);
+        assert_eq!(
+            events,
+            vec![
+                InotifyEvent {
+                    wd: 1,
+                    mask: IN_CREATE,
+                    name: Some("a.iso".into()),
+                },
+                InotifyEvent {
+                    wd: 2,
+                    mask: IN_IGNORED,
+                    name: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_new_files() {
+        let dir = std::env::temp_dir().join(format!("ferric-inotify-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let inotify = Inotify::new().unwrap();
+        let wd = inotify.add_watch(&dir, DIR_EVENTS).unwrap();
+        std::fs::write(dir.join("new.iso"), b"x").unwrap();
+        assert!(inotify.wait(Duration::from_secs(2)).unwrap());
+        let events = inotify.read_events().unwrap();
+        assert!(events.iter().any(|e| e.wd == wd
+            && e.mask & IN_CLOSE_WRITE != 0
+            && e.name.as_deref() == Some("new.iso".as_ref())));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
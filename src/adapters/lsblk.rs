@@ -1,4 +1,7 @@
-use crate::domain::{device::Device, paths::DevicePath, units::ByteSize};
+use crate::{
+    domain::{device::Device, paths::DevicePath, units::ByteSize},
+    log,
+};
 use std::{
     collections::{BTreeMap, HashMap},
     path::PathBuf,
@@ -12,12 +15,24 @@ pub fn refresh_devices() -> Vec<Device> {
         .arg("NAME,TYPE,SIZE,RM,RO,MODEL,SERIAL,TRAN,HOTPLUG,MOUNTPOINT,PKNAME,LABEL");
     let output = match cmd.output() {
         Ok(o) => o,
-        Err(_) => return Vec::new(),
+        Err(e) => {
+            log::error("lsblk", format_args!("failed to run lsblk: {}", e));
+            return Vec::new();
+        }
     };
     if !output.status.success() {
+        log::error(
+            "lsblk",
+            format_args!(
+                "lsblk failed with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        );
         return Vec::new();
     }
     let stdout = String::from_utf8_lossy(&output.stdout);
+    log::trace("lsblk", format_args!("said:\n{}", stdout));
 
     #[derive(Default)]
     struct DiskAgg {
@@ -85,12 +100,12 @@ pub fn refresh_devices() -> Vec<Device> {
                         }
                     }
                     let mut label = lbl;
-                    if label.is_empty() && !mp.is_empty() {
-                        if let Some(last) = mp.rsplit('/').next() {
-                            if !last.is_empty() {
-                                label = last.to_string();
-                            }
-                        }
+                    if label.is_empty()
+                        && !mp.is_empty()
+                        && let Some(last) = mp.rsplit('/').next()
+                        && !last.is_empty()
+                    {
+                        label = last.to_string();
                     }
                     if !label.is_empty() && !d.labels.iter().any(|l| l == &label) {
                         d.labels.push(label);
@@ -115,7 +130,6 @@ pub fn refresh_devices() -> Vec<Device> {
         let path = DevicePath::from(PathBuf::from(format!("/dev/{}", name)));
         out.push(Device {
             name,
-            path,
             size: ByteSize::new(d.size),
             model: d.model,
             serial: d.serial,
@@ -125,6 +139,9 @@ pub fn refresh_devices() -> Vec<Device> {
             ro: d.ro,
             mounted: d.any_mounted,
             labels: d.labels,
+            access: crate::adapters::platform::device_access(path.as_path()),
+            udisks: None,
+            path,
         });
     }
     out.sort_by(|a, b| {
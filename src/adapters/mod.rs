@@ -1,2 +1,6 @@
+pub mod dbus;
+pub mod elevate;
+pub mod inotify;
 pub mod lsblk;
 pub mod platform;
+pub mod udisks;
@@ -1,8 +1,199 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
+use crate::{domain::device::DeviceAccess, log};
+
+static LAZY_UNMOUNT: OnceLock<bool> = OnceLock::new();
+
+/// Allow `MNT_DETACH` when a mount stays busy; only the first call has an effect
+pub fn set_lazy_unmount(lazy: bool) {
+    let _ = LAZY_UNMOUNT.set(lazy);
+}
+
+fn lazy_unmount() -> bool {
+    LAZY_UNMOUNT.get().copied().unwrap_or(false)
+}
+
+/// Unmount everything mounted from a disk or its partitions.
+///
+/// Mounts come from `/proc/self/mountinfo` and go through `umount2`,
+/// falling back to the `umount` tool per mount and to `lsblk` + `umount`
+/// when mountinfo cannot be read.
 #[must_use = "unmount errors should be handled or logged"]
 pub fn unmount_partitions_of(device_path: &Path) -> Result<(), String> {
     let name = device_basename(device_path).ok_or_else(|| "invalid device path".to_string())?;
+    let mountinfo = match std::fs::read_to_string("/proc/self/mountinfo") {
+        Ok(text) => text,
+        Err(e) => {
+            log::debug("platform", format_args!("mountinfo: {}; asking lsblk", e));
+            return unmount_with_tools(&name);
+        }
+    };
+    let devs = block_devs_of(&name);
+    // Innermost first, so nested mounts do not keep their parent busy
+    let targets: Vec<PathBuf> = parse_mountinfo(&mountinfo)
+        .into_iter()
+        .rev()
+        .filter(|(dev, _)| devs.contains(dev))
+        .map(|(_, mountpoint)| mountpoint)
+        .collect();
+    let errors: Vec<String> = targets
+        .iter()
+        .filter_map(|mp| {
+            let result = unmount_native(mp);
+            log::outcome("platform", format_args!("umount {}", mp.display()), &result);
+            result.err()
+        })
+        .collect();
+    if errors.is_empty() {
+        return Ok(());
+    }
+    Err(format!(
+        "failed to unmount {} of {} partition(s): {}",
+        errors.len(),
+        targets.len(),
+        errors.join("; ")
+    ))
+}
+
+/// Where the running system keeps itself; a disk holding one is no target
+const SYSTEM_MOUNTS: &[&str] = &[
+    "/", "/boot", "/efi", "/usr", "/var", "/home", "/etc", "/opt", "/nix",
+];
+
+/// The system mount points and swap areas on the disk or its partitions
+pub fn system_use_of(device_path: &Path) -> Result<Vec<String>, String> {
+    use std::os::unix::fs::MetadataExt;
+    let name = device_basename(device_path).ok_or_else(|| "invalid device path".to_string())?;
+    let devs = block_devs_of(&name);
+    if devs.is_empty() {
+        return Err(format!("{}: not in /sys/block", device_path.display()));
+    }
+    let mountinfo = std::fs::read_to_string("/proc/self/mountinfo")
+        .map_err(|e| format!("Failed to read the mount table: {}", e))?;
+    let mut used: Vec<String> = system_mounts(&parse_mountinfo(&mountinfo), &devs)
+        .map(|mp| mp.display().to_string())
+        .collect();
+    let swaps = std::fs::read_to_string("/proc/swaps").unwrap_or_default();
+    used.extend(
+        swaps
+            .lines()
+            .skip(1)
+            .filter_map(|line| line.split_whitespace().next())
+            .filter(|swap| {
+                std::fs::metadata(swap).is_ok_and(|m| {
+                    let dev = format!("{}:{}", libc::major(m.rdev()), libc::minor(m.rdev()));
+                    devs.contains(&dev)
+                })
+            })
+            .map(|swap| format!("swap {}", swap)),
+    );
+    Ok(used)
+}
+
+/// Mount points from `mounts` on one of `devs` that belong to the system
+fn system_mounts<'a>(
+    mounts: &'a [(String, PathBuf)],
+    devs: &'a [String],
+) -> impl Iterator<Item = &'a PathBuf> {
+    mounts
+        .iter()
+        .filter(|(dev, _)| devs.contains(dev))
+        .map(|(_, mountpoint)| mountpoint)
+        .filter(|mp| {
+            SYSTEM_MOUNTS
+                .iter()
+                .any(|s| *mp == Path::new(s) || (*s != "/" && mp.starts_with(s)))
+        })
+}
+
+/// `maj:min` of a disk and each of its partitions, from sysfs
+fn block_devs_of(name: &str) -> Vec<String> {
+    let sys = Path::new("/sys/block").join(name);
+    let read_dev = |dir: &Path| {
+        std::fs::read_to_string(dir.join("dev"))
+            .ok()
+            .map(|s| s.trim().to_string())
+    };
+    let mut devs: Vec<String> = read_dev(&sys).into_iter().collect();
+    if let Ok(entries) = std::fs::read_dir(&sys) {
+        devs.extend(
+            entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|dir| dir.join("partition").is_file())
+                .filter_map(|dir| read_dev(&dir)),
+        );
+    }
+    devs
+}
+
+/// `(maj:min, mountpoint)` of every line of a mountinfo file, in mount order
+fn parse_mountinfo(text: &str) -> Vec<(String, PathBuf)> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.split(' ');
+            let dev = fields.nth(2)?;
+            let mountpoint = fields.nth(1)?;
+            Some((dev.to_string(), PathBuf::from(unescape_octal(mountpoint))))
+        })
+        .collect()
+}
+
+/// Undo the kernel's `\040`-style escaping of spaces, tabs, newlines and backslashes
+fn unescape_octal(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let octal = bytes
+            .get(i + 1..i + 4)
+            .filter(|d| bytes[i] == b'\\' && d.iter().all(|c| (b'0'..=b'7').contains(c)));
+        match octal {
+            Some(d) => {
+                out.push((d[0] - b'0') * 64 + (d[1] - b'0') * 8 + (d[2] - b'0'));
+                i += 4;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// `umount2`, then a lazy detach if allowed and busy, then the `umount` tool
+fn unmount_native(mountpoint: &Path) -> Result<(), String> {
+    use std::os::unix::ffi::OsStrExt;
+    let c_path = std::ffi::CString::new(mountpoint.as_os_str().as_bytes())
+        .map_err(|_| format!("{}: path contains NUL", mountpoint.display()))?;
+    let umount2 = |flags| {
+        // SAFETY: `c_path` is NUL-terminated and outlives the call.
+        if unsafe { libc::umount2(c_path.as_ptr(), flags) } == 0 {
+            Ok(())
+        } else {
+            Err(std::io::Error::last_os_error())
+        }
+    };
+    let err = match umount2(0) {
+        Ok(()) => return Ok(()),
+        Err(e) if e.raw_os_error() == Some(libc::EBUSY) && lazy_unmount() => {
+            match umount2(libc::MNT_DETACH) {
+                Ok(()) => return Ok(()),
+                Err(e) => e,
+            }
+        }
+        Err(e) => e,
+    };
+    if has_command("umount") && unmount(mountpoint).is_ok() {
+        return Ok(());
+    }
+    Err(format!("umount {} failed: {}", mountpoint.display(), err))
+}
+
+/// The old path: list mounts with `lsblk` and run `umount` on each
+fn unmount_with_tools(name: &str) -> Result<(), String> {
     let mut cmd = std::process::Command::new("lsblk");
     cmd.arg("-P")
         .arg("-b")
@@ -22,26 +213,10 @@ pub fn unmount_partitions_of(device_path: &Path) -> Result<(), String> {
             kv.get("PKNAME"),
             kv.get("MOUNTPOINT"),
         ) {
-            (Some("part"), Some(pk), Some(mp)) if pk == &name && !mp.is_empty() => {
+            (Some("part"), Some(pk), Some(mp)) if pk == name && !mp.is_empty() => {
                 attempted += 1;
-                match std::process::Command::new("umount").arg(mp).output() {
-                    Ok(out) => {
-                        if !out.status.success() {
-                            let mut msg = format!(
-                                "umount {} failed with status {}",
-                                mp,
-                                out.status.code().unwrap_or(-1)
-                            );
-                            let stderr = String::from_utf8_lossy(&out.stderr);
-                            let stderr = stderr.trim();
-                            if !stderr.is_empty() {
-                                msg.push_str(": ");
-                                msg.push_str(stderr);
-                            }
-                            errors.push(msg);
-                        }
-                    }
-                    Err(e) => errors.push(format!("failed to run umount {}: {}", mp, e)),
+                if let Err(e) = unmount(Path::new(mp)) {
+                    errors.push(format!("{}: {}", mp, e));
                 }
             }
             _ => {}
@@ -58,82 +233,426 @@ pub fn unmount_partitions_of(device_path: &Path) -> Result<(), String> {
     ))
 }
 
+const REREAD_ATTEMPTS: u32 = 5;
+const REREAD_BACKOFF: std::time::Duration = std::time::Duration::from_millis(200);
+
+/// Make the kernel re-read a disk's partition table.
+///
+/// Uses the `BLKRRPART` ioctl, retried while the disk is busy, then
+/// `partprobe` and `blockdev --rereadpt` if that does not work.
 #[must_use = "partprobe errors may indicate device not ready"]
 pub fn partprobe(device_path: &Path) -> Result<(), String> {
-    match std::process::Command::new("partprobe")
-        .arg(device_path)
-        .output()
-    {
-        Ok(out) if out.status.success() => return Ok(()),
-        Ok(out) => {
-            let err = String::from_utf8_lossy(&out.stderr).trim().to_string();
-            let code = out.status.code().unwrap_or(-1);
-            match std::process::Command::new("blockdev")
-                .arg("--rereadpt")
-                .arg(device_path)
-                .output()
-            {
-                Ok(o2) if o2.status.success() => return Ok(()),
-                Ok(o2) => {
-                    let err2 = String::from_utf8_lossy(&o2.stderr).trim().to_string();
-                    let code2 = o2.status.code().unwrap_or(-1);
-                    return Err(format!(
-                        "partprobe failed (code {}): {}; blockdev --rereadpt failed (code {}): {}",
-                        code, err, code2, err2
-                    ));
-                }
-                Err(e2) => {
-                    return Err(format!(
-                        "partprobe failed (code {}): {}; blockdev --rereadpt spawn error: {}",
-                        code, err, e2
-                    ));
-                }
-            }
+    let native = match reread_partitions(device_path) {
+        Ok(()) => return Ok(()),
+        Err(e) => format!("BLKRRPART failed: {}", e),
+    };
+    log::debug(
+        "platform",
+        format_args!("{}: {}", device_path.display(), native),
+    );
+    let mut errors = vec![native];
+    for (program, args) in [
+        ("partprobe", vec![device_path.as_os_str()]),
+        (
+            "blockdev",
+            vec![std::ffi::OsStr::new("--rereadpt"), device_path.as_os_str()],
+        ),
+    ] {
+        if !has_command(program) {
+            continue;
         }
-        Err(e) => {
-            match std::process::Command::new("blockdev")
-                .arg("--rereadpt")
-                .arg(device_path)
-                .output()
-            {
-                Ok(o2) if o2.status.success() => return Ok(()),
-                Ok(o2) => {
-                    let err2 = String::from_utf8_lossy(&o2.stderr).trim().to_string();
-                    let code2 = o2.status.code().unwrap_or(-1);
-                    return Err(format!(
-                        "partprobe spawn error: {}; blockdev --rereadpt failed (code {}): {}",
-                        e, code2, err2
-                    ));
-                }
-                Err(e2) => {
-                    return Err(format!(
-                        "partprobe spawn error: {}; blockdev --rereadpt spawn error: {}",
-                        e, e2
-                    ));
-                }
-            }
+        match run_tool(program, &args) {
+            Ok(()) => return Ok(()),
+            Err(e) => errors.push(e),
         }
     }
+    Err(errors.join("; "))
 }
 
-pub fn is_root() -> bool {
-    if let Ok(status) = std::fs::read_to_string("/proc/self/status") {
-        for line in status.lines() {
-            if let Some(rest) = line.strip_prefix("Uid:") {
-                let mut it = rest.split_whitespace();
-                if let Some(real) = it.next() {
-                    if real == "0" {
-                        return true;
-                    }
-                }
-                break;
+fn reread_partitions(device_path: &Path) -> std::io::Result<()> {
+    use std::os::fd::AsRawFd;
+    const BLKRRPART: libc::Ioctl = 0x125f;
+    let file = std::fs::File::open(device_path)?;
+    retry_while_busy(REREAD_ATTEMPTS, REREAD_BACKOFF, || {
+        // SAFETY: argument-less ioctl on a descriptor `file` keeps open.
+        if unsafe { libc::ioctl(file.as_raw_fd(), BLKRRPART) } == 0 {
+            Ok(())
+        } else {
+            Err(std::io::Error::last_os_error())
+        }
+    })
+}
+
+/// Run `op` up to `attempts` times, sleeping between tries, while it fails with EBUSY
+fn retry_while_busy(
+    attempts: u32,
+    backoff: std::time::Duration,
+    mut op: impl FnMut() -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    let mut tries = 1;
+    loop {
+        match op() {
+            Err(e) if e.raw_os_error() == Some(libc::EBUSY) && tries < attempts => {
+                tries += 1;
+                std::thread::sleep(backoff);
             }
+            res => return res,
         }
     }
-    false
+}
+
+const CAP_SYS_ADMIN: u32 = 21;
+
+/// Probe what this process may do with a device node: `faccessat(W_OK)`
+/// with the effective ids and capabilities, `CapEff` for the admin side,
+/// and the node's owner, group and mode to explain a refusal
+pub fn device_access(device_path: &Path) -> DeviceAccess {
+    let caps = std::fs::read_to_string("/proc/self/status")
+        .ok()
+        .and_then(|status| effective_caps(&status))
+        .unwrap_or(0);
+    let write = writable(device_path);
+    DeviceAccess {
+        write,
+        admin: caps & (1 << CAP_SYS_ADMIN) != 0,
+        denied: (!write).then(|| why_not_writable(device_path)),
+    }
+}
+
+fn effective_caps(status: &str) -> Option<u64> {
+    let hex = status.lines().find_map(|l| l.strip_prefix("CapEff:"))?;
+    u64::from_str_radix(hex.trim(), 16).ok()
+}
+
+fn writable(path: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+    let Ok(c_path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    // SAFETY: `c_path` is NUL-terminated and outlives the call.
+    unsafe {
+        libc::faccessat(
+            libc::AT_FDCWD,
+            c_path.as_ptr(),
+            libc::W_OK,
+            libc::AT_EACCESS,
+        ) == 0
+    }
+}
+
+fn why_not_writable(path: &Path) -> String {
+    use std::os::unix::fs::MetadataExt;
+    let meta = match std::fs::metadata(path) {
+        Ok(meta) => meta,
+        Err(e) => return e.to_string(),
+    };
+    let passwd = std::fs::read_to_string("/etc/passwd").unwrap_or_default();
+    let groups = std::fs::read_to_string("/etc/group").unwrap_or_default();
+    // SAFETY: id getters cannot fail; `gids` has room for the count passed.
+    let euid = unsafe { libc::geteuid() };
+    let me = id_entry(&passwd, euid).map(|fields| fields[0].to_string());
+    let group = id_entry(&groups, meta.gid());
+    let mut gids = vec![0 as libc::gid_t; 256];
+    let n = unsafe { libc::getgroups(gids.len() as i32, gids.as_mut_ptr()) };
+    gids.truncate(n.max(0) as usize);
+    gids.push(unsafe { libc::getegid() });
+    let listed = match (&me, &group) {
+        (Some(me), Some(fields)) => fields
+            .get(3)
+            .is_some_and(|members| members.split(',').any(|m| m == me)),
+        _ => false,
+    };
+    let owner =
+        id_entry(&passwd, meta.uid()).map_or_else(|| meta.uid().to_string(), |f| f[0].to_string());
+    let group_name = group.map_or_else(|| meta.gid().to_string(), |f| f[0].to_string());
+    denial(
+        meta.mode(),
+        &owner,
+        &group_name,
+        gids.contains(&meta.gid()),
+        listed,
+    )
+}
+
+/// Fields of the `/etc/passwd` or `/etc/group` line with id `id`
+fn id_entry(file: &str, id: u32) -> Option<Vec<&str>> {
+    file.lines()
+        .map(|line| line.split(':').collect::<Vec<_>>())
+        .find(|fields| fields.get(2).and_then(|f| f.parse::<u32>().ok()) == Some(id))
+}
+
+/// Explain a refused write from the node's mode and the caller's groups.
+/// `listed` means the group file names the user even if this session
+/// does not have the group yet.
+fn denial(mode: u32, owner: &str, group: &str, in_group: bool, listed: bool) -> String {
+    let node = format!(
+        "owned by {}:{} with mode {:04o}",
+        owner,
+        group,
+        mode & 0o7777
+    );
+    let group_writable = mode & 0o020 != 0;
+    match (group_writable, in_group, listed) {
+        (true, false, true) => format!(
+            "{}; you were added to {} after this session started, log in again",
+            node, group
+        ),
+        (true, false, false) => format!("{}; you are not in the {} group", node, group),
+        (false, true, _) => format!("{}; the {} group may not write it", node, group),
+        _ => node,
+    }
 }
 
 fn device_basename(p: &Path) -> Option<String> {
     let s = p.file_name()?.to_string_lossy().to_string();
     Some(s)
 }
+
+/// Path of partition `n` on a whole-disk device (`sdb` → `sdb1`, `nvme0n1` → `nvme0n1p1`)
+pub fn partition_path(device_path: &Path, n: usize) -> std::path::PathBuf {
+    let s = device_path.to_string_lossy();
+    if s.ends_with(|c: char| c.is_ascii_digit()) {
+        std::path::PathBuf::from(format!("{}p{}", s, n))
+    } else {
+        std::path::PathBuf::from(format!("{}{}", s, n))
+    }
+}
+
+/// Poll until a device node shows up, e.g. after a partition table re-read
+pub fn wait_for_node(path: &Path, timeout: std::time::Duration) -> bool {
+    let deadline = std::time::Instant::now() + timeout;
+    while std::time::Instant::now() < deadline {
+        if path.exists() {
+            return true;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    path.exists()
+}
+
+/// Size of a block device or file in bytes
+pub fn device_size(device_path: &Path) -> std::io::Result<u64> {
+    use std::io::Seek;
+    let mut f = std::fs::File::open(device_path)?;
+    f.seek(std::io::SeekFrom::End(0))
+}
+
+/// True if `program` resolves on `$PATH`
+pub fn has_command(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+        .unwrap_or(false)
+}
+
+/// Run an external tool, folding a non-zero exit and its stderr into the error
+pub fn run_tool<S: AsRef<std::ffi::OsStr>>(program: &str, args: &[S]) -> Result<(), String> {
+    let line = std::iter::once(program.to_string())
+        .chain(
+            args.iter()
+                .map(|a| a.as_ref().to_string_lossy().into_owned()),
+        )
+        .collect::<Vec<_>>()
+        .join(" ");
+    let result = run_tool_inner(program, args);
+    log::outcome("tool", line, &result);
+    result
+}
+
+fn run_tool_inner<S: AsRef<std::ffi::OsStr>>(program: &str, args: &[S]) -> Result<(), String> {
+    match std::process::Command::new(program).args(args).output() {
+        Ok(out) if out.status.success() => {
+            let said = String::from_utf8_lossy(&out.stdout);
+            if !said.trim().is_empty() {
+                log::trace("tool", format_args!("{} said:\n{}", program, said));
+            }
+            Ok(())
+        }
+        Ok(out) => {
+            let mut msg = format!(
+                "{} failed with status {}",
+                program,
+                out.status.code().unwrap_or(-1)
+            );
+            let stderr = String::from_utf8_lossy(&out.stderr);
+            let stderr = stderr.trim();
+            if !stderr.is_empty() {
+                msg.push_str(": ");
+                msg.push_str(stderr);
+            }
+            Err(msg)
+        }
+        Err(e) => Err(format!("failed to run {}: {}", program, e)),
+    }
+}
+
+#[must_use = "mount errors should be handled or logged"]
+pub fn mount(source: &Path, target: &Path) -> Result<(), String> {
+    run_tool("mount", &[source.as_os_str(), target.as_os_str()])
+}
+
+#[must_use = "unmount errors should be handled or logged"]
+pub fn unmount(target: &Path) -> Result<(), String> {
+    run_tool("umount", &[target.as_os_str()])
+}
+
+/// Flush, detach and, for USB sticks, power down a whole-disk device so
+/// it can be pulled. Returns whether the port was powered off; a detached
+/// disk is safe to remove either way.
+pub fn eject(device_path: &Path) -> Result<bool, String> {
+    let result = eject_inner(device_path);
+    match &result {
+        Ok(powered_off) => log::debug(
+            "platform",
+            format_args!(
+                "ejected {}{}",
+                device_path.display(),
+                if *powered_off { ", port off" } else { "" }
+            ),
+        ),
+        Err(e) => log::warn(
+            "platform",
+            format_args!("eject {}: {}", device_path.display(), e),
+        ),
+    }
+    result
+}
+
+fn eject_inner(device_path: &Path) -> Result<bool, String> {
+    let name = device_basename(device_path).ok_or_else(|| "invalid device path".to_string())?;
+    unmount_partitions_of(device_path)?;
+    flush(device_path).map_err(|e| format!("Failed to flush {}: {}", device_path.display(), e))?;
+    let sys = Path::new("/sys/block").join(&name);
+    let real = std::fs::canonicalize(&sys).map_err(|e| format!("{}: {}", sys.display(), e))?;
+    // Found before the disk goes away and takes its sysfs path with it
+    let usb = usb_device_of(&real);
+    let delete = sys.join("device").join("delete");
+    std::fs::write(&delete, "1")
+        .map_err(|e| format!("Failed to detach {}: {}", device_path.display(), e))?;
+    Ok(usb.is_some_and(|dir| std::fs::write(dir.join("remove"), "1").is_ok()))
+}
+
+/// Write back dirty pages and drop the device's buffer cache
+fn flush(device_path: &Path) -> std::io::Result<()> {
+    use std::os::fd::AsRawFd;
+    const BLKFLSBUF: libc::Ioctl = 0x1261;
+    let file = std::fs::File::open(device_path)?;
+    file.sync_all()?;
+    // SAFETY: argument-less ioctl on a descriptor `file` keeps open.
+    if unsafe { libc::ioctl(file.as_raw_fd(), BLKFLSBUF) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// The USB device a sysfs block path hangs off: the nearest ancestor
+/// with a vendor id and a `remove` switch
+fn usb_device_of(sys_path: &Path) -> Option<std::path::PathBuf> {
+    sys_path
+        .ancestors()
+        .find(|dir| dir.join("idVendor").is_file() && dir.join("remove").is_file())
+        .map(Path::to_path_buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partition_paths_follow_kernel_naming() {
+        assert_eq!(
+            partition_path(Path::new("/dev/sdb"), 1),
+            std::path::PathBuf::from("/dev/sdb1")
+        );
+        assert_eq!(
+            partition_path(Path::new("/dev/nvme0n1"), 2),
+            std::path::PathBuf::from("/dev/nvme0n1p2")
+        );
+        assert_eq!(
+            partition_path(Path::new("/dev/mmcblk0"), 1),
+            std::path::PathBuf::from("/dev/mmcblk0p1")
+        );
+    }
+
+    #[test]
+    fn usb_parent_is_found_above_the_disk() {
+        let root = std::env::temp_dir().join(format!("ferric-sysfs-{}", std::process::id()));
+        let port = root.join("usb2").join("2-1");
+        let disk = port.join("2-1:1.0/host6/target6:0:0/6:0:0:0/block/sdb");
+        std::fs::create_dir_all(&disk).unwrap();
+        for dir in [root.join("usb2"), port.clone()] {
+            std::fs::write(dir.join("idVendor"), "0781\n").unwrap();
+            std::fs::write(dir.join("remove"), "").unwrap();
+        }
+        assert_eq!(usb_device_of(&disk), Some(port));
+        assert_eq!(usb_device_of(Path::new("/nonexistent/block/nvme0n1")), None);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn mountinfo_lines_give_device_and_unescaped_mountpoint() {
+        let text = "36 35 98:0 / /mnt1 rw,noatime master:1 - ext3 /dev/root rw\n\
+                    40 36 8:17 / /media/me/Ubuntu\\04024.04 ro - iso9660 /dev/sdb1 ro\n";
+        assert_eq!(
+            parse_mountinfo(text),
+            vec![
+                ("98:0".to_string(), PathBuf::from("/mnt1")),
+                ("8:17".to_string(), PathBuf::from("/media/me/Ubuntu 24.04")),
+            ]
+        );
+        assert_eq!(unescape_octal("a\\134b\\01"), "a\\b\\01");
+    }
+
+    #[test]
+    fn only_system_mount_points_mark_a_system_disk() {
+        let mounts = parse_mountinfo(
+            "1 0 8:2 / / rw - ext4 /dev/sda2 rw\n\
+             2 1 8:1 / /boot/efi rw - vfat /dev/sda1 rw\n\
+             3 1 8:17 / /media/me/STICK rw - vfat /dev/sdb1 rw\n\
+             4 1 8:18 / /varnish rw - ext4 /dev/sdb2 rw\n",
+        );
+        let sda = ["8:0".to_string(), "8:1".to_string(), "8:2".to_string()];
+        let sdb = ["8:16".to_string(), "8:17".to_string(), "8:18".to_string()];
+        assert_eq!(
+            system_mounts(&mounts, &sda).collect::<Vec<_>>(),
+            [Path::new("/"), Path::new("/boot/efi")]
+        );
+        assert_eq!(system_mounts(&mounts, &sdb).count(), 0);
+    }
+
+    #[test]
+    fn busy_reread_is_retried_then_given_up() {
+        let busy = || Err(std::io::Error::from_raw_os_error(libc::EBUSY));
+        let mut calls = 0;
+        let res = retry_while_busy(3, std::time::Duration::ZERO, || {
+            calls += 1;
+            if calls < 3 { busy() } else { Ok(()) }
+        });
+        assert!(res.is_ok());
+        assert_eq!(calls, 3);
+
+        calls = 0;
+        let res = retry_while_busy(2, std::time::Duration::ZERO, || {
+            calls += 1;
+            busy()
+        });
+        assert_eq!(res.unwrap_err().raw_os_error(), Some(libc::EBUSY));
+        assert_eq!(calls, 2);
+    }
+
+    #[test]
+    fn capabilities_and_denials_are_read_and_explained() {
+        let status = "Name:\tferric\nCapPrm:\t0000000000000000\nCapEff:\t0000000000200002\n";
+        let caps = effective_caps(status).unwrap();
+        assert_ne!(caps & (1 << CAP_SYS_ADMIN), 0);
+        assert_ne!(caps & 0b10, 0);
+
+        let passwd = "root:x:0:0::/root:/bin/sh\nsimon:x:1000:1000::/home/simon:/bin/sh\n";
+        assert_eq!(id_entry(passwd, 1000).unwrap()[0], "simon");
+        assert_eq!(
+            denial(0o60660, "root", "disk", false, false),
+            "owned by root:disk with mode 0660; you are not in the disk group"
+        );
+        assert!(denial(0o60660, "root", "disk", false, true).contains("log in again"));
+        assert!(denial(0o60640, "root", "disk", true, true).ends_with("may not write it"));
+    }
+}
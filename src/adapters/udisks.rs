@@ -0,0 +1,507 @@
+//! UDisks2 backend: lists drives and opens block devices through the
+//! udisks daemon, which asks polkit instead of requiring root.
+
+use crate::{
+    adapters::{
+        dbus::{self, Connection, Value},
+        platform,
+    },
+    app::msg::Msg,
+    domain::{
+        device::Device,
+        paths::{DevicePath, IsoPath},
+        units::ByteSize,
+        writer,
+    },
+    event::Event,
+    helper::Request,
+    log,
+};
+use std::{
+    ffi::OsString,
+    fs::File,
+    os::unix::ffi::{OsStrExt, OsStringExt},
+    path::PathBuf,
+};
+
+const SERVICE: &str = "org.freedesktop.UDisks2";
+const MANAGER: &str = "/org/freedesktop/UDisks2";
+const OBJECT_MANAGER: &str = "org.freedesktop.DBus.ObjectManager";
+const BLOCK: &str = "org.freedesktop.UDisks2.Block";
+const PARTITION: &str = "org.freedesktop.UDisks2.Partition";
+const FILESYSTEM: &str = "org.freedesktop.UDisks2.Filesystem";
+const DRIVE: &str = "org.freedesktop.UDisks2.Drive";
+
+/// Add the drives udisks knows to `devices`, tagging the ones lsblk
+/// already found. Leaves `devices` alone when udisks is not running.
+pub fn supplement(devices: &mut Vec<Device>) {
+    match Connection::system().and_then(|mut conn| list_devices(&mut conn)) {
+        Ok(found) => {
+            log::debug("udisks", format_args!("{} drives", found.len()));
+            merge(devices, found);
+        }
+        Err(e) => log::debug("udisks", format_args!("not used: {}", e)),
+    }
+}
+
+fn merge(devices: &mut Vec<Device>, found: Vec<Device>) {
+    for device in found {
+        match devices.iter_mut().find(|d| d.path == device.path) {
+            Some(known) => known.udisks = device.udisks,
+            None => devices.push(device),
+        }
+    }
+}
+
+/// Object path and interfaces (`a{sa{sv}}`) of everything udisks exports
+fn managed_objects(conn: &mut Connection) -> Result<Vec<(String, Value)>, String> {
+    let reply = conn.call(
+        SERVICE,
+        MANAGER,
+        OBJECT_MANAGER,
+        "GetManagedObjects",
+        vec![],
+    )?;
+    let objects = reply
+        .body
+        .first()
+        .and_then(Value::as_array)
+        .ok_or("udisks: unexpected GetManagedObjects reply")?;
+    Ok(objects
+        .iter()
+        .filter_map(|entry| match entry {
+            Value::DictEntry(path, ifaces) => {
+                Some((path.as_str()?.to_string(), (**ifaces).clone()))
+            }
+            _ => None,
+        })
+        .collect())
+}
+
+fn prop<'a>(ifaces: &'a Value, interface: &str, name: &str) -> Option<&'a Value> {
+    ifaces.get(interface)?.get(name)
+}
+
+fn mount_points(ifaces: &Value) -> Vec<Vec<u8>> {
+    prop(ifaces, FILESYSTEM, "MountPoints")
+        .and_then(Value::as_array)
+        .map(|mounts| mounts.iter().filter_map(Value::as_bytes).collect())
+        .unwrap_or_default()
+}
+
+/// Whole disks with a drive behind them, filtered like lsblk's list
+fn list_devices(conn: &mut Connection) -> Result<Vec<Device>, String> {
+    let objects = managed_objects(conn)?;
+    let find = |path: &str| objects.iter().find(|(p, _)| p == path).map(|(_, v)| v);
+    let mut out = Vec::new();
+    for (path, ifaces) in &objects {
+        if ifaces.get(BLOCK).is_none() || ifaces.get(PARTITION).is_some() {
+            continue;
+        }
+        let Some(node) = prop(ifaces, BLOCK, "Device").and_then(Value::as_bytes) else {
+            continue;
+        };
+        // Loop devices, RAID and the like have no drive
+        let drive_path = prop(ifaces, BLOCK, "Drive").and_then(Value::as_str);
+        let Some(drive) = drive_path.and_then(find) else {
+            continue;
+        };
+        if prop(ifaces, BLOCK, "ReadOnly").and_then(Value::as_bool) == Some(true) {
+            continue;
+        }
+        let partitions: Vec<&Value> = objects
+            .iter()
+            .filter(|(_, v)| prop(v, PARTITION, "Table").and_then(Value::as_str) == Some(path))
+            .map(|(_, v)| v)
+            .collect();
+        let mounts: Vec<Vec<u8>> = std::iter::once(ifaces)
+            .chain(partitions.iter().copied())
+            .flat_map(mount_points)
+            .collect();
+        if mounts.iter().any(|m| m == b"/") {
+            continue;
+        }
+        let mut labels: Vec<String> = Vec::new();
+        for label in partitions
+            .iter()
+            .filter_map(|p| prop(p, BLOCK, "IdLabel").and_then(Value::as_str))
+        {
+            if !label.is_empty() && !labels.iter().any(|l| l == label) {
+                labels.push(label.to_string());
+            }
+        }
+        let text = |name: &str| {
+            prop(drive, DRIVE, name)
+                .and_then(Value::as_str)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+        };
+        let removable = prop(drive, DRIVE, "Removable")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        let tran = text("ConnectionBus");
+        let device_path = DevicePath::from(PathBuf::from(OsString::from_vec(node)));
+        out.push(Device {
+            name: device_path
+                .as_path()
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            size: ByteSize::new(
+                prop(ifaces, BLOCK, "Size")
+                    .and_then(Value::as_u64)
+                    .unwrap_or(0),
+            ),
+            model: text("Model"),
+            serial: text("Serial"),
+            hotplug: removable || tran.as_deref() == Some("usb"),
+            tran,
+            removable,
+            ro: false,
+            mounted: !mounts.is_empty(),
+            labels,
+            access: platform::device_access(device_path.as_path()),
+            udisks: Some(path.clone()),
+            path: device_path,
+        });
+    }
+    Ok(out)
+}
+
+/// Run a raw write or verify through udisks, reporting like the writer does
+pub fn run(request: Request, tx: tokio::sync::mpsc::UnboundedSender<Event>) {
+    log::info("udisks", format_args!("{:?}", request));
+    match Connection::system() {
+        Ok(mut conn) => run_on(&mut conn, request, &tx),
+        Err(e) => {
+            for msg in request.failed(e) {
+                let _: Result<_, _> = tx.send(Event::App(msg));
+            }
+        }
+    }
+}
+
+fn run_on(conn: &mut Connection, request: Request, tx: &tokio::sync::mpsc::UnboundedSender<Event>) {
+    match request {
+        Request::Write {
+            iso,
+            device,
+            buffer_size,
+        } => {
+            let result = write_on(conn, &iso, &device, buffer_size, tx);
+            let _: Result<_, _> = tx.send(Event::App(Msg::WriteFinished(result)));
+        }
+        Request::Verify { iso, device, size } => writer::verify_against(&iso, size, tx, || {
+            target(conn, &device)
+                .and_then(|t| open_device(conn, &t.block, "r", 0))
+                .map_err(|e| format!("Failed to open device for verify: {}", e))
+        }),
+        Request::Eject { .. } => {
+            for msg in request.failed("udisks does not eject".to_string()) {
+                let _: Result<_, _> = tx.send(Event::App(msg));
+            }
+        }
+    }
+}
+
+fn write_on(
+    conn: &mut Connection,
+    iso: &IsoPath,
+    device: &DevicePath,
+    buffer_size: usize,
+    tx: &tokio::sync::mpsc::UnboundedSender<Event>,
+) -> Result<(), String> {
+    let target = target(conn, device)?;
+    for fs in &target.mounted {
+        let unmounted = conn
+            .call(SERVICE, fs, FILESYSTEM, "Unmount", vec![dbus::dict(vec![])])
+            .map(drop);
+        writer::warn(tx, device, unmounted);
+    }
+    writer::copy_image(iso, buffer_size, tx, || {
+        open_device(conn, &target.block, "rw", libc::O_EXCL)
+            .map_err(|e| format!("Failed to open device {}: {}", device, e))
+    })?;
+    let rescanned = conn
+        .call(
+            SERVICE,
+            &target.block,
+            BLOCK,
+            "Rescan",
+            vec![dbus::dict(vec![])],
+        )
+        .map(drop);
+    writer::warn(tx, device, rescanned);
+    Ok(())
+}
+
+/// A disk's block object and its mounted filesystems
+struct Target {
+    block: String,
+    mounted: Vec<String>,
+}
+
+fn target(conn: &mut Connection, device: &DevicePath) -> Result<Target, String> {
+    let objects = managed_objects(conn)?;
+    let node = device.as_path().as_os_str().as_bytes();
+    let block = objects
+        .iter()
+        .find(|(_, v)| {
+            prop(v, BLOCK, "Device")
+                .and_then(Value::as_bytes)
+                .as_deref()
+                == Some(node)
+        })
+        .map(|(p, _)| p.clone())
+        .ok_or_else(|| format!("udisks does not know {}", device))?;
+    let mounted = objects
+        .iter()
+        .filter(|(p, v)| {
+            (*p == block || prop(v, PARTITION, "Table").and_then(Value::as_str) == Some(&block))
+                && !mount_points(v).is_empty()
+        })
+        .map(|(p, _)| p.clone())
+        .collect();
+    Ok(Target { block, mounted })
+}
+
+/// `Block.OpenDevice`: udisks checks polkit, opens the node and passes the fd
+fn open_device(conn: &mut Connection, block: &str, mode: &str, flags: i32) -> Result<File, String> {
+    let options = dbus::dict(vec![("flags", Value::I32(flags | libc::O_CLOEXEC))]);
+    let mut reply = conn.call(
+        SERVICE,
+        block,
+        BLOCK,
+        "OpenDevice",
+        vec![Value::Str(mode.to_string()), options],
+    )?;
+    match reply.body.first() {
+        Some(&Value::Fd(index)) if (index as usize) < reply.fds.len() => {
+            Ok(File::from(reply.fds.remove(index as usize)))
+        }
+        _ => Err("udisks did not pass a file descriptor".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::dbus::{METHOD_CALL, Message, bytestring, dict};
+    use std::{
+        io::{BufRead, BufReader},
+        process::{Command, Stdio},
+        sync::mpsc,
+    };
+
+    const DISK: &str = "/org/freedesktop/UDisks2/block_devices/sdz";
+    const PART: &str = "/org/freedesktop/UDisks2/block_devices/sdz1";
+    const STICK: &str = "/org/freedesktop/UDisks2/drives/Mock_Stick";
+    const SYSTEM: &str = "/org/freedesktop/UDisks2/block_devices/sdy";
+    const SYSTEM_ROOT: &str = "/org/freedesktop/UDisks2/block_devices/sdy1";
+    const SSD: &str = "/org/freedesktop/UDisks2/drives/Mock_SSD";
+
+    fn object(path: &str, ifaces: Vec<(&str, Value)>) -> Value {
+        let ifaces = ifaces
+            .into_iter()
+            .map(|(name, props)| {
+                Value::DictEntry(Box::new(Value::Str(name.to_string())), Box::new(props))
+            })
+            .collect();
+        Value::DictEntry(
+            Box::new(Value::Path(path.to_string())),
+            Box::new(Value::Array("{sa{sv}}".to_string(), ifaces)),
+        )
+    }
+
+    fn block(node: &str, drive: &str) -> Value {
+        dict(vec![
+            ("Device", bytestring(node)),
+            ("Size", Value::U64(8 << 30)),
+            ("ReadOnly", Value::Bool(false)),
+            ("Drive", Value::Path(drive.to_string())),
+            ("IdLabel", Value::Str("STICK".to_string())),
+        ])
+    }
+
+    fn partition(table: &str, mount: &str) -> Vec<(&'static str, Value)> {
+        vec![
+            (
+                PARTITION,
+                dict(vec![("Table", Value::Path(table.to_string()))]),
+            ),
+            (
+                FILESYSTEM,
+                dict(vec![(
+                    "MountPoints",
+                    Value::Array("ay".to_string(), vec![bytestring(mount)]),
+                )]),
+            ),
+        ]
+    }
+
+    fn managed_objects() -> Value {
+        let drive = |model: &str, removable: bool| {
+            dict(vec![
+                ("Model", Value::Str(model.to_string())),
+                ("Serial", Value::Str("M0CK".to_string())),
+                ("Removable", Value::Bool(removable)),
+                ("ConnectionBus", Value::Str("usb".to_string())),
+            ])
+        };
+        let mut part = vec![(BLOCK, block("/dev/sdz1", STICK))];
+        part.extend(partition(DISK, "/media/stick"));
+        let mut root = vec![(BLOCK, block("/dev/sdy1", SSD))];
+        root.extend(partition(SYSTEM, "/"));
+        Value::Array(
+            "{oa{sa{sv}}}".to_string(),
+            vec![
+                object(DISK, vec![(BLOCK, block("/dev/sdz", STICK))]),
+                object(PART, part),
+                object(STICK, vec![(DRIVE, drive("Mock Stick", true))]),
+                object(SYSTEM, vec![(BLOCK, block("/dev/sdy", SSD))]),
+                object(SYSTEM_ROOT, root),
+                object(SSD, vec![(DRIVE, drive("Mock SSD", false))]),
+            ],
+        )
+    }
+
+    /// Stand-in udisks daemon; `disk` plays /dev/sdz
+    fn serve(address: &str, disk: PathBuf, calls: mpsc::Sender<String>) {
+        let mut conn = Connection::open(address).unwrap();
+        conn.call(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "RequestName",
+            vec![Value::Str(SERVICE.to_string()), Value::U32(4)],
+        )
+        .unwrap();
+        calls.send("ready".to_string()).unwrap();
+        while let Ok(call) = conn.receive() {
+            if call.kind != METHOD_CALL {
+                continue;
+            }
+            let member = call.member.clone().unwrap_or_default();
+            let _ = calls.send(format!(
+                "{} {}",
+                member,
+                call.path.clone().unwrap_or_default()
+            ));
+            let reply = match member.as_str() {
+                "GetManagedObjects" => Message::reply(&call, vec![managed_objects()]),
+                "OpenDevice" => {
+                    let write = call.body.first().and_then(Value::as_str) == Some("rw");
+                    let file = std::fs::OpenOptions::new()
+                        .read(true)
+                        .write(write)
+                        .open(&disk)
+                        .unwrap();
+                    let mut reply = Message::reply(&call, vec![Value::Fd(0)]);
+                    reply.fds.push(file.into());
+                    reply
+                }
+                "Unmount" | "Rescan" => Message::reply(&call, vec![]),
+                _ => Message::error(&call, "org.freedesktop.DBus.Error.UnknownMethod", &member),
+            };
+            if conn.send(reply).is_err() {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn drives_are_listed_and_written_through_a_mock_udisks() {
+        if !platform::has_command("dbus-daemon") {
+            eprintln!("skipping: dbus-daemon is not installed");
+            return;
+        }
+        let dir = std::env::temp_dir().join(format!("ferric-udisks-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = dir.join("bus.conf");
+        std::fs::write(
+            &config,
+            format!(
+                "<busconfig><type>session</type><listen>unix:path={}</listen>\
+                 <auth>EXTERNAL</auth><policy context=\"default\">\
+                 <allow send_destination=\"*\" eavesdrop=\"true\"/>\
+                 <allow eavesdrop=\"true\"/><allow own=\"*\"/>\
+                 </policy></busconfig>",
+                dir.join("bus").display()
+            ),
+        )
+        .unwrap();
+        let mut daemon = Command::new("dbus-daemon")
+            .arg(format!("--config-file={}", config.display()))
+            .args(["--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+        let address = address.trim().to_string();
+
+        let iso = dir.join("image.iso");
+        let disk = dir.join("sdz");
+        let image: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&iso, &image).unwrap();
+        std::fs::write(&disk, vec![0u8; image.len()]).unwrap();
+        let (calls_tx, calls) = mpsc::channel();
+        let service_address = address.clone();
+        let service_disk = disk.clone();
+        std::thread::spawn(move || serve(&service_address, service_disk, calls_tx));
+        assert_eq!(calls.recv().unwrap(), "ready");
+
+        let mut conn = Connection::open(&address).unwrap();
+        let devices = list_devices(&mut conn).unwrap();
+        assert_eq!(devices.len(), 1, "the disk holding / is left out");
+        let stick = &devices[0];
+        assert_eq!(stick.name, "sdz");
+        assert_eq!(stick.model.as_deref(), Some("Mock Stick"));
+        assert!(stick.removable && stick.hotplug && stick.mounted);
+        assert_eq!(stick.labels, ["STICK"]);
+        assert_eq!(stick.udisks.as_deref(), Some(DISK));
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let device = DevicePath::from(PathBuf::from("/dev/sdz"));
+        let iso = IsoPath::from(iso);
+        run_on(
+            &mut conn,
+            Request::Write {
+                iso: iso.clone(),
+                device: device.clone(),
+                buffer_size: 64 * 1024,
+            },
+            &tx,
+        );
+        run_on(
+            &mut conn,
+            Request::Verify {
+                iso,
+                device,
+                size: image.len() as u64,
+            },
+            &tx,
+        );
+        drop(tx);
+        let mut finished = Vec::new();
+        while let Some(Event::App(msg)) = rx.blocking_recv() {
+            match msg {
+                Msg::WriteFinished(r) | Msg::VerifyFinished(r) => finished.push(r),
+                Msg::WriteWarning(w) => panic!("unexpected warning: {}", w),
+                _ => {}
+            }
+        }
+        assert_eq!(finished, [Ok(()), Ok(())]);
+        assert_eq!(std::fs::read(&disk).unwrap(), image);
+        let calls: Vec<String> = calls.try_iter().collect();
+        assert!(calls.contains(&format!("Unmount {}", PART)));
+        assert!(calls.contains(&format!("OpenDevice {}", DISK)));
+        assert!(calls.contains(&format!("Rescan {}", DISK)));
+
+        let _ = daemon.kill();
+        let _ = daemon.wait();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,20 +1,23 @@
-use super::msg::{Cmd, Msg};
-use crate::{adapters, domain, event::Event};
+use super::{
+    effects::Effects,
+    msg::{Cmd, Msg},
+};
+use crate::{domain, event::Event, log};
 use tokio::task;
 
-pub fn spawn_all(cmds: Vec<Cmd>, tx: tokio::sync::mpsc::UnboundedSender<Event>) {
+pub fn spawn_all(cmds: Vec<Cmd>, effects: &Effects, tx: tokio::sync::mpsc::UnboundedSender<Event>) {
     for cmd in cmds {
+        log::debug("cmd", format_args!("{:?}", cmd));
         match cmd {
             Cmd::Noop => {}
-            Cmd::ScanIso { query } => {
+            Cmd::ScanIso { options } => {
                 let tx = tx.clone();
+                let scanner = effects.scanner.clone();
                 task::spawn(async move {
-                    let res =
-                        task::spawn_blocking(move || domain::iso::scan_default_roots(&query)).await;
+                    let tx2 = tx.clone();
+                    let res = task::spawn_blocking(move || scanner.scan(options, tx2)).await;
                     match res {
-                        Ok(results) => {
-                            let _: Result<_, _> = tx.send(Event::App(Msg::IsoResults(results)));
-                        }
+                        Ok(()) => {}
                         Err(e) => {
                             let _: Result<_, _> =
                                 tx.send(Event::App(Msg::IsoSearchFailed(e.to_string())));
@@ -22,10 +25,64 @@ pub fn spawn_all(cmds: Vec<Cmd>, tx: tokio::sync::mpsc::UnboundedSender<Event>)
                     }
                 });
             }
+            Cmd::BrowseOpen { path } => {
+                let tx = tx.clone();
+                task::spawn(async move {
+                    let res = task::spawn_blocking(move || domain::browse::open(&path)).await;
+                    let opened = res.unwrap_or_else(|e| Err(e.to_string()));
+                    let _: Result<_, _> = tx.send(Event::App(Msg::BrowseOpened(opened)));
+                });
+            }
+            Cmd::CompletePath { dir, input } => {
+                let tx = tx.clone();
+                task::spawn(async move {
+                    let typed = input.clone();
+                    let res =
+                        task::spawn_blocking(move || domain::browse::complete(&dir, &typed)).await;
+                    if let Ok(completed) = res {
+                        let _: Result<_, _> =
+                            tx.send(Event::App(Msg::PathCompleted { input, completed }));
+                    }
+                });
+            }
+            Cmd::RecordHistory(entries) => {
+                let tx = tx.clone();
+                let history = effects.history.clone();
+                task::spawn(async move {
+                    let res = task::spawn_blocking(move || history.record(entries)).await;
+                    let recorded = res.unwrap_or_else(|e| Err(e.to_string()));
+                    let _: Result<_, _> = tx.send(Event::App(Msg::HistoryRecorded(recorded)));
+                });
+            }
+            Cmd::Eject { devices } => {
+                let tx = tx.clone();
+                let mounter = effects.mounter.clone();
+                task::spawn_blocking(move || {
+                    for device in devices {
+                        let result = mounter.eject(device.as_path());
+                        let _: Result<_, _> = tx.send(Event::App(Msg::Ejected { device, result }));
+                    }
+                });
+            }
+            Cmd::ExportReport { report, dir } => {
+                let tx = tx.clone();
+                task::spawn(async move {
+                    let res = task::spawn_blocking(move || {
+                        let dir = dir
+                            .or_else(domain::report::default_dir)
+                            .ok_or_else(|| "no home directory for reports".to_string())?;
+                        domain::report::export(report, &dir)
+                    })
+                    .await;
+                    let exported = res.unwrap_or_else(|e| Err(e.to_string()));
+                    let _: Result<_, _> = tx.send(Event::App(Msg::ReportExported(exported)));
+                });
+            }
             Cmd::RefreshDevices => {
                 let tx = tx.clone();
+                let provider = effects.devices.clone();
                 task::spawn(async move {
-                    let res = task::spawn_blocking(adapters::lsblk::refresh_devices).await;
+                    let res = task::spawn_blocking(move || provider.devices()).await;
                     match res {
                         Ok(devs) => {
                             let _: Result<_, _> = tx.send(Event::App(Msg::DevicesRefreshed(devs)));
@@ -37,12 +94,17 @@ pub fn spawn_all(cmds: Vec<Cmd>, tx: tokio::sync::mpsc::UnboundedSender<Event>)
                     }
                 });
             }
-            Cmd::Write { iso, device } => {
+            Cmd::Write {
+                iso,
+                device,
+                buffer_size,
+            } => {
                 let tx = tx.clone();
+                let writer = effects.writer.clone();
                 task::spawn(async move {
                     let tx2 = tx.clone();
                     let res =
-                        task::spawn_blocking(move || domain::writer::write_image(iso, device, tx2))
+                        task::spawn_blocking(move || writer.write(iso, device, buffer_size, tx2))
                             .await;
                     if let Err(e) = res {
                         let _: Result<_, _> = tx.send(Event::App(Msg::WriteFinished(Err(
@@ -51,59 +113,117 @@ pub fn spawn_all(cmds: Vec<Cmd>, tx: tokio::sync::mpsc::UnboundedSender<Event>)
                     }
                 });
             }
-            Cmd::Verify { iso, device, size } => {
+            Cmd::WriteWindows {
+                iso,
+                device,
+                options,
+            } => {
                 let tx = tx.clone();
+                let builder = effects.builder.clone();
                 task::spawn(async move {
                     let tx2 = tx.clone();
                     let res = task::spawn_blocking(move || {
-                        domain::writer::verify_image(iso, device, size, tx2)
+                        builder.write_windows(iso, device, options, tx2)
                     })
                     .await;
                     if let Err(e) = res {
-                        let _: Result<_, _> = tx.send(Event::App(Msg::VerifyFinished(Err(
+                        let _: Result<_, _> = tx.send(Event::App(Msg::WriteFinished(Err(
                             format!("Join error: {}", e),
                         ))));
                     }
                 });
             }
-            Cmd::ReexecWithSudo => {
-                ratatui::restore();
-                let exe = match std::env::current_exe() {
-                    Ok(p) => p,
-                    Err(e) => {
+            Cmd::WriteMultiboot { isos, device } => {
+                let tx = tx.clone();
+                let builder = effects.builder.clone();
+                task::spawn(async move {
+                    let tx2 = tx.clone();
+                    let res =
+                        task::spawn_blocking(move || builder.write_multiboot(isos, device, tx2))
+                            .await;
+                    if let Err(e) = res {
                         let _: Result<_, _> = tx.send(Event::App(Msg::WriteFinished(Err(
-                            format!("Cannot determine current executable: {}", e),
+                            format!("Join error: {}", e),
                         ))));
-                        return;
                     }
-                };
-                let args: Vec<std::ffi::OsString> = std::env::args_os().skip(1).collect();
-                let mut cmd = std::process::Command::new("sudo");
-                cmd.arg("-E").arg("--").arg(exe);
-                for a in &args {
-                    cmd.arg(a);
-                }
-                #[cfg(unix)]
-                {
-                    use std::os::unix::process::CommandExt;
-                    let _err = cmd.exec();
-                    let _: Result<_, _> = tx.send(Event::App(Msg::WriteFinished(Err(
-                        "Failed to exec sudo".to_string(),
-                    ))));
-                    std::process::exit(1);
-                }
-                #[cfg(not(unix))]
-                {
-                    match cmd.status() {
-                        Ok(st) if st.success() => std::process::exit(0),
-                        _ => {
-                            let _: Result<_, _> = tx.send(Event::App(Msg::WriteFinished(Err(
-                                "Failed to run sudo".to_string(),
-                            ))));
+                });
+            }
+            Cmd::CreatePersistence {
+                device,
+                image_size,
+                kind,
+            } => {
+                let tx = tx.clone();
+                let builder = effects.builder.clone();
+                task::spawn(async move {
+                    let tx2 = tx.clone();
+                    let res = task::spawn_blocking(move || {
+                        builder.create_persistence(device, image_size, kind, tx2)
+                    })
+                    .await;
+                    if let Err(e) = res {
+                        let _: Result<_, _> = tx.send(Event::App(Msg::PersistenceFinished(Err(
+                            format!("Join error: {}", e),
+                        ))));
+                    }
+                });
+            }
+            Cmd::WriteBatch {
+                iso,
+                devices,
+                verify,
+                buffer_size,
+            } => {
+                let tx = tx.clone();
+                let writer = effects.writer.clone();
+                task::spawn(async move {
+                    let tx2 = tx.clone();
+                    let targets = devices.clone();
+                    let res = task::spawn_blocking(move || {
+                        writer.write_batch(iso, devices, verify, buffer_size, tx2)
+                    })
+                    .await;
+                    if let Err(e) = res {
+                        for device in targets {
+                            let _: Result<_, _> = tx.send(Event::App(Msg::TargetWriteFinished {
+                                device,
+                                result: Err(format!("Join error: {}", e)),
+                            }));
                         }
                     }
-                }
+                });
+            }
+            Cmd::Verify { iso, device, size } => {
+                let tx = tx.clone();
+                let writer = effects.writer.clone();
+                task::spawn(async move {
+                    let tx2 = tx.clone();
+                    let res =
+                        task::spawn_blocking(move || writer.verify(iso, device, size, tx2)).await;
+                    if let Err(e) = res {
+                        let _: Result<_, _> = tx.send(Event::App(Msg::VerifyFinished(Err(
+                            format!("Join error: {}", e),
+                        ))));
+                    }
+                });
+            }
+            Cmd::Helper(request) => {
+                let tx = tx.clone();
+                let privileged = effects.privileged.clone();
+                task::spawn_blocking(move || {
+                    if let Err(e) = privileged.helper(&request) {
+                        for msg in request.failed(e) {
+                            let _: Result<_, _> = tx.send(Event::App(msg));
+                        }
+                    }
+                });
+            }
+            Cmd::Udisks(request) => {
+                let tx = tx.clone();
+                let privileged = effects.privileged.clone();
+                task::spawn_blocking(move || privileged.udisks(request, tx));
             }
+            Cmd::StartHelper(_) | Cmd::OpenLog => {}
         }
     }
 }
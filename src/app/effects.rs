@@ -0,0 +1,648 @@
+//! The side effects `cmd::spawn_all` performs, behind traits.
+//!
+//! [`Effects::system`] lists real devices, unmounts real partitions and
+//! writes real disks; [`Effects::simulated`] sends all of it to the dry-run
+//! stick instead; the [`fake`] implementations script all of that so whole
+//! journeys can be driven through `App` in tests.
+
+use crate::{
+    adapters,
+    app::msg::Msg,
+    domain::{
+        self,
+        device::Device,
+        history::HistoryEntry,
+        iso::ScanOptions,
+        paths::{DevicePath, IsoPath},
+        persistence::PersistenceKind,
+        simulate::Simulation,
+        windows::WindowsOptions,
+    },
+    event::Event,
+    helper::Request,
+};
+use std::{path::Path, sync::Arc};
+use tokio::sync::mpsc::UnboundedSender;
+
+/// Lists the drives that can be written
+pub trait DeviceProvider: Send + Sync {
+    fn devices(&self) -> Vec<Device>;
+}
+
+/// Mount-table and partition-table chores around a write
+pub trait Mounter: Send + Sync {
+    /// Unmount everything mounted from the disk or its partitions
+    fn unmount_partitions_of(&self, device: &Path) -> Result<(), String>;
+    /// Have the kernel re-read the partition table
+    fn partprobe(&self, device: &Path) -> Result<(), String>;
+    /// Detach the disk; whether its port was powered off
+    fn eject(&self, device: &Path) -> Result<bool, String>;
+}
+
+/// Raw image writes and verifies. Both run on a blocking thread and report
+/// through `tx`: `WriteStarted`, `WriteProgress`, `WriteFinished` and the
+/// `Verify*` counterparts.
+pub trait BlockWriter: Send + Sync {
+    fn write(
+        &self,
+        iso: IsoPath,
+        device: DevicePath,
+        buffer_size: usize,
+        tx: UnboundedSender<Event>,
+    );
+    fn verify(&self, iso: IsoPath, device: DevicePath, size: u64, tx: UnboundedSender<Event>);
+    /// The same image onto several devices at once, reporting per device:
+    /// `TargetWriteFinished` and, with `verify`, `TargetVerifyFinished`
+    fn write_batch(
+        &self,
+        iso: IsoPath,
+        devices: Vec<DevicePath>,
+        verify: bool,
+        buffer_size: usize,
+        tx: UnboundedSender<Event>,
+    );
+}
+
+/// Jobs that partition and format the stick rather than copy an image
+/// onto it. Each ends with `WriteFinished`, or `PersistenceFinished`.
+pub trait DiskBuilder: Send + Sync {
+    fn write_windows(
+        &self,
+        iso: IsoPath,
+        device: DevicePath,
+        options: WindowsOptions,
+        tx: UnboundedSender<Event>,
+    );
+    fn write_multiboot(&self, isos: Vec<IsoPath>, device: DevicePath, tx: UnboundedSender<Event>);
+    fn create_persistence(
+        &self,
+        device: DevicePath,
+        image_size: u64,
+        kind: PersistenceKind,
+        tx: UnboundedSender<Event>,
+    );
+}
+
+/// Whoever opens a device this process cannot: the root helper or udisks
+pub trait Privileged: Send + Sync {
+    /// Hand `request` to the running helper, whose events arrive on its own
+    fn helper(&self, request: &Request) -> Result<(), String>;
+    /// Run `request` through UDisks2, reporting through `tx`
+    fn udisks(&self, request: Request, tx: UnboundedSender<Event>);
+}
+
+/// Finds images under the search roots and reports them through `tx`,
+/// blocking while it watches for changes
+pub trait ImageScanner: Send + Sync {
+    fn scan(&self, options: ScanOptions, tx: UnboundedSender<Event>);
+}
+
+/// Where finished jobs are kept
+pub trait HistoryStore: Send + Sync {
+    /// Append `entries`; hands them back with the image hashes filled in
+    fn record(&self, entries: Vec<HistoryEntry>) -> Result<Vec<HistoryEntry>, String>;
+}
+
+/// Everything `App` reaches the outside world through
+#[derive(Clone)]
+pub struct Effects {
+    pub devices: Arc<dyn DeviceProvider>,
+    pub mounter: Arc<dyn Mounter>,
+    pub writer: Arc<dyn BlockWriter>,
+    pub scanner: Arc<dyn ImageScanner>,
+    pub history: Arc<dyn HistoryStore>,
+    pub builder: Arc<dyn DiskBuilder>,
+    pub privileged: Arc<dyn Privileged>,
+}
+
+impl Effects {
+    /// The real machine; with `udisks`, drives are also listed through UDisks2
+    pub fn system(udisks: bool) -> Self {
+        Self {
+            devices: Arc::new(Drives { udisks }),
+            mounter: Arc::new(System),
+            writer: Arc::new(System),
+            scanner: Arc::new(System),
+            history: Arc::new(System),
+            builder: Arc::new(System),
+            privileged: Arc::new(System),
+        }
+    }
+
+    /// A dry run: images are still found and the history read, but every
+    /// device effect goes to the simulated stick
+    pub fn simulated(simulation: Simulation) -> Self {
+        let simulated = Arc::new(Simulated(Arc::new(simulation)));
+        Self {
+            devices: simulated.clone(),
+            mounter: simulated.clone(),
+            writer: simulated.clone(),
+            builder: simulated.clone(),
+            privileged: simulated,
+            ..Self::system(false)
+        }
+    }
+}
+
+impl Default for Effects {
+    fn default() -> Self {
+        Self::system(false)
+    }
+}
+
+impl std::fmt::Debug for Effects {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Effects").finish_non_exhaustive()
+    }
+}
+
+/// The drives lsblk lists, plus the ones only udisks knows when asked to
+#[derive(Debug, Clone, Copy)]
+pub struct Drives {
+    pub udisks: bool,
+}
+
+impl DeviceProvider for Drives {
+    fn devices(&self) -> Vec<Device> {
+        let mut devices = adapters::lsblk::refresh_devices();
+        if self.udisks {
+            adapters::udisks::supplement(&mut devices);
+        }
+        devices
+    }
+}
+
+/// sysfs, the mount table, the helper and the device nodes themselves
+#[derive(Debug, Clone, Copy)]
+pub struct System;
+
+impl Mounter for System {
+    fn unmount_partitions_of(&self, device: &Path) -> Result<(), String> {
+        adapters::platform::unmount_partitions_of(device)
+    }
+
+    fn partprobe(&self, device: &Path) -> Result<(), String> {
+        adapters::platform::partprobe(device)
+    }
+
+    fn eject(&self, device: &Path) -> Result<bool, String> {
+        adapters::platform::eject(device)
+    }
+}
+
+impl BlockWriter for System {
+    fn write(
+        &self,
+        iso: IsoPath,
+        device: DevicePath,
+        buffer_size: usize,
+        tx: UnboundedSender<Event>,
+    ) {
+        domain::writer::write_image(iso, device, buffer_size, self, tx)
+    }
+
+    fn verify(&self, iso: IsoPath, device: DevicePath, size: u64, tx: UnboundedSender<Event>) {
+        domain::writer::verify_image(iso, device, size, tx)
+    }
+
+    fn write_batch(
+        &self,
+        iso: IsoPath,
+        devices: Vec<DevicePath>,
+        verify: bool,
+        buffer_size: usize,
+        tx: UnboundedSender<Event>,
+    ) {
+        domain::batch::write_batch(iso, devices, verify, buffer_size, self, tx)
+    }
+}
+
+impl DiskBuilder for System {
+    fn write_windows(
+        &self,
+        iso: IsoPath,
+        device: DevicePath,
+        options: WindowsOptions,
+        tx: UnboundedSender<Event>,
+    ) {
+        domain::windows::write_windows(iso, device, options, self, tx)
+    }
+
+    fn write_multiboot(&self, isos: Vec<IsoPath>, device: DevicePath, tx: UnboundedSender<Event>) {
+        domain::multiboot::write_multiboot(isos, device, self, tx)
+    }
+
+    fn create_persistence(
+        &self,
+        device: DevicePath,
+        image_size: u64,
+        kind: PersistenceKind,
+        tx: UnboundedSender<Event>,
+    ) {
+        domain::persistence::create_persistence(device, image_size, kind, self, tx)
+    }
+}
+
+impl Privileged for System {
+    fn helper(&self, request: &Request) -> Result<(), String> {
+        crate::helper::send(request)
+    }
+
+    fn udisks(&self, request: Request, tx: UnboundedSender<Event>) {
+        adapters::udisks::run(request, tx)
+    }
+}
+
+impl ImageScanner for System {
+    fn scan(&self, options: ScanOptions, tx: UnboundedSender<Event>) {
+        domain::iso_index::scan_and_watch(options, tx)
+    }
+}
+
+impl HistoryStore for System {
+    fn record(&self, entries: Vec<HistoryEntry>) -> Result<Vec<HistoryEntry>, String> {
+        domain::history::record(entries)
+    }
+}
+
+/// What a dry run answers for the jobs it does not simulate
+const NOT_SIMULATED: &str = "not simulated in a dry run";
+
+fn send_all(tx: &UnboundedSender<Event>, msgs: impl IntoIterator<Item = Msg>) {
+    for msg in msgs {
+        let _: Result<_, _> = tx.send(Event::App(msg));
+    }
+}
+
+/// The dry-run stick standing in for every device: raw writes and verifies
+/// reach it, anything that would partition a disk is refused
+#[derive(Debug)]
+pub struct Simulated(pub Arc<Simulation>);
+
+impl DeviceProvider for Simulated {
+    // Nothing else can be touched, so nothing else is offered
+    fn devices(&self) -> Vec<Device> {
+        vec![domain::simulate::device()]
+    }
+}
+
+impl Mounter for Simulated {
+    fn unmount_partitions_of(&self, _: &Path) -> Result<(), String> {
+        self.0.unmount()
+    }
+
+    fn partprobe(&self, _: &Path) -> Result<(), String> {
+        self.0.partprobe()
+    }
+
+    // Nothing to power off; the stick is as safe to unplug as it gets
+    fn eject(&self, _: &Path) -> Result<bool, String> {
+        Ok(true)
+    }
+}
+
+impl BlockWriter for Simulated {
+    fn write(
+        &self,
+        iso: IsoPath,
+        device: DevicePath,
+        buffer_size: usize,
+        tx: UnboundedSender<Event>,
+    ) {
+        domain::writer::write_onto(&iso, &device, buffer_size, self, &tx, || {
+            self.0.open_write()
+        })
+    }
+
+    fn verify(&self, iso: IsoPath, _: DevicePath, size: u64, tx: UnboundedSender<Event>) {
+        domain::writer::verify_against(&iso, size, &tx, || self.0.open_read())
+    }
+
+    fn write_batch(
+        &self,
+        _: IsoPath,
+        devices: Vec<DevicePath>,
+        _: bool,
+        _: usize,
+        tx: UnboundedSender<Event>,
+    ) {
+        send_all(
+            &tx,
+            devices.into_iter().map(|device| Msg::TargetWriteFinished {
+                device,
+                result: Err(NOT_SIMULATED.to_string()),
+            }),
+        )
+    }
+}
+
+impl DiskBuilder for Simulated {
+    fn write_windows(
+        &self,
+        _: IsoPath,
+        _: DevicePath,
+        _: WindowsOptions,
+        tx: UnboundedSender<Event>,
+    ) {
+        send_all(&tx, [Msg::WriteFinished(Err(NOT_SIMULATED.to_string()))])
+    }
+
+    fn write_multiboot(&self, _: Vec<IsoPath>, _: DevicePath, tx: UnboundedSender<Event>) {
+        send_all(&tx, [Msg::WriteFinished(Err(NOT_SIMULATED.to_string()))])
+    }
+
+    fn create_persistence(
+        &self,
+        _: DevicePath,
+        _: u64,
+        _: PersistenceKind,
+        tx: UnboundedSender<Event>,
+    ) {
+        send_all(
+            &tx,
+            [Msg::PersistenceFinished(Err(NOT_SIMULATED.to_string()))],
+        )
+    }
+}
+
+impl Privileged for Simulated {
+    fn helper(&self, _: &Request) -> Result<(), String> {
+        Err(NOT_SIMULATED.to_string())
+    }
+
+    fn udisks(&self, request: Request, tx: UnboundedSender<Event>) {
+        send_all(&tx, request.failed(NOT_SIMULATED.to_string()))
+    }
+}
+
+/// Scripted stand-ins that touch nothing
+#[cfg(any(test, feature = "test-support"))]
+pub mod fake {
+    use super::*;
+    use crate::domain::iso::IsoMeta;
+    use std::sync::Mutex;
+
+    /// Hands out whatever list it was last given
+    #[derive(Debug, Default)]
+    pub struct Devices(Mutex<Vec<Device>>);
+
+    impl Devices {
+        pub fn new(devices: Vec<Device>) -> Self {
+            Self(Mutex::new(devices))
+        }
+
+        /// What the next refresh sees, e.g. after a stick is plugged in
+        pub fn set(&self, devices: Vec<Device>) {
+            *self.0.lock().unwrap() = devices;
+        }
+    }
+
+    impl DeviceProvider for Devices {
+        fn devices(&self) -> Vec<Device> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    /// Records each call as `"unmount /dev/sdb"`, `"partprobe …"` or
+    /// `"eject …"`; fails the ones named in `failing`
+    #[derive(Debug, Default)]
+    pub struct Mounter {
+        pub failing: Vec<&'static str>,
+        calls: Mutex<Vec<String>>,
+    }
+
+    impl Mounter {
+        pub fn failing(failing: Vec<&'static str>) -> Self {
+            Self {
+                failing,
+                ..Self::default()
+            }
+        }
+
+        pub fn calls(&self) -> Vec<String> {
+            self.calls.lock().unwrap().clone()
+        }
+
+        fn call(&self, what: &'static str, device: &Path) -> Result<(), String> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("{} {}", what, device.display()));
+            match self.failing.contains(&what) {
+                true => Err(format!("fake {} failure", what)),
+                false => Ok(()),
+            }
+        }
+    }
+
+    impl super::Mounter for Mounter {
+        fn unmount_partitions_of(&self, device: &Path) -> Result<(), String> {
+            self.call("unmount", device)
+        }
+
+        fn partprobe(&self, device: &Path) -> Result<(), String> {
+            self.call("partprobe", device)
+        }
+
+        fn eject(&self, device: &Path) -> Result<bool, String> {
+            self.call("eject", device).map(|()| true)
+        }
+    }
+
+    /// Pretends to write and verify `size` bytes in one step, ending with
+    /// the scripted results
+    #[derive(Debug, Clone)]
+    pub struct Writer {
+        pub size: u64,
+        pub write: Result<(), String>,
+        pub verify: Result<(), String>,
+    }
+
+    impl Default for Writer {
+        fn default() -> Self {
+            Self {
+                size: 1024 * 1024,
+                write: Ok(()),
+                verify: Ok(()),
+            }
+        }
+    }
+
+    impl BlockWriter for Writer {
+        fn write(&self, _: IsoPath, _: DevicePath, _: usize, tx: UnboundedSender<Event>) {
+            let total = self.size;
+            for msg in [
+                Msg::WriteStarted { total },
+                Msg::WriteProgress {
+                    written: total,
+                    total,
+                    bps: total as f64,
+                },
+                Msg::WriteFinished(self.write.clone()),
+            ] {
+                let _: Result<_, _> = tx.send(Event::App(msg));
+            }
+        }
+
+        fn verify(&self, _: IsoPath, _: DevicePath, size: u64, tx: UnboundedSender<Event>) {
+            for msg in [
+                Msg::VerifyStarted { total: size },
+                Msg::VerifyProgress {
+                    checked: size,
+                    total: size,
+                    bps: size as f64,
+                },
+                Msg::VerifyFinished(self.verify.clone()),
+            ] {
+                let _: Result<_, _> = tx.send(Event::App(msg));
+            }
+        }
+
+        fn write_batch(
+            &self,
+            _: IsoPath,
+            devices: Vec<DevicePath>,
+            verify: bool,
+            _: usize,
+            tx: UnboundedSender<Event>,
+        ) {
+            for device in devices {
+                let write = Msg::TargetWriteFinished {
+                    device: device.clone(),
+                    result: self.write.clone(),
+                };
+                let checked = (verify && self.write.is_ok()).then(|| Msg::TargetVerifyFinished {
+                    device,
+                    result: self.verify.clone(),
+                });
+                send_all(&tx, std::iter::once(write).chain(checked));
+            }
+        }
+    }
+
+    /// Ends every job with the scripted result, touching no disk
+    #[derive(Debug, Clone)]
+    pub struct Builder {
+        pub result: Result<(), String>,
+    }
+
+    impl Default for Builder {
+        fn default() -> Self {
+            Self { result: Ok(()) }
+        }
+    }
+
+    impl DiskBuilder for Builder {
+        fn write_windows(
+            &self,
+            _: IsoPath,
+            _: DevicePath,
+            _: WindowsOptions,
+            tx: UnboundedSender<Event>,
+        ) {
+            send_all(&tx, [Msg::WriteFinished(self.result.clone())])
+        }
+
+        fn write_multiboot(&self, _: Vec<IsoPath>, _: DevicePath, tx: UnboundedSender<Event>) {
+            send_all(&tx, [Msg::WriteFinished(self.result.clone())])
+        }
+
+        fn create_persistence(
+            &self,
+            _: DevicePath,
+            _: u64,
+            _: PersistenceKind,
+            tx: UnboundedSender<Event>,
+        ) {
+            send_all(&tx, [Msg::PersistenceFinished(self.result.clone())])
+        }
+    }
+
+    /// Keeps the requests it is handed and answers none of them
+    #[derive(Debug, Default)]
+    pub struct Privileged(Mutex<Vec<Request>>);
+
+    impl Privileged {
+        pub fn requests(&self) -> Vec<Request> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    impl super::Privileged for Privileged {
+        fn helper(&self, request: &Request) -> Result<(), String> {
+            self.0.lock().unwrap().push(request.clone());
+            Ok(())
+        }
+
+        fn udisks(&self, request: Request, _: UnboundedSender<Event>) {
+            self.0.lock().unwrap().push(request);
+        }
+    }
+
+    /// Finds exactly these images and never watches
+    #[derive(Debug, Default)]
+    pub struct Scanner(pub Vec<IsoMeta>);
+
+    impl ImageScanner for Scanner {
+        fn scan(&self, _: ScanOptions, tx: UnboundedSender<Event>) {
+            let _: Result<_, _> = tx.send(Event::App(Msg::IsoResults(self.0.clone())));
+        }
+    }
+
+    /// Keeps the history in memory; hashes stay as recorded
+    #[derive(Debug, Default)]
+    pub struct History(Mutex<Vec<HistoryEntry>>);
+
+    impl History {
+        pub fn entries(&self) -> Vec<HistoryEntry> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    impl HistoryStore for History {
+        fn record(&self, entries: Vec<HistoryEntry>) -> Result<Vec<HistoryEntry>, String> {
+            self.0.lock().unwrap().extend(entries.iter().cloned());
+            Ok(entries)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn dry_runs_reach_only_the_simulated_stick() {
+        let dir = std::env::temp_dir().join(format!("ferric-effects-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let effects = Effects::simulated(Simulation {
+            target: domain::simulate::SimTarget::File(dir.join("stick.img")),
+            ..Simulation::default()
+        });
+        let devices = effects.devices.devices();
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].path.to_string(), "/dev/ferric-sim");
+
+        let iso = IsoPath::from(dir.join("image.iso"));
+        std::fs::write(iso.as_path(), vec![9u8; 5000]).unwrap();
+        let device = devices[0].path.clone();
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        effects
+            .writer
+            .write(iso.clone(), device.clone(), 4096, tx.clone());
+        effects.writer.verify(iso, device.clone(), 5000, tx.clone());
+        let multiboot = DevicePath::from(PathBuf::from("/dev/sdz"));
+        effects.builder.write_multiboot(vec![], multiboot, tx);
+        let mut finished = Vec::new();
+        while let Ok(Event::App(msg)) = rx.try_recv() {
+            match msg {
+                Msg::WriteFinished(r) | Msg::VerifyFinished(r) => finished.push(r),
+                _ => {}
+            }
+        }
+        assert_eq!(finished, [Ok(()), Ok(()), Err(NOT_SIMULATED.to_string())]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
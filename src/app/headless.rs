@@ -0,0 +1,190 @@
+//! Driving `App` without a terminal.
+//!
+//! [`Headless`] feeds scripted events through the same path `App::run`
+//! takes (key mapping, `update`, commands through [`Effects`]) and draws
+//! each frame into ratatui's `TestBackend`, so whole journeys can be
+//! checked against golden screens kept as text fixtures.
+//!
+//! A step is done when every command it started has finished: each
+//! message gets its own channel, and the tasks running its commands hold
+//! the only senders, so the channel closes once they return.
+
+use super::{
+    App,
+    effects::Effects,
+    msg::{Cmd, Msg},
+    state::Model,
+};
+use crate::event::{Event, EventHandler};
+use futures::stream::{self, SelectAll, StreamExt};
+use ratatui::{
+    Terminal,
+    backend::TestBackend,
+    buffer::Buffer,
+    crossterm::event::{KeyCode, KeyEvent},
+};
+use std::path::Path;
+use tokio::sync::mpsc;
+
+/// What the commands of one message send back, until they have all ended
+type Reports = SelectAll<stream::BoxStream<'static, Event>>;
+
+/// Set to rewrite snapshot fixtures from what is rendered now
+pub const BLESS_VAR: &str = "FERRIC_BLESS";
+
+pub struct Headless {
+    app: App,
+    terminal: Terminal<TestBackend>,
+}
+
+impl Headless {
+    pub fn new(model: Model, effects: Effects, width: u16, height: u16) -> Self {
+        let app = App {
+            running: true,
+            events: EventHandler::headless(),
+            model,
+            effects,
+            recorder: None,
+        };
+        let terminal =
+            Terminal::new(TestBackend::new(width, height)).expect("the test backend does not fail");
+        let mut headless = Self { app, terminal };
+        headless.draw();
+        headless
+    }
+
+    /// Trace the run like `--record` does
+    pub fn record_to(mut self, path: &Path, redact: bool) -> Result<Self, String> {
+        self.app = self.app.record_to(path, redact)?;
+        Ok(self)
+    }
+
+    /// Handle `event`, then everything the commands it started send back,
+    /// until they have all finished; then draw a frame
+    pub async fn send(&mut self, event: Event) {
+        let mut reports = Reports::new();
+        if let Some(msg) = self.app.to_msg(event) {
+            self.apply(msg, &mut reports);
+        }
+        self.settle(reports).await;
+        self.draw();
+    }
+
+    pub async fn drive(&mut self, events: impl IntoIterator<Item = Event>) {
+        for event in events {
+            self.send(event).await;
+        }
+    }
+
+    pub fn model(&self) -> &Model {
+        &self.app.model
+    }
+
+    /// False once a `Quit` went through
+    pub fn running(&self) -> bool {
+        self.app.running
+    }
+
+    pub fn buffer(&self) -> &Buffer {
+        self.terminal.backend().buffer()
+    }
+
+    /// The last frame as text, one line per row, trailing blanks trimmed
+    pub fn screen(&self) -> String {
+        let buffer = self.buffer();
+        let area = buffer.area;
+        let mut text = String::new();
+        for y in area.top()..area.bottom() {
+            let row: String = (area.left()..area.right())
+                .map(|x| buffer[(x, y)].symbol())
+                .collect();
+            text.push_str(row.trim_end());
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Compare the last frame with the fixture at `path`. With
+    /// `FERRIC_BLESS` set the fixture is written instead.
+    pub fn assert_screen(&self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+        let screen = self.screen();
+        if std::env::var_os(BLESS_VAR).is_some() {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir).expect("fixture directory");
+            }
+            std::fs::write(path, &screen).expect("fixture written");
+            return;
+        }
+        let expected = std::fs::read_to_string(path).unwrap_or_else(|e| {
+            panic!(
+                "{}: {}; run with {}=1 to create it",
+                path.display(),
+                e,
+                BLESS_VAR
+            )
+        });
+        if screen != expected {
+            let diff: Vec<String> = expected
+                .lines()
+                .zip(screen.lines())
+                .enumerate()
+                .filter(|(_, (want, got))| want != got)
+                .map(|(row, (want, got))| format!("row {}:\n- {}\n+ {}", row, want, got))
+                .collect();
+            panic!(
+                "{} does not match the screen; run with {}=1 to accept it\n{}\n\nscreen:\n{}",
+                path.display(),
+                BLESS_VAR,
+                diff.join("\n"),
+                screen
+            );
+        }
+    }
+
+    fn apply(&mut self, msg: Msg, reports: &mut Reports) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        for cmd in self.app.apply(msg, tx) {
+            // There is no terminal to ask for a password on, or to page the log
+            if let Cmd::StartHelper(elevator) = cmd {
+                let refused = format!("{} needs a terminal; none in a headless run", elevator);
+                self.apply(Msg::HelperStarted(Err(refused)), reports);
+            }
+        }
+        reports.push(
+            stream::unfold(rx, |mut rx| async move {
+                rx.recv().await.map(|event| (event, rx))
+            })
+            .boxed(),
+        );
+    }
+
+    /// Apply what comes back until every command has dropped its sender
+    async fn settle(&mut self, mut reports: Reports) {
+        while self.app.running {
+            let Some(event) = reports.next().await else {
+                return;
+            };
+            if let Some(msg) = self.app.to_msg(event) {
+                self.apply(msg, &mut reports);
+            }
+        }
+    }
+
+    fn draw(&mut self) {
+        let model = &self.app.model;
+        self.terminal
+            .draw(|frame| frame.render_widget(model, frame.area()))
+            .expect("the test backend does not fail");
+    }
+}
+
+/// A key press
+pub fn key(code: KeyCode) -> Event {
+    Event::Crossterm(crossterm::event::Event::Key(KeyEvent::from(code)))
+}
+
+/// Key presses typing `text`
+pub fn type_text(text: &str) -> Vec<Event> {
+    text.chars().map(|c| key(KeyCode::Char(c))).collect()
+}
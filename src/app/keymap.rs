@@ -0,0 +1,260 @@
+//! Rebindable keys for screen actions.
+//!
+//! Navigation (arrows, Enter, Esc, Tab, Backspace) is fixed; the action
+//! keys listed in [`Action`] can be changed with `key.<action> = <key>`
+//! in the config file.
+
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    MarkIso,
+    Browse,
+    MarkDevice,
+    RefreshDevices,
+    ToggleVerify,
+    QueueJob,
+    TogglePersistence,
+    TogglePartitionScheme,
+    ToggleFilesystem,
+    Sudo,
+    ExportReport,
+    Eject,
+    OpenLog,
+}
+
+impl Action {
+    pub const ALL: [Action; 14] = [
+        Action::Quit,
+        Action::MarkIso,
+        Action::Browse,
+        Action::MarkDevice,
+        Action::RefreshDevices,
+        Action::ToggleVerify,
+        Action::QueueJob,
+        Action::TogglePersistence,
+        Action::TogglePartitionScheme,
+        Action::ToggleFilesystem,
+        Action::Sudo,
+        Action::ExportReport,
+        Action::Eject,
+        Action::OpenLog,
+    ];
+
+    /// Name used after `key.` in the config file
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::MarkIso => "mark_iso",
+            Action::Browse => "browse",
+            Action::MarkDevice => "mark_device",
+            Action::RefreshDevices => "refresh",
+            Action::ToggleVerify => "verify",
+            Action::QueueJob => "queue",
+            Action::TogglePersistence => "persistence",
+            Action::TogglePartitionScheme => "scheme",
+            Action::ToggleFilesystem => "filesystem",
+            Action::Sudo => "sudo",
+            Action::ExportReport => "export_report",
+            Action::Eject => "eject",
+            Action::OpenLog => "open_log",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|a| a.name() == name)
+    }
+
+    fn default_binding(self) -> KeyBinding {
+        let (c, ctrl) = match self {
+            Action::Quit => ('q', false),
+            Action::MarkIso => ('t', true),
+            Action::Browse => ('o', true),
+            Action::MarkDevice => (' ', false),
+            Action::RefreshDevices => ('r', false),
+            Action::ToggleVerify => ('v', false),
+            Action::QueueJob => ('a', true),
+            Action::TogglePersistence => ('p', true),
+            Action::TogglePartitionScheme => ('g', true),
+            Action::ToggleFilesystem => ('n', true),
+            Action::Sudo => ('s', true),
+            Action::ExportReport => ('e', false),
+            Action::Eject => ('e', true),
+            Action::OpenLog => ('l', true),
+        };
+        KeyBinding {
+            code: KeyCode::Char(c),
+            ctrl,
+        }
+    }
+
+    /// Actions that can fire on the same screen must not share a key
+    fn screens(self) -> &'static [&'static str] {
+        match self {
+            Action::Quit | Action::OpenLog => &["iso", "devices", "confirm", "writing", "done"],
+            Action::MarkIso | Action::Browse => &["iso"],
+            Action::MarkDevice | Action::RefreshDevices => &["devices"],
+            Action::ToggleVerify
+            | Action::QueueJob
+            | Action::TogglePersistence
+            | Action::TogglePartitionScheme
+            | Action::ToggleFilesystem
+            | Action::Sudo => &["confirm"],
+            Action::ExportReport => &["done"],
+            // Toggles the post-write eject on Confirm, ejects now on Done
+            Action::Eject => &["confirm", "done"],
+        }
+    }
+}
+
+/// A key with or without Ctrl; letters match either case
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyBinding {
+    pub code: KeyCode,
+    pub ctrl: bool,
+}
+
+impl KeyBinding {
+    /// Parse `r`, `ctrl-t`, `space` or `f5`
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let lower = s.trim().to_ascii_lowercase();
+        let (ctrl, rest) = match lower.strip_prefix("ctrl-").or(lower.strip_prefix("ctrl+")) {
+            Some(rest) => (true, rest),
+            None => (false, lower.as_str()),
+        };
+        let code = match rest {
+            "space" => KeyCode::Char(' '),
+            "delete" | "del" => KeyCode::Delete,
+            "insert" | "ins" => KeyCode::Insert,
+            f if f.len() > 1 && f.starts_with('f') => f[1..]
+                .parse::<u8>()
+                .ok()
+                .filter(|n| (1..=12).contains(n))
+                .map(KeyCode::F)
+                .ok_or_else(|| format!("`{}` is not a key", s.trim()))?,
+            c if c.chars().count() == 1 => KeyCode::Char(c.chars().next().unwrap_or(' ')),
+            _ => return Err(format!("`{}` is not a key", s.trim())),
+        };
+        Ok(Self { code, ctrl })
+    }
+
+    pub fn matches(&self, key: &KeyEvent) -> bool {
+        let code_matches = match (self.code, key.code) {
+            (KeyCode::Char(a), KeyCode::Char(b)) => a.eq_ignore_ascii_case(&b),
+            (a, b) => a == b,
+        };
+        code_matches && key.modifiers.contains(KeyModifiers::CONTROL) == self.ctrl
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl-")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) if self.ctrl => write!(f, "{}", c.to_ascii_uppercase()),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            other => write!(f, "{:?}", other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    bindings: Vec<(Action, KeyBinding)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self {
+            bindings: Action::ALL
+                .into_iter()
+                .map(|a| (a, a.default_binding()))
+                .collect(),
+        }
+    }
+}
+
+impl Keymap {
+    pub fn binding(&self, action: Action) -> KeyBinding {
+        self.bindings
+            .iter()
+            .find(|(a, _)| *a == action)
+            .map(|(_, b)| *b)
+            .unwrap_or_else(|| action.default_binding())
+    }
+
+    pub fn set(&mut self, action: Action, binding: KeyBinding) {
+        match self.bindings.iter_mut().find(|(a, _)| *a == action) {
+            Some(entry) => entry.1 = binding,
+            None => self.bindings.push((action, binding)),
+        }
+    }
+
+    pub fn matches(&self, action: Action, key: &KeyEvent) -> bool {
+        self.binding(action).matches(key)
+    }
+
+    /// The action `key` fires on `screen`, if any
+    pub fn action_on(&self, screen: &str, key: &KeyEvent) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(a, b)| a.screens().contains(&screen) && b.matches(key))
+            .map(|(a, _)| *a)
+    }
+
+    /// Pairs of actions bound to the same key on a shared screen
+    pub fn conflicts(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        for (i, (a, ka)) in self.bindings.iter().enumerate() {
+            for (b, kb) in &self.bindings[i + 1..] {
+                let shared = a.screens().iter().any(|s| b.screens().contains(s));
+                if shared && ka == kb {
+                    out.push(format!(
+                        "key.{} and key.{} are both bound to {}",
+                        a.name(),
+                        b.name(),
+                        ka
+                    ));
+                }
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bindings_parse_and_match() {
+        let b = KeyBinding::parse("Ctrl-T").unwrap();
+        assert!(b.matches(&KeyEvent::new(KeyCode::Char('t'), KeyModifiers::CONTROL)));
+        assert!(!b.matches(&KeyEvent::new(KeyCode::Char('t'), KeyModifiers::NONE)));
+        assert_eq!(b.to_string(), "Ctrl-T");
+        assert_eq!(KeyBinding::parse("space").unwrap().code, KeyCode::Char(' '));
+        assert_eq!(KeyBinding::parse("f5").unwrap().code, KeyCode::F(5));
+        assert!(KeyBinding::parse("ctrl-").is_err());
+        assert!(KeyBinding::parse("f13").is_err());
+    }
+
+    #[test]
+    fn conflicts_only_count_on_shared_screens() {
+        let mut keymap = Keymap::default();
+        assert!(keymap.conflicts().is_empty());
+        // `r` refreshes devices; reusing it on Confirm is fine
+        keymap.set(Action::ToggleVerify, KeyBinding::parse("r").unwrap());
+        assert!(keymap.conflicts().is_empty());
+        keymap.set(Action::QueueJob, KeyBinding::parse("r").unwrap());
+        assert_eq!(
+            keymap.conflicts(),
+            vec!["key.verify and key.queue are both bound to r"]
+        );
+    }
+}
@@ -1,14 +1,22 @@
+use crate::config::Config;
 use crate::event::{Event, EventHandler};
+use crate::log;
 use ratatui::{
     DefaultTerminal,
     crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
 };
 
 pub mod cmd;
+pub mod effects;
+#[cfg(any(test, feature = "test-support"))]
+pub mod headless;
+pub mod keymap;
 pub mod msg;
 pub mod state;
+pub mod trace;
 pub mod update;
 
+use keymap::Action;
 use msg::Msg;
 use state::Model;
 
@@ -17,6 +25,9 @@ pub struct App {
     pub running: bool,
     pub events: EventHandler,
     pub model: Model,
+    pub effects: effects::Effects,
+    /// Where dispatched messages are traced with `--record`
+    pub recorder: Option<trace::Recorder>,
 }
 
 impl Default for App {
@@ -25,6 +36,8 @@ impl Default for App {
             running: true,
             events: EventHandler::new(),
             model: Model::default(),
+            effects: effects::Effects::default(),
+            recorder: None,
         }
     }
 }
@@ -34,53 +47,332 @@ impl App {
         Self::default()
     }
 
+    /// Start with the user's configuration and job history applied to the model
+    pub fn with_config(config: &Config) -> Self {
+        crate::ui::set_appearance(&config.theme, &config.icons);
+        crate::adapters::platform::set_lazy_unmount(config.lazy_unmount);
+        let effects = match config.dry_run {
+            true => effects::Effects::simulated(config.simulation.clone()),
+            false => effects::Effects::system(config.udisks),
+        };
+        let mut model = Model::from_config(config);
+        model.history = crate::domain::history::load();
+        Self {
+            model,
+            effects,
+            ..Self::default()
+        }
+    }
+
+    /// Nothing from this machine: a clean model with no job history and
+    /// simulated effects, so a replay draws what the trace holds
+    pub fn for_replay(config: &Config) -> Self {
+        crate::ui::set_appearance(&config.theme, &config.icons);
+        Self {
+            model: Model::from_config(config),
+            effects: effects::Effects::simulated(config.simulation.clone()),
+            ..Self::default()
+        }
+    }
+
+    /// Trace every dispatched message to `path`; see [`trace`]
+    pub fn record_to(mut self, path: &std::path::Path, redact: bool) -> Result<Self, String> {
+        self.recorder = Some(trace::Recorder::create(path, redact)?);
+        Ok(self)
+    }
+
+    /// Reach devices, mounts and images through `effects` instead
+    pub fn with_effects(mut self, effects: effects::Effects) -> Self {
+        self.effects = effects;
+        self
+    }
+
     pub async fn run(mut self, mut terminal: DefaultTerminal) -> color_eyre::Result<()> {
         while self.running {
             terminal.draw(|frame| frame.render_widget(&self.model, frame.area()))?;
-            match self.events.next().await? {
-                Event::Tick => {
-                    self.dispatch(Msg::Tick).await?;
-                }
-                Event::Crossterm(event) => match event {
-                    crossterm::event::Event::Key(key) if key.kind == KeyEventKind::Press => {
-                        if let Some(msg) = self.map_key_to_msg(key) {
-                            self.dispatch(msg).await?;
-                        } else {
-                            self.dispatch(Msg::Key(key)).await?;
-                        }
-                    }
-                    _ => {}
-                },
-                Event::App(app_msg) => {
-                    self.dispatch(app_msg).await?;
+            let event = self.events.next().await?;
+            if let Some(msg) = self.to_msg(event) {
+                self.dispatch(&mut terminal, msg).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Play a recorded trace: each message goes through `update` at its
+    /// recorded pace (idle stretches shortened) and every frame is drawn.
+    /// Start from [`App::for_replay`]. `q`, Esc or Ctrl-C stops; after the
+    /// last message any key does.
+    pub async fn replay(
+        mut self,
+        mut terminal: DefaultTerminal,
+        trace: Vec<(std::time::Duration, Msg)>,
+    ) -> color_eyre::Result<()> {
+        use std::time::{Duration, Instant};
+        const MAX_GAP: Duration = Duration::from_secs(2);
+        let start = Instant::now();
+        let mut due = Duration::ZERO;
+        let mut last = Duration::ZERO;
+        terminal.draw(|frame| frame.render_widget(&self.model, frame.area()))?;
+        let stop = |event: &Event| match event {
+            Event::Crossterm(crossterm::event::Event::Key(key))
+                if key.kind == KeyEventKind::Press =>
+            {
+                matches!(key.code, KeyCode::Esc | KeyCode::Char('q'))
+                    || (key.code == KeyCode::Char('c') && key.modifiers == KeyModifiers::CONTROL)
+            }
+            _ => false,
+        };
+        for (at, msg) in trace {
+            due += at.saturating_sub(last).min(MAX_GAP);
+            last = at;
+            while let Some(wait) = due.checked_sub(start.elapsed()).filter(|w| !w.is_zero()) {
+                if let Ok(event) = tokio::time::timeout(wait, self.events.next()).await
+                    && stop(&event?)
+                {
+                    return Ok(());
                 }
             }
+            if matches!(msg, Msg::Quit) {
+                break;
+            }
+            self.replay_msg(msg);
+            terminal.draw(|frame| frame.render_widget(&self.model, frame.area()))?;
+        }
+        loop {
+            if let Event::Crossterm(crossterm::event::Event::Key(key)) = self.events.next().await?
+                && key.kind == KeyEventKind::Press
+            {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Apply one recorded message. Its commands are dropped, not run: the
+    /// trace already holds what they answered, recorded after it, so
+    /// running them would deliver every answer twice.
+    fn replay_msg(&mut self, msg: Msg) {
+        let (model, _) = update::update(std::mem::take(&mut self.model), msg);
+        self.model = model;
+    }
+
+    /// The message an event stands for; key releases and resizes have none
+    pub(crate) fn to_msg(&self, event: Event) -> Option<Msg> {
+        match event {
+            Event::Tick => Some(Msg::Tick),
+            Event::Crossterm(crossterm::event::Event::Key(key))
+                if key.kind == KeyEventKind::Press =>
+            {
+                Some(self.map_key_to_msg(key).unwrap_or(Msg::Key(key)))
+            }
+            Event::Crossterm(_) => None,
+            Event::App(msg) => Some(msg),
+        }
+    }
+
+    async fn dispatch(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        msg: Msg,
+    ) -> color_eyre::Result<()> {
+        let tx = self.events.sender_clone();
+        for cmd in self.apply(msg, tx) {
+            match cmd {
+                msg::Cmd::StartHelper(elevator) => self.start_helper(terminal, elevator).await?,
+                msg::Cmd::OpenLog => self.open_log(terminal).await?,
+                _ => {}
+            }
         }
         Ok(())
     }
 
-    async fn dispatch(&mut self, msg: Msg) -> color_eyre::Result<()> {
+    /// Update the model and launch the commands that come back. Starting
+    /// the helper and paging the log need the terminal, which effects
+    /// never get, so those commands are handed back instead. What the
+    /// commands report goes to `tx`.
+    pub(crate) fn apply(
+        &mut self,
+        msg: Msg,
+        tx: tokio::sync::mpsc::UnboundedSender<Event>,
+    ) -> Vec<msg::Cmd> {
+        log_msg(&msg);
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(&msg);
+        }
         if matches!(msg, Msg::Quit) {
+            log::info("app", "quit");
             self.running = false;
-            return Ok(());
+            return Vec::new();
         }
         let (model, cmds) = update::update(std::mem::take(&mut self.model), msg);
         self.model = model;
+        let (for_app, cmds): (Vec<_>, Vec<_>) = cmds
+            .into_iter()
+            .partition(|cmd| matches!(cmd, msg::Cmd::StartHelper(_) | msg::Cmd::OpenLog));
         if !cmds.is_empty() {
-            let tx = self.events.sender_clone();
-            crate::app::cmd::spawn_all(cmds, tx);
+            crate::app::cmd::spawn_all(cmds, &self.effects, tx);
+        }
+        for_app
+    }
+
+    /// Leave the TUI while the elevation tool asks for a password (on the
+    /// terminal or in a polkit dialog), then come back with the model untouched
+    async fn start_helper(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        elevator: crate::adapters::elevate::Elevator,
+    ) -> color_eyre::Result<()> {
+        use ratatui::crossterm::{
+            execute,
+            terminal::{EnterAlternateScreen, enable_raw_mode},
+        };
+        self.events.suspend().await;
+        ratatui::restore();
+        if elevator.prompts_on_tty() {
+            println!(
+                "ferric: starting the privileged write helper with {}",
+                elevator
+            );
+        } else {
+            println!(
+                "ferric: starting the privileged write helper with {}; authenticate in the dialog",
+                elevator
+            );
         }
+        let tx = self.events.sender_clone();
+        let started = tokio::task::spawn_blocking(move || crate::helper::start(elevator, tx))
+            .await
+            .unwrap_or_else(|e| Err(e.to_string()));
+        // Keys typed while away belong to nobody.
+        // SAFETY: plain syscall on stdin; a failure only leaves stale input.
+        unsafe { libc::tcflush(libc::STDIN_FILENO, libc::TCIFLUSH) };
+        enable_raw_mode()?;
+        execute!(std::io::stdout(), EnterAlternateScreen)?;
+        terminal.clear()?;
+        self.events.resume();
+        self.events.send(Msg::HelperStarted(started));
+        Ok(())
+    }
+
+    /// Leave the TUI for `$PAGER` (default `less`) on the log file
+    async fn open_log(&mut self, terminal: &mut DefaultTerminal) -> color_eyre::Result<()> {
+        use ratatui::crossterm::{
+            execute,
+            terminal::{EnterAlternateScreen, enable_raw_mode},
+        };
+        let Some(path) = log::log_path() else {
+            log::warn("app", "no home directory for the log file");
+            return Ok(());
+        };
+        self.events.suspend().await;
+        ratatui::restore();
+        let pager = std::env::var("PAGER")
+            .ok()
+            .filter(|p| !p.trim().is_empty())
+            .unwrap_or_else(|| "less +G".to_string());
+        let mut words = pager.split_whitespace();
+        let program = words.next().unwrap_or("less");
+        let status = std::process::Command::new(program)
+            .args(words)
+            .arg(&path)
+            .status();
+        let failed = match status {
+            Ok(status) if status.success() => None,
+            Ok(status) => Some(format!("{}: {}", pager, status)),
+            Err(e) => Some(format!("{}: {}", pager, e)),
+        };
+        if let Some(e) = failed {
+            log::warn("app", format_args!("paging {}: {}", path.display(), e));
+            println!("ferric: cannot show {}: {}", path.display(), e);
+            println!("Press Enter to return");
+            let _ = std::io::stdin().read_line(&mut String::new());
+        }
+        // SAFETY: plain syscall on stdin; a failure only leaves stale input.
+        unsafe { libc::tcflush(libc::STDIN_FILENO, libc::TCIFLUSH) };
+        enable_raw_mode()?;
+        execute!(std::io::stdout(), EnterAlternateScreen)?;
+        terminal.clear()?;
+        self.events.resume();
         Ok(())
     }
 
     fn map_key_to_msg(&self, key: KeyEvent) -> Option<Msg> {
         match key.code {
             KeyCode::Esc => Some(Msg::Back),
-            KeyCode::Char('q') => Some(Msg::Quit),
             KeyCode::Char('c' | 'C') if key.modifiers == KeyModifiers::CONTROL => Some(Msg::Quit),
+            // The path browser takes every other key, `q` and Tab included
+            _ if self.model.browser.is_some() => None,
+            _ if self.model.keymap.matches(Action::Quit, &key) => Some(Msg::Quit),
+            _ if self.model.keymap.matches(Action::OpenLog, &key) => Some(Msg::OpenLogRequested),
             KeyCode::Tab => Some(Msg::NextScreen),
             KeyCode::BackTab => Some(Msg::PrevScreen),
             _ => None,
         }
     }
 }
+
+/// Outcomes go to the log at `Info` or `Error`; other messages only at
+/// `Trace`, and ticks and progress not at all
+fn log_msg(msg: &Msg) {
+    fn finished<T>(scope: &str, what: impl std::fmt::Display, result: &Result<T, String>) {
+        match result {
+            Ok(_) => log::info(scope, format_args!("{}: done", what)),
+            Err(e) => log::error(scope, format_args!("{}: {}", what, e)),
+        }
+    }
+    match msg {
+        Msg::Tick
+        | Msg::WriteProgress { .. }
+        | Msg::VerifyProgress { .. }
+        | Msg::TargetWriteProgress { .. }
+        | Msg::TargetVerifyProgress { .. } => {}
+        Msg::IsoSearchFailed(e) => log::error("scan", e),
+        Msg::IsoResults(isos) => log::debug("scan", format_args!("{} images", isos.len())),
+        Msg::DevicesRefreshFailed(e) => log::error("devices", e),
+        Msg::DevicesRefreshed(devices) => {
+            log::debug("devices", format_args!("{} devices", devices.len()))
+        }
+        Msg::WriteStarted { total } => log::info("write", format_args!("{} bytes", total)),
+        Msg::WriteWarning(w) => log::warn("write", w),
+        Msg::WriteFinished(result) => finished("write", "write", result),
+        Msg::VerifyStarted { total } => log::info("verify", format_args!("{} bytes", total)),
+        Msg::VerifyFinished(result) => finished("verify", "verify", result),
+        Msg::PersistenceFinished(result) => finished("write", "persistence", result),
+        Msg::TargetWriteFinished { device, result } => finished("write", device, result),
+        Msg::TargetVerifyFinished { device, result } => finished("verify", device, result),
+        Msg::HistoryRecorded(result) => finished("history", "record", result),
+        Msg::ReportExported(result) => finished("report", "export", result),
+        Msg::Ejected { device, result } => finished("eject", device, result),
+        Msg::HelperStarted(result) => finished("helper", "start", result),
+        Msg::HelperExited => log::warn("helper", "exited"),
+        other if log::enabled(log::Level::Trace) => log::trace("msg", format_args!("{:?}", other)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{paths::DevicePath, simulate};
+    use std::path::PathBuf;
+
+    #[tokio::test]
+    async fn replay_shows_what_the_trace_answered() {
+        let config = Config {
+            dry_run: true,
+            ..Config::default()
+        };
+        let mut app = App::for_replay(&config);
+        assert!(app.model.history.is_empty());
+
+        // Recorded without --dry-run; the simulated effects would list only
+        // their stand-in stick if the refresh ran again
+        let mut recorded = simulate::device();
+        recorded.name = "sdb".to_string();
+        recorded.path = DevicePath::from(PathBuf::from("/dev/sdb"));
+        app.replay_msg(Msg::RefreshDevicesRequested);
+        app.replay_msg(Msg::DevicesRefreshed(vec![recorded]));
+        let listed: Vec<String> = app.model.devices.iter().map(|d| d.name.clone()).collect();
+        assert_eq!(listed, ["sdb"]);
+        assert!(!app.model.device_refreshing);
+    }
+}
@@ -13,9 +13,22 @@ pub enum Msg {
     IsoQueryChanged(String),
     IsoSearchRequested,
     IsoSearchFailed(String),
+    /// The index saved by the last run, shown while the roots are walked
+    IsoCacheLoaded(Vec<crate::app::state::IsoMeta>),
+    /// Images found so far by a walk that is still running
+    IsoResultsPartial(Vec<crate::app::state::IsoMeta>),
+    /// The full index after a walk of the roots
     IsoResults(Vec<crate::app::state::IsoMeta>),
+    IsoIndexChanged(crate::domain::iso_index::IndexChange),
     IsoMoveSelection(i32),
     IsoConfirmSelect,
+    /// A directory listing, or an image picked in the browser
+    BrowseOpened(Result<crate::domain::browse::Opened, String>),
+    /// Tab completion of the browser's `input`
+    PathCompleted {
+        input: String,
+        completed: String,
+    },
 
     DevicesRefreshFailed(String),
     DevicesRefreshed(Vec<crate::app::state::Device>),
@@ -23,33 +36,131 @@ pub enum Msg {
     DeviceConfirmSelect,
     RefreshDevicesRequested,
 
-    WriteStarted { total: u64 },
-    WriteProgress { written: u64, total: u64, bps: f64 },
+    WriteStarted {
+        total: u64,
+    },
+    WriteProgress {
+        written: u64,
+        total: u64,
+        bps: f64,
+    },
     WriteFinished(Result<(), String>),
+    /// A best-effort step (unmount, partprobe) failed; the write went on
+    WriteWarning(String),
 
-    VerifyStarted { total: u64 },
-    VerifyProgress { checked: u64, total: u64, bps: f64 },
+    VerifyStarted {
+        total: u64,
+    },
+    VerifyProgress {
+        checked: u64,
+        total: u64,
+        bps: f64,
+    },
     VerifyFinished(Result<(), String>),
+    /// Entries appended to the history file, hashes filled in
+    HistoryRecorded(Result<Vec<crate::domain::history::HistoryEntry>, String>),
+    PersistenceFinished(Result<(), String>),
+    /// `Ok(true)` when the USB port was powered off too
+    Ejected {
+        device: crate::domain::paths::DevicePath,
+        result: Result<bool, String>,
+    },
+    /// Paths of the saved text and JSON report
+    ReportExported(Result<Vec<std::path::PathBuf>, String>),
+
+    /// Per-device progress while writing several devices at once
+    TargetWriteProgress {
+        device: crate::domain::paths::DevicePath,
+        written: u64,
+        total: u64,
+        bps: f64,
+    },
+    TargetWriteFinished {
+        device: crate::domain::paths::DevicePath,
+        result: Result<(), String>,
+    },
+    TargetVerifyProgress {
+        device: crate::domain::paths::DevicePath,
+        checked: u64,
+        total: u64,
+        bps: f64,
+    },
+    TargetVerifyFinished {
+        device: crate::domain::paths::DevicePath,
+        result: Result<(), String>,
+    },
 
     ElevateRequested,
+    OpenLogRequested,
+    /// The privileged helper is up, or why it is not
+    HelperStarted(Result<(), String>),
+    HelperExited,
 }
 
 #[non_exhaustive]
 #[derive(Debug, Clone)]
 pub enum Cmd {
     Noop,
+    /// Build the ISO index and keep watching the roots
     ScanIso {
-        query: String,
+        options: crate::domain::iso::ScanOptions,
+    },
+    /// List a directory or validate an image for the browser
+    BrowseOpen {
+        path: std::path::PathBuf,
+    },
+    CompletePath {
+        dir: std::path::PathBuf,
+        input: String,
     },
     RefreshDevices,
+    /// Hash the images where needed and append to the history file
+    RecordHistory(Vec<crate::domain::history::HistoryEntry>),
+    /// Flush, detach and power down the written devices, one by one
+    Eject {
+        devices: Vec<crate::domain::paths::DevicePath>,
+    },
+    /// Save the report as text and JSON; `None` means the default directory
+    ExportReport {
+        report: crate::domain::report::Report,
+        dir: Option<std::path::PathBuf>,
+    },
     Write {
         iso: crate::domain::paths::IsoPath,
         device: crate::domain::paths::DevicePath,
+        buffer_size: usize,
+    },
+    WriteWindows {
+        iso: crate::domain::paths::IsoPath,
+        device: crate::domain::paths::DevicePath,
+        options: crate::domain::windows::WindowsOptions,
+    },
+    WriteMultiboot {
+        isos: Vec<crate::domain::paths::IsoPath>,
+        device: crate::domain::paths::DevicePath,
+    },
+    CreatePersistence {
+        device: crate::domain::paths::DevicePath,
+        image_size: u64,
+        kind: crate::domain::persistence::PersistenceKind,
+    },
+    WriteBatch {
+        iso: crate::domain::paths::IsoPath,
+        devices: Vec<crate::domain::paths::DevicePath>,
+        verify: bool,
+        buffer_size: usize,
     },
     Verify {
         iso: crate::domain::paths::IsoPath,
         device: crate::domain::paths::DevicePath,
         size: u64,
     },
-    ReexecWithSudo,
+    /// Run `ferric helper` as root; handled by the app, which owns the terminal
+    StartHelper(crate::adapters::elevate::Elevator),
+    /// Page through the log file; handled by the app like `StartHelper`
+    OpenLog,
+    /// Have the privileged helper write or verify
+    Helper(crate::helper::Request),
+    /// Write or verify through a device UDisks2 opens for us
+    Udisks(crate::helper::Request),
 }
@@ -3,9 +3,39 @@
 //! The model is a single immutable struct updated by the event loop.
 //! Counters are in bytes unless stated otherwise.
 
-pub use crate::domain::device::Device;
-pub use crate::domain::iso::IsoMeta;
+pub use crate::adapters::elevate::Elevator;
+pub use crate::app::keymap::{Action, Keymap};
+pub use crate::domain::browse::DirEntry;
+pub use crate::domain::device::{Device, DeviceAccess, SafetyPolicy};
+pub use crate::domain::history::HistoryEntry;
+pub use crate::domain::iso::{IsoMeta, ScanOptions};
 pub use crate::domain::paths::{DevicePath, IsoPath};
+pub use crate::domain::persistence::PersistenceKind;
+pub use crate::domain::report::Report;
+pub use crate::domain::windows::{WindowsFs, WindowsOptions};
+
+/// How the chosen image is put on the device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WriteMode {
+    /// Block-for-block copy of a hybrid image
+    #[default]
+    Raw,
+    /// Partition, format and unpack a Windows installer ISO
+    Windows(WindowsOptions),
+    /// Copy the marked ISOs onto a GRUB multi-boot drive
+    Multiboot,
+}
+
+/// Who opens the device for a raw write or verify
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteRoute {
+    /// This process, with its own access
+    Direct,
+    /// The privileged helper
+    Helper,
+    /// UDisks2, once polkit allows it
+    Udisks,
+}
 
 /// UI screens
 ///
@@ -29,6 +59,114 @@ pub enum ActivePanel {
     ConfirmInput,
 }
 
+/// Progress of one device in a multi-device write
+#[derive(Debug, Clone, PartialEq)]
+pub struct TargetState {
+    pub device: DevicePath,
+    pub written: u64,
+    pub total: u64,
+    pub speed_bps: f64,
+    pub write_result: Option<Result<(), String>>,
+    pub verifying: bool,
+    pub verified: u64,
+    pub verify_speed_bps: f64,
+    pub verify_result: Option<Result<(), String>>,
+}
+
+impl TargetState {
+    pub fn new(device: DevicePath) -> Self {
+        Self {
+            device,
+            written: 0,
+            total: 0,
+            speed_bps: 0.0,
+            write_result: None,
+            verifying: false,
+            verified: 0,
+            verify_speed_bps: 0.0,
+            verify_result: None,
+        }
+    }
+
+    /// True once nothing more will happen on this device
+    pub fn is_finished(&self, verify: bool) -> bool {
+        match &self.write_result {
+            None => false,
+            Some(Err(_)) => true,
+            Some(Ok(())) => !verify || self.verify_result.is_some(),
+        }
+    }
+
+    /// True if the device was written (and verified, when asked) cleanly
+    pub fn succeeded(&self) -> bool {
+        self.write_result == Some(Ok(())) && !matches!(self.verify_result, Some(Err(_)))
+    }
+}
+
+/// One queued write: an image, a device and how to write it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Job {
+    pub iso: IsoPath,
+    pub device: DevicePath,
+    pub write_mode: WriteMode,
+    /// Images for a multi-ISO drive job; empty otherwise
+    pub multiboot_isos: Vec<IsoPath>,
+    pub verify: bool,
+    pub persistence: Option<PersistenceKind>,
+    pub eject: bool,
+    /// `None` until the job has run
+    pub result: Option<Result<(), String>>,
+}
+
+/// Writes run one after another, one device at a time
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JobQueue {
+    pub jobs: Vec<Job>,
+    /// Index of the running (or next) job once the queue has started
+    pub current: Option<usize>,
+    /// Waiting for the user to insert the device of the current job
+    pub awaiting_device: bool,
+    /// The last device refresh did not show that device
+    pub device_missing: bool,
+    /// Serial of the stick the last job wrote, when lsblk knew it
+    pub written_serial: Option<String>,
+    /// That stick has been seen unplugged since, so a device at the next
+    /// job's path is a new one
+    pub written_removed: bool,
+}
+
+impl JobQueue {
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.current.is_some()
+    }
+
+    pub fn current_job(&self) -> Option<&Job> {
+        self.current.and_then(|i| self.jobs.get(i))
+    }
+
+    /// `(1-based position, total)` of the current job
+    pub fn position(&self) -> Option<(usize, usize)> {
+        self.current.map(|i| (i + 1, self.jobs.len()))
+    }
+}
+
+/// The ISO screen's path browser
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Browser {
+    /// Directory being listed
+    pub dir: std::path::PathBuf,
+    pub entries: Vec<DirEntry>,
+    pub selected: usize,
+    /// Typed path; when set, Enter opens it instead of the selected entry
+    pub input: String,
+    /// Why the last path could not be opened
+    pub error: Option<String>,
+}
+
 /// Full application model.
 ///
 /// Invariants
@@ -48,14 +186,29 @@ pub struct Model {
     pub iso_results: Vec<IsoMeta>,
     pub iso_selected: usize,
     pub iso_searching: bool,
-    pub iso_debounce_until: Option<std::time::Instant>,
+    /// Every image under the roots; `iso_results` is its filtered view
+    pub iso_index: Vec<IsoMeta>,
+    /// A walk of the roots has completed and watches are live
+    pub iso_indexed: bool,
     pub iso_chosen: Option<IsoPath>,
+    /// Roots and filters used for every search
+    pub scan: ScanOptions,
+    /// Results marked for a multi-ISO drive, in marking order
+    pub iso_marked: Vec<IsoPath>,
+    /// The path browser, while it replaces the result list
+    pub browser: Option<Browser>,
+    /// Images picked in the browser, which the index does not cover
+    pub iso_browsed: Vec<IsoMeta>,
+    /// Chosen from the image's recognition when it is selected
+    pub write_mode: WriteMode,
 
     /// Discovered writable block devices
     pub devices: Vec<Device>,
     pub device_selected: usize,
     pub device_refreshing: bool,
     pub device_chosen: Option<DevicePath>,
+    /// Devices marked for a parallel write, in marking order
+    pub device_marked: Vec<DevicePath>,
 
     /// Raw user input for destructive action confirmation
     pub confirm_input: String,
@@ -71,6 +224,8 @@ pub struct Model {
     /// Result of the write: `None` while running; `Some(Ok(()))` on success
     /// `Some(Err(msg))` on failure
     pub write_result: Option<Result<(), String>>,
+    /// Problems that did not stop the current write
+    pub write_warnings: Vec<String>,
 
     pub verify_after_write: bool,
     pub verifying: bool,
@@ -84,22 +239,192 @@ pub struct Model {
     /// `Some(Err(msg))` on failure
     pub verify_result: Option<Result<(), String>>,
 
-    pub is_root: bool,
+    /// Sequential jobs built from the regular screens
+    pub queue: JobQueue,
+    /// Completed jobs from this and earlier runs, oldest first
+    pub history: Vec<HistoryEntry>,
+
+    /// One row per device while writing several at once; empty otherwise
+    pub targets: Vec<TargetState>,
+
+    /// Audit report of the last finished job
+    pub report: Option<Report>,
+    /// Where reports are saved; `None` for the default directory
+    pub report_dir: Option<std::path::PathBuf>,
+    /// Save a report after every job without asking
+    pub auto_report: bool,
+    /// Outcome of the last report export
+    pub report_exported: Option<Result<Vec<std::path::PathBuf>, String>>,
+
+    /// Persistence flavour offered for the current image/device pair
+    pub persistence_offer: Option<PersistenceKind>,
+    pub persistence_enabled: bool,
+    pub persisting: bool,
+    /// Result of the post-write persistence step, if it ran
+    pub persistence_result: Option<Result<(), String>>,
+
+    /// Eject the written devices once the job succeeds
+    pub eject_after_write: bool,
+    /// Devices being ejected, with the outcome once known
+    pub ejects: Vec<(DevicePath, Option<Result<bool, String>>)>,
+
+    /// The privileged write helper: `Some(Ok)` while it runs, `Some(Err)`
+    /// if it failed to start or went away
+    pub helper: Option<Result<(), String>>,
+    /// Tool that starts the helper; `None` if none is installed. Looked up
+    /// on `$PATH` by [`Model::from_config`], never by `Default`
+    pub elevator: Option<Elevator>,
+    /// Writes go to a simulated device; see `domain::simulate`
+    pub dry_run: bool,
+
+    /// Action keys, rebindable from the config file
+    pub keymap: Keymap,
+    /// Confirmation word and which devices may be listed
+    pub safety: SafetyPolicy,
+    /// Bytes per read/write when copying an image
+    pub buffer_size: usize,
 
     pub active_panel: ActivePanel,
 }
 
 impl Model {
+    /// Default model with the user's preferences applied
+    pub fn from_config(config: &crate::config::Config) -> Self {
+        Self {
+            scan: config.scan_options(),
+            verify_after_write: config.verify,
+            eject_after_write: config.eject,
+            elevator: Elevator::detect(config.elevate),
+            dry_run: config.dry_run,
+            buffer_size: config.buffer_size,
+            report_dir: config.report_dir.clone(),
+            auto_report: config.auto_report,
+            keymap: config.keymap.clone(),
+            safety: config.safety.clone(),
+            ..Self::default()
+        }
+    }
+
+    /// Metadata of the chosen image, from the index or the browser
+    pub fn chosen_iso_meta(&self) -> Option<&IsoMeta> {
+        let path = self.iso_chosen.as_ref()?;
+        self.iso_index
+            .iter()
+            .chain(&self.iso_browsed)
+            .chain(&self.iso_results)
+            .find(|m| &m.path == path)
+    }
+
     /// True when both an ISO and a device have been selected
     pub fn has_both_selections(&self) -> bool {
         self.iso_chosen.is_some() && self.device_chosen.is_some()
     }
 
-    /// True when model has the prerequisites to start a write
-    ///
-    /// Requires running as root and both selections
-    pub fn can_write(&self) -> bool {
-        self.is_root && self.has_both_selections()
+    /// Whether a write can start, or exactly what stands in the way
+    pub fn can_write(&self) -> Result<(), String> {
+        if !self.has_both_selections() {
+            return Err("choose an image and a device first".to_string());
+        }
+        if let Some(gap) = self.simulation_gap() {
+            return Err(gap.to_string());
+        }
+        match self.access_missing() {
+            Some(missing) if self.write_route() == WriteRoute::Direct => match self.needs_root() {
+                Some(why) => Err(format!("{}; this process {}", why, missing)),
+                None => Err(missing),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Why neither the root helper nor udisks can take this job: they
+    /// only open one device for an image write, its verify and eject
+    pub fn needs_root(&self) -> Option<&'static str> {
+        match self.write_mode {
+            WriteMode::Windows(_) => Some("Windows installs need ferric itself run as root"),
+            WriteMode::Multiboot => Some("multi-ISO drives need ferric itself run as root"),
+            WriteMode::Raw if self.device_marked.len() > 1 => {
+                Some("parallel writes need ferric itself run as root")
+            }
+            WriteMode::Raw if self.persistence_enabled => {
+                Some("persistence needs ferric itself run as root")
+            }
+            WriteMode::Raw => None,
+        }
+    }
+
+    /// Why a dry run cannot simulate this job; `None` outside dry runs
+    pub fn simulation_gap(&self) -> Option<&'static str> {
+        if !self.dry_run {
+            None
+        } else if self.write_mode != WriteMode::Raw {
+            Some("a dry run only simulates raw image writes")
+        } else if self.device_marked.len() > 1 {
+            Some("a dry run simulates one device at a time")
+        } else if self.persistence_enabled {
+            Some("a dry run does not simulate persistence")
+        } else {
+            None
+        }
+    }
+
+    /// What this process lacks to write the selected devices itself;
+    /// devices no longer listed are not held against it
+    pub fn access_missing(&self) -> Option<String> {
+        self.target_notes(|d| d.access.missing(&d.path, d.mounted))
+    }
+
+    /// What this process goes without when it writes the selected
+    /// devices itself; only asked once nothing is missing
+    pub fn access_caveat(&self) -> Option<String> {
+        if self.access_missing().is_some() {
+            return None;
+        }
+        self.target_notes(|d| d.access.caveat(&d.path))
+    }
+
+    fn target_notes(&self, note: impl Fn(&Device) -> Option<String>) -> Option<String> {
+        // A dry run opens no device
+        if self.dry_run {
+            return None;
+        }
+        let targets: Vec<&DevicePath> = if self.device_marked.len() > 1 {
+            self.device_marked.iter().collect()
+        } else {
+            self.device_chosen.iter().collect()
+        };
+        let notes: Vec<String> = targets
+            .into_iter()
+            .filter_map(|path| self.devices.iter().find(|d| &d.path == path))
+            .filter_map(note)
+            .collect();
+        (!notes.is_empty()).then(|| notes.join("; "))
+    }
+
+    /// Who opens the device: this process while it has the access, else
+    /// the running helper, else udisks for a single raw write it knows
+    pub fn write_route(&self) -> WriteRoute {
+        if self.access_missing().is_none() || self.needs_root().is_some() {
+            return WriteRoute::Direct;
+        }
+        if self.helper == Some(Ok(())) {
+            return WriteRoute::Helper;
+        }
+        let known_to_udisks = self
+            .device_chosen
+            .as_ref()
+            .and_then(|path| self.devices.iter().find(|d| &d.path == path))
+            .is_some_and(|d| d.udisks.is_some());
+        if known_to_udisks {
+            WriteRoute::Udisks
+        } else {
+            WriteRoute::Direct
+        }
+    }
+
+    /// Raw writes and verifies go through the privileged helper
+    pub fn uses_helper(&self) -> bool {
+        self.write_route() == WriteRoute::Helper
     }
 
     /// True while write screen is active and the write has not finished
@@ -112,9 +437,9 @@ impl Model {
         self.verifying && self.verify_result.is_none()
     }
 
-    /// True when confirmation input matches the input string
+    /// True when confirmation input matches the configured word
     pub fn is_confirmation_valid(&self) -> bool {
-        self.confirm_input == "YES"
+        self.confirm_input == self.safety.confirm_word
     }
 }
 
@@ -127,25 +452,51 @@ impl Default for Model {
             iso_results: Vec::new(),
             iso_selected: 0,
             iso_searching: false,
-            iso_debounce_until: None,
+            iso_index: Vec::new(),
+            iso_indexed: false,
             iso_chosen: None,
+            scan: ScanOptions::default(),
+            iso_marked: Vec::new(),
+            browser: None,
+            iso_browsed: Vec::new(),
+            write_mode: WriteMode::Raw,
             devices: Vec::new(),
             device_selected: 0,
             device_refreshing: false,
             device_chosen: None,
+            device_marked: Vec::new(),
             confirm_input: String::new(),
             writing_total: 0,
             writing_written: 0,
             writing_started: None,
             writing_speed_bps: 0.0,
             write_result: None,
+            write_warnings: Vec::new(),
             verify_after_write: false,
             verifying: false,
             verifying_total: 0,
             verifying_checked: 0,
             verifying_speed_bps: 0.0,
             verify_result: None,
-            is_root: crate::adapters::platform::is_root(),
+            queue: JobQueue::default(),
+            history: Vec::new(),
+            targets: Vec::new(),
+            report: None,
+            report_dir: None,
+            auto_report: false,
+            report_exported: None,
+            persistence_offer: None,
+            persistence_enabled: false,
+            persisting: false,
+            persistence_result: None,
+            eject_after_write: false,
+            ejects: Vec::new(),
+            helper: None,
+            dry_run: false,
+            elevator: None,
+            keymap: Keymap::default(),
+            safety: SafetyPolicy::default(),
+            buffer_size: crate::config::DEFAULT_BUFFER_SIZE,
             active_panel: ActivePanel::IsoList,
         }
     }
@@ -0,0 +1,788 @@
+//! Session traces for bug reports.
+//!
+//! `--record FILE` writes every message `App` dispatches to FILE, one line
+//! each after a `ferric-trace 1` header: milliseconds since start, a kind
+//! and its fields, tab-separated with the helper's escaping. Nested
+//! records (an image, a device) are one field, joined the same way. Runs
+//! of ticks are one `tick` line with a count. With `--redact`, paths other
+//! than `/dev` nodes become `/redacted/N.ext` (the same N each time), the
+//! home directory `~`, and serial numbers are dropped; typed keys are kept.
+//!
+//! `--replay FILE` feeds the messages back through `update`; see
+//! `App::replay`.
+
+use super::msg::Msg;
+use crate::{
+    domain::{
+        browse::{DirEntry, Opened},
+        device::{Device, DeviceAccess},
+        distro::{Arch, Distro, DistroInfo},
+        history::{self, HistoryEntry},
+        iso::{self, IsoMeta},
+        iso_index::IndexChange,
+        paths::{DevicePath, IsoPath},
+        units::ByteSize,
+    },
+    helper::{join, split},
+};
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, UNIX_EPOCH},
+};
+
+const HEADER: &str = "ferric-trace 1";
+
+/// Appends dispatched messages to a trace file
+pub struct Recorder {
+    out: BufWriter<File>,
+    start: Instant,
+    redactor: Redactor,
+    /// Ticks not written yet, and when the last of them came
+    ticks: u64,
+    last_tick: Duration,
+}
+
+impl std::fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Recorder").finish_non_exhaustive()
+    }
+}
+
+impl Recorder {
+    pub fn create(path: &Path, redact: bool) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut out = BufWriter::new(file);
+        writeln!(out, "{}", HEADER).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(Self {
+            out,
+            start: Instant::now(),
+            redactor: Redactor::new(redact),
+            ticks: 0,
+            last_tick: Duration::ZERO,
+        })
+    }
+
+    /// Best effort: a trace that cannot be written never stops the app
+    pub fn record(&mut self, msg: &Msg) {
+        let at = self.start.elapsed();
+        if let Msg::Tick = msg {
+            self.ticks += 1;
+            self.last_tick = at;
+            return;
+        }
+        self.flush_ticks();
+        let line = encode(msg, &mut self.redactor);
+        let _ = writeln!(self.out, "{}\t{}", at.as_millis(), line);
+        // Flushed per message so a crash still leaves the trace behind
+        let _ = self.out.flush();
+    }
+
+    fn flush_ticks(&mut self) {
+        if self.ticks > 0 {
+            let at = self.last_tick.as_millis();
+            let _ = writeln!(self.out, "{}\ttick\t{}", at, self.ticks);
+            self.ticks = 0;
+        }
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.flush_ticks();
+        let _ = self.out.flush();
+    }
+}
+
+/// The messages of a trace, each with its time since the start
+pub fn load(path: &Path) -> Result<Vec<(Duration, Msg)>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+pub fn parse(text: &str) -> Result<Vec<(Duration, Msg)>, String> {
+    let mut lines = text.lines().enumerate();
+    if lines.next().map(|(_, l)| l) != Some(HEADER) {
+        return Err(format!("not a ferric trace (no `{}` header)", HEADER));
+    }
+    let mut trace = Vec::new();
+    for (n, line) in lines {
+        if line.is_empty() {
+            continue;
+        }
+        let bad = || format!("line {}: cannot read `{}`", n + 1, line);
+        let (at, rest) = line.split_once('\t').ok_or_else(bad)?;
+        let at = Duration::from_millis(at.parse().map_err(|_| bad())?);
+        let fields = split(rest);
+        let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+        if let ["tick", count] = fields[..] {
+            let count: u64 = count.parse().map_err(|_| bad())?;
+            trace.extend((0..count).map(|_| (at, Msg::Tick)));
+            continue;
+        }
+        trace.push((at, decode(&fields).ok_or_else(bad)?));
+    }
+    Ok(trace)
+}
+
+/// Consistent stand-ins for paths and serial numbers
+#[derive(Debug, Default)]
+struct Redactor {
+    enabled: bool,
+    home: Option<String>,
+    /// Original path to its stand-in
+    paths: HashMap<String, String>,
+}
+
+impl Redactor {
+    fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            home: iso::home_dir().map(|h| h.as_path().display().to_string()),
+            paths: HashMap::new(),
+        }
+    }
+
+    fn path(&mut self, path: &Path) -> String {
+        let text = path.display().to_string();
+        if !self.enabled || path.starts_with("/dev") {
+            return text;
+        }
+        let next = self.paths.len() + 1;
+        self.paths
+            .entry(text)
+            .or_insert_with(|| match path.extension() {
+                Some(ext) => format!("/redacted/{}.{}", next, ext.to_string_lossy()),
+                None => format!("/redacted/{}", next),
+            })
+            .clone()
+    }
+
+    /// A file name in a listing
+    fn name(&mut self, name: &str) -> String {
+        match self.enabled {
+            true => self.path(Path::new(name)).replace("/redacted/", ""),
+            false => name.to_string(),
+        }
+    }
+
+    /// Free text: known paths, longest first, then the home directory
+    fn text(&self, text: &str) -> String {
+        if !self.enabled {
+            return text.to_string();
+        }
+        let mut known: Vec<(&String, &String)> = self.paths.iter().collect();
+        known.sort_by_key(|(original, _)| std::cmp::Reverse(original.len()));
+        let mut text = text.to_string();
+        for (original, stand_in) in known {
+            text = text.replace(original.as_str(), stand_in);
+        }
+        match &self.home {
+            Some(home) if home.len() > 1 => text.replace(home.as_str(), "~"),
+            _ => text,
+        }
+    }
+
+    fn serial(&self, serial: &Option<String>) -> String {
+        match self.enabled {
+            true => String::new(),
+            false => serial.clone().unwrap_or_default(),
+        }
+    }
+}
+
+fn encode(msg: &Msg, r: &mut Redactor) -> String {
+    let result = |fields: &mut Vec<String>, res: &Result<(), String>, r: &Redactor| match res {
+        Ok(()) => fields.push("ok".to_string()),
+        Err(e) => fields.extend(["err".to_string(), r.text(e)]),
+    };
+    let plain = |kind: &str| vec![kind.to_string()];
+    let fields: Vec<String> = match msg {
+        Msg::Tick => vec!["tick".to_string(), "1".to_string()],
+        Msg::Key(key) => vec![
+            "key".to_string(),
+            key_code(key.code),
+            key.modifiers.bits().to_string(),
+        ],
+        Msg::Quit => plain("quit"),
+        Msg::Back => plain("back"),
+        Msg::NextScreen => plain("next-screen"),
+        Msg::PrevScreen => plain("prev-screen"),
+        Msg::IsoQueryChanged(q) => vec!["iso-query".to_string(), q.clone()],
+        Msg::IsoSearchRequested => plain("iso-search"),
+        Msg::IsoSearchFailed(e) => vec!["iso-search-failed".to_string(), r.text(e)],
+        Msg::IsoCacheLoaded(isos) => isos_fields("iso-cache", isos, r),
+        Msg::IsoResultsPartial(isos) => isos_fields("iso-partial", isos, r),
+        Msg::IsoResults(isos) => isos_fields("iso-results", isos, r),
+        Msg::IsoIndexChanged(change) => {
+            let mut fields = vec!["iso-changed".to_string(), change.added.len().to_string()];
+            fields.extend(change.added.iter().map(|m| iso_record(m, r)));
+            fields.extend(change.removed.iter().map(|p| r.path(p.as_path())));
+            fields
+        }
+        Msg::IsoMoveSelection(n) => vec!["iso-move".to_string(), n.to_string()],
+        Msg::IsoConfirmSelect => plain("iso-select"),
+        Msg::BrowseOpened(Ok(Opened::Dir(dir, entries))) => {
+            let mut fields = vec!["browse-dir".to_string(), r.path(dir)];
+            fields.extend(entries.iter().map(|e| {
+                join(&[
+                    r.name(&e.name),
+                    u8::from(e.is_dir).to_string(),
+                    e.size.as_u64().to_string(),
+                ])
+            }));
+            fields
+        }
+        Msg::BrowseOpened(Ok(Opened::Image(meta))) => {
+            vec!["browse-image".to_string(), iso_record(meta, r)]
+        }
+        Msg::BrowseOpened(Err(e)) => vec!["browse-failed".to_string(), r.text(e)],
+        Msg::PathCompleted { input, completed } => {
+            vec!["completed".to_string(), r.text(input), r.text(completed)]
+        }
+        Msg::DevicesRefreshFailed(e) => vec!["devices-failed".to_string(), r.text(e)],
+        Msg::DevicesRefreshed(devices) => {
+            let mut fields = plain("devices");
+            fields.extend(devices.iter().map(|d| device_record(d, r)));
+            fields
+        }
+        Msg::DeviceMoveSelection(n) => vec!["device-move".to_string(), n.to_string()],
+        Msg::DeviceConfirmSelect => plain("device-select"),
+        Msg::RefreshDevicesRequested => plain("refresh-devices"),
+        Msg::WriteStarted { total } => vec!["write-started".to_string(), total.to_string()],
+        Msg::WriteProgress {
+            written,
+            total,
+            bps,
+        } => progress("write-progress", None, *written, *total, *bps),
+        Msg::WriteFinished(res) => {
+            let mut fields = plain("write-finished");
+            result(&mut fields, res, r);
+            fields
+        }
+        Msg::WriteWarning(w) => vec!["warning".to_string(), r.text(w)],
+        Msg::VerifyStarted { total } => vec!["verify-started".to_string(), total.to_string()],
+        Msg::VerifyProgress {
+            checked,
+            total,
+            bps,
+        } => progress("verify-progress", None, *checked, *total, *bps),
+        Msg::VerifyFinished(res) => {
+            let mut fields = plain("verify-finished");
+            result(&mut fields, res, r);
+            fields
+        }
+        Msg::HistoryRecorded(Ok(entries)) => {
+            let mut fields = plain("history");
+            fields.extend(entries.iter().map(|e| {
+                let mut e = e.clone();
+                e.image = IsoPath::from(PathBuf::from(r.path(e.image.as_path())));
+                if let Err(why) = &e.result {
+                    e.result = Err(r.text(why));
+                }
+                e.serial = Some(r.serial(&e.serial)).filter(|s| !s.is_empty());
+                history::render_line(&e)
+            }));
+            fields
+        }
+        Msg::HistoryRecorded(Err(e)) => vec!["history-failed".to_string(), r.text(e)],
+        Msg::PersistenceFinished(res) => {
+            let mut fields = plain("persistence");
+            result(&mut fields, res, r);
+            fields
+        }
+        Msg::Ejected { device, result } => {
+            let mut fields = vec!["ejected".to_string(), device.to_string()];
+            match result {
+                Ok(off) => fields.extend(["ok".to_string(), u8::from(*off).to_string()]),
+                Err(e) => fields.extend(["err".to_string(), r.text(e)]),
+            }
+            fields
+        }
+        Msg::ReportExported(Ok(paths)) => {
+            let mut fields = plain("report");
+            fields.extend(paths.iter().map(|p| r.path(p)));
+            fields
+        }
+        Msg::ReportExported(Err(e)) => vec!["report-failed".to_string(), r.text(e)],
+        Msg::TargetWriteProgress {
+            device,
+            written,
+            total,
+            bps,
+        } => progress(
+            "target-write-progress",
+            Some(device),
+            *written,
+            *total,
+            *bps,
+        ),
+        Msg::TargetWriteFinished {
+            device,
+            result: res,
+        } => {
+            let mut fields = vec!["target-write-finished".to_string(), device.to_string()];
+            result(&mut fields, res, r);
+            fields
+        }
+        Msg::TargetVerifyProgress {
+            device,
+            checked,
+            total,
+            bps,
+        } => progress(
+            "target-verify-progress",
+            Some(device),
+            *checked,
+            *total,
+            *bps,
+        ),
+        Msg::TargetVerifyFinished {
+            device,
+            result: res,
+        } => {
+            let mut fields = vec!["target-verify-finished".to_string(), device.to_string()];
+            result(&mut fields, res, r);
+            fields
+        }
+        Msg::ElevateRequested => plain("elevate"),
+        Msg::OpenLogRequested => plain("open-log"),
+        Msg::HelperStarted(res) => {
+            let mut fields = plain("helper-started");
+            result(&mut fields, res, r);
+            fields
+        }
+        Msg::HelperExited => plain("helper-exited"),
+    };
+    join(&fields)
+}
+
+fn decode(fields: &[&str]) -> Option<Msg> {
+    let result = |rest: &[&str]| match rest {
+        ["ok"] => Some(Ok(())),
+        ["err", e] => Some(Err(e.to_string())),
+        _ => None,
+    };
+    let device = |d: &str| DevicePath::from(PathBuf::from(d));
+    let isos = |rest: &[&str]| {
+        rest.iter()
+            .map(|f| parse_iso(f))
+            .collect::<Option<Vec<_>>>()
+    };
+    Some(match fields {
+        ["key", code, mods] => Msg::Key(KeyEvent::new(
+            parse_key_code(code)?,
+            KeyModifiers::from_bits_truncate(mods.parse().ok()?),
+        )),
+        ["quit"] => Msg::Quit,
+        ["back"] => Msg::Back,
+        ["next-screen"] => Msg::NextScreen,
+        ["prev-screen"] => Msg::PrevScreen,
+        ["iso-query", q] => Msg::IsoQueryChanged(q.to_string()),
+        ["iso-search"] => Msg::IsoSearchRequested,
+        ["iso-search-failed", e] => Msg::IsoSearchFailed(e.to_string()),
+        ["iso-cache", rest @ ..] => Msg::IsoCacheLoaded(isos(rest)?),
+        ["iso-partial", rest @ ..] => Msg::IsoResultsPartial(isos(rest)?),
+        ["iso-results", rest @ ..] => Msg::IsoResults(isos(rest)?),
+        ["iso-changed", added, rest @ ..] => {
+            let (added, removed) = rest.split_at_checked(added.parse().ok()?)?;
+            Msg::IsoIndexChanged(IndexChange {
+                added: isos(added)?,
+                removed: removed
+                    .iter()
+                    .map(|p| IsoPath::from(PathBuf::from(p)))
+                    .collect(),
+            })
+        }
+        ["iso-move", n] => Msg::IsoMoveSelection(n.parse().ok()?),
+        ["iso-select"] => Msg::IsoConfirmSelect,
+        ["browse-dir", dir, entries @ ..] => {
+            let entries = entries
+                .iter()
+                .map(|e| match &split(e)[..] {
+                    [name, is_dir, size] => Some(DirEntry {
+                        name: name.clone(),
+                        is_dir: is_dir == "1",
+                        size: ByteSize::new(size.parse().ok()?),
+                    }),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()?;
+            Msg::BrowseOpened(Ok(Opened::Dir(PathBuf::from(dir), entries)))
+        }
+        ["browse-image", meta] => Msg::BrowseOpened(Ok(Opened::Image(parse_iso(meta)?))),
+        ["browse-failed", e] => Msg::BrowseOpened(Err(e.to_string())),
+        ["completed", input, completed] => Msg::PathCompleted {
+            input: input.to_string(),
+            completed: completed.to_string(),
+        },
+        ["devices-failed", e] => Msg::DevicesRefreshFailed(e.to_string()),
+        ["devices", rest @ ..] => Msg::DevicesRefreshed(
+            rest.iter()
+                .map(|d| parse_device(d))
+                .collect::<Option<Vec<_>>>()?,
+        ),
+        ["device-move", n] => Msg::DeviceMoveSelection(n.parse().ok()?),
+        ["device-select"] => Msg::DeviceConfirmSelect,
+        ["refresh-devices"] => Msg::RefreshDevicesRequested,
+        ["write-started", total] => Msg::WriteStarted {
+            total: total.parse().ok()?,
+        },
+        ["write-progress", written, total, bps] => Msg::WriteProgress {
+            written: written.parse().ok()?,
+            total: total.parse().ok()?,
+            bps: bps.parse().ok()?,
+        },
+        ["write-finished", rest @ ..] => Msg::WriteFinished(result(rest)?),
+        ["warning", w] => Msg::WriteWarning(w.to_string()),
+        ["verify-started", total] => Msg::VerifyStarted {
+            total: total.parse().ok()?,
+        },
+        ["verify-progress", checked, total, bps] => Msg::VerifyProgress {
+            checked: checked.parse().ok()?,
+            total: total.parse().ok()?,
+            bps: bps.parse().ok()?,
+        },
+        ["verify-finished", rest @ ..] => Msg::VerifyFinished(result(rest)?),
+        ["history", rest @ ..] => Msg::HistoryRecorded(Ok(rest
+            .iter()
+            .map(|line| history::parse_line(line))
+            .collect::<Option<Vec<HistoryEntry>>>()?)),
+        ["history-failed", e] => Msg::HistoryRecorded(Err(e.to_string())),
+        ["persistence", rest @ ..] => Msg::PersistenceFinished(result(rest)?),
+        ["ejected", dev, "ok", off] => Msg::Ejected {
+            device: device(dev),
+            result: Ok(*off == "1"),
+        },
+        ["ejected", dev, "err", e] => Msg::Ejected {
+            device: device(dev),
+            result: Err(e.to_string()),
+        },
+        ["report", paths @ ..] => {
+            Msg::ReportExported(Ok(paths.iter().map(PathBuf::from).collect()))
+        }
+        ["report-failed", e] => Msg::ReportExported(Err(e.to_string())),
+        ["target-write-progress", dev, written, total, bps] => Msg::TargetWriteProgress {
+            device: device(dev),
+            written: written.parse().ok()?,
+            total: total.parse().ok()?,
+            bps: bps.parse().ok()?,
+        },
+        ["target-write-finished", dev, rest @ ..] => Msg::TargetWriteFinished {
+            device: device(dev),
+            result: result(rest)?,
+        },
+        ["target-verify-progress", dev, checked, total, bps] => Msg::TargetVerifyProgress {
+            device: device(dev),
+            checked: checked.parse().ok()?,
+            total: total.parse().ok()?,
+            bps: bps.parse().ok()?,
+        },
+        ["target-verify-finished", dev, rest @ ..] => Msg::TargetVerifyFinished {
+            device: device(dev),
+            result: result(rest)?,
+        },
+        ["elevate"] => Msg::ElevateRequested,
+        ["open-log"] => Msg::OpenLogRequested,
+        ["helper-started", rest @ ..] => Msg::HelperStarted(result(rest)?),
+        ["helper-exited"] => Msg::HelperExited,
+        _ => return None,
+    })
+}
+
+fn progress(
+    kind: &str,
+    device: Option<&DevicePath>,
+    done: u64,
+    total: u64,
+    bps: f64,
+) -> Vec<String> {
+    let mut fields = vec![kind.to_string()];
+    fields.extend(device.map(|d| d.to_string()));
+    fields.extend([done.to_string(), total.to_string(), bps.to_string()]);
+    fields
+}
+
+fn isos_fields(kind: &str, isos: &[IsoMeta], r: &mut Redactor) -> Vec<String> {
+    let mut fields = vec![kind.to_string()];
+    fields.extend(isos.iter().map(|m| iso_record(m, r)));
+    fields
+}
+
+fn iso_record(m: &IsoMeta, r: &mut Redactor) -> String {
+    let mtime = m
+        .modified
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs().to_string())
+        .unwrap_or_default();
+    join(&[
+        r.path(m.path.as_path()),
+        m.size.as_u64().to_string(),
+        mtime,
+        u8::from(m.hybrid).to_string(),
+        m.distro.distro.badge().to_string(),
+        m.distro.version.clone().unwrap_or_default(),
+        m.distro.arch.map(|a| a.as_str()).unwrap_or("").to_string(),
+        m.distro.label.clone().unwrap_or_default(),
+    ])
+}
+
+fn parse_iso(record: &str) -> Option<IsoMeta> {
+    let non_empty = |s: &String| (!s.is_empty()).then(|| s.clone());
+    let [path, size, mtime, hybrid, distro, version, arch, label] = &split(record)[..] else {
+        return None;
+    };
+    Some(IsoMeta {
+        path: IsoPath::from(PathBuf::from(path)),
+        size: ByteSize::new(size.parse().ok()?),
+        modified: mtime
+            .parse()
+            .ok()
+            .map(|s| UNIX_EPOCH + Duration::from_secs(s)),
+        hybrid: hybrid == "1",
+        distro: DistroInfo {
+            distro: Distro::parse(distro).unwrap_or_default(),
+            version: non_empty(version),
+            arch: Arch::parse(arch),
+            label: non_empty(label),
+        },
+    })
+}
+
+fn device_record(d: &Device, r: &mut Redactor) -> String {
+    let flag = |b: bool| u8::from(b).to_string();
+    let mut fields = vec![
+        d.name.clone(),
+        d.path.to_string(),
+        d.size.as_u64().to_string(),
+        d.model.clone().unwrap_or_default(),
+        r.serial(&d.serial),
+        d.tran.clone().unwrap_or_default(),
+        flag(d.removable),
+        flag(d.hotplug),
+        flag(d.ro),
+        flag(d.mounted),
+        flag(d.access.write),
+        flag(d.access.admin),
+        d.access.denied.clone().unwrap_or_default(),
+        d.udisks.clone().unwrap_or_default(),
+    ];
+    fields.extend(d.labels.iter().cloned());
+    join(&fields)
+}
+
+fn parse_device(record: &str) -> Option<Device> {
+    let fields = split(record);
+    let [
+        name,
+        path,
+        size,
+        model,
+        serial,
+        tran,
+        removable,
+        hotplug,
+        ro,
+        mounted,
+        write,
+        admin,
+        denied,
+        udisks,
+        labels @ ..,
+    ] = &fields[..]
+    else {
+        return None;
+    };
+    let non_empty = |s: &String| (!s.is_empty()).then(|| s.clone());
+    Some(Device {
+        name: name.clone(),
+        path: DevicePath::from(PathBuf::from(path)),
+        size: ByteSize::new(size.parse().ok()?),
+        model: non_empty(model),
+        serial: non_empty(serial),
+        tran: non_empty(tran),
+        removable: removable == "1",
+        hotplug: hotplug == "1",
+        ro: ro == "1",
+        mounted: mounted == "1",
+        labels: labels.to_vec(),
+        access: DeviceAccess {
+            write: write == "1",
+            admin: admin == "1",
+            denied: non_empty(denied),
+        },
+        udisks: non_empty(udisks),
+    })
+}
+
+/// Keys that are not characters, by the name they are written as
+const NAMED_KEYS: [(&str, KeyCode); 15] = [
+    ("enter", KeyCode::Enter),
+    ("esc", KeyCode::Esc),
+    ("backspace", KeyCode::Backspace),
+    ("tab", KeyCode::Tab),
+    ("backtab", KeyCode::BackTab),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+    ("delete", KeyCode::Delete),
+    ("insert", KeyCode::Insert),
+];
+
+fn key_code(code: KeyCode) -> String {
+    match code {
+        KeyCode::Char(c) => format!("char:{}", c),
+        KeyCode::F(n) => format!("f:{}", n),
+        other => NAMED_KEYS
+            .iter()
+            .find(|(_, k)| *k == other)
+            .map(|(name, _)| name.to_string())
+            .unwrap_or_else(|| "null".to_string()),
+    }
+}
+
+fn parse_key_code(s: &str) -> Option<KeyCode> {
+    if let Some(c) = s.strip_prefix("char:") {
+        let mut chars = c.chars();
+        return match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(KeyCode::Char(c)),
+            _ => None,
+        };
+    }
+    if let Some(n) = s.strip_prefix("f:") {
+        return n.parse().ok().map(KeyCode::F);
+    }
+    match s {
+        "null" => Some(KeyCode::Null),
+        _ => NAMED_KEYS
+            .iter()
+            .find(|(name, _)| *name == s)
+            .map(|(_, k)| *k),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn iso(path: &str) -> IsoMeta {
+        IsoMeta {
+            path: IsoPath::from(PathBuf::from(path)),
+            size: ByteSize::new(42),
+            modified: Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+            distro: DistroInfo {
+                distro: Distro::Ubuntu,
+                version: Some("24.04".to_string()),
+                arch: Arch::parse("x86_64"),
+                label: Some("Ubuntu\t24.04".to_string()),
+            },
+            hybrid: true,
+        }
+    }
+
+    fn round_trip(msg: &Msg, redact: bool) -> Msg {
+        let line = encode(msg, &mut Redactor::new(redact));
+        assert!(!line.contains('\n'));
+        let fields = split(&line);
+        let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+        decode(&fields).unwrap_or_else(|| panic!("cannot decode `{}`", line))
+    }
+
+    #[test]
+    fn messages_survive_the_trace() {
+        let Msg::IsoResults(isos) = round_trip(&Msg::IsoResults(vec![iso("/srv/a.iso")]), false)
+        else {
+            panic!("not iso results");
+        };
+        assert_eq!(isos[0].path, iso("/srv/a.iso").path);
+        assert_eq!(isos[0].distro.label.as_deref(), Some("Ubuntu\t24.04"));
+
+        let key = Msg::Key(KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL));
+        assert!(matches!(
+            round_trip(&key, false),
+            Msg::Key(k) if k.code == KeyCode::Char('r') && k.modifiers == KeyModifiers::CONTROL
+        ));
+
+        let change = Msg::IsoIndexChanged(IndexChange {
+            added: vec![iso("/srv/b.iso")],
+            removed: vec![IsoPath::from(PathBuf::from("/srv/a.iso"))],
+        });
+        assert!(matches!(
+            round_trip(&change, false),
+            Msg::IsoIndexChanged(c) if c.added.len() == 1 && c.removed.len() == 1
+        ));
+
+        let ejected = Msg::Ejected {
+            device: DevicePath::from(PathBuf::from("/dev/sdb")),
+            result: Ok(true),
+        };
+        assert!(matches!(
+            round_trip(&ejected, false),
+            Msg::Ejected {
+                result: Ok(true),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn redaction_hides_paths_consistently() {
+        let mut r = Redactor::new(true);
+        r.home = Some("/home/alice".to_string());
+        let first = encode(
+            &Msg::IsoResults(vec![iso("/home/alice/secret.iso")]),
+            &mut r,
+        );
+        assert!(first.contains("/redacted/1.iso"));
+        assert!(!first.contains("secret"));
+        let failed = Msg::WriteFinished(Err(
+            "Failed to open ISO /home/alice/secret.iso in /home/alice/isos".to_string(),
+        ));
+        let line = encode(&failed, &mut r);
+        assert_eq!(
+            line,
+            "write-finished\terr\tFailed to open ISO /redacted/1.iso in ~/isos"
+        );
+        // Device nodes say nothing about the user
+        let warning = Msg::Ejected {
+            device: DevicePath::from(PathBuf::from("/dev/sdb")),
+            result: Ok(false),
+        };
+        assert!(encode(&warning, &mut r).contains("/dev/sdb"));
+    }
+
+    #[test]
+    fn ticks_collapse_and_expand() {
+        let path = std::env::temp_dir().join(format!("ferric-trace-{}.txt", std::process::id()));
+        {
+            let mut recorder = Recorder::create(&path, false).unwrap();
+            for _ in 0..3 {
+                recorder.record(&Msg::Tick);
+            }
+            recorder.record(&Msg::IsoConfirmSelect);
+            recorder.record(&Msg::Tick);
+        }
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(text.lines().count(), 4);
+        let trace = load(&path).unwrap();
+        let kinds: Vec<&str> = trace
+            .iter()
+            .map(|(_, m)| match m {
+                Msg::Tick => "tick",
+                Msg::IsoConfirmSelect => "select",
+                _ => "other",
+            })
+            .collect();
+        assert_eq!(kinds, ["tick", "tick", "tick", "select", "tick"]);
+        assert!(parse("ferric-trace 1\n12\tformat-disk\n").is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+}
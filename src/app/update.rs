@@ -1,22 +1,36 @@
 use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use std::{path::PathBuf, time::SystemTime};
 
 use super::{
+    keymap::Action,
     msg::{Cmd, Msg},
-    state::{ActivePanel, Model, Screen},
+    state::{
+        ActivePanel, Browser, DevicePath, Job, JobQueue, Model, Screen, TargetState, WindowsFs,
+        WindowsOptions, WriteMode, WriteRoute,
+    },
 };
+use crate::domain::{
+    IsoMeta,
+    browse::{self, Opened},
+    history::{self, HistoryEntry},
+    iso,
+    iso_index::IndexChange,
+    partition::PartitionScheme,
+    persistence,
+    report::{Report, ReportTarget, VerifyMode},
+};
+use crate::helper::Request;
 
 pub fn update(mut model: Model, msg: Msg) -> (Model, Vec<Cmd>) {
     match msg {
         Msg::Tick => {
-            if matches!(model.screen, Screen::IsoSearch) && !model.iso_searching {
-                let now = std::time::Instant::now();
-                let debounce_due = model.iso_debounce_until.map(|t| now >= t).unwrap_or(false);
-                if model.iso_results.is_empty() || debounce_due {
-                    model.iso_searching = true;
-                    model.iso_debounce_until = None;
-                    let query = model.iso_query.clone();
-                    return (model, vec![Cmd::ScanIso { query }]);
-                }
+            if matches!(model.screen, Screen::IsoSearch)
+                && !model.iso_searching
+                && !model.iso_indexed
+            {
+                model.iso_searching = true;
+                let options = model.scan.clone();
+                return (model, vec![Cmd::ScanIso { options }]);
             }
             if matches!(model.screen, Screen::DeviceSelect)
                 && !model.device_refreshing
@@ -28,7 +42,9 @@ pub fn update(mut model: Model, msg: Msg) -> (Model, Vec<Cmd>) {
         }
         Msg::Quit => {}
         Msg::Back => match model.screen {
-            Screen::IsoSearch => {}
+            Screen::IsoSearch => {
+                model.browser = None;
+            }
             Screen::DeviceSelect => {
                 model.screen = Screen::IsoSearch;
                 model.active_panel = sync_active_panel(&model.screen);
@@ -42,15 +58,17 @@ pub fn update(mut model: Model, msg: Msg) -> (Model, Vec<Cmd>) {
                 model.active_panel = sync_active_panel(&model.screen);
             }
             Screen::Done => {
+                // Leaving Done drops a finished queue and cancels a waiting one
+                model.queue = JobQueue::default();
                 model.screen = Screen::DeviceSelect;
                 model.active_panel = sync_active_panel(&model.screen);
             }
         },
         Msg::Key(key) => {
-            if key.kind == KeyEventKind::Press {
-                if let Some(cmd) = handle_key(&mut model, key) {
-                    return (model, vec![cmd]);
-                }
+            if key.kind == KeyEventKind::Press
+                && let Some(cmd) = handle_key(&mut model, key)
+            {
+                return (model, vec![cmd]);
             }
         }
         Msg::NextScreen => {
@@ -63,24 +81,41 @@ pub fn update(mut model: Model, msg: Msg) -> (Model, Vec<Cmd>) {
         }
         Msg::IsoQueryChanged(q) => {
             model.iso_query = q;
+            refilter_isos(&mut model);
             model.iso_selected = 0;
-            model.iso_debounce_until =
-                Some(std::time::Instant::now() + std::time::Duration::from_millis(200));
         }
         Msg::IsoSearchRequested => {
             model.iso_searching = true;
-            let query = model.iso_query.clone();
-            return (model, vec![Cmd::ScanIso { query }]);
+            let options = model.scan.clone();
+            return (model, vec![Cmd::ScanIso { options }]);
         }
         Msg::IsoSearchFailed(_err) => {
             model.iso_searching = false;
         }
-        Msg::IsoResults(results) => {
-            model.iso_results = results;
-            if model.iso_selected >= model.iso_results.len() {
-                model.iso_selected = model.iso_results.len().saturating_sub(1);
+        Msg::IsoCacheLoaded(entries) => {
+            // Shown until the walk finishes; a finished index wins
+            if !model.iso_indexed {
+                model.iso_index = entries;
+                refilter_isos(&mut model);
             }
+        }
+        Msg::IsoResultsPartial(entries) => {
+            let change = IndexChange {
+                added: entries,
+                removed: Vec::new(),
+            };
+            change.apply(&mut model.iso_index);
+            refilter_isos(&mut model);
+        }
+        Msg::IsoResults(entries) => {
+            model.iso_index = entries;
+            model.iso_indexed = true;
             model.iso_searching = false;
+            refilter_isos(&mut model);
+        }
+        Msg::IsoIndexChanged(change) => {
+            change.apply(&mut model.iso_index);
+            refilter_isos(&mut model);
         }
         Msg::IsoMoveSelection(delta) => {
             let len = model.iso_results.len();
@@ -92,22 +127,87 @@ pub fn update(mut model: Model, msg: Msg) -> (Model, Vec<Cmd>) {
         }
         Msg::IsoConfirmSelect => {
             if let Some(meta) = model.iso_results.get(model.iso_selected).cloned() {
-                model.iso_chosen = Some(meta.path);
+                choose_iso(&mut model, meta);
                 model.screen = Screen::DeviceSelect;
                 model.active_panel = sync_active_panel(&model.screen);
                 model.device_refreshing = true;
                 return (model, vec![Cmd::RefreshDevices]);
             }
         }
+        Msg::BrowseOpened(result) => {
+            let Some(browser) = model.browser.as_mut() else {
+                return (model, vec![]);
+            };
+            match result {
+                Ok(Opened::Dir(dir, entries)) => {
+                    // Coming up from a subdirectory, keep it under the cursor
+                    let selected = entries
+                        .iter()
+                        .position(|e| dir.join(&e.name) == browser.dir)
+                        .unwrap_or(0);
+                    *browser = Browser {
+                        dir,
+                        entries,
+                        selected,
+                        ..Browser::default()
+                    };
+                }
+                Ok(Opened::Image(meta)) => {
+                    model.browser = None;
+                    model.iso_browsed.retain(|m| m.path != meta.path);
+                    model.iso_browsed.push(meta.clone());
+                    choose_iso(&mut model, meta);
+                    model.screen = Screen::DeviceSelect;
+                    model.active_panel = sync_active_panel(&model.screen);
+                    model.device_refreshing = true;
+                    return (model, vec![Cmd::RefreshDevices]);
+                }
+                Err(err) => browser.error = Some(err),
+            }
+        }
+        Msg::PathCompleted { input, completed } => {
+            if let Some(browser) = model.browser.as_mut()
+                && browser.input == input
+            {
+                browser.input = completed;
+            }
+        }
         Msg::DevicesRefreshFailed(_err) => {
             model.device_refreshing = false;
         }
         Msg::DevicesRefreshed(devs) => {
-            model.devices = devs;
+            model.devices = devs
+                .into_iter()
+                .filter(|d| model.safety.permits(d))
+                .collect();
             if model.device_selected >= model.devices.len() {
                 model.device_selected = model.devices.len().saturating_sub(1);
             }
             model.device_refreshing = false;
+            if model.queue.awaiting_device && matches!(model.screen, Screen::Done) {
+                let found = model
+                    .queue
+                    .current_job()
+                    .and_then(|job| model.devices.iter().find(|d| d.path == job.device).cloned());
+                // Without a serial, only an empty path proves the stick left
+                let same_stick = found.as_ref().is_some_and(|d| {
+                    model.queue.written_serial.is_none() || d.serial == model.queue.written_serial
+                });
+                if !same_stick {
+                    model.queue.written_removed = true;
+                }
+                match found {
+                    Some(_) if model.queue.written_removed => {
+                        // A new stick goes through Confirm like the first one
+                        let index = model.queue.current.unwrap_or(0);
+                        load_job(&mut model, index);
+                        model.confirm_input.clear();
+                        model.screen = Screen::Confirm;
+                        model.active_panel = sync_active_panel(&model.screen);
+                    }
+                    _ => model.queue.device_missing = true,
+                }
+            }
         }
         Msg::DeviceMoveSelection(delta) => {
             let len = model.devices.len();
@@ -119,7 +219,9 @@ pub fn update(mut model: Model, msg: Msg) -> (Model, Vec<Cmd>) {
         }
         Msg::DeviceConfirmSelect => {
             if let Some(dev) = model.devices.get(model.device_selected).cloned() {
-                model.device_chosen = Some(dev.path);
+                model.device_chosen =
+                    Some(model.device_marked.first().cloned().unwrap_or(dev.path));
+                offer_persistence(&mut model);
                 model.confirm_input.clear();
                 model.screen = Screen::Confirm;
                 model.active_panel = sync_active_panel(&model.screen);
@@ -130,8 +232,29 @@ pub fn update(mut model: Model, msg: Msg) -> (Model, Vec<Cmd>) {
             return (model, vec![Cmd::RefreshDevices]);
         }
         Msg::ElevateRequested => {
-            if !model.is_root {
-                return (model, vec![Cmd::ReexecWithSudo]);
+            if model.access_missing().is_some()
+                && model.write_route() == WriteRoute::Direct
+                && model.needs_root().is_none()
+                && let Some(elevator) = model.elevator
+            {
+                return (model, vec![Cmd::StartHelper(elevator)]);
+            }
+        }
+        Msg::OpenLogRequested => return (model, vec![Cmd::OpenLog]),
+        Msg::HelperStarted(started) => {
+            model.helper = Some(started);
+        }
+        Msg::HelperExited => {
+            model.helper = Some(Err("the privileged helper exited".to_string()));
+            let lost = || Err("the privileged helper exited mid-job".to_string());
+            if model.is_writing() && model.targets.is_empty() && !model.verifying {
+                return update(model, Msg::WriteFinished(lost()));
+            }
+            if model.is_verifying() {
+                return update(model, Msg::VerifyFinished(lost()));
+            }
+            for (_, outcome) in model.ejects.iter_mut().filter(|(_, o)| o.is_none()) {
+                *outcome = Some(Err("the privileged helper exited".to_string()));
             }
         }
         Msg::WriteStarted { total } => {
@@ -151,27 +274,41 @@ pub fn update(mut model: Model, msg: Msg) -> (Model, Vec<Cmd>) {
         }
         Msg::WriteFinished(result) => {
             model.write_result = Some(result);
-            if model.write_result == Some(Ok(())) && model.verify_after_write {
+            // A file-level install no longer matches the image byte for byte.
+            if model.write_result == Some(Ok(()))
+                && model.verify_after_write
+                && model.write_mode == WriteMode::Raw
+            {
                 let total = model.writing_total;
+                let (Some(iso), Some(dev)) =
+                    (model.iso_chosen.clone(), model.device_chosen.clone())
+                else {
+                    let lost = "nothing to verify: the image or device selection is gone";
+                    return update(model, Msg::VerifyFinished(Err(lost.to_string())));
+                };
                 model.verifying = true;
                 model.verifying_total = total;
-                if let (Some(iso), Some(dev)) =
-                    (model.iso_chosen.clone(), model.device_chosen.clone())
-                {
-                    return (
-                        model,
-                        vec![Cmd::Verify {
-                            iso,
-                            device: dev,
-                            size: total,
-                        }],
-                    );
-                }
+                let cmd = raw_cmd(
+                    &model,
+                    Request::Verify {
+                        iso,
+                        device: dev,
+                        size: total,
+                    },
+                );
+                return (model, vec![cmd]);
+            } else if model.write_result == Some(Ok(()))
+                && let Some(cmd) = start_persistence(&mut model)
+            {
+                return (model, vec![cmd]);
             } else {
-                model.screen = Screen::Done;
-                model.active_panel = sync_active_panel(&model.screen);
+                let cmds = finish_write(&mut model);
+                return (model, cmds);
             }
         }
+        Msg::WriteWarning(warning) => {
+            model.write_warnings.push(warning);
+        }
         Msg::VerifyStarted { total } => {
             model.verifying = true;
             model.verifying_total = total;
@@ -188,10 +325,100 @@ pub fn update(mut model: Model, msg: Msg) -> (Model, Vec<Cmd>) {
             model.verifying_speed_bps = bps;
         }
         Msg::VerifyFinished(result) => {
+            let verified = result.is_ok();
             model.verify_result = Some(result);
             model.verifying = false;
-            model.screen = Screen::Done;
-            model.active_panel = sync_active_panel(&model.screen);
+            // Runs after the verify: the new partition changes the MBR
+            if verified && let Some(cmd) = start_persistence(&mut model) {
+                return (model, vec![cmd]);
+            }
+            let cmds = finish_write(&mut model);
+            return (model, cmds);
+        }
+        Msg::TargetWriteProgress {
+            device,
+            written,
+            total,
+            bps,
+        } => {
+            if let Some(t) = model.targets.iter_mut().find(|t| t.device == device) {
+                t.written = written;
+                t.total = total;
+                t.speed_bps = bps;
+            }
+        }
+        Msg::TargetWriteFinished { device, result } => {
+            let verify = model.verify_after_write;
+            if let Some(t) = model.targets.iter_mut().find(|t| t.device == device) {
+                t.verifying = verify && result.is_ok();
+                t.write_result = Some(result);
+            }
+            let cmds = finish_batch_if_done(&mut model);
+            return (model, cmds);
+        }
+        Msg::TargetVerifyProgress {
+            device,
+            checked,
+            total,
+            bps,
+        } => {
+            if let Some(t) = model.targets.iter_mut().find(|t| t.device == device) {
+                t.verified = checked;
+                t.total = total;
+                t.verify_speed_bps = bps;
+            }
+        }
+        Msg::TargetVerifyFinished { device, result } => {
+            if let Some(t) = model.targets.iter_mut().find(|t| t.device == device) {
+                t.verifying = false;
+                t.verify_result = Some(result);
+            }
+            let cmds = finish_batch_if_done(&mut model);
+            return (model, cmds);
+        }
+        Msg::PersistenceFinished(result) => {
+            model.persisting = false;
+            model.persistence_result = Some(result);
+            let cmds = finish_write(&mut model);
+            return (model, cmds);
+        }
+        Msg::HistoryRecorded(recorded) => {
+            if let Ok(entries) = recorded {
+                if let Some(report) = model.report.as_mut()
+                    && let Some(hashed) = entries
+                        .iter()
+                        .find(|e| e.image == report.image && !e.sha256.is_empty())
+                {
+                    report.sha256 = hashed.sha256.clone();
+                }
+                model.history.extend(entries);
+                refilter_isos(&mut model);
+            }
+            // Saved once the hash is known; export hashes it otherwise
+            if model.auto_report
+                && let Some(report) = model.report.clone()
+            {
+                let dir = model.report_dir.clone();
+                return (model, vec![Cmd::ExportReport { report, dir }]);
+            }
+        }
+        Msg::Ejected { device, result } => {
+            let gone = result.is_ok();
+            if let Some(entry) = model.ejects.iter_mut().find(|(d, _)| *d == device) {
+                entry.1 = Some(result);
+            }
+            if gone {
+                model.devices.retain(|d| d.path != device);
+                model.device_marked.retain(|d| *d != device);
+                model.device_selected = model
+                    .device_selected
+                    .min(model.devices.len().saturating_sub(1));
+                model.device_refreshing = true;
+                return (model, vec![Cmd::RefreshDevices]);
+            }
+        }
+        Msg::ReportExported(result) => {
+            model.report_exported = Some(result);
         }
     }
     (model, Vec::new())
@@ -199,34 +426,55 @@ pub fn update(mut model: Model, msg: Msg) -> (Model, Vec<Cmd>) {
 
 fn handle_key(model: &mut Model, key: KeyEvent) -> Option<Cmd> {
     match model.screen {
+        Screen::IsoSearch if model.browser.is_some() => return handle_browse_key(model, key),
         Screen::IsoSearch => match key.code {
             KeyCode::Tab => {
-                model.screen = next_screen(&model);
+                model.screen = next_screen(model);
                 model.active_panel = sync_active_panel(&model.screen);
             }
             KeyCode::BackTab => {
-                model.screen = prev_screen(&model);
+                model.screen = prev_screen(model);
                 model.active_panel = sync_active_panel(&model.screen);
             }
             KeyCode::Up => {
                 let len = model.iso_results.len();
-                if len > 0 {
-                    if model.iso_selected > 0 {
-                        model.iso_selected -= 1;
-                    }
+                if len > 0 && model.iso_selected > 0 {
+                    model.iso_selected -= 1;
                 }
             }
             KeyCode::Down => {
                 let len = model.iso_results.len();
-                if len > 0 {
-                    if model.iso_selected + 1 < len {
-                        model.iso_selected += 1;
+                if len > 0 && model.iso_selected + 1 < len {
+                    model.iso_selected += 1;
+                }
+            }
+            _ if model.keymap.matches(Action::Browse, &key) => {
+                let dir = model
+                    .scan
+                    .roots
+                    .first()
+                    .map(|r| r.as_path().to_path_buf())
+                    .unwrap_or_else(|| PathBuf::from("/"));
+                model.browser = Some(Browser {
+                    dir: dir.clone(),
+                    ..Browser::default()
+                });
+                return Some(Cmd::BrowseOpen { path: dir });
+            }
+            _ if model.keymap.matches(Action::MarkIso, &key) => {
+                if let Some(meta) = model.iso_results.get(model.iso_selected) {
+                    let path = meta.path.clone();
+                    match model.iso_marked.iter().position(|p| *p == path) {
+                        Some(i) => {
+                            model.iso_marked.remove(i);
+                        }
+                        None => model.iso_marked.push(path),
                     }
                 }
             }
             KeyCode::Enter => {
                 if let Some(meta) = model.iso_results.get(model.iso_selected).cloned() {
-                    model.iso_chosen = Some(meta.path);
+                    choose_iso(model, meta);
                     model.screen = Screen::DeviceSelect;
                     model.active_panel = sync_active_panel(&model.screen);
                     return Some(Cmd::RefreshDevices);
@@ -234,15 +482,13 @@ fn handle_key(model: &mut Model, key: KeyEvent) -> Option<Cmd> {
             }
             KeyCode::Backspace => {
                 model.iso_query.pop();
+                refilter_isos(model);
                 model.iso_selected = 0;
-                model.iso_debounce_until =
-                    Some(std::time::Instant::now() + std::time::Duration::from_millis(200));
             }
             KeyCode::Char(c) => {
                 model.iso_query.push(c);
+                refilter_isos(model);
                 model.iso_selected = 0;
-                model.iso_debounce_until =
-                    Some(std::time::Instant::now() + std::time::Duration::from_millis(200));
             }
             _ => {}
         },
@@ -267,13 +513,26 @@ fn handle_key(model: &mut Model, key: KeyEvent) -> Option<Cmd> {
                     model.device_selected += 1;
                 }
             }
-            KeyCode::Char('r') | KeyCode::Char('R') => {
+            _ if model.keymap.matches(Action::RefreshDevices, &key) => {
                 model.device_refreshing = true;
                 return Some(Cmd::RefreshDevices);
             }
+            _ if model.keymap.matches(Action::MarkDevice, &key) => {
+                if let Some(dev) = model.devices.get(model.device_selected) {
+                    let path = dev.path.clone();
+                    match model.device_marked.iter().position(|p| *p == path) {
+                        Some(i) => {
+                            model.device_marked.remove(i);
+                        }
+                        None => model.device_marked.push(path),
+                    }
+                }
+            }
             KeyCode::Enter => {
                 if let Some(dev) = model.devices.get(model.device_selected).cloned() {
-                    model.device_chosen = Some(dev.path);
+                    model.device_chosen =
+                        Some(model.device_marked.first().cloned().unwrap_or(dev.path));
+                    offer_persistence(model);
                     model.confirm_input.clear();
                     model.screen = Screen::Confirm;
                     model.active_panel = sync_active_panel(&model.screen);
@@ -293,36 +552,64 @@ fn handle_key(model: &mut Model, key: KeyEvent) -> Option<Cmd> {
             KeyCode::Backspace => {
                 model.confirm_input.pop();
             }
-            KeyCode::Enter => {
-                if can_confirm(model) {
-                    model.screen = Screen::Writing;
-                    model.active_panel = sync_active_panel(&model.screen);
-                    model.writing_written = 0;
-                    model.writing_total = 0;
-                    model.writing_started = Some(std::time::Instant::now());
-                    model.writing_speed_bps = 0.0;
-                    if let (Some(iso), Some(dev)) =
-                        (model.iso_chosen.clone(), model.device_chosen.clone())
-                    {
-                        return Some(Cmd::Write { iso, device: dev });
-                    }
+            // Jobs this process cannot run stay on Confirm, which says why
+            KeyCode::Enter if can_confirm(model) && model.can_write().is_ok() => {
+                if model.queue.is_running() {
+                    return start_write(model);
+                }
+                // Confirming with jobs queued runs the whole queue, this one last.
+                if !model.queue.is_empty() {
+                    enqueue_current(model);
+                    return start_job(model, 0);
                 }
+                return start_write(model);
+            }
+            _ if model.keymap.matches(Action::QueueJob, &key) && model.has_both_selections() => {
+                enqueue_current(model);
+                model.screen = Screen::IsoSearch;
+                model.active_panel = sync_active_panel(&model.screen);
             }
-            KeyCode::Char('s') | KeyCode::Char('S')
-                if key.modifiers.contains(KeyModifiers::CONTROL) =>
+            _ if model.keymap.matches(Action::Sudo, &key)
+                && model.access_missing().is_some()
+                && model.write_route() == WriteRoute::Direct
+                && model.needs_root().is_none() =>
             {
-                if !model.is_root {
-                    return Some(Cmd::ReexecWithSudo);
-                }
+                return model.elevator.map(Cmd::StartHelper);
             }
-            KeyCode::Char('v') | KeyCode::Char('V') => {
+            _ if model.keymap.matches(Action::ToggleVerify, &key) => {
                 model.verify_after_write = !model.verify_after_write;
             }
-            KeyCode::Char(c) => {
-                if c.is_ascii_alphabetic() && model.confirm_input.len() < 3 {
-                    model.confirm_input.push(c.to_ascii_uppercase());
+            _ if model.keymap.matches(Action::Eject, &key) => {
+                model.eject_after_write = !model.eject_after_write;
+            }
+            _ if model.keymap.matches(Action::TogglePersistence, &key)
+                && model.persistence_offer.is_some() =>
+            {
+                model.persistence_enabled = !model.persistence_enabled;
+            }
+            _ if model.keymap.matches(Action::TogglePartitionScheme, &key) => {
+                if let WriteMode::Windows(options) = &mut model.write_mode {
+                    options.scheme = match options.scheme {
+                        PartitionScheme::Mbr => PartitionScheme::Gpt,
+                        PartitionScheme::Gpt => PartitionScheme::Mbr,
+                    };
                 }
             }
+            _ if model.keymap.matches(Action::ToggleFilesystem, &key) => {
+                if let WriteMode::Windows(options) = &mut model.write_mode {
+                    options.filesystem = match options.filesystem {
+                        WindowsFs::Fat32 => WindowsFs::Ntfs,
+                        WindowsFs::Ntfs => WindowsFs::Fat32,
+                    };
+                }
+            }
+            KeyCode::Char(c)
+                if c.is_ascii_alphabetic()
+                    && model.confirm_input.len() < model.safety.confirm_word.len()
+                    && !key.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                model.confirm_input.push(c.to_ascii_uppercase());
+            }
             _ => {}
         },
         Screen::Writing => match key.code {
@@ -336,7 +623,7 @@ fn handle_key(model: &mut Model, key: KeyEvent) -> Option<Cmd> {
             }
             _ => {}
         },
-        _ => match key.code {
+        Screen::Done => match key.code {
             KeyCode::Tab => {
                 model.screen = next_screen(model);
                 model.active_panel = sync_active_panel(&model.screen);
@@ -345,6 +632,21 @@ fn handle_key(model: &mut Model, key: KeyEvent) -> Option<Cmd> {
                 model.screen = prev_screen(model);
                 model.active_panel = sync_active_panel(&model.screen);
             }
+            KeyCode::Enter if model.queue.awaiting_device => {
+                model.device_refreshing = true;
+                return Some(Cmd::RefreshDevices);
+            }
+            _ if model.keymap.matches(Action::Eject, &key) && model.ejects.is_empty() => {
+                let devices = written_devices(model, false);
+                return start_eject(model, devices);
+            }
+            _ if model.keymap.matches(Action::ExportReport, &key) => {
+                let report = model.report.clone()?;
+                return Some(Cmd::ExportReport {
+                    report,
+                    dir: model.report_dir.clone(),
+                });
+            }
             _ => {}
         },
     }
@@ -385,8 +687,490 @@ fn prev_screen(model: &Model) -> Screen {
     }
 }
 
+/// Keys while the path browser replaces the result list
+fn handle_browse_key(model: &mut Model, key: KeyEvent) -> Option<Cmd> {
+    if model.keymap.matches(Action::Browse, &key) {
+        model.browser = None;
+        return None;
+    }
+    let browser = model.browser.as_mut()?;
+    match key.code {
+        KeyCode::Up => browser.selected = browser.selected.saturating_sub(1),
+        KeyCode::Down if browser.selected + 1 < browser.entries.len() => browser.selected += 1,
+        KeyCode::Tab => {
+            return Some(Cmd::CompletePath {
+                dir: browser.dir.clone(),
+                input: browser.input.clone(),
+            });
+        }
+        KeyCode::Enter => {
+            let path = if browser.input.is_empty() {
+                browser
+                    .dir
+                    .join(&browser.entries.get(browser.selected)?.name)
+            } else {
+                browse::resolve(&browser.dir, &browser.input)
+            };
+            browser.error = None;
+            return Some(Cmd::BrowseOpen { path });
+        }
+        KeyCode::Backspace if browser.input.is_empty() => {
+            let parent = browser.dir.parent()?.to_path_buf();
+            return Some(Cmd::BrowseOpen { path: parent });
+        }
+        KeyCode::Backspace => {
+            browser.input.pop();
+            browser.error = None;
+        }
+        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+            browser.input.push(c);
+            browser.error = None;
+        }
+        _ => {}
+    }
+    None
+}
+
+/// Rebuild `iso_results` from the index, keeping the selected image
+/// under the cursor when it is still listed
+fn refilter_isos(model: &mut Model) {
+    let selected = model
+        .iso_results
+        .get(model.iso_selected)
+        .map(|m| m.path.clone());
+    model.iso_results = iso::filter(&model.iso_index, &model.iso_query);
+    // With no query, images written before come first
+    if model.iso_query.trim().is_empty() {
+        let recent = history::recent_images(&model.history, history::RECENT_LIMIT);
+        model.iso_results.sort_by_key(|m| {
+            recent
+                .iter()
+                .position(|p| *p == m.path)
+                .unwrap_or(usize::MAX)
+        });
+    }
+    model.iso_selected = selected
+        .and_then(|p| model.iso_results.iter().position(|m| m.path == p))
+        .unwrap_or_else(|| {
+            model
+                .iso_selected
+                .min(model.iso_results.len().saturating_sub(1))
+        });
+}
+
+/// Remember the image and pick the write mode it needs.
+///
+/// Any marked results turn the write into a multi-ISO drive instead.
+fn choose_iso(model: &mut Model, meta: IsoMeta) {
+    if let Some(first) = model.iso_marked.first() {
+        model.write_mode = WriteMode::Multiboot;
+        model.iso_chosen = Some(first.clone());
+        offer_persistence(model);
+        return;
+    }
+    model.write_mode = if meta.needs_windows_mode() {
+        WriteMode::Windows(WindowsOptions::default())
+    } else {
+        WriteMode::Raw
+    };
+    model.iso_chosen = Some(meta.path);
+    offer_persistence(model);
+}
+
+/// Re-evaluate whether a persistence partition fits the current selection
+fn offer_persistence(model: &mut Model) {
+    let meta = model.chosen_iso_meta();
+    let device = model
+        .device_chosen
+        .as_ref()
+        .and_then(|p| model.devices.iter().find(|d| &d.path == p));
+    let offer = match (model.write_mode, meta, device) {
+        (WriteMode::Raw, Some(meta), Some(device)) => {
+            persistence::offer(&meta.distro, meta.size.as_u64(), device.size.as_u64())
+        }
+        _ => None,
+    };
+    model.persistence_offer = offer;
+    if model.persistence_offer.is_none() {
+        model.persistence_enabled = false;
+    }
+}
+
+/// Reset progress and emit the write for the current selection
+fn start_write(model: &mut Model) -> Option<Cmd> {
+    model.screen = Screen::Writing;
+    model.active_panel = sync_active_panel(&model.screen);
+    model.writing_written = 0;
+    model.writing_total = 0;
+    model.writing_started = Some(std::time::Instant::now());
+    model.writing_speed_bps = 0.0;
+    model.write_result = None;
+    model.write_warnings.clear();
+    model.verify_result = None;
+    model.persistence_result = None;
+    model.targets.clear();
+    model.report = None;
+    model.report_exported = None;
+    model.ejects.clear();
+    if is_batch(model)
+        && let Some(iso) = model.iso_chosen.clone()
+    {
+        let devices = model.device_marked.clone();
+        model.targets = devices.iter().cloned().map(TargetState::new).collect();
+        return Some(Cmd::WriteBatch {
+            iso,
+            devices,
+            verify: model.verify_after_write,
+            buffer_size: model.buffer_size,
+        });
+    }
+    let (iso, dev) = (model.iso_chosen.clone()?, model.device_chosen.clone()?);
+    Some(match model.write_mode {
+        WriteMode::Raw => raw_cmd(
+            model,
+            Request::Write {
+                iso,
+                device: dev,
+                buffer_size: model.buffer_size,
+            },
+        ),
+        WriteMode::Windows(options) => Cmd::WriteWindows {
+            iso,
+            device: dev,
+            options,
+        },
+        WriteMode::Multiboot => Cmd::WriteMultiboot {
+            isos: model.iso_marked.clone(),
+            device: dev,
+        },
+    })
+}
+
+/// A raw write, verify or eject, sent to whoever opens the device;
+/// udisks does not eject, so that is tried here
+fn raw_cmd(model: &Model, request: Request) -> Cmd {
+    match model.write_route() {
+        WriteRoute::Helper => Cmd::Helper(request),
+        WriteRoute::Udisks if !matches!(request, Request::Eject { .. }) => Cmd::Udisks(request),
+        WriteRoute::Direct | WriteRoute::Udisks => match request {
+            Request::Write {
+                iso,
+                device,
+                buffer_size,
+            } => Cmd::Write {
+                iso,
+                device,
+                buffer_size,
+            },
+            Request::Verify { iso, device, size } => Cmd::Verify { iso, device, size },
+            Request::Eject { devices } => Cmd::Eject { devices },
+        },
+    }
+}
+
+/// Move the current selection into the job queue and clear it for the next
+/// job; marked devices each get a job of their own
+fn enqueue_current(model: &mut Model) {
+    let (Some(iso), Some(device)) = (model.iso_chosen.take(), model.device_chosen.take()) else {
+        return;
+    };
+    let devices = if model.device_marked.is_empty() {
+        vec![device]
+    } else {
+        std::mem::take(&mut model.device_marked)
+    };
+    let multiboot_isos = if model.write_mode == WriteMode::Multiboot {
+        std::mem::take(&mut model.iso_marked)
+    } else {
+        Vec::new()
+    };
+    for device in devices {
+        model.queue.jobs.push(Job {
+            iso: iso.clone(),
+            device,
+            write_mode: model.write_mode,
+            multiboot_isos: multiboot_isos.clone(),
+            verify: model.verify_after_write,
+            persistence: model
+                .persistence_offer
+                .filter(|_| model.persistence_enabled),
+            eject: model.eject_after_write,
+            result: None,
+        });
+    }
+    model.iso_marked.clear();
+    model.device_marked.clear();
+    model.confirm_input.clear();
+    model.write_mode = WriteMode::Raw;
+    model.persistence_offer = None;
+    model.persistence_enabled = false;
+}
+
+/// Load job `index` into the model and start writing it
+fn start_job(model: &mut Model, index: usize) -> Option<Cmd> {
+    load_job(model, index)?;
+    start_write(model)
+}
+
+/// Put job `index` back into the selections it was queued from
+fn load_job(model: &mut Model, index: usize) -> Option<()> {
+    let job = model.queue.jobs.get(index)?.clone();
+    model.queue.current = Some(index);
+    model.queue.awaiting_device = false;
+    model.queue.device_missing = false;
+    model.iso_chosen = Some(job.iso);
+    model.device_chosen = Some(job.device);
+    model.write_mode = job.write_mode;
+    model.iso_marked = job.multiboot_isos;
+    model.device_marked.clear();
+    model.verify_after_write = job.verify;
+    model.eject_after_write = job.eject;
+    model.persistence_offer = job.persistence;
+    model.persistence_enabled = job.persistence.is_some();
+    Some(())
+}
+
+/// Show the outcome; in a queue, record it and wait for the next device.
+/// Returns the commands that add the job to the history file and, when
+/// asked for, eject the stick.
+fn finish_write(model: &mut Model) -> Vec<Cmd> {
+    model.screen = Screen::Done;
+    model.active_panel = sync_active_panel(&model.screen);
+    let cmds = finish_job(model);
+    let Some(index) = model.queue.current else {
+        return cmds;
+    };
+    let result = first_error(&[
+        &model.write_result,
+        &model.verify_result,
+        &model.persistence_result,
+    ]);
+    if let Some(job) = model.queue.jobs.get_mut(index) {
+        job.result = Some(result);
+    }
+    if index + 1 < model.queue.jobs.len() {
+        model.queue.current = Some(index + 1);
+        model.queue.awaiting_device = true;
+        model.queue.device_missing = false;
+        model.queue.written_serial = model.device_chosen.as_ref().and_then(|path| {
+            model
+                .devices
+                .iter()
+                .find(|d| d.path == *path)
+                .and_then(|d| d.serial.clone())
+        });
+        model.queue.written_removed = false;
+    } else {
+        model.queue.current = None;
+    }
+    cmds
+}
+
+/// Report, record and optionally eject the job that just finished
+fn finish_job(model: &mut Model) -> Vec<Cmd> {
+    model.report = build_report(model);
+    let mut cmds: Vec<Cmd> = match &model.report {
+        Some(report) if !model.dry_run => vec![record_history(model, report)],
+        // Simulated jobs stay out of the history, so nothing comes back
+        // to export after; the export hashes the image itself
+        Some(report) if model.auto_report => vec![Cmd::ExportReport {
+            report: report.clone(),
+            dir: model.report_dir.clone(),
+        }],
+        _ => Vec::new(),
+    };
+    if model.eject_after_write {
+        let devices = written_devices(model, true);
+        cmds.extend(start_eject(model, devices));
+    }
+    cmds
+}
+
+/// Devices of the finished job; with `succeeded`, only the clean ones
+fn written_devices(model: &Model, succeeded: bool) -> Vec<DevicePath> {
+    if model.targets.is_empty() {
+        let ok = first_error(&[
+            &model.write_result,
+            &model.verify_result,
+            &model.persistence_result,
+        ])
+        .is_ok();
+        model
+            .device_chosen
+            .iter()
+            .filter(|_| ok || !succeeded)
+            .cloned()
+            .collect()
+    } else {
+        model
+            .targets
+            .iter()
+            .filter(|t| t.succeeded() || !succeeded)
+            .map(|t| t.device.clone())
+            .collect()
+    }
+}
+
+fn start_eject(model: &mut Model, devices: Vec<DevicePath>) -> Option<Cmd> {
+    if devices.is_empty() {
+        return None;
+    }
+    model.ejects = devices.iter().map(|d| (d.clone(), None)).collect();
+    Some(raw_cmd(model, Request::Eject { devices }))
+}
+
+/// The first failure among a job's steps, or success
+fn first_error(steps: &[&Option<Result<(), String>>]) -> Result<(), String> {
+    steps
+        .iter()
+        .copied()
+        .flatten()
+        .find(|r| r.is_err())
+        .cloned()
+        .unwrap_or(Ok(()))
+}
+
+/// The audit report of the job that just finished
+fn build_report(model: &Model) -> Option<Report> {
+    let image = model.iso_chosen.clone()?;
+    let meta = model.chosen_iso_meta();
+    let duration = model
+        .writing_started
+        .map(|t| t.elapsed())
+        .unwrap_or_default();
+    let finished = SystemTime::now();
+    let target = |device: &DevicePath, bytes, write, verify| {
+        let dev = model.devices.iter().find(|d| &d.path == device);
+        ReportTarget {
+            device: device.clone(),
+            model: dev.and_then(|d| d.model.clone()),
+            serial: dev.and_then(|d| d.serial.clone()),
+            transport: dev.and_then(|d| d.tran.clone()),
+            bytes,
+            duration,
+            write,
+            verify,
+        }
+    };
+    let targets = if model.targets.is_empty() {
+        let device = model.device_chosen.as_ref()?;
+        let write = model.write_result.clone().unwrap_or(Ok(()));
+        vec![target(
+            device,
+            model.writing_written,
+            write,
+            model.verify_result.clone(),
+        )]
+    } else {
+        model
+            .targets
+            .iter()
+            .map(|t| {
+                let write = t.write_result.clone().unwrap_or(Ok(()));
+                target(&t.device, t.written, write, t.verify_result.clone())
+            })
+            .collect()
+    };
+    Some(Report {
+        started: finished.checked_sub(duration).unwrap_or(finished),
+        finished,
+        image,
+        image_size: meta.map_or(model.writing_total, |m| m.size.as_u64()),
+        sha256: String::new(),
+        write_mode: match model.write_mode {
+            WriteMode::Raw => "raw".to_string(),
+            WriteMode::Windows(o) => format!("windows ({}, {})", o.scheme, o.filesystem),
+            WriteMode::Multiboot => "multiboot".to_string(),
+        },
+        verify: match model.write_mode {
+            WriteMode::Raw if model.verify_after_write => VerifyMode::ByteCompare,
+            WriteMode::Raw => VerifyMode::Off,
+            _ => VerifyMode::NotApplicable,
+        },
+        result: first_error(&[
+            &model.write_result,
+            &model.verify_result,
+            &model.persistence_result,
+        ]),
+        targets,
+        warnings: model.write_warnings.clone(),
+    })
+}
+
+/// One history entry per device in the report
+fn record_history(model: &Model, report: &Report) -> Cmd {
+    let modified = model.chosen_iso_meta().and_then(|m| m.modified);
+    let batch = report.targets.len() > 1;
+    let mut entries: Vec<HistoryEntry> = report
+        .targets
+        .iter()
+        .map(|t| HistoryEntry {
+            finished: report.finished,
+            image: report.image.clone(),
+            image_size: report.image_size,
+            image_modified: modified,
+            sha256: String::new(),
+            device: t.device.clone(),
+            model: t.model.clone(),
+            serial: t.serial.clone(),
+            // A single device also answers for the persistence step
+            result: if batch {
+                first_error(&[&Some(t.write.clone()), &t.verify])
+            } else {
+                report.result.clone()
+            },
+            duration: t.duration,
+            bytes: t.bytes,
+        })
+        .collect();
+    history::fill_known_hashes(&mut entries, &model.history);
+    Cmd::RecordHistory(entries)
+}
+
+/// True when the raw image goes to several marked devices at once.
+///
+/// The installer modes format a single stick and ignore extra marks.
+fn is_batch(model: &Model) -> bool {
+    model.write_mode == WriteMode::Raw && model.device_marked.len() > 1
+}
+
+/// Move to Done once every device in a parallel write has settled
+fn finish_batch_if_done(model: &mut Model) -> Vec<Cmd> {
+    let verify = model.verify_after_write;
+    if model.targets.is_empty() || !model.targets.iter().all(|t| t.is_finished(verify)) {
+        return Vec::new();
+    }
+    let failed = model.targets.iter().filter(|t| !t.succeeded()).count();
+    model.write_result = Some(if failed == 0 {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} of {} devices failed",
+            failed,
+            model.targets.len()
+        ))
+    });
+    model.screen = Screen::Done;
+    model.active_panel = sync_active_panel(&model.screen);
+    finish_job(model)
+}
+
+/// The post-write persistence command, if the user asked for one
+fn start_persistence(model: &mut Model) -> Option<Cmd> {
+    let kind = model
+        .persistence_offer
+        .filter(|_| model.persistence_enabled)?;
+    let device = model.device_chosen.clone()?;
+    model.persisting = true;
+    Some(Cmd::CreatePersistence {
+        device,
+        image_size: model.writing_total,
+        kind,
+    })
+}
+
 fn can_confirm(model: &Model) -> bool {
-    model.iso_chosen.is_some() && model.device_chosen.is_some() && model.confirm_input == "YES"
+    model.has_both_selections() && model.is_confirmation_valid() && model.simulation_gap().is_none()
 }
 
 fn sync_active_panel(screen: &Screen) -> ActivePanel {
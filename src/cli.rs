@@ -0,0 +1,217 @@
+//! Command-line arguments.
+//!
+//! Search, dry-run and log flags are turned into config `(key, value)` pairs so
+//! that they override the config file and environment through [`Config::set`].
+//! Tracing flags are kept apart: they are about this run, not settings.
+//!
+//! [`Config::set`]: crate::config::Config::set
+
+pub const USAGE: &str = "\
+Usage: ferric [OPTIONS]
+       ferric config check [FILE]
+       ferric helper
+
+Options:
+  --root DIR            Also search DIR (repeatable)
+  --no-default-roots    Don't search the current directory, ~/Downloads and ~
+  --exclude DIR         Skip directories named DIR, or under path DIR (repeatable)
+  --max-depth N         Descend at most N directories below each root
+  --min-size SIZE       Ignore files smaller than SIZE, e.g. 10M
+  --ext LIST            Comma-separated extensions to list, e.g. iso,img
+  --dry-run             Write to a simulated device; no disk is touched
+  --sim-speed RATE      Simulated device speed per second, e.g. 20M
+  --sim-target TARGET   `scratch` (default, an unlinked temp file) or a FILE to write to
+  --sim-flush-delay S   Seconds the simulated final flush takes
+  --sim-fail FAULT      Inject write@OFFSET, verify@OFFSET, flush, unmount
+                        or partprobe (repeatable)
+  --log-level LEVEL     off, error, warn, info (default), debug or trace;
+                        the log is $XDG_STATE_HOME/ferric/ferric.log
+  --record FILE         Trace every message of this session to FILE
+  --redact              Hide image paths, home and serials in the trace
+  --replay FILE         Play a trace back on screen; nothing is run
+  -h, --help            Show this help
+
+Commands:
+  config check [FILE]   Validate the config file (default:
+                        $XDG_CONFIG_HOME/ferric/config) and exit
+  helper                Privileged write helper; ferric starts it as root
+                        with sudo, doas, pkexec or run0
+
+Environment:
+  FERRIC_ROOTS, FERRIC_EXCLUDE (colon-separated), FERRIC_DEFAULT_ROOTS,
+  FERRIC_MAX_DEPTH, FERRIC_MIN_SIZE, FERRIC_EXTENSIONS (comma-separated)
+";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Start the TUI
+    Run,
+    Help,
+    /// Validate a config file; `None` means the default location
+    ConfigCheck(Option<std::path::PathBuf>),
+    /// Serve write requests on stdin/stdout, as root
+    Helper,
+    /// Play back a trace written with `--record`
+    Replay(std::path::PathBuf),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cli {
+    pub command: Command,
+    /// Config overrides from flags, in command-line order
+    pub settings: Vec<(String, String)>,
+    /// `--record`: where to trace the session
+    pub record: Option<std::path::PathBuf>,
+    /// `--redact`: hide paths and serials in the trace
+    pub redact: bool,
+}
+
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Cli, String> {
+    let mut cli = Cli {
+        command: Command::Run,
+        settings: Vec::new(),
+        record: None,
+        redact: false,
+    };
+    let mut args = args.into_iter().peekable();
+    if args.peek().map(String::as_str) == Some("config") {
+        args.next();
+        return match (args.next().as_deref(), args.next(), args.next()) {
+            (Some("check"), file, None) => Ok(Cli {
+                command: Command::ConfigCheck(file.map(Into::into)),
+                ..cli
+            }),
+            _ => Err("usage: ferric config check [FILE]".to_string()),
+        };
+    }
+    if args.peek().map(String::as_str) == Some("helper") {
+        args.next();
+        return match args.next() {
+            None => Ok(Cli {
+                command: Command::Helper,
+                ..cli
+            }),
+            Some(_) => Err("usage: ferric helper".to_string()),
+        };
+    }
+    while let Some(arg) = args.next() {
+        // Accept both `--flag value` and `--flag=value`
+        let (flag, inline) = match arg.split_once('=') {
+            Some((f, v)) if f.starts_with("--") => (f.to_string(), Some(v.to_string())),
+            _ => (arg.clone(), None),
+        };
+        let key = match flag.as_str() {
+            "-h" | "--help" => {
+                cli.command = Command::Help;
+                continue;
+            }
+            "--no-default-roots" => {
+                cli.settings
+                    .push(("default_roots".to_string(), "false".to_string()));
+                continue;
+            }
+            "--redact" => {
+                cli.redact = true;
+                continue;
+            }
+            "--dry-run" => {
+                cli.settings
+                    .push(("dry_run".to_string(), "true".to_string()));
+                continue;
+            }
+            "--root" => "roots",
+            "--exclude" => "exclude",
+            "--max-depth" => "max_depth",
+            "--min-size" => "min_size",
+            "--ext" => "extensions",
+            "--sim-speed" => "sim_speed",
+            "--sim-target" => "sim_target",
+            "--sim-flush-delay" => "sim_flush_delay",
+            "--sim-fail" => "sim_fail",
+            "--log-level" => "log_level",
+            "--record" => "record",
+            "--replay" => "replay",
+            _ => return Err(format!("unknown argument `{}`", arg)),
+        };
+        let value = match inline {
+            Some(v) => v,
+            None => args
+                .next()
+                .ok_or_else(|| format!("{} needs a value", flag))?,
+        };
+        match key {
+            "record" => cli.record = Some(value.into()),
+            "replay" => cli.command = Command::Replay(value.into()),
+            _ => cli.settings.push((key.to_string(), value)),
+        }
+    }
+    Ok(cli)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn flags_become_config_settings() {
+        let cli = parse(args(&[
+            "--root",
+            "/srv/images",
+            "--min-size=1M",
+            "--no-default-roots",
+            "--ext",
+            "img",
+        ]))
+        .unwrap();
+        assert_eq!(cli.command, Command::Run);
+        assert_eq!(
+            cli.settings,
+            vec![
+                ("roots".to_string(), "/srv/images".to_string()),
+                ("min_size".to_string(), "1M".to_string()),
+                ("default_roots".to_string(), "false".to_string()),
+                ("extensions".to_string(), "img".to_string()),
+            ]
+        );
+        assert_eq!(parse(args(&["-h"])).unwrap().command, Command::Help);
+        assert!(parse(args(&["--root"])).is_err());
+        assert!(parse(args(&["--bogus"])).is_err());
+
+        let cli = parse(args(&["--dry-run", "--sim-fail=write@1G"])).unwrap();
+        assert_eq!(
+            cli.settings,
+            vec![
+                ("dry_run".to_string(), "true".to_string()),
+                ("sim_fail".to_string(), "write@1G".to_string()),
+            ]
+        );
+
+        let cli = parse(args(&["--record=/tmp/s.trace", "--redact"])).unwrap();
+        assert_eq!(cli.record, Some("/tmp/s.trace".into()));
+        assert!(cli.redact && cli.settings.is_empty());
+        assert_eq!(
+            parse(args(&["--replay", "/tmp/s.trace"])).unwrap().command,
+            Command::Replay("/tmp/s.trace".into())
+        );
+    }
+
+    #[test]
+    fn config_check_subcommand() {
+        assert_eq!(
+            parse(args(&["config", "check"])).unwrap().command,
+            Command::ConfigCheck(None)
+        );
+        assert_eq!(
+            parse(args(&["config", "check", "/tmp/cfg"]))
+                .unwrap()
+                .command,
+            Command::ConfigCheck(Some("/tmp/cfg".into()))
+        );
+        assert!(parse(args(&["config"])).is_err());
+        assert!(parse(args(&["config", "check", "a", "b"])).is_err());
+    }
+}
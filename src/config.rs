@@ -0,0 +1,598 @@
+//! User configuration.
+//!
+//! Settings are layered: built-in defaults, then the config file at
+//! `$XDG_CONFIG_HOME/ferric/config`, then `FERRIC_*` environment
+//! variables, then command-line flags. Every layer goes through
+//! [`Config::set`], so a key means the same thing wherever it comes from.
+//!
+//! The file is plain `key = value` lines; `#` starts a comment. List
+//! keys (`roots`, `exclude`, `sim_fail`) accumulate across lines and layers, the
+//! rest are overwritten by later layers. `ferric config check` reports
+//! every problem in the file, not just the first.
+
+use crate::{
+    adapters::elevate::Elevator,
+    app::keymap::{Action, KeyBinding, Keymap},
+    domain::{
+        device::SafetyPolicy,
+        iso::{self, ScanOptions},
+        paths::DirPath,
+        simulate::{Fault, SimTarget, Simulation},
+        units::ByteSize,
+    },
+    log,
+    ui::{icons::Icons, theme::ThemeVariant},
+};
+use ratatui::crossterm::event::{KeyCode, KeyEvent};
+use std::{fs, path::PathBuf};
+
+pub const DEFAULT_BUFFER_SIZE: usize = 4 * 1024 * 1024;
+const MIN_BUFFER_SIZE: u64 = 64 * 1024;
+const MAX_BUFFER_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Environment variables and the config key each one sets
+pub const ENV_VARS: [(&str, &str); 6] = [
+    ("FERRIC_ROOTS", "roots"),
+    ("FERRIC_DEFAULT_ROOTS", "default_roots"),
+    ("FERRIC_EXCLUDE", "exclude"),
+    ("FERRIC_MAX_DEPTH", "max_depth"),
+    ("FERRIC_MIN_SIZE", "min_size"),
+    ("FERRIC_EXTENSIONS", "extensions"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    /// Searched in addition to the defaults, unless those are disabled
+    pub roots: Vec<PathBuf>,
+    /// Search the current directory, `~/Downloads` and `~`
+    pub default_roots: bool,
+    pub exclude: Vec<String>,
+    pub max_depth: usize,
+    pub min_size: u64,
+    pub extensions: Vec<String>,
+    /// Verify after writing unless toggled off on Confirm
+    pub verify: bool,
+    /// Bytes per read/write when copying an image
+    pub buffer_size: usize,
+    /// Eject the stick after a successful write unless toggled off on Confirm
+    pub eject: bool,
+    /// How to become root for the write helper; `None` picks the first installed
+    pub elevate: Option<Elevator>,
+    /// Detach busy mounts lazily when a plain unmount fails
+    pub lazy_unmount: bool,
+    /// List drives through UDisks2 too, and let it open devices we cannot
+    pub udisks: bool,
+    /// Write to a simulated device instead of a real one
+    pub dry_run: bool,
+    /// Speed, target and faults of the simulated device
+    pub simulation: Simulation,
+    /// How much goes into `$XDG_STATE_HOME/ferric/ferric.log`
+    pub log_level: log::Level,
+    /// Where audit reports go; `None` for `$XDG_STATE_HOME/ferric/reports`
+    pub report_dir: Option<PathBuf>,
+    /// Save a report after every job, not only on request
+    pub auto_report: bool,
+    pub theme: String,
+    pub icons: String,
+    pub keymap: Keymap,
+    pub safety: SafetyPolicy,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            roots: Vec::new(),
+            default_roots: true,
+            exclude: Vec::new(),
+            max_depth: iso::DEFAULT_MAX_DEPTH,
+            min_size: iso::DEFAULT_MIN_SIZE,
+            extensions: iso::DEFAULT_EXTENSIONS
+                .iter()
+                .map(|e| e.to_string())
+                .collect(),
+            verify: false,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            eject: false,
+            elevate: None,
+            lazy_unmount: false,
+            udisks: false,
+            dry_run: false,
+            log_level: log::Level::default(),
+            simulation: Simulation::default(),
+            report_dir: None,
+            auto_report: false,
+            theme: "dark".to_string(),
+            icons: "unicode".to_string(),
+            keymap: Keymap::default(),
+            safety: SafetyPolicy::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Defaults, then the config file, the environment and `overrides`
+    /// (command-line `(key, value)` pairs), in that order.
+    pub fn load(overrides: &[(String, String)]) -> Result<Self, String> {
+        let mut config = Config::default();
+        if let Some(path) = config_path() {
+            match fs::read_to_string(&path) {
+                Ok(text) => config.apply_file(&text, &path.to_string_lossy())?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(format!("{}: {}", path.display(), e)),
+            }
+        }
+        config.apply_env(std::env::vars())?;
+        for (key, value) in overrides {
+            config.set(key, value)?;
+        }
+        config.validate().map_err(|problems| problems.join("; "))?;
+        Ok(config)
+    }
+
+    /// Apply the lines of a config file; errors name `origin` and the line
+    pub fn apply_file(&mut self, text: &str, origin: &str) -> Result<(), String> {
+        for (index, raw) in text.lines().enumerate() {
+            self.apply_line(raw)
+                .map_err(|msg| format!("{}:{}: {}", origin, index + 1, msg))?;
+        }
+        self.validate()
+            .map_err(|problems| format!("{}: {}", origin, problems.join("; ")))
+    }
+
+    /// Every problem in a config file, for `ferric config check`
+    pub fn check_file(text: &str, origin: &str) -> Vec<String> {
+        let mut config = Config::default();
+        let mut problems: Vec<String> = text
+            .lines()
+            .enumerate()
+            .filter_map(|(index, raw)| {
+                let msg = config.apply_line(raw).err()?;
+                Some(format!("{}:{}: {}", origin, index + 1, msg))
+            })
+            .collect();
+        if let Err(found) = config.validate() {
+            problems.extend(found.into_iter().map(|p| format!("{}: {}", origin, p)));
+        }
+        for root in &config.roots {
+            if !root.is_dir() {
+                problems.push(format!(
+                    "{}: root {} is not a directory",
+                    origin,
+                    root.display()
+                ));
+            }
+        }
+        problems
+    }
+
+    fn apply_line(&mut self, raw: &str) -> Result<(), String> {
+        let line = strip_comment(raw).trim();
+        if line.is_empty() {
+            return Ok(());
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("expected `key = value`, got `{}`", line))?;
+        self.set(key.trim(), value.trim())
+    }
+
+    /// Checks that involve more than one key
+    fn validate(&self) -> Result<(), Vec<String>> {
+        let mut problems = self.keymap.conflicts();
+        // Confirm keys fire before typing, so the word could never be entered
+        let word = &self.safety.confirm_word;
+        if let Some((c, action)) = word.chars().find_map(|c| {
+            let key = KeyEvent::from(KeyCode::Char(c));
+            self.keymap.action_on("confirm", &key).map(|a| (c, a))
+        }) {
+            problems.push(format!(
+                "confirm_word: `{}` cannot be typed on Confirm, where {} is key.{}",
+                word,
+                c.to_ascii_lowercase(),
+                action.name()
+            ));
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    /// Apply the `FERRIC_*` variables found in `vars`
+    pub fn apply_env(
+        &mut self,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<(), String> {
+        for (name, value) in vars {
+            if let Some((_, key)) = ENV_VARS.iter().find(|(var, _)| *var == name) {
+                self.set(key, &value)
+                    .map_err(|e| format!("{}: {}", name, e))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Set one key. Lists are `:`-separated for paths and `,`-separated
+    /// for extensions.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "roots" => self
+                .roots
+                .extend(split_list(value, ':').map(|p| expand_home(&p))),
+            "default_roots" => {
+                self.default_roots =
+                    parse_bool(value).map_err(|e| format!("default_roots: {}", e))?
+            }
+            "exclude" => self.exclude.extend(split_list(value, ':')),
+            "max_depth" => {
+                self.max_depth = value
+                    .parse()
+                    .map_err(|_| format!("max_depth: `{}` is not a number", value))?
+            }
+            "min_size" => {
+                self.min_size = ByteSize::parse(value)
+                    .ok_or_else(|| format!("min_size: `{}` is not a size like 10M", value))?
+                    .as_u64()
+            }
+            "extensions" => {
+                let exts: Vec<String> = split_list(value, ',')
+                    .map(|e| e.trim_start_matches('.').to_ascii_lowercase())
+                    .collect();
+                if exts.is_empty() {
+                    return Err("extensions: list is empty".to_string());
+                }
+                self.extensions = exts;
+            }
+            "verify" => self.verify = parse_bool(value).map_err(|e| format!("verify: {}", e))?,
+            "buffer_size" => {
+                let size = ByteSize::parse(value)
+                    .ok_or_else(|| format!("buffer_size: `{}` is not a size like 4M", value))?
+                    .as_u64();
+                if !(MIN_BUFFER_SIZE..=MAX_BUFFER_SIZE).contains(&size) || size % 512 != 0 {
+                    return Err(format!(
+                        "buffer_size: must be a multiple of 512 between {} and {}",
+                        ByteSize::new(MIN_BUFFER_SIZE),
+                        ByteSize::new(MAX_BUFFER_SIZE)
+                    ));
+                }
+                self.buffer_size = size as usize;
+            }
+            "eject" => self.eject = parse_bool(value).map_err(|e| format!("eject: {}", e))?,
+            "elevate" => {
+                self.elevate = match value {
+                    "auto" => None,
+                    name => Some(Elevator::by_name(name).ok_or_else(|| {
+                        format!(
+                            "elevate: `{}` is not one of auto, {}",
+                            name,
+                            Elevator::NAMES.join(", ")
+                        )
+                    })?),
+                }
+            }
+            "lazy_unmount" => {
+                self.lazy_unmount = parse_bool(value).map_err(|e| format!("lazy_unmount: {}", e))?
+            }
+            "udisks" => self.udisks = parse_bool(value).map_err(|e| format!("udisks: {}", e))?,
+            "log_level" => {
+                self.log_level =
+                    log::Level::parse(value).map_err(|e| format!("log_level: {}", e))?
+            }
+            "dry_run" => self.dry_run = parse_bool(value).map_err(|e| format!("dry_run: {}", e))?,
+            "sim_speed" => {
+                let speed = ByteSize::parse(value)
+                    .ok_or_else(|| format!("sim_speed: `{}` is not a size like 20M", value))?
+                    .as_u64();
+                self.simulation.speed = (speed > 0).then_some(speed);
+            }
+            "sim_target" => {
+                self.simulation.target = match value {
+                    "" => return Err("sim_target: path is empty".to_string()),
+                    "scratch" => SimTarget::Scratch,
+                    path => SimTarget::File(expand_home(path)),
+                }
+            }
+            "sim_flush_delay" => {
+                let secs: f64 = value
+                    .parse()
+                    .ok()
+                    .filter(|s: &f64| s.is_finite() && *s >= 0.0)
+                    .ok_or_else(|| format!("sim_flush_delay: `{}` is not seconds", value))?;
+                self.simulation.flush_delay = std::time::Duration::from_secs_f64(secs);
+            }
+            "sim_fail" => {
+                for spec in split_list(value, ',') {
+                    let fault = Fault::parse(&spec).map_err(|e| format!("sim_fail: {}", e))?;
+                    self.simulation.faults.push(fault);
+                }
+            }
+            "report_dir" => {
+                if value.is_empty() {
+                    return Err("report_dir: path is empty".to_string());
+                }
+                self.report_dir = Some(expand_home(value));
+            }
+            "auto_report" => {
+                self.auto_report = parse_bool(value).map_err(|e| format!("auto_report: {}", e))?
+            }
+            "theme" => {
+                if ThemeVariant::by_name(value).is_none() {
+                    return Err(format!(
+                        "theme: `{}` is not one of {}",
+                        value,
+                        ThemeVariant::NAMES.join(", ")
+                    ));
+                }
+                self.theme = value.to_string();
+            }
+            "icons" => {
+                if Icons::by_name(value).is_none() {
+                    return Err(format!(
+                        "icons: `{}` is not one of {}",
+                        value,
+                        Icons::NAMES.join(", ")
+                    ));
+                }
+                self.icons = value.to_string();
+            }
+            "confirm_word" => {
+                if value.is_empty()
+                    || value.len() > 16
+                    || !value.chars().all(|c| c.is_ascii_uppercase())
+                {
+                    return Err(format!(
+                        "confirm_word: `{}` must be 1-16 upper-case letters",
+                        value
+                    ));
+                }
+                self.safety.confirm_word = value.to_string();
+            }
+            "removable_only" => {
+                self.safety.removable_only =
+                    parse_bool(value).map_err(|e| format!("removable_only: {}", e))?
+            }
+            "max_device_size" => {
+                let size = ByteSize::parse(value)
+                    .ok_or_else(|| format!("max_device_size: `{}` is not a size like 64G", value))?
+                    .as_u64();
+                self.safety.max_device_size = (size > 0).then_some(size);
+            }
+            _ => match key.strip_prefix("key.") {
+                Some(name) => {
+                    let action = Action::from_name(name)
+                        .ok_or_else(|| format!("unknown action `{}`", name))?;
+                    let binding =
+                        KeyBinding::parse(value).map_err(|e| format!("{}: {}", key, e))?;
+                    self.keymap.set(action, binding);
+                }
+                None => return Err(format!("unknown key `{}`", key)),
+            },
+        }
+        Ok(())
+    }
+
+    /// Resolve the roots and filters used by the ISO search
+    pub fn scan_options(&self) -> ScanOptions {
+        let mut roots = if self.default_roots {
+            iso::default_roots()
+        } else {
+            Vec::new()
+        };
+        roots.extend(self.roots.iter().cloned().map(DirPath::from));
+        ScanOptions {
+            roots: iso::normalize_roots(roots),
+            exclude: self
+                .exclude
+                .iter()
+                .map(|e| expand_home(e))
+                .map(|p| p.to_string_lossy().to_string())
+                .collect(),
+            max_depth: self.max_depth,
+            min_size: self.min_size,
+            extensions: self.extensions.clone(),
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME/ferric/config`, falling back to `~/.config`
+pub fn config_path() -> Option<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME", ".config").map(|d| d.join("ferric").join("config"))
+}
+
+/// An XDG base directory: `$var` if set and absolute, else `~/fallback`
+pub fn xdg_dir(var: &str, fallback: &str) -> Option<PathBuf> {
+    match std::env::var_os(var).map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => Some(dir),
+        _ => iso::home_dir().map(|home| home.join(fallback).into_inner()),
+    }
+}
+
+/// Drop a `#` comment: one starting the line or after whitespace, so
+/// `/srv/iso#2` keeps its `#`
+fn strip_comment(line: &str) -> &str {
+    let mut prev = None;
+    for (i, c) in line.char_indices() {
+        if c == '#' && prev.is_none_or(char::is_whitespace) {
+            return &line[..i];
+        }
+        prev = Some(c);
+    }
+    line
+}
+
+fn split_list(value: &str, sep: char) -> impl Iterator<Item = String> + '_ {
+    value
+        .split(sep)
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Ok(true),
+        "false" | "no" | "off" | "0" => Ok(false),
+        _ => Err(format!("`{}` is not true or false", value)),
+    }
+}
+
+/// Expand a leading `~/` to the home directory
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), iso::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest).into_inner(),
+        _ => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_keys_accumulate_and_override() {
+        let mut config = Config::default();
+        config
+            .apply_file(
+                "# images\nroots = /srv/images:/mnt/nfs\nroots = /data\n\
+                 exclude = node_modules # vendored\nmin_size = 1M\n\
+                 extensions = .ISO, img\ndefault_roots = no\n",
+                "config",
+            )
+            .unwrap();
+        assert_eq!(
+            config.roots,
+            vec![
+                PathBuf::from("/srv/images"),
+                PathBuf::from("/mnt/nfs"),
+                PathBuf::from("/data")
+            ]
+        );
+        assert_eq!(config.exclude, vec!["node_modules"]);
+        assert_eq!(config.min_size, 1024 * 1024);
+        assert_eq!(config.extensions, vec!["iso", "img"]);
+
+        let scan = config.scan_options();
+        assert_eq!(scan.roots.len(), 3);
+        assert_eq!(scan.min_size, 1024 * 1024);
+    }
+
+    #[test]
+    fn hash_starts_a_comment_only_after_whitespace() {
+        let mut config = Config::default();
+        config
+            .apply_file(
+                "# header\nroots = /srv/iso#2   # mirror\nexclude = cache#old\n",
+                "config",
+            )
+            .unwrap();
+        assert_eq!(config.roots, vec![PathBuf::from("/srv/iso#2")]);
+        assert_eq!(config.exclude, vec!["cache#old"]);
+    }
+
+    #[test]
+    fn dry_run_keys_describe_the_simulated_device() {
+        let mut config = Config::default();
+        config
+            .apply_file(
+                "dry_run = on\nsim_speed = 20M\nsim_target = /tmp/stick.img\n\
+                 sim_flush_delay = 1.5\nsim_fail = write@1G, unmount\nsim_fail = flush\n",
+                "config",
+            )
+            .unwrap();
+        assert!(config.dry_run);
+        assert_eq!(
+            config.simulation,
+            Simulation {
+                speed: Some(20 * 1024 * 1024),
+                target: SimTarget::File(PathBuf::from("/tmp/stick.img")),
+                flush_delay: std::time::Duration::from_millis(1500),
+                faults: vec![Fault::WriteAt(1 << 30), Fault::Unmount, Fault::Flush],
+            }
+        );
+        assert!(config.set("sim_fail", "format").is_err());
+        assert!(config.set("sim_flush_delay", "-1").is_err());
+
+        config.set("log_level", "debug").unwrap();
+        assert_eq!(config.log_level, log::Level::Debug);
+        assert!(config.set("log_level", "loud").is_err());
+    }
+
+    #[test]
+    fn errors_carry_line_numbers() {
+        let mut config = Config::default();
+        let err = config
+            .apply_file("max_depth = 3\n\nmax_depth = deep\n", "cfg")
+            .unwrap_err();
+        assert_eq!(err, "cfg:3: max_depth: `deep` is not a number");
+        let err = config.apply_file("colour = red\n", "cfg").unwrap_err();
+        assert_eq!(err, "cfg:1: unknown key `colour`");
+        let err = config.apply_file("just words\n", "cfg").unwrap_err();
+        assert!(err.starts_with("cfg:1: expected"));
+    }
+
+    #[test]
+    fn check_reports_every_problem() {
+        let problems = Config::check_file(
+            "verify = maybe\ntheme = dark\nicons = emoji\n\
+             key.verify = ctrl-a\nkey.launch = x\nconfirm_word = ERASE\n",
+            "cfg",
+        );
+        assert_eq!(
+            problems,
+            vec![
+                "cfg:1: verify: `maybe` is not true or false",
+                "cfg:3: icons: `emoji` is not one of unicode, ascii",
+                "cfg:5: unknown action `launch`",
+                "cfg: key.verify and key.queue are both bound to Ctrl-A",
+            ]
+        );
+        assert!(Config::check_file("buffer_size = 1M\nmax_device_size = 0\n", "cfg").is_empty());
+    }
+
+    #[test]
+    fn confirm_word_must_not_hold_confirm_keys() {
+        let mut config = Config::default();
+        let err = config
+            .apply_file(
+                "confirm_word = VERIFY
+",
+                "cfg",
+            )
+            .unwrap_err();
+        assert_eq!(
+            err,
+            "cfg: confirm_word: `VERIFY` cannot be typed on Confirm, where v is key.verify"
+        );
+        // Rebinding verify frees the letter; `r` only refreshes on Device select
+        let mut config = Config::default();
+        config
+            .apply_file(
+                "key.verify = ctrl-v
+confirm_word = VIRTUE
+",
+                "cfg",
+            )
+            .unwrap();
+        assert_eq!(config.safety.confirm_word, "VIRTUE");
+    }
+
+    #[test]
+    fn env_overrides_file() {
+        let mut config = Config::default();
+        config.apply_file("max_depth = 3\n", "cfg").unwrap();
+        config
+            .apply_env([
+                ("FERRIC_MAX_DEPTH".to_string(), "8".to_string()),
+                ("FERRIC_ROOTS".to_string(), "/srv/images".to_string()),
+                ("PATH".to_string(), "/usr/bin".to_string()),
+            ])
+            .unwrap();
+        assert_eq!(config.max_depth, 8);
+        assert_eq!(config.roots, vec![PathBuf::from("/srv/images")]);
+        let err = config
+            .apply_env([("FERRIC_MIN_SIZE".to_string(), "big".to_string())])
+            .unwrap_err();
+        assert!(err.starts_with("FERRIC_MIN_SIZE: min_size:"));
+    }
+}
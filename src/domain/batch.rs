@@ -0,0 +1,229 @@
+//! Writing one image to several devices at once.
+//!
+//! The image is read once; each chunk is shared between per-device
+//! writer threads through small bounded queues, so the slowest stick
+//! sets the pace without the image being read N times. A device that
+//! fails drops out of the fan-out and the others carry on. Verification
+//! runs per device afterwards and re-reads the image, which by then is
+//! usually served from the page cache.
+
+use crate::{
+    app::{effects::Mounter, msg::Msg},
+    domain::{
+        paths::{DevicePath, IsoPath},
+        writer,
+    },
+    event::Event,
+};
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Write},
+    sync::{Arc, mpsc},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Chunks buffered per device before the reader waits for it
+const QUEUE_DEPTH: usize = 8;
+
+enum Chunk {
+    Data(Arc<Vec<u8>>),
+    /// The image could not be read; every writer stops with this error
+    Failed(String),
+}
+
+pub fn write_batch(
+    iso_path: IsoPath,
+    devices: Vec<DevicePath>,
+    verify: bool,
+    chunk_size: usize,
+    mounter: &dyn Mounter,
+    tx: tokio::sync::mpsc::UnboundedSender<Event>,
+) {
+    let fail_all = |msg: String| {
+        for device in &devices {
+            let _: Result<_, _> = tx.send(Event::App(Msg::TargetWriteFinished {
+                device: device.clone(),
+                result: Err(msg.clone()),
+            }));
+        }
+    };
+    let mut src = match File::open(iso_path.as_path()) {
+        Ok(f) => f,
+        Err(e) => return fail_all(format!("Failed to open ISO: {}", e)),
+    };
+    let total = match src.metadata() {
+        Ok(m) => m.len(),
+        Err(e) => return fail_all(format!("Failed to stat ISO: {}", e)),
+    };
+
+    // Scoped, so the writers can share the caller's mounter
+    thread::scope(|scope| {
+        let mut senders = Vec::new();
+        for device in &devices {
+            let (chunk_tx, chunk_rx) = mpsc::sync_channel::<Chunk>(QUEUE_DEPTH);
+            let (iso_path, tx) = (&iso_path, &tx);
+            senders.push(chunk_tx);
+            scope.spawn(move || run_target(iso_path, device, total, verify, chunk_rx, mounter, tx));
+        }
+
+        let mut buf = vec![0u8; chunk_size];
+        loop {
+            let chunk = match src.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => Chunk::Data(Arc::new(buf[..n].to_vec())),
+                Err(e) => Chunk::Failed(format!("Read error: {}", e)),
+            };
+            let failed = matches!(chunk, Chunk::Failed(_));
+            // Writers that gave up have dropped their receiver; forget them.
+            senders.retain(|s| s.send(clone_chunk(&chunk)).is_ok());
+            if failed || senders.is_empty() {
+                break;
+            }
+        }
+    });
+}
+
+fn clone_chunk(chunk: &Chunk) -> Chunk {
+    match chunk {
+        Chunk::Data(data) => Chunk::Data(Arc::clone(data)),
+        Chunk::Failed(msg) => Chunk::Failed(msg.clone()),
+    }
+}
+
+/// One device's share of the batch: write, then optionally verify
+fn run_target(
+    iso_path: &IsoPath,
+    device: &DevicePath,
+    total: u64,
+    verify: bool,
+    chunks: mpsc::Receiver<Chunk>,
+    mounter: &dyn Mounter,
+    tx: &tokio::sync::mpsc::UnboundedSender<Event>,
+) {
+    let result = write_target(device, total, chunks, mounter, tx);
+    let ok = result.is_ok();
+    let _: Result<_, _> = tx.send(Event::App(Msg::TargetWriteFinished {
+        device: device.clone(),
+        result,
+    }));
+    if !ok || !verify {
+        return;
+    }
+    let start = Instant::now();
+    let mut last_sent = start;
+    let result = writer::compare_image(
+        iso_path.as_path(),
+        device.as_path(),
+        total,
+        &mut |checked| {
+            if last_sent.elapsed() < Duration::from_millis(100) && checked < total {
+                return;
+            }
+            last_sent = Instant::now();
+            let elapsed = start.elapsed().as_secs_f64().max(0.000_001);
+            let _: Result<_, _> = tx.send(Event::App(Msg::TargetVerifyProgress {
+                device: device.clone(),
+                checked,
+                total,
+                bps: checked as f64 / elapsed,
+            }));
+        },
+    );
+    let _: Result<_, _> = tx.send(Event::App(Msg::TargetVerifyFinished {
+        device: device.clone(),
+        result,
+    }));
+}
+
+/// Drain the chunk queue onto the device. Returning early drops the
+/// receiver, which takes this device out of the fan-out.
+fn write_target(
+    device: &DevicePath,
+    total: u64,
+    chunks: mpsc::Receiver<Chunk>,
+    mounter: &dyn Mounter,
+    tx: &tokio::sync::mpsc::UnboundedSender<Event>,
+) -> Result<(), String> {
+    writer::warn(tx, device, mounter.unmount_partitions_of(device.as_path()));
+    let mut dst = OpenOptions::new()
+        .write(true)
+        .open(device.as_path())
+        .map_err(|e| format!("Failed to open device {}: {}", device, e))?;
+    let start = Instant::now();
+    let mut last_sent = start;
+    let mut written = 0u64;
+    for chunk in chunks {
+        let data = match chunk {
+            Chunk::Data(data) => data,
+            Chunk::Failed(msg) => return Err(msg),
+        };
+        dst.write_all(&data)
+            .map_err(|e| format!("Write error: {}", e))?;
+        written += data.len() as u64;
+        if last_sent.elapsed() >= Duration::from_millis(100) || written == total {
+            last_sent = Instant::now();
+            let elapsed = start.elapsed().as_secs_f64().max(0.000_001);
+            let _: Result<_, _> = tx.send(Event::App(Msg::TargetWriteProgress {
+                device: device.clone(),
+                written,
+                total,
+                bps: written as f64 / elapsed,
+            }));
+        }
+    }
+    if written < total {
+        return Err("Image ended early".to_string());
+    }
+    dst.flush().map_err(|e| format!("Flush error: {}", e))?;
+    dst.sync_all()
+        .map_err(|e| format!("sync_all error: {}", e))?;
+    writer::warn(tx, device, mounter.partprobe(device.as_path()));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn failing_target_does_not_stop_the_others() {
+        let dir = std::env::temp_dir().join(format!("ferric-batch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let chunk_size = 64 * 1024;
+        let image: Vec<u8> = (0..chunk_size * 2 + 123).map(|i| (i % 251) as u8).collect();
+        let iso = dir.join("image.iso");
+        std::fs::write(&iso, &image).unwrap();
+        let good: Vec<PathBuf> = (0..2).map(|i| dir.join(format!("stick{}", i))).collect();
+        for path in &good {
+            File::create(path).unwrap();
+        }
+        let missing = dir.join("missing").join("stick");
+        let mut devices: Vec<DevicePath> = good.iter().cloned().map(DevicePath::from).collect();
+        devices.push(DevicePath::from(missing));
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mounter = crate::app::effects::fake::Mounter::default();
+        write_batch(IsoPath::from(iso), devices, true, chunk_size, &mounter, tx);
+        // The stick that failed to open was never written, so not re-read
+        assert_eq!(mounter.calls().len(), 3 + 2);
+
+        let mut written_ok = 0;
+        let mut written_err = 0;
+        let mut verified_ok = 0;
+        while let Ok(Event::App(msg)) = rx.try_recv() {
+            match msg {
+                Msg::TargetWriteFinished { result: Ok(()), .. } => written_ok += 1,
+                Msg::TargetWriteFinished { result: Err(_), .. } => written_err += 1,
+                Msg::TargetVerifyFinished { result: Ok(()), .. } => verified_ok += 1,
+                _ => {}
+            }
+        }
+        assert_eq!((written_ok, written_err, verified_ok), (2, 1, 2));
+        for path in &good {
+            assert_eq!(std::fs::read(path).unwrap(), image);
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,156 @@
+//! Filesystem browsing for images outside the search roots.
+//!
+//! Paths typed into the browser may be absolute, start with `~/`, or be
+//! relative to the directory being shown.
+
+use crate::domain::{
+    iso::{self, IsoMeta},
+    units::ByteSize,
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntry {
+    pub name: String,
+    pub is_dir: bool,
+    pub size: ByteSize,
+}
+
+/// What a path turned out to be when opened
+#[derive(Debug, Clone)]
+pub enum Opened {
+    Dir(PathBuf, Vec<DirEntry>),
+    Image(IsoMeta),
+}
+
+/// Resolve `input` against the directory being shown
+pub fn resolve(dir: &Path, input: &str) -> PathBuf {
+    let path = match (input.strip_prefix('~'), iso::home_dir()) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            home.join(rest.trim_start_matches('/')).into_inner()
+        }
+        _ => PathBuf::from(input),
+    };
+    if path.is_absolute() {
+        path
+    } else {
+        dir.join(path)
+    }
+}
+
+/// Directories first, then files, each sorted by name; dotfiles hidden
+pub fn list_dir(dir: &Path) -> Result<Vec<DirEntry>, String> {
+    let read = fs::read_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let mut entries: Vec<DirEntry> = read
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') {
+                return None;
+            }
+            // Follow symlinks so a link to a directory can be entered
+            let meta = fs::metadata(entry.path()).ok()?;
+            Some(DirEntry {
+                name,
+                is_dir: meta.is_dir(),
+                size: ByteSize::new(if meta.is_dir() { 0 } else { meta.len() }),
+            })
+        })
+        .collect();
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+    Ok(entries)
+}
+
+/// A directory is listed; anything else must be a readable, non-empty
+/// regular file to be used as an image
+pub fn open(path: &Path) -> Result<Opened, String> {
+    let meta = fs::metadata(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    if meta.is_dir() {
+        return Ok(Opened::Dir(path.to_path_buf(), list_dir(path)?));
+    }
+    if !meta.is_file() {
+        return Err(format!("{} is not a regular file", path.display()));
+    }
+    fs::File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    if meta.len() == 0 {
+        return Err(format!("{} is empty", path.display()));
+    }
+    Ok(Opened::Image(iso::describe(path, &meta)))
+}
+
+/// Tab completion: extend the last component of `input` as far as the
+/// entries it could name agree, adding `/` after a unique directory
+pub fn complete(dir: &Path, input: &str) -> String {
+    let (head, prefix) = match input.rfind('/') {
+        Some(i) => input.split_at(i + 1),
+        None => ("", input),
+    };
+    let parent = if head.is_empty() {
+        dir.to_path_buf()
+    } else {
+        resolve(dir, head)
+    };
+    let Ok(entries) = list_dir(&parent) else {
+        return input.to_string();
+    };
+    let names: Vec<&DirEntry> = entries
+        .iter()
+        .filter(|e| e.name.starts_with(prefix))
+        .collect();
+    match names.as_slice() {
+        [] => input.to_string(),
+        [only] => format!(
+            "{}{}{}",
+            head,
+            only.name,
+            if only.is_dir { "/" } else { "" }
+        ),
+        [first, rest @ ..] => {
+            let common = rest.iter().fold(first.name.as_str(), |acc, e| {
+                let len = acc
+                    .char_indices()
+                    .zip(e.name.chars())
+                    .take_while(|((_, a), b)| a == b)
+                    .last()
+                    .map_or(0, |((i, a), _)| i + a.len_utf8());
+                &acc[..len]
+            });
+            format!("{}{}", head, common)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn completes_opens_and_validates() {
+        let root = std::env::temp_dir().join(format!("ferric-browse-{}", std::process::id()));
+        fs::create_dir_all(root.join("images")).unwrap();
+        fs::write(root.join("images").join("alpine-3.20.iso"), b"x").unwrap();
+        fs::write(root.join("images").join("alpine-3.21.iso"), b"x").unwrap();
+        fs::write(root.join("images").join("empty.iso"), b"").unwrap();
+
+        assert_eq!(complete(&root, "im"), "images/");
+        assert_eq!(complete(&root, "images/al"), "images/alpine-3.2");
+        assert_eq!(complete(&root, "images/zz"), "images/zz");
+        let abs = format!("{}/images/alpine-3.20", root.display());
+        assert_eq!(complete(&root, &abs), format!("{}.iso", abs));
+
+        match open(&resolve(&root, "images")).unwrap() {
+            Opened::Dir(_, entries) => assert_eq!(entries.len(), 3),
+            Opened::Image(_) => panic!("expected a directory"),
+        }
+        assert!(matches!(
+            open(&root.join("images/alpine-3.21.iso")),
+            Ok(Opened::Image(_))
+        ));
+        assert!(open(&root.join("images/empty.iso")).is_err());
+        assert!(open(&root.join("missing.iso")).is_err());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -13,6 +13,10 @@ pub struct Device {
     pub ro: bool,
     pub mounted: bool,
     pub labels: Vec<String>,
+    /// What this process may do with the device, probed when listed
+    pub access: DeviceAccess,
+    /// UDisks2 block object, when udisks knows the device
+    pub udisks: Option<String>,
 }
 
 impl Device {
@@ -21,6 +25,83 @@ impl Device {
     }
 }
 
+/// What the running process may do with a block device
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceAccess {
+    /// The node opens for writing
+    pub write: bool,
+    /// `CAP_SYS_ADMIN`: unmount, re-read the partition table, eject
+    pub admin: bool,
+    /// Why the node is not writable, from its owner, group and mode
+    pub denied: Option<String>,
+}
+
+impl DeviceAccess {
+    /// Everything, as root has
+    pub const FULL: DeviceAccess = DeviceAccess {
+        write: true,
+        admin: true,
+        denied: None,
+    };
+
+    /// What stops this process writing `device`; `None` when it can.
+    /// Without `CAP_SYS_ADMIN` only mounted partitions stand in the way.
+    pub fn missing(&self, device: &DevicePath, mounted: bool) -> Option<String> {
+        match (self.write, self.admin) {
+            (true, true) => None,
+            (true, false) if mounted => Some(format!(
+                "can write {} but cannot unmount its partitions (needs CAP_SYS_ADMIN)",
+                device
+            )),
+            (true, false) => None,
+            (false, _) => Some(match &self.denied {
+                Some(why) => format!("cannot write {}: {}", device, why),
+                None => format!("cannot write {}", device),
+            }),
+        }
+    }
+
+    /// What a write goes without, when it can go ahead at all
+    pub fn caveat(&self, device: &DevicePath) -> Option<String> {
+        (self.write && !self.admin).then(|| {
+            format!(
+                "can write {} but cannot re-read its partition table afterwards (needs CAP_SYS_ADMIN); replug it before use",
+                device
+            )
+        })
+    }
+}
+
+/// User-configurable guard rails on top of the built-in device filter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SafetyPolicy {
+    /// Word that must be typed on Confirm, upper-case letters only
+    pub confirm_word: String,
+    /// Hide devices that are not removable or hotplugged
+    pub removable_only: bool,
+    /// Hide devices larger than this, to keep backup disks out of reach
+    pub max_device_size: Option<u64>,
+}
+
+impl Default for SafetyPolicy {
+    fn default() -> Self {
+        Self {
+            confirm_word: "YES".to_string(),
+            removable_only: false,
+            max_device_size: None,
+        }
+    }
+}
+
+impl SafetyPolicy {
+    pub fn permits(&self, device: &Device) -> bool {
+        (!self.removable_only || device.removable || device.hotplug)
+            && self
+                .max_device_size
+                .is_none_or(|max| device.size.as_u64() <= max)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -39,6 +120,8 @@ mod tests {
             ro,
             mounted,
             labels: vec![],
+            access: DeviceAccess::FULL,
+            udisks: None,
         }
     }
 
@@ -47,4 +130,51 @@ mod tests {
         let d = make_device(false, true);
         assert!(!d.is_safe_target());
     }
+
+    #[test]
+    fn policy_hides_fixed_and_oversized_devices() {
+        let mut fixed = make_device(false, false);
+        fixed.removable = false;
+        fixed.hotplug = false;
+        let stick = make_device(false, false);
+        let mut policy = SafetyPolicy::default();
+        assert!(policy.permits(&fixed));
+        policy.removable_only = true;
+        assert!(!policy.permits(&fixed));
+        assert!(policy.permits(&stick));
+        policy.max_device_size = Some(999_999);
+        assert!(!policy.permits(&stick));
+    }
+
+    #[test]
+    fn missing_access_is_spelled_out() {
+        let sdb = DevicePath::from(PathBuf::from("/dev/sdb"));
+        assert_eq!(DeviceAccess::FULL.missing(&sdb, true), None);
+        let disk_group = DeviceAccess {
+            write: true,
+            admin: false,
+            denied: None,
+        };
+        assert_eq!(disk_group.missing(&sdb, false), None);
+        assert_eq!(
+            disk_group.missing(&sdb, true).unwrap(),
+            "can write /dev/sdb but cannot unmount its partitions (needs CAP_SYS_ADMIN)"
+        );
+        assert!(
+            disk_group
+                .caveat(&sdb)
+                .unwrap()
+                .contains("re-read its partition table")
+        );
+        assert_eq!(DeviceAccess::FULL.caveat(&sdb), None);
+        let user = DeviceAccess {
+            write: false,
+            admin: false,
+            denied: Some("owned by root:disk with mode 0660".to_string()),
+        };
+        assert_eq!(
+            user.missing(&sdb, false).unwrap(),
+            "cannot write /dev/sdb: owned by root:disk with mode 0660"
+        );
+    }
 }
@@ -0,0 +1,439 @@
+//! Image recognition: which OS an image contains, its version and arch.
+//!
+//! Combines three sources, most trusted first: the file layout inside
+//! an ISO9660 image (`/.disk/info`, `/sources/install.wim`, EFI loaders),
+//! the volume label, and the file name. Raw disk images are probed for a
+//! Raspberry Pi style boot partition.
+
+use crate::domain::{image_fs::ImageFs, iso9660::IsoImage};
+use std::{
+    fmt,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Distro {
+    Ubuntu,
+    Debian,
+    Mint,
+    Fedora,
+    Arch,
+    Manjaro,
+    NixOS,
+    OpenSuse,
+    Alpine,
+    Windows,
+    RaspberryPi,
+    #[default]
+    Unknown,
+}
+
+impl Distro {
+    pub const ALL: [Distro; 11] = [
+        Distro::Ubuntu,
+        Distro::Debian,
+        Distro::Mint,
+        Distro::Fedora,
+        Distro::Arch,
+        Distro::Manjaro,
+        Distro::NixOS,
+        Distro::OpenSuse,
+        Distro::Alpine,
+        Distro::Windows,
+        Distro::RaspberryPi,
+    ];
+
+    /// Short lowercase tag used in the list badge and `distro:` filters
+    pub fn badge(self) -> &'static str {
+        match self {
+            Distro::Ubuntu => "ubuntu",
+            Distro::Debian => "debian",
+            Distro::Mint => "mint",
+            Distro::Fedora => "fedora",
+            Distro::Arch => "arch",
+            Distro::Manjaro => "manjaro",
+            Distro::NixOS => "nixos",
+            Distro::OpenSuse => "opensuse",
+            Distro::Alpine => "alpine",
+            Distro::Windows => "windows",
+            Distro::RaspberryPi => "rpi",
+            Distro::Unknown => "?",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Distro> {
+        let s = s.to_ascii_lowercase();
+        match s.as_str() {
+            "win" | "win10" | "win11" => return Some(Distro::Windows),
+            "raspios" | "raspberrypi" | "pi" => return Some(Distro::RaspberryPi),
+            "suse" => return Some(Distro::OpenSuse),
+            _ => {}
+        }
+        Distro::ALL.into_iter().find(|d| d.badge() == s)
+    }
+}
+
+impl fmt::Display for Distro {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Distro::Ubuntu => "Ubuntu",
+            Distro::Debian => "Debian",
+            Distro::Mint => "Linux Mint",
+            Distro::Fedora => "Fedora",
+            Distro::Arch => "Arch Linux",
+            Distro::Manjaro => "Manjaro",
+            Distro::NixOS => "NixOS",
+            Distro::OpenSuse => "openSUSE",
+            Distro::Alpine => "Alpine",
+            Distro::Windows => "Windows",
+            Distro::RaspberryPi => "Raspberry Pi OS",
+            Distro::Unknown => "Unknown",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Arch {
+    X86_64,
+    X86,
+    Arm64,
+    Armhf,
+    Riscv64,
+    Ppc64le,
+    S390x,
+}
+
+impl Arch {
+    /// Canonical name, as accepted by `arch:` filters
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Arch::X86_64 => "x86_64",
+            Arch::X86 => "i686",
+            Arch::Arm64 => "arm64",
+            Arch::Armhf => "armhf",
+            Arch::Riscv64 => "riscv64",
+            Arch::Ppc64le => "ppc64le",
+            Arch::S390x => "s390x",
+        }
+    }
+
+    /// Accepts the usual aliases (`amd64`, `aarch64`, `i386`, ...)
+    pub fn parse(s: &str) -> Option<Arch> {
+        match s.to_ascii_lowercase().as_str() {
+            "x86_64" | "amd64" | "x64" => Some(Arch::X86_64),
+            "i686" | "i386" | "x86" | "ia32" => Some(Arch::X86),
+            "arm64" | "aarch64" | "a64" => Some(Arch::Arm64),
+            "armhf" | "armv7" | "armv7l" | "armv7hl" | "arm" => Some(Arch::Armhf),
+            "riscv64" => Some(Arch::Riscv64),
+            "ppc64le" | "ppc64el" => Some(Arch::Ppc64le),
+            "s390x" => Some(Arch::S390x),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Arch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What the recognizer learned about an image. Every field is optional.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DistroInfo {
+    pub distro: Distro,
+    pub version: Option<String>,
+    pub arch: Option<Arch>,
+    /// Volume label, when the image has one
+    pub label: Option<String>,
+}
+
+impl DistroInfo {
+    pub fn is_known(&self) -> bool {
+        self.distro != Distro::Unknown
+    }
+
+    /// Fill any gaps in `self` from a less trusted source
+    pub fn or(mut self, other: DistroInfo) -> DistroInfo {
+        if self.distro == Distro::Unknown {
+            self.distro = other.distro;
+        }
+        if self.version.is_none() {
+            self.version = other.version;
+        }
+        if self.arch.is_none() {
+            self.arch = other.arch;
+        }
+        if self.label.is_none() {
+            self.label = other.label;
+        }
+        self
+    }
+}
+
+/// Recognize an image on disk. Never fails; unknown fields stay empty.
+pub fn recognize(path: &Path) -> DistroInfo {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let from_name = classify_text(&name);
+    let Ok(file) = File::open(path) else {
+        return from_name;
+    };
+    match IsoImage::open(file) {
+        Ok(mut iso) => {
+            let label = iso.volume_id().to_string();
+            let mut from_label = classify_text(&label);
+            if !label.is_empty() {
+                from_label.label = Some(label);
+            }
+            probe_layout(&mut iso).or(from_label).or(from_name)
+        }
+        Err(_) => probe_disk_image(path).or(from_name),
+    }
+}
+
+/// Look at well-known files inside an ISO9660 image
+fn probe_layout<F: ImageFs>(iso: &mut F) -> DistroInfo {
+    let mut info = DistroInfo::default();
+    let exists = |iso: &mut F, p: &str| matches!(iso.lookup(p), Ok(Some(_)));
+
+    if let Ok(Some(entry)) = iso.lookup("/.disk/info")
+        && let Ok(bytes) = iso.read_all(&entry, 4096)
+    {
+        info = classify_text(&String::from_utf8_lossy(&bytes));
+    }
+
+    if info.distro == Distro::Unknown {
+        info.distro = if exists(iso, "/sources/install.wim") || exists(iso, "/sources/install.esd")
+        {
+            Distro::Windows
+        } else if exists(iso, "/arch/boot") {
+            Distro::Arch
+        } else if exists(iso, "/casper") {
+            Distro::Ubuntu
+        } else if exists(iso, "/LiveOS") {
+            Distro::Fedora
+        } else if exists(iso, "/nix-store.squashfs") || exists(iso, "/nix/store") {
+            Distro::NixOS
+        } else if exists(iso, "/live") && exists(iso, "/isolinux") {
+            Distro::Debian
+        } else {
+            Distro::Unknown
+        };
+    }
+
+    if info.arch.is_none() {
+        info.arch = if exists(iso, "/EFI/BOOT/BOOTX64.EFI") {
+            Some(Arch::X86_64)
+        } else if exists(iso, "/EFI/BOOT/BOOTAA64.EFI") {
+            Some(Arch::Arm64)
+        } else if exists(iso, "/EFI/BOOT/BOOTRISCV64.EFI") {
+            Some(Arch::Riscv64)
+        } else if exists(iso, "/boot/grub/x86_64-efi") || exists(iso, "/boot/grub/i386-pc") {
+            Some(Arch::X86_64)
+        } else if exists(iso, "/boot/grub/arm64-efi") {
+            Some(Arch::Arm64)
+        } else {
+            None
+        };
+    }
+    info
+}
+
+/// Probe a raw disk image for a Raspberry Pi style FAT boot partition
+fn probe_disk_image(path: &Path) -> DistroInfo {
+    let mut info = DistroInfo::default();
+    let Ok(mut f) = File::open(path) else {
+        return info;
+    };
+    let mut mbr = [0u8; 512];
+    if f.read_exact(&mut mbr).is_err() || mbr[510..512] != [0x55, 0xAA] {
+        return info;
+    }
+    let part = &mbr[446..462];
+    let ptype = part[4];
+    let start = u32::from_le_bytes([part[8], part[9], part[10], part[11]]) as u64;
+    if !matches!(ptype, 0x0b | 0x0c | 0x0e) || start == 0 {
+        return info;
+    }
+    let mut boot = [0u8; 512];
+    if f.seek(SeekFrom::Start(start * 512)).is_err() || f.read_exact(&mut boot).is_err() {
+        return info;
+    }
+    // FAT32 keeps the label at 0x47, FAT12/16 at 0x2B.
+    let label_at = if &boot[0x52..0x57] == b"FAT32" {
+        0x47
+    } else {
+        0x2B
+    };
+    let label = String::from_utf8_lossy(&boot[label_at..label_at + 11])
+        .trim()
+        .to_string();
+    if label.eq_ignore_ascii_case("boot") || label.eq_ignore_ascii_case("bootfs") {
+        info.distro = Distro::RaspberryPi;
+        info.label = Some(label);
+    }
+    info
+}
+
+/// Classify free text: a file name, a volume label or `.disk/info`
+pub fn classify_text(text: &str) -> DistroInfo {
+    let lower = text.to_ascii_lowercase();
+    let distro = classify_distro(&lower);
+    DistroInfo {
+        distro,
+        version: find_version(&lower, distro),
+        arch: find_arch(&lower),
+        label: None,
+    }
+}
+
+fn classify_distro(lower: &str) -> Distro {
+    let has = |needle: &str| lower.contains(needle);
+    if has("raspios") || has("raspbian") || has("raspberry") {
+        Distro::RaspberryPi
+    } else if has("linuxmint") || has("linux mint") || lower.starts_with("mint") {
+        Distro::Mint
+    } else if has("manjaro") {
+        Distro::Manjaro
+    } else if has("ubuntu") {
+        Distro::Ubuntu
+    } else if has("debian") || lower.starts_with("d-live") {
+        Distro::Debian
+    } else if has("fedora") {
+        Distro::Fedora
+    } else if has("archlinux") || lower.starts_with("arch_") || lower.starts_with("arch-") {
+        Distro::Arch
+    } else if has("nixos") {
+        Distro::NixOS
+    } else if has("opensuse") {
+        Distro::OpenSuse
+    } else if has("alpine") {
+        Distro::Alpine
+    } else if has("windows")
+        || lower.starts_with("win1")
+        || lower.starts_with("cccoma")
+        || lower.starts_with("cpba")
+        || has("_x64fre")
+        || has("_a64fre")
+    {
+        Distro::Windows
+    } else {
+        Distro::Unknown
+    }
+}
+
+fn find_arch(lower: &str) -> Option<Arch> {
+    const PATTERNS: [(&str, Arch); 14] = [
+        ("aarch64", Arch::Arm64),
+        ("arm64", Arch::Arm64),
+        ("a64fre", Arch::Arm64),
+        ("x86_64", Arch::X86_64),
+        ("amd64", Arch::X86_64),
+        ("x64", Arch::X86_64),
+        ("64-bit", Arch::X86_64),
+        ("riscv64", Arch::Riscv64),
+        ("ppc64el", Arch::Ppc64le),
+        ("ppc64le", Arch::Ppc64le),
+        ("s390x", Arch::S390x),
+        ("armhf", Arch::Armhf),
+        ("i686", Arch::X86),
+        ("i386", Arch::X86),
+    ];
+    PATTERNS
+        .iter()
+        .find(|(needle, _)| lower.contains(needle))
+        .map(|(_, arch)| *arch)
+}
+
+fn find_version(lower: &str, distro: Distro) -> Option<String> {
+    let tokens: Vec<&str> = lower
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '.'))
+        .map(|t| t.trim_matches('.'))
+        .filter(|t| !t.is_empty())
+        .collect();
+    let numeric = |t: &str| t.chars().all(|c| c.is_ascii_digit() || c == '.');
+
+    if distro == Distro::Arch
+        && let Some(t) = tokens.iter().find(|t| t.len() == 6 && numeric(t))
+    {
+        // Arch labels carry the release as YYYYMM
+        return Some(format!("{}.{}", &t[..4], &t[4..]));
+    }
+    if distro == Distro::Fedora
+        && let Some(t) = tokens
+            .iter()
+            .find(|t| t.len() == 2 && numeric(t) && !matches!(**t, "32" | "64" | "86"))
+    {
+        return Some(t.to_string());
+    }
+    tokens
+        .iter()
+        .find(|t| numeric(t) && t.contains('.') && t.starts_with(|c: char| c.is_ascii_digit()))
+        .map(|t| t.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::iso9660::tests::sample_image;
+    use std::io::Cursor;
+
+    #[test]
+    fn classifies_common_file_names() {
+        let u = classify_text("ubuntu-24.04.1-desktop-amd64.iso");
+        assert_eq!(u.distro, Distro::Ubuntu);
+        assert_eq!(u.version.as_deref(), Some("24.04.1"));
+        assert_eq!(u.arch, Some(Arch::X86_64));
+
+        let f = classify_text("Fedora-Workstation-Live-x86_64-40-1.14.iso");
+        assert_eq!(f.distro, Distro::Fedora);
+        assert_eq!(f.version.as_deref(), Some("40"));
+        assert_eq!(f.arch, Some(Arch::X86_64));
+
+        let a = classify_text("archlinux-2024.05.01-x86_64.iso");
+        assert_eq!(a.distro, Distro::Arch);
+        assert_eq!(a.version.as_deref(), Some("2024.05.01"));
+
+        let d = classify_text("debian-12.5.0-arm64-netinst.iso");
+        assert_eq!(d.distro, Distro::Debian);
+        assert_eq!(d.arch, Some(Arch::Arm64));
+
+        let r = classify_text("2024-03-15-raspios-bookworm-arm64-lite.img");
+        assert_eq!(r.distro, Distro::RaspberryPi);
+        assert_eq!(r.arch, Some(Arch::Arm64));
+    }
+
+    #[test]
+    fn classifies_volume_labels() {
+        assert_eq!(
+            classify_text("ARCH_202405").version.as_deref(),
+            Some("2024.05")
+        );
+        let w = classify_text("CPBA_A64FRE_EN-US_DV9");
+        assert_eq!(w.distro, Distro::Windows);
+        assert_eq!(w.arch, Some(Arch::Arm64));
+        assert_eq!(classify_text("random-backup.img").distro, Distro::Unknown);
+    }
+
+    #[test]
+    fn layout_beats_file_name() {
+        let mut iso = IsoImage::open(Cursor::new(sample_image("DVD"))).unwrap();
+        let info = probe_layout(&mut iso).or(classify_text("ubuntu.iso"));
+        assert_eq!(info.distro, Distro::Windows);
+    }
+
+    #[test]
+    fn arch_aliases_parse() {
+        assert_eq!(Arch::parse("amd64"), Some(Arch::X86_64));
+        assert_eq!(Arch::parse("AARCH64"), Some(Arch::Arm64));
+        assert_eq!(Arch::parse("sparc"), None);
+        assert_eq!(Distro::parse("Ubuntu"), Some(Distro::Ubuntu));
+        assert_eq!(Distro::parse("win"), Some(Distro::Windows));
+    }
+}
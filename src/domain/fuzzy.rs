@@ -0,0 +1,144 @@
+//! Fuzzy subsequence matching for the ISO search.
+//!
+//! Every query character must appear in the candidate, in order and
+//! ignoring case. Among all such alignments the best-scoring one wins:
+//! matches at word boundaries and runs of adjacent characters score
+//! higher, gaps between matched characters cost a little.
+
+const SCORE_MATCH: i32 = 16;
+/// Start of the name, or right after `-`, `_`, `.`, `/` or a space
+const BONUS_BOUNDARY: i32 = 10;
+/// `fooBar`, `ubuntu24`, `24lts`
+const BONUS_TRANSITION: i32 = 7;
+const BONUS_CONSECUTIVE: i32 = 8;
+const PENALTY_GAP_START: i32 = 3;
+const PENALTY_GAP_EXTEND: i32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzyMatch {
+    pub score: i32,
+    /// Char indices into the candidate, ascending
+    pub positions: Vec<usize>,
+}
+
+/// Best alignment of `query` in `candidate`; whitespace in the query is
+/// ignored. An empty query matches everything with score 0.
+pub fn score(query: &str, candidate: &str) -> Option<FuzzyMatch> {
+    let query: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect();
+    if query.is_empty() {
+        return Some(FuzzyMatch {
+            score: 0,
+            positions: Vec::new(),
+        });
+    }
+    let chars: Vec<char> = candidate.chars().collect();
+    let lower: Vec<char> = chars
+        .iter()
+        .map(|c| c.to_lowercase().next().unwrap_or(*c))
+        .collect();
+    let (n, m) = (query.len(), chars.len());
+    if n > m {
+        return None;
+    }
+    let bonus: Vec<i32> = (0..m).map(|j| position_bonus(&chars, j)).collect();
+
+    // best[i][j]: top score with query[i] matched at candidate[j];
+    // from[i][j]: where query[i - 1] was matched on that path
+    let mut best = vec![vec![None::<i32>; m]; n];
+    let mut from = vec![vec![0usize; m]; n];
+    for j in 0..m {
+        if lower[j] == query[0] {
+            best[0][j] = Some(SCORE_MATCH + bonus[j]);
+        }
+    }
+    for i in 1..n {
+        for j in i..m {
+            if lower[j] != query[i] {
+                continue;
+            }
+            let mut top: Option<(i32, usize)> = None;
+            for (k, prev) in best[i - 1].iter().enumerate().take(j).skip(i - 1) {
+                let Some(prev) = *prev else {
+                    continue;
+                };
+                let step = if k + 1 == j {
+                    BONUS_CONSECUTIVE
+                } else {
+                    -(PENALTY_GAP_START + PENALTY_GAP_EXTEND * (j - k - 2) as i32)
+                };
+                let total = prev + step;
+                if top.is_none_or(|(s, _)| total > s) {
+                    top = Some((total, k));
+                }
+            }
+            if let Some((s, k)) = top {
+                best[i][j] = Some(s + SCORE_MATCH + bonus[j]);
+                from[i][j] = k;
+            }
+        }
+    }
+
+    let (mut j, score) = best[n - 1]
+        .iter()
+        .enumerate()
+        .filter_map(|(j, s)| s.map(|s| (j, s)))
+        .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))?;
+    let mut positions = vec![0; n];
+    for i in (0..n).rev() {
+        positions[i] = j;
+        j = from[i][j];
+    }
+    Some(FuzzyMatch { score, positions })
+}
+
+fn position_bonus(chars: &[char], j: usize) -> i32 {
+    let Some(&prev) = j.checked_sub(1).and_then(|p| chars.get(p)) else {
+        return BONUS_BOUNDARY;
+    };
+    let cur = chars[j];
+    if matches!(prev, '-' | '_' | '.' | '/' | ' ') {
+        BONUS_BOUNDARY
+    } else if (prev.is_lowercase() && cur.is_uppercase())
+        || (prev.is_alphabetic() && cur.is_ascii_digit())
+        || (prev.is_ascii_digit() && cur.is_alphabetic())
+    {
+        BONUS_TRANSITION
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefers_boundaries_and_runs() {
+        let names = [
+            "xubuntu-22.04-desktop-amd64.iso",
+            "ubuntu-22.04.4-live-server-amd64.iso",
+            "ubuntu-24.04.1-desktop-amd64.iso",
+        ];
+        let mut ranked: Vec<(i32, &str)> = names
+            .iter()
+            .filter_map(|n| score("ubu24", n).map(|m| (m.score, *n)))
+            .collect();
+        ranked.sort_by_key(|r| std::cmp::Reverse(r.0));
+        assert_eq!(ranked[0].1, "ubuntu-24.04.1-desktop-amd64.iso");
+
+        let m = score("ubu24", "ubuntu-24.04.1-desktop-amd64.iso").unwrap();
+        assert_eq!(m.positions, vec![0, 1, 2, 7, 8]);
+    }
+
+    #[test]
+    fn requires_every_char_in_order() {
+        assert!(score("deb12", "debian-12.5.0-amd64-netinst.iso").is_some());
+        assert!(score("21deb", "debian-12.5.0-amd64-netinst.iso").is_none());
+        assert!(score("FEDORA", "Fedora-Workstation-Live.iso").is_some());
+        assert_eq!(score("", "anything").unwrap().score, 0);
+    }
+}
@@ -0,0 +1,297 @@
+//! Completed jobs, appended to `$XDG_STATE_HOME/ferric/history`.
+//!
+//! One tab-separated line per device written, after a header line:
+//! finish time, image, image size, image mtime, image SHA-256, device,
+//! device model, device serial, duration (ms), bytes, average speed
+//! (bytes/s) and `ok` or `failed: <why>`. Times are Unix seconds.
+
+use crate::{
+    config,
+    domain::{
+        paths::{DevicePath, IsoPath},
+        sha256,
+        units::Throughput,
+    },
+};
+use std::{
+    fs,
+    io::Write,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const HEADER: &str = "ferric-history 1";
+
+/// How many recently written images the ISO screen puts first
+pub const RECENT_LIMIT: usize = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub finished: SystemTime,
+    pub image: IsoPath,
+    pub image_size: u64,
+    pub image_modified: Option<SystemTime>,
+    /// Hex SHA-256 of the image; empty when it could not be read
+    pub sha256: String,
+    pub device: DevicePath,
+    pub model: Option<String>,
+    pub serial: Option<String>,
+    pub result: Result<(), String>,
+    pub duration: Duration,
+    pub bytes: u64,
+}
+
+impl HistoryEntry {
+    pub fn speed(&self) -> Throughput {
+        let secs = self.duration.as_secs_f64();
+        Throughput::new(if secs > 0.0 {
+            self.bytes as f64 / secs
+        } else {
+            0.0
+        })
+    }
+
+    /// Same file as when `other` was hashed, going by size and mtime
+    fn same_image(&self, other: &HistoryEntry) -> bool {
+        self.image == other.image
+            && self.image_size == other.image_size
+            && self.image_modified == other.image_modified
+    }
+}
+
+pub fn history_path() -> Option<PathBuf> {
+    config::xdg_dir("XDG_STATE_HOME", ".local/state").map(|d| d.join("ferric").join("history"))
+}
+
+/// Everything recorded so far, oldest first; a missing file is empty
+pub fn load() -> Vec<HistoryEntry> {
+    history_path()
+        .and_then(|p| fs::read_to_string(p).ok())
+        .map(|text| parse(&text))
+        .unwrap_or_default()
+}
+
+/// Reuse the hash of an earlier job on the same unchanged image
+pub fn fill_known_hashes(entries: &mut [HistoryEntry], known: &[HistoryEntry]) {
+    for entry in entries.iter_mut().filter(|e| e.sha256.is_empty()) {
+        if let Some(prev) = known
+            .iter()
+            .rev()
+            .find(|k| !k.sha256.is_empty() && k.same_image(entry))
+        {
+            entry.sha256 = prev.sha256.clone();
+        }
+    }
+}
+
+/// Hash whatever is still missing and append the entries to the file
+pub fn record(mut entries: Vec<HistoryEntry>) -> Result<Vec<HistoryEntry>, String> {
+    for i in 0..entries.len() {
+        if !entries[i].sha256.is_empty() {
+            continue;
+        }
+        // A batch writes one image to several sticks: hash it once
+        let (done, rest) = entries.split_at_mut(i);
+        let entry = &mut rest[0];
+        entry.sha256 = match done.iter().find(|d| d.same_image(entry)) {
+            Some(d) => d.sha256.clone(),
+            None => sha256::file(entry.image.as_path()).unwrap_or_default(),
+        };
+    }
+    let path = history_path().ok_or("no home directory for the history file")?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    }
+    let is_new = !path.exists();
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut text = String::new();
+    if is_new {
+        text.push_str(HEADER);
+        text.push('\n');
+    }
+    for entry in &entries {
+        text.push_str(&render_line(entry));
+        text.push('\n');
+    }
+    file.write_all(text.as_bytes())
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(entries)
+}
+
+/// Images of successful jobs, most recent first, without repeats
+pub fn recent_images(history: &[HistoryEntry], limit: usize) -> Vec<IsoPath> {
+    let mut out: Vec<IsoPath> = Vec::new();
+    for entry in history.iter().rev().filter(|e| e.result.is_ok()) {
+        if out.len() == limit {
+            break;
+        }
+        if !out.contains(&entry.image) {
+            out.push(entry.image.clone());
+        }
+    }
+    out
+}
+
+/// How often a stick was written, and its latest job
+pub fn device_uses<'a>(
+    history: &'a [HistoryEntry],
+    serial: &str,
+) -> Option<(usize, &'a HistoryEntry)> {
+    let uses: Vec<&HistoryEntry> = history
+        .iter()
+        .filter(|e| e.serial.as_deref() == Some(serial))
+        .collect();
+    uses.last().map(|last| (uses.len(), *last))
+}
+
+/// The latest job that wrote `image`
+pub fn last_use<'a>(history: &'a [HistoryEntry], image: &IsoPath) -> Option<&'a HistoryEntry> {
+    history.iter().rev().find(|e| &e.image == image)
+}
+
+pub(crate) fn render_line(e: &HistoryEntry) -> String {
+    let clean = |s: &str| s.replace(['\t', '\n'], " ");
+    let secs = |t: SystemTime| {
+        t.duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+    };
+    let result = match &e.result {
+        Ok(()) => "ok".to_string(),
+        Err(why) => format!("failed: {}", clean(why)),
+    };
+    [
+        secs(e.finished).to_string(),
+        clean(&e.image.to_string()),
+        e.image_size.to_string(),
+        e.image_modified
+            .map(|t| secs(t).to_string())
+            .unwrap_or_default(),
+        e.sha256.clone(),
+        clean(&e.device.to_string()),
+        clean(e.model.as_deref().unwrap_or("")),
+        clean(e.serial.as_deref().unwrap_or("")),
+        e.duration.as_millis().to_string(),
+        e.bytes.to_string(),
+        format!("{:.0}", e.speed().as_f64()),
+        result,
+    ]
+    .join("\t")
+}
+
+fn parse(text: &str) -> Vec<HistoryEntry> {
+    let mut lines = text.lines();
+    if lines.next() != Some(HEADER) {
+        return Vec::new();
+    }
+    lines.filter_map(parse_line).collect()
+}
+
+/// One entry as [`render_line`] wrote it
+pub(crate) fn parse_line(line: &str) -> Option<HistoryEntry> {
+    let time = |s: &str| s.parse().ok().map(|s| UNIX_EPOCH + Duration::from_secs(s));
+    let opt = |s: &str| (!s.is_empty()).then(|| s.to_string());
+    let f: Vec<&str> = line.split('\t').collect();
+    let [
+        finished,
+        image,
+        size,
+        mtime,
+        hash,
+        device,
+        model,
+        serial,
+        ms,
+        bytes,
+        _speed,
+        result,
+    ] = f[..]
+    else {
+        return None;
+    };
+    Some(HistoryEntry {
+        finished: time(finished)?,
+        image: IsoPath::from(PathBuf::from(image)),
+        image_size: size.parse().ok()?,
+        image_modified: time(mtime),
+        sha256: hash.to_string(),
+        device: DevicePath::from(PathBuf::from(device)),
+        model: opt(model),
+        serial: opt(serial),
+        result: match result.strip_prefix("failed: ") {
+            Some(why) => Err(why.to_string()),
+            None => Ok(()),
+        },
+        duration: Duration::from_millis(ms.parse().ok()?),
+        bytes: bytes.parse().ok()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(image: &str, serial: &str, result: Result<(), String>) -> HistoryEntry {
+        HistoryEntry {
+            finished: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+            image: IsoPath::from(PathBuf::from(image)),
+            image_size: 4096,
+            image_modified: Some(UNIX_EPOCH + Duration::from_secs(1_600_000_000)),
+            sha256: String::new(),
+            device: DevicePath::from(PathBuf::from("/dev/sdb")),
+            model: Some("Cruzer Blade".to_string()),
+            serial: Some(serial.to_string()),
+            result,
+            duration: Duration::from_millis(2000),
+            bytes: 4096,
+        }
+    }
+
+    #[test]
+    fn lines_round_trip() {
+        let mut ok = entry("/isos/a.iso", "S1", Ok(()));
+        ok.sha256 = "ab".repeat(32);
+        let failed = entry("/isos/b.iso", "S2", Err("short\twrite".to_string()));
+        let text = format!(
+            "{}\n{}\n{}\n",
+            HEADER,
+            render_line(&ok),
+            render_line(&failed)
+        );
+        let parsed = parse(&text);
+        assert_eq!(parsed[0], ok);
+        assert_eq!(parsed[1].result, Err("short write".to_string()));
+        assert_eq!(parsed[0].speed().as_f64(), 2048.0);
+    }
+
+    #[test]
+    fn recent_images_and_known_devices() {
+        let mut history = vec![
+            entry("/isos/a.iso", "S1", Ok(())),
+            entry("/isos/b.iso", "S1", Ok(())),
+            entry("/isos/c.iso", "S2", Err("io".to_string())),
+            entry("/isos/a.iso", "S1", Ok(())),
+        ];
+        history[0].sha256 = "00".repeat(32);
+        let recent = recent_images(&history, 5);
+        assert_eq!(
+            recent,
+            vec![
+                IsoPath::from(PathBuf::from("/isos/a.iso")),
+                IsoPath::from(PathBuf::from("/isos/b.iso")),
+            ]
+        );
+        let (uses, last) = device_uses(&history, "S1").unwrap();
+        assert_eq!(uses, 3);
+        assert_eq!(last.image, IsoPath::from(PathBuf::from("/isos/a.iso")));
+        assert!(device_uses(&history, "S9").is_none());
+
+        let mut new = vec![entry("/isos/a.iso", "S3", Ok(()))];
+        fill_known_hashes(&mut new, &history);
+        assert_eq!(new[0].sha256, "00".repeat(32));
+    }
+}
@@ -0,0 +1,257 @@
+//! Common interface over the read-only image filesystems (ISO9660, UDF).
+
+use std::{
+    collections::HashSet,
+    io::{self, Write},
+};
+
+/// Deepest directory nesting [`ImageFs::walk`] follows
+pub const MAX_WALK_DEPTH: usize = 64;
+/// Most entries [`ImageFs::walk`] lists before giving up
+pub const MAX_WALK_ENTRIES: usize = 1 << 20;
+
+pub trait ImageEntry: Clone {
+    fn name(&self) -> &str;
+    fn is_dir(&self) -> bool;
+    fn size(&self) -> u64;
+    /// Where the entry lives in the image; a directory reached twice has
+    /// the same location
+    fn location(&self) -> u64;
+}
+
+pub trait ImageFs {
+    type Entry: ImageEntry;
+
+    fn root(&self) -> Self::Entry;
+
+    fn read_dir(&mut self, dir: &Self::Entry) -> io::Result<Vec<Self::Entry>>;
+
+    /// Stream an entry into `dst`, reporting bytes copied after each chunk
+    fn copy_to(
+        &mut self,
+        entry: &Self::Entry,
+        dst: &mut dyn Write,
+        on_progress: &mut dyn FnMut(u64),
+    ) -> io::Result<u64>;
+
+    /// Resolve a `/`-separated path relative to the root, ignoring case
+    fn lookup(&mut self, path: &str) -> io::Result<Option<Self::Entry>> {
+        let mut current = self.root();
+        for part in path.split('/').filter(|p| !p.is_empty()) {
+            if !current.is_dir() {
+                return Ok(None);
+            }
+            match self
+                .read_dir(&current)?
+                .into_iter()
+                .find(|e| e.name().eq_ignore_ascii_case(part))
+            {
+                Some(e) => current = e,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(current))
+    }
+
+    /// Read a whole entry into memory, refusing anything above `limit`
+    fn read_all(&mut self, entry: &Self::Entry, limit: u64) -> io::Result<Vec<u8>> {
+        if entry.size() > limit {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "entry too large to buffer",
+            ));
+        }
+        let mut out = Vec::with_capacity(entry.size() as usize);
+        self.copy_to(entry, &mut out, &mut |_| {})?;
+        Ok(out)
+    }
+
+    /// Every entry below the root as `(relative path, entry)`, parents first.
+    ///
+    /// Fails on a directory that contains one of its ancestors and when
+    /// the tree is deeper than [`MAX_WALK_DEPTH`] or larger than
+    /// [`MAX_WALK_ENTRIES`], as only a crafted image would be.
+    fn walk(&mut self) -> io::Result<Vec<(String, Self::Entry)>> {
+        let limit = |what: &str| io::Error::new(io::ErrorKind::InvalidData, what.to_string());
+        let root = self.root();
+        let mut seen = HashSet::from([root.location()]);
+        let mut out = Vec::new();
+        let mut stack = vec![(String::new(), root, 0)];
+        while let Some((prefix, dir, depth)) = stack.pop() {
+            for child in self.read_dir(&dir)? {
+                let path = if prefix.is_empty() {
+                    child.name().to_string()
+                } else {
+                    format!("{}/{}", prefix, child.name())
+                };
+                if child.is_dir() {
+                    if !seen.insert(child.location()) {
+                        return Err(limit("directory loop in image"));
+                    }
+                    if depth + 1 >= MAX_WALK_DEPTH {
+                        return Err(limit("directories nested too deep in image"));
+                    }
+                    stack.push((path.clone(), child.clone(), depth + 1));
+                }
+                out.push((path, child));
+                if out.len() > MAX_WALK_ENTRIES {
+                    return Err(limit("too many entries in image"));
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// An image built in memory, for tests of code that reads images
+#[cfg(test)]
+pub(crate) mod mem {
+    use super::{ImageEntry, ImageFs};
+    use std::io::{self, Write};
+
+    #[derive(Debug, Clone)]
+    pub struct MemEntry {
+        name: String,
+        node: usize,
+        is_dir: bool,
+        size: u64,
+    }
+
+    enum Node {
+        Dir(Vec<MemEntry>),
+        File(Vec<u8>),
+    }
+
+    /// Node 0 is the root directory
+    pub struct MemFs {
+        nodes: Vec<Node>,
+    }
+
+    impl MemFs {
+        pub fn new() -> Self {
+            Self {
+                nodes: vec![Node::Dir(Vec::new())],
+            }
+        }
+
+        /// Add an empty directory under `parent`; returns its node
+        pub fn dir(&mut self, parent: usize, name: &str) -> usize {
+            self.nodes.push(Node::Dir(Vec::new()));
+            let node = self.nodes.len() - 1;
+            self.link(parent, name, node);
+            node
+        }
+
+        pub fn file(&mut self, parent: usize, name: &str, contents: &[u8]) {
+            self.nodes.push(Node::File(contents.to_vec()));
+            let node = self.nodes.len() - 1;
+            self.link(parent, name, node);
+        }
+
+        /// List `node` under `parent` as `name`, even where that makes a loop
+        pub fn link(&mut self, parent: usize, name: &str, node: usize) {
+            let entry = match &self.nodes[node] {
+                Node::Dir(_) => MemEntry {
+                    name: name.to_string(),
+                    node,
+                    is_dir: true,
+                    size: 0,
+                },
+                Node::File(bytes) => MemEntry {
+                    name: name.to_string(),
+                    node,
+                    is_dir: false,
+                    size: bytes.len() as u64,
+                },
+            };
+            if let Node::Dir(children) = &mut self.nodes[parent] {
+                children.push(entry);
+            }
+        }
+    }
+
+    impl ImageEntry for MemEntry {
+        fn name(&self) -> &str {
+            &self.name
+        }
+
+        fn is_dir(&self) -> bool {
+            self.is_dir
+        }
+
+        fn size(&self) -> u64 {
+            self.size
+        }
+
+        fn location(&self) -> u64 {
+            self.node as u64
+        }
+    }
+
+    impl ImageFs for MemFs {
+        type Entry = MemEntry;
+
+        fn root(&self) -> MemEntry {
+            MemEntry {
+                name: String::new(),
+                node: 0,
+                is_dir: true,
+                size: 0,
+            }
+        }
+
+        fn read_dir(&mut self, dir: &MemEntry) -> io::Result<Vec<MemEntry>> {
+            match &self.nodes[dir.node] {
+                Node::Dir(children) => Ok(children.clone()),
+                Node::File(_) => Err(io::Error::other("not a directory")),
+            }
+        }
+
+        fn copy_to(
+            &mut self,
+            entry: &MemEntry,
+            dst: &mut dyn Write,
+            on_progress: &mut dyn FnMut(u64),
+        ) -> io::Result<u64> {
+            match &self.nodes[entry.node] {
+                Node::File(bytes) => {
+                    dst.write_all(bytes)?;
+                    on_progress(bytes.len() as u64);
+                    Ok(bytes.len() as u64)
+                }
+                Node::Dir(_) => Err(io::Error::other("not a file")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mem::MemFs, *};
+
+    #[test]
+    fn walk_lists_parents_first() {
+        let mut fs = MemFs::new();
+        let boot = fs.dir(0, "boot");
+        fs.file(boot, "bcd", b"x");
+        fs.file(0, "setup.exe", b"mz");
+        let paths: Vec<String> = fs.walk().unwrap().into_iter().map(|(p, _)| p).collect();
+        assert_eq!(paths, ["boot", "setup.exe", "boot/bcd"]);
+    }
+
+    #[test]
+    fn walk_stops_at_loops_and_runaway_trees() {
+        let mut looped = MemFs::new();
+        let a = looped.dir(0, "a");
+        let b = looped.dir(a, "b");
+        looped.link(b, "up", a);
+        assert!(looped.walk().unwrap_err().to_string().contains("loop"));
+
+        let mut deep = MemFs::new();
+        let mut dir = 0;
+        for _ in 0..MAX_WALK_DEPTH {
+            dir = deep.dir(dir, "d");
+        }
+        assert!(deep.walk().unwrap_err().to_string().contains("deep"));
+    }
+}
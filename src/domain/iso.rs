@@ -1,17 +1,119 @@
 use crate::domain::{
+    distro::{self, Arch, Distro, DistroInfo},
+    fuzzy, iso_walk,
     paths::{DirPath, IsoPath},
     units::ByteSize,
+    windows,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::{LazyLock, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
 };
-use std::{collections::HashSet, fs, path::Path, time::SystemTime};
 
 #[derive(Debug, Clone)]
 pub struct IsoMeta {
     pub path: IsoPath,
     pub size: ByteSize,
     pub modified: Option<SystemTime>,
+    pub distro: DistroInfo,
+    /// Carries its own partition table, so a raw block copy boots
+    pub hybrid: bool,
+}
+
+impl IsoMeta {
+    /// Windows installer ISOs that must be unpacked onto a fresh filesystem
+    pub fn needs_windows_mode(&self) -> bool {
+        self.distro.distro == Distro::Windows && !self.hybrid
+    }
+}
+
+/// A parsed search query: free text plus `arch:` / `distro:` filters.
+///
+/// Unknown `key:value` tokens are kept as text so a literal colon still
+/// searches file names.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IsoQuery {
+    pub text: String,
+    pub arch: Option<Arch>,
+    pub distro: Option<Distro>,
+}
+
+impl IsoQuery {
+    pub fn parse(query: &str) -> Self {
+        let mut out = IsoQuery::default();
+        let mut words: Vec<&str> = Vec::new();
+        for token in query.split_whitespace() {
+            match token.split_once(':') {
+                Some(("arch", v)) if Arch::parse(v).is_some() => out.arch = Arch::parse(v),
+                Some(("distro", v)) if Distro::parse(v).is_some() => out.distro = Distro::parse(v),
+                _ => words.push(token),
+            }
+        }
+        out.text = words.join(" ");
+        out
+    }
+
+    fn has_filters(&self) -> bool {
+        self.arch.is_some() || self.distro.is_some()
+    }
+
+    fn matches_distro(&self, info: &DistroInfo) -> bool {
+        self.arch.is_none_or(|a| info.arch == Some(a))
+            && self.distro.is_none_or(|d| info.distro == d)
+    }
+}
+
+/// Where to look for images and which files count as one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanOptions {
+    pub roots: Vec<DirPath>,
+    /// Directory names (no `/`) or path prefixes to skip
+    pub exclude: Vec<String>,
+    pub max_depth: usize,
+    pub min_size: u64,
+    /// Lower-case extensions without the dot
+    pub extensions: Vec<String>,
+}
+
+pub const DEFAULT_MAX_DEPTH: usize = 5;
+pub const DEFAULT_MIN_SIZE: u64 = 100 * 1024 * 1024;
+pub const DEFAULT_EXTENSIONS: [&str; 3] = ["iso", "img", "raw"];
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            roots: default_roots(),
+            exclude: Vec::new(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            min_size: DEFAULT_MIN_SIZE,
+            extensions: DEFAULT_EXTENSIONS.iter().map(|e| e.to_string()).collect(),
+        }
+    }
+}
+
+impl ScanOptions {
+    pub fn is_excluded(&self, dir: &Path) -> bool {
+        self.exclude.iter().any(|ex| {
+            if ex.contains('/') {
+                dir.starts_with(ex)
+            } else {
+                dir.file_name().is_some_and(|n| n == ex.as_str())
+            }
+        })
+    }
+
+    fn has_extension(&self, p: &Path) -> bool {
+        p.extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| self.extensions.iter().any(|x| x.eq_ignore_ascii_case(e)))
+    }
 }
 
-pub fn scan_default_roots(query: &str) -> Vec<IsoMeta> {
+/// The current directory, `~/Downloads` and `~`
+pub fn default_roots() -> Vec<DirPath> {
     let mut roots: Vec<DirPath> = Vec::new();
     if let Ok(cwd) = std::env::current_dir() {
         roots.push(DirPath::from(cwd));
@@ -20,122 +122,163 @@ pub fn scan_default_roots(query: &str) -> Vec<IsoMeta> {
         roots.push(home.join("Downloads"));
         roots.push(home);
     }
-    let roots = normalize_roots(roots);
-    scan(roots, query, 5, 100 * 1024 * 1024)
+    normalize_roots(roots)
 }
 
-pub fn scan(roots: Vec<DirPath>, query: &str, max_depth: usize, min_size: u64) -> Vec<IsoMeta> {
+/// Every image under the roots, newest first
+pub fn scan(options: &ScanOptions) -> Vec<IsoMeta> {
+    let starts = options
+        .roots
+        .iter()
+        .filter(|r| r.exists())
+        .map(|r| (r.as_path().to_path_buf(), 0))
+        .collect();
     let mut out = Vec::new();
     let mut seen: HashSet<IsoPath> = HashSet::new();
-    for root in roots {
-        if root.exists() {
-            walk(
-                root.as_path(),
-                0,
-                max_depth,
-                min_size,
-                query,
-                &mut out,
-                &mut seen,
-            );
+    iso_walk::walk(starts, options, &|| false, &mut |item| {
+        if let iso_walk::WalkItem::Image(meta) = item
+            && seen.insert(meta.path.clone())
+        {
+            out.push(meta);
         }
-    }
-    out.sort_by(|a, b| {
+    });
+    sort_newest_first(&mut out);
+    out
+}
+
+/// The entries of an index that match `query`, best fuzzy match first
+/// and newest first among equals
+pub fn filter(entries: &[IsoMeta], query: &str) -> Vec<IsoMeta> {
+    let query = IsoQuery::parse(query);
+    let mut scored: Vec<(i32, &IsoMeta)> = entries
+        .iter()
+        .filter(|meta| !query.has_filters() || query.matches_distro(&meta.distro))
+        .filter_map(|meta| fuzzy::score(&query.text, &file_name(meta)).map(|m| (m.score, meta)))
+        .collect();
+    scored.sort_by(|(sa, a), (sb, b)| {
+        sb.cmp(sa)
+            .then_with(|| b.modified.cmp(&a.modified))
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    scored.into_iter().map(|(_, meta)| meta.clone()).collect()
+}
+
+/// Char positions of the query's match in the file name, for highlighting
+pub fn match_positions(meta: &IsoMeta, query: &str) -> Vec<usize> {
+    fuzzy::score(&IsoQuery::parse(query).text, &file_name(meta))
+        .map(|m| m.positions)
+        .unwrap_or_default()
+}
+
+fn file_name(meta: &IsoMeta) -> String {
+    meta.path
+        .as_path()
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+pub fn sort_newest_first(entries: &mut [IsoMeta]) {
+    entries.sort_by(|a, b| {
         b.modified
             .cmp(&a.modified)
             .then_with(|| a.path.cmp(&b.path))
     });
-    out
 }
 
-fn walk(
-    path: &Path,
-    depth: usize,
-    max_depth: usize,
-    min_size: u64,
-    query: &str,
-    out: &mut Vec<IsoMeta>,
-    seen: &mut HashSet<IsoPath>,
-) {
-    if depth > max_depth {
-        return;
+/// Inspect one file; `None` unless it is an image under the options
+pub fn probe(path: &Path, options: &ScanOptions) -> Option<IsoMeta> {
+    if !options.has_extension(path) {
+        return None;
     }
-    let entries = match fs::read_dir(path) {
-        Ok(rd) => rd,
-        Err(_) => return,
-    };
-    for entry in entries.flatten() {
-        let path = entry.path();
-        let file_name = entry.file_name();
-        let file_name = file_name.to_string_lossy();
-        if file_name.starts_with('.') && path.is_dir() {
-            continue;
-        }
-        let file_type = match entry.file_type() {
-            Ok(ft) => ft,
-            Err(_) => continue,
+    let meta = fs::metadata(path).ok()?;
+    if !meta.is_file() || meta.len() < options.min_size {
+        return None;
+    }
+    Some(describe(path, &meta))
+}
+
+/// What opening an image taught us, kept while its size and mtime hold
+#[derive(Clone)]
+struct Recognized {
+    stamp: (u64, Option<u64>),
+    distro: DistroInfo,
+    hybrid: bool,
+}
+
+static RECOGNIZED: LazyLock<Mutex<HashMap<PathBuf, Recognized>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Size and whole-second mtime, as precise as the index cache keeps it
+fn stamp(size: u64, modified: Option<SystemTime>) -> (u64, Option<u64>) {
+    let secs = modified
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs());
+    (size, secs)
+}
+
+/// Recognize an image file whose metadata is already known. The image
+/// is only opened when it is new or changed since it was last seen.
+pub fn describe(path: &Path, meta: &fs::Metadata) -> IsoMeta {
+    let modified = meta.modified().ok();
+    let stamp = stamp(meta.len(), modified);
+    let known = RECOGNIZED
+        .lock()
+        .ok()
+        .and_then(|seen| seen.get(path).filter(|r| r.stamp == stamp).cloned());
+    let recognized = known.unwrap_or_else(|| {
+        let fresh = Recognized {
+            stamp,
+            distro: distro::recognize(path),
+            hybrid: windows::is_hybrid(path),
         };
-        if file_type.is_symlink() {
-            continue;
-        }
-        if file_type.is_dir() {
-            walk(&path, depth + 1, max_depth, min_size, query, out, seen);
-        } else if file_type.is_file() {
-            if !has_iso_extension(&path) {
-                continue;
-            }
-            if !matches_filter(&file_name, query) {
-                continue;
-            }
-            match fs::metadata(&path) {
-                Ok(meta) => {
-                    let size = meta.len();
-                    if size < min_size {
-                        continue;
-                    }
-                    let modified = meta.modified().ok();
-                    let iso_path = IsoPath::from(path.clone());
-                    if seen.insert(iso_path.clone()) {
-                        out.push(IsoMeta {
-                            path: iso_path,
-                            size: ByteSize::new(size),
-                            modified,
-                        });
-                    }
-                }
-                Err(_) => continue,
-            }
+        if let Ok(mut seen) = RECOGNIZED.lock() {
+            seen.insert(path.to_path_buf(), fresh.clone());
         }
+        fresh
+    });
+    IsoMeta {
+        path: IsoPath::from(path.to_path_buf()),
+        size: ByteSize::new(meta.len()),
+        modified,
+        distro: recognized.distro,
+        hybrid: recognized.hybrid,
     }
 }
 
-fn has_iso_extension(p: &Path) -> bool {
-    match p
-        .extension()
-        .and_then(|e| e.to_str())
-        .map(|s| s.to_ascii_lowercase())
-    {
-        Some(ext) if matches!(ext.as_str(), "iso" | "img" | "raw") => true,
-        _ => false,
+/// Take what is already known about `entries` (the index cache) so a
+/// rescan does not open them again
+pub fn remember(entries: &[IsoMeta]) {
+    let Ok(mut seen) = RECOGNIZED.lock() else {
+        return;
+    };
+    for m in entries {
+        seen.insert(
+            m.path.as_path().to_path_buf(),
+            Recognized {
+                stamp: stamp(m.size.as_u64(), m.modified),
+                distro: m.distro.clone(),
+                hybrid: m.hybrid,
+            },
+        );
     }
 }
 
-fn matches_filter(name: &str, query: &str) -> bool {
-    if query.is_empty() {
-        return true;
-    }
-    let name = name.to_ascii_lowercase();
-    let q = query.to_ascii_lowercase();
-    name.contains(&q)
+/// Dot-directories are skipped by the walk and the watcher alike
+pub fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|n| n.to_string_lossy().starts_with('.'))
 }
 
-fn home_dir() -> Option<DirPath> {
+/// `$HOME`, if set
+pub fn home_dir() -> Option<DirPath> {
     std::env::var("HOME")
         .ok()
         .map(|s| DirPath::from(Path::new(&s).to_owned()))
 }
 
-fn normalize_roots(roots: Vec<DirPath>) -> Vec<DirPath> {
+/// Drop duplicate roots, and `~` when `~/Downloads` is also listed
+pub fn normalize_roots(roots: Vec<DirPath>) -> Vec<DirPath> {
     let home = home_dir();
     let home_can = home.as_ref().and_then(|h| h.canonicalize().ok());
     let downloads_can = home
@@ -160,11 +303,92 @@ fn normalize_roots(roots: Vec<DirPath>) -> Vec<DirPath> {
         .as_ref()
         .and_then(|h| out.iter().position(|(_o, c)| c == h));
 
-    if has_downloads {
-        if let Some(idx) = home_index {
-            out.remove(idx);
-        }
+    if has_downloads && let Some(idx) = home_index {
+        out.remove(idx);
     }
 
     out.into_iter().map(|(o, _c)| o).collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn images_are_only_recognized_again_once_changed() {
+        let path = std::env::temp_dir().join(format!("ferric-seen-{}.iso", std::process::id()));
+        fs::write(&path, vec![0u8; 4096]).unwrap();
+        let first = describe(&path, &fs::metadata(&path).unwrap());
+        assert_eq!(first.distro.distro, Distro::Unknown);
+
+        // Stands in for what opening the image found last time
+        let mut known = first.clone();
+        known.distro.distro = Distro::Fedora;
+        remember(&[known]);
+        let again = describe(&path, &fs::metadata(&path).unwrap());
+        assert_eq!(again.distro.distro, Distro::Fedora);
+
+        fs::write(&path, vec![0u8; 8192]).unwrap();
+        let changed = describe(&path, &fs::metadata(&path).unwrap());
+        assert_eq!(changed.distro.distro, Distro::Unknown);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn scan_honours_options() {
+        let dir = std::env::temp_dir().join(format!("ferric-scan-{}", std::process::id()));
+        let skip = dir.join("node_modules");
+        fs::create_dir_all(&skip).unwrap();
+        fs::write(dir.join("fw.bin"), vec![0u8; 2048]).unwrap();
+        fs::write(dir.join("tiny.img"), vec![0u8; 16]).unwrap();
+        fs::write(skip.join("hidden.bin"), vec![0u8; 2048]).unwrap();
+
+        let options = ScanOptions {
+            roots: vec![DirPath::from(dir.clone())],
+            exclude: vec!["node_modules".to_string()],
+            max_depth: 2,
+            min_size: 1024,
+            extensions: vec!["bin".to_string(), "img".to_string()],
+        };
+        let names: Vec<String> = scan(&options)
+            .into_iter()
+            .map(|m| {
+                m.path
+                    .as_path()
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(names, vec!["fw.bin"]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn query_extracts_filters() {
+        let q = IsoQuery::parse("arch:aarch64 ubuntu distro:ubuntu");
+        assert_eq!(q.text, "ubuntu");
+        assert_eq!(q.arch, Some(Arch::Arm64));
+        assert_eq!(q.distro, Some(Distro::Ubuntu));
+    }
+
+    #[test]
+    fn unknown_filter_values_stay_text() {
+        let q = IsoQuery::parse("arch:sparc");
+        assert_eq!(q.text, "arch:sparc");
+        assert!(q.arch.is_none());
+    }
+
+    #[test]
+    fn filters_compare_recognized_info() {
+        let q = IsoQuery::parse("arch:arm64");
+        let mut info = DistroInfo {
+            arch: Some(Arch::Arm64),
+            ..DistroInfo::default()
+        };
+        assert!(q.matches_distro(&info));
+        info.arch = Some(Arch::X86_64);
+        assert!(!q.matches_distro(&info));
+    }
+}
@@ -0,0 +1,297 @@
+//! Minimal read-only ISO9660 reader.
+//!
+//! Understands the primary volume descriptor and Joliet supplementary
+//! descriptors, directory records and multi-extent files. Rock Ridge
+//! and El Torito are ignored; names are compared case-insensitively.
+
+use crate::domain::image_fs::{ImageEntry, ImageFs};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+pub const SECTOR_SIZE: u64 = 2048;
+
+const FLAG_DIRECTORY: u8 = 0x02;
+const FLAG_MULTI_EXTENT: u8 = 0x80;
+
+/// A file or directory inside the image.
+///
+/// Large files are stored as several consecutive extents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IsoEntry {
+    pub name: String,
+    pub is_dir: bool,
+    pub extents: Vec<(u32, u32)>,
+}
+
+impl IsoEntry {
+    /// Total size in bytes across all extents
+    pub fn size(&self) -> u64 {
+        self.extents.iter().map(|(_, len)| *len as u64).sum()
+    }
+}
+
+pub struct IsoImage<R> {
+    reader: R,
+    volume_id: String,
+    root: IsoEntry,
+    joliet: bool,
+}
+
+impl<R: Read + Seek> IsoImage<R> {
+    /// Parse the volume descriptor set, preferring a Joliet root if present
+    pub fn open(mut reader: R) -> io::Result<Self> {
+        let mut primary: Option<(String, IsoEntry)> = None;
+        let mut joliet: Option<IsoEntry> = None;
+        let mut sector = [0u8; SECTOR_SIZE as usize];
+        for lba in 16..64u64 {
+            reader.seek(SeekFrom::Start(lba * SECTOR_SIZE))?;
+            reader.read_exact(&mut sector)?;
+            if &sector[1..6] != b"CD001" {
+                return Err(invalid("missing CD001 signature"));
+            }
+            match sector[0] {
+                1 if primary.is_none() => {
+                    let volume_id = String::from_utf8_lossy(&sector[40..72]).trim().to_string();
+                    let root = parse_record(&sector[156..190], false)
+                        .ok_or_else(|| invalid("bad root directory record"))?;
+                    primary = Some((volume_id, root));
+                }
+                2 if is_joliet_escape(&sector[88..120]) => {
+                    joliet = parse_record(&sector[156..190], true);
+                }
+                255 => break,
+                _ => {}
+            }
+        }
+        let (volume_id, primary_root) =
+            primary.ok_or_else(|| invalid("no primary volume descriptor"))?;
+        let (root, joliet) = match joliet {
+            Some(root) => (root, true),
+            None => (primary_root, false),
+        };
+        Ok(Self {
+            reader,
+            volume_id,
+            root,
+            joliet,
+        })
+    }
+
+    pub fn volume_id(&self) -> &str {
+        &self.volume_id
+    }
+
+    /// List a directory, skipping the `.` and `..` records
+    pub fn read_dir(&mut self, dir: &IsoEntry) -> io::Result<Vec<IsoEntry>> {
+        if !dir.is_dir {
+            return Err(invalid("not a directory"));
+        }
+        let data = ImageFs::read_all(self, dir, 16 * 1024 * 1024)?;
+        let mut out: Vec<IsoEntry> = Vec::new();
+        let mut continuing = false;
+        let mut off = 0usize;
+        while off < data.len() {
+            let len = data[off] as usize;
+            if len == 0 {
+                // Records never straddle sectors; skip the padding.
+                off = (off / SECTOR_SIZE as usize + 1) * SECTOR_SIZE as usize;
+                continue;
+            }
+            if off + len > data.len() {
+                break;
+            }
+            let rec = &data[off..off + len];
+            off += len;
+            let name_len = rec.get(32).copied().unwrap_or(0) as usize;
+            if name_len == 1 && matches!(rec.get(33), Some(0) | Some(1)) {
+                continue;
+            }
+            let Some(entry) = parse_record(rec, self.joliet) else {
+                continue;
+            };
+            let multi = rec[25] & FLAG_MULTI_EXTENT != 0;
+            match out.last_mut() {
+                Some(prev) if continuing && prev.name == entry.name => {
+                    prev.extents.extend(entry.extents);
+                }
+                _ => out.push(entry),
+            }
+            continuing = multi;
+        }
+        Ok(out)
+    }
+}
+
+impl ImageEntry for IsoEntry {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+
+    fn size(&self) -> u64 {
+        IsoEntry::size(self)
+    }
+
+    fn location(&self) -> u64 {
+        self.extents.first().map_or(0, |(lba, _)| *lba as u64)
+    }
+}
+
+impl<R: Read + Seek> ImageFs for IsoImage<R> {
+    type Entry = IsoEntry;
+
+    fn root(&self) -> IsoEntry {
+        self.root.clone()
+    }
+
+    fn read_dir(&mut self, dir: &IsoEntry) -> io::Result<Vec<IsoEntry>> {
+        IsoImage::read_dir(self, dir)
+    }
+
+    fn copy_to(
+        &mut self,
+        entry: &IsoEntry,
+        dst: &mut dyn Write,
+        on_progress: &mut dyn FnMut(u64),
+    ) -> io::Result<u64> {
+        let mut buf = vec![0u8; 1024 * 1024];
+        let mut copied = 0u64;
+        for &(lba, len) in &entry.extents {
+            self.reader
+                .seek(SeekFrom::Start(lba as u64 * SECTOR_SIZE))?;
+            let mut left = len as u64;
+            while left > 0 {
+                let n = left.min(buf.len() as u64) as usize;
+                self.reader.read_exact(&mut buf[..n])?;
+                dst.write_all(&buf[..n])?;
+                left -= n as u64;
+                copied += n as u64;
+                on_progress(n as u64);
+            }
+        }
+        Ok(copied)
+    }
+}
+
+fn parse_record(rec: &[u8], joliet: bool) -> Option<IsoEntry> {
+    if rec.len() < 34 {
+        return None;
+    }
+    let lba = u32::from_le_bytes(rec[2..6].try_into().ok()?);
+    let len = u32::from_le_bytes(rec[10..14].try_into().ok()?);
+    let flags = rec[25];
+    let name_len = rec[32] as usize;
+    let raw = rec.get(33..33 + name_len)?;
+    let name = if joliet {
+        let units: Vec<u16> = raw
+            .chunks_exact(2)
+            .map(|c| u16::from_be_bytes([c[0], c[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    } else {
+        String::from_utf8_lossy(raw).to_string()
+    };
+    Some(IsoEntry {
+        name: clean_name(&name),
+        is_dir: flags & FLAG_DIRECTORY != 0,
+        extents: vec![(lba, len)],
+    })
+}
+
+/// Strip the `;1` version suffix and a dangling `.` from ISO names
+fn clean_name(name: &str) -> String {
+    let base = name.split(';').next().unwrap_or(name);
+    base.strip_suffix('.').unwrap_or(base).to_string()
+}
+
+fn is_joliet_escape(esc: &[u8]) -> bool {
+    esc.windows(3)
+        .any(|w| w == b"%/@" || w == b"%/C" || w == b"%/E")
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn record(name: &[u8], lba: u32, len: u32, flags: u8) -> Vec<u8> {
+        let mut len_rec = 33 + name.len();
+        if len_rec % 2 == 1 {
+            len_rec += 1;
+        }
+        let mut r = vec![0u8; len_rec];
+        r[0] = len_rec as u8;
+        r[2..6].copy_from_slice(&lba.to_le_bytes());
+        r[6..10].copy_from_slice(&lba.to_be_bytes());
+        r[10..14].copy_from_slice(&len.to_le_bytes());
+        r[14..18].copy_from_slice(&len.to_be_bytes());
+        r[25] = flags;
+        r[32] = name.len() as u8;
+        r[33..33 + name.len()].copy_from_slice(name);
+        r
+    }
+
+    /// Build a tiny image: `/SOURCES/INSTALL.WIM` plus `/README.TXT`
+    pub(crate) fn sample_image(volume_id: &str) -> Vec<u8> {
+        let ss = SECTOR_SIZE as usize;
+        let mut img = vec![0u8; ss * 22];
+        let pvd = &mut img[16 * ss..17 * ss];
+        pvd[0] = 1;
+        pvd[1..6].copy_from_slice(b"CD001");
+        let mut vid = [b' '; 32];
+        vid[..volume_id.len()].copy_from_slice(volume_id.as_bytes());
+        pvd[40..72].copy_from_slice(&vid);
+        pvd[156..190].copy_from_slice(&record(&[0], 18, ss as u32, FLAG_DIRECTORY)[..34]);
+        let term = &mut img[17 * ss..18 * ss];
+        term[0] = 255;
+        term[1..6].copy_from_slice(b"CD001");
+
+        let mut root = Vec::new();
+        root.extend(record(&[0], 18, ss as u32, FLAG_DIRECTORY));
+        root.extend(record(&[1], 18, ss as u32, FLAG_DIRECTORY));
+        root.extend(record(b"SOURCES", 19, ss as u32, FLAG_DIRECTORY));
+        root.extend(record(b"README.TXT;1", 21, 5, 0));
+        img[18 * ss..18 * ss + root.len()].copy_from_slice(&root);
+
+        let mut sources = Vec::new();
+        sources.extend(record(&[0], 19, ss as u32, FLAG_DIRECTORY));
+        sources.extend(record(&[1], 18, ss as u32, FLAG_DIRECTORY));
+        sources.extend(record(b"INSTALL.WIM;1", 20, 4, 0));
+        img[19 * ss..19 * ss + sources.len()].copy_from_slice(&sources);
+
+        img[20 * ss..20 * ss + 4].copy_from_slice(b"WIM!");
+        img[21 * ss..21 * ss + 5].copy_from_slice(b"hello");
+        img
+    }
+
+    #[test]
+    fn reads_volume_id_and_paths() {
+        let mut iso = IsoImage::open(Cursor::new(sample_image("CCCOMA_X64FRE"))).unwrap();
+        assert_eq!(iso.volume_id(), "CCCOMA_X64FRE");
+
+        let wim = iso.lookup("/sources/install.wim").unwrap().unwrap();
+        assert_eq!(wim.size(), 4);
+        assert_eq!(iso.read_all(&wim, 1024).unwrap(), b"WIM!");
+
+        assert!(iso.lookup("/boot/grub").unwrap().is_none());
+        let names: Vec<String> = iso
+            .read_dir(&iso.root())
+            .unwrap()
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        assert_eq!(names, vec!["SOURCES", "README.TXT"]);
+    }
+
+    #[test]
+    fn rejects_non_iso_data() {
+        let data = vec![0u8; SECTOR_SIZE as usize * 20];
+        assert!(IsoImage::open(Cursor::new(data)).is_err());
+    }
+}
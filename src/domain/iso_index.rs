@@ -0,0 +1,450 @@
+//! In-memory ISO index kept fresh by inotify.
+//!
+//! The first scan walks the roots once, registering a watch on every
+//! directory it enters, and hands the whole index to the model. Queries
+//! are then filtered in memory. Watch events patch the index with
+//! [`IndexChange`]s; a new scan retires the previous watcher.
+//!
+//! The index is also written to `$XDG_CACHE_HOME/ferric/iso-index` so
+//! the list can be shown before the first walk finishes. The cache is
+//! tied to the scan options and ignored when they change.
+
+use crate::{
+    adapters::inotify::{self, Inotify},
+    app::msg::Msg,
+    config,
+    domain::{
+        distro::{Arch, Distro, DistroInfo},
+        iso::{self, IsoMeta, ScanOptions},
+        iso_walk::{self, WalkItem},
+        paths::IsoPath,
+        units::ByteSize,
+    },
+    event::Event,
+    log,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant, UNIX_EPOCH},
+};
+
+const CACHE_MAGIC: &str = "ferric-iso-index 1";
+
+/// How often the first walk reports what it has found so far
+const PARTIAL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Bumped by every scan; a watcher whose generation is stale stops
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Images that appeared or changed, and paths that went away
+#[derive(Debug, Clone, Default)]
+pub struct IndexChange {
+    pub added: Vec<IsoMeta>,
+    pub removed: Vec<IsoPath>,
+}
+
+impl IndexChange {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+
+    /// Apply to an index held elsewhere, e.g. in the model
+    pub fn apply(&self, index: &mut Vec<IsoMeta>) {
+        index.retain(|m| {
+            !self.removed.contains(&m.path) && !self.added.iter().any(|a| a.path == m.path)
+        });
+        index.extend(self.added.iter().cloned());
+    }
+}
+
+/// Scan the roots, streaming partial results, report the index, then watch for changes until the
+/// next scan starts or the receiver goes away.
+pub fn scan_and_watch(options: ScanOptions, tx: tokio::sync::mpsc::UnboundedSender<Event>) {
+    let generation = GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    let send = |msg: Msg| tx.send(Event::App(msg)).is_ok();
+
+    if let Some(cached) = load_cache(&options) {
+        iso::remember(&cached);
+        if !send(Msg::IsoCacheLoaded(cached)) {
+            return;
+        }
+    }
+    let mut watcher = Watcher::new(options, generation);
+    let mut partial = |batch: Vec<IsoMeta>| {
+        if !batch.is_empty() {
+            send(Msg::IsoResultsPartial(batch));
+        }
+    };
+    let Some(entries) = watcher.scan_all(&mut partial) else {
+        return;
+    };
+    log::info(
+        "scan",
+        format_args!(
+            "{} images, {} directories watched",
+            entries.len(),
+            watcher.dirs.len()
+        ),
+    );
+    save_cache(&watcher.options, &entries);
+    if !send(Msg::IsoResults(entries)) || watcher.inotify.is_none() {
+        return;
+    }
+    while !watcher.is_stale() {
+        let change = match watcher.poll(Duration::from_millis(500)) {
+            Ok(change) => change,
+            Err(e) => {
+                log::warn("scan", format_args!("no longer watching: {}", e));
+                return;
+            }
+        };
+        if change.is_empty() {
+            continue;
+        }
+        save_cache(&watcher.options, &watcher.entries());
+        if !send(Msg::IsoIndexChanged(change)) {
+            return;
+        }
+    }
+}
+
+struct Watcher {
+    options: ScanOptions,
+    /// The scan this watcher belongs to; see [`GENERATION`]
+    generation: u64,
+    inotify: Option<Inotify>,
+    /// Watch descriptor to (directory, depth below its root)
+    dirs: HashMap<i32, (PathBuf, usize)>,
+    index: HashMap<IsoPath, IsoMeta>,
+}
+
+impl Watcher {
+    fn new(options: ScanOptions, generation: u64) -> Self {
+        Self {
+            options,
+            generation,
+            inotify: Inotify::new()
+                .map_err(|e| log::warn("scan", format_args!("not watching for changes: {}", e)))
+                .ok(),
+            dirs: HashMap::new(),
+            index: HashMap::new(),
+        }
+    }
+
+    fn is_stale(&self) -> bool {
+        GENERATION.load(Ordering::SeqCst) != self.generation
+    }
+
+    fn entries(&self) -> Vec<IsoMeta> {
+        self.index.values().cloned().collect()
+    }
+
+    /// Walk every root, passing batches of new images to `on_partial`
+    /// as they turn up; `None` if a newer scan cancelled this one
+    fn scan_all(&mut self, on_partial: &mut dyn FnMut(Vec<IsoMeta>)) -> Option<Vec<IsoMeta>> {
+        let starts = self
+            .options
+            .roots
+            .iter()
+            .filter(|r| r.exists())
+            .map(|r| (r.as_path().to_path_buf(), 0))
+            .collect();
+        self.walk(starts, on_partial)?;
+        let mut out = self.entries();
+        iso::sort_newest_first(&mut out);
+        Some(out)
+    }
+
+    /// Walk and watch a directory tree; returns the images found
+    fn scan_dir(&mut self, dir: &Path, depth: usize) -> Vec<IsoMeta> {
+        self.walk(vec![(dir.to_path_buf(), depth)], &mut |_| {})
+            .unwrap_or_default()
+    }
+
+    fn walk(
+        &mut self,
+        starts: Vec<(PathBuf, usize)>,
+        on_partial: &mut dyn FnMut(Vec<IsoMeta>),
+    ) -> Option<Vec<IsoMeta>> {
+        let generation = self.generation;
+        let cancelled = move || GENERATION.load(Ordering::SeqCst) != generation;
+        let (inotify, dirs, index) = (&self.inotify, &mut self.dirs, &mut self.index);
+        let mut found = Vec::new();
+        let mut batch = Vec::new();
+        let mut flushed = Instant::now();
+        let complete = iso_walk::walk(starts, &self.options, &cancelled, &mut |item| match item {
+            WalkItem::Dir(path, depth) => {
+                // Out of watches (ENOSPC) just means this part stays static
+                if let Some(wd) = inotify
+                    .as_ref()
+                    .and_then(|ino| ino.add_watch(&path, inotify::DIR_EVENTS).ok())
+                {
+                    dirs.insert(wd, (path, depth));
+                }
+            }
+            WalkItem::Image(meta) => {
+                if index.insert(meta.path.clone(), meta.clone()).is_none() {
+                    batch.push(meta.clone());
+                }
+                found.push(meta);
+                if flushed.elapsed() >= PARTIAL_INTERVAL {
+                    on_partial(std::mem::take(&mut batch));
+                    flushed = Instant::now();
+                }
+            }
+        });
+        complete.then_some(found)
+    }
+
+    fn poll(&mut self, timeout: Duration) -> std::io::Result<IndexChange> {
+        let Some(inotify) = &self.inotify else {
+            return Ok(IndexChange::default());
+        };
+        if !inotify.wait(timeout)? {
+            return Ok(IndexChange::default());
+        }
+        let events = inotify.read_events()?;
+        let mut change = IndexChange::default();
+        for event in events {
+            if event.mask & inotify::IN_Q_OVERFLOW != 0 {
+                // Lost events: diff a fresh walk against what we had
+                let before: HashSet<IsoPath> = self.index.keys().cloned().collect();
+                self.index.clear();
+                change.added = self.scan_all(&mut |_| {}).unwrap_or_default();
+                change.removed = before
+                    .into_iter()
+                    .filter(|p| !self.index.contains_key(p))
+                    .collect();
+                return Ok(change);
+            }
+            if event.mask & inotify::IN_IGNORED != 0 {
+                self.dirs.remove(&event.wd);
+                continue;
+            }
+            let (Some((dir, depth)), Some(name)) = (self.dirs.get(&event.wd).cloned(), event.name)
+            else {
+                continue;
+            };
+            let path = dir.join(name);
+            let appeared = event.mask
+                & (inotify::IN_CREATE | inotify::IN_MOVED_TO | inotify::IN_CLOSE_WRITE)
+                != 0;
+            if event.mask & inotify::IN_ISDIR != 0 {
+                if appeared && !iso::is_hidden(&path) {
+                    change.added.extend(self.scan_dir(&path, depth + 1));
+                } else if !appeared {
+                    let gone: Vec<IsoPath> = self
+                        .index
+                        .keys()
+                        .filter(|p| p.as_path().starts_with(&path))
+                        .cloned()
+                        .collect();
+                    for p in gone {
+                        self.index.remove(&p);
+                        change.removed.push(p);
+                    }
+                }
+                continue;
+            }
+            let key = IsoPath::from(path.clone());
+            match appeared.then(|| iso::probe(&path, &self.options)).flatten() {
+                Some(meta) => {
+                    self.index.insert(key, meta.clone());
+                    change.added.retain(|m| m.path != meta.path);
+                    change.added.push(meta);
+                }
+                None => {
+                    if self.index.remove(&key).is_some() {
+                        change.added.retain(|m| m.path != key);
+                        change.removed.push(key);
+                    }
+                }
+            }
+        }
+        Ok(change)
+    }
+}
+
+fn cache_path() -> Option<PathBuf> {
+    config::xdg_dir("XDG_CACHE_HOME", ".cache").map(|d| d.join("ferric").join("iso-index"))
+}
+
+/// Identifies the options a cache was built with
+fn fingerprint(options: &ScanOptions) -> String {
+    format!(
+        "{:?}|{:?}|{}|{}|{:?}",
+        options.roots, options.exclude, options.max_depth, options.min_size, options.extensions
+    )
+}
+
+fn load_cache(options: &ScanOptions) -> Option<Vec<IsoMeta>> {
+    let text = fs::read_to_string(cache_path()?).ok()?;
+    parse_cache(&text, options)
+}
+
+fn save_cache(options: &ScanOptions, entries: &[IsoMeta]) {
+    let Some(path) = cache_path() else {
+        return;
+    };
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    // Write then rename so a crash never leaves half a cache behind
+    let tmp = path.with_extension("tmp");
+    let saved =
+        fs::write(&tmp, render_cache(options, entries)).and_then(|()| fs::rename(&tmp, &path));
+    if let Err(e) = saved {
+        log::debug("scan", format_args!("{}: {}", path.display(), e));
+    }
+}
+
+/// One tab-separated line per image after a header and the fingerprint
+fn render_cache(options: &ScanOptions, entries: &[IsoMeta]) -> String {
+    let mut out = format!(
+        "{}\n{}\n",
+        CACHE_MAGIC,
+        fingerprint(options).replace('\n', " ")
+    );
+    for m in entries {
+        let path = m.path.to_string();
+        let label = m.distro.label.clone().unwrap_or_default();
+        let version = m.distro.version.clone().unwrap_or_default();
+        if [&path, &label, &version]
+            .iter()
+            .any(|s| s.contains(['\t', '\n']))
+        {
+            continue;
+        }
+        let mtime = m
+            .modified
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs().to_string())
+            .unwrap_or_default();
+        out.push_str(&format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            path,
+            m.size.as_u64(),
+            mtime,
+            u8::from(m.hybrid),
+            m.distro.distro.badge(),
+            version,
+            m.distro.arch.map(|a| a.as_str()).unwrap_or(""),
+            label,
+        ));
+    }
+    out
+}
+
+fn parse_cache(text: &str, options: &ScanOptions) -> Option<Vec<IsoMeta>> {
+    let mut lines = text.lines();
+    if lines.next()? != CACHE_MAGIC || lines.next()? != fingerprint(options).replace('\n', " ") {
+        return None;
+    }
+    let non_empty = |s: &str| (!s.is_empty()).then(|| s.to_string());
+    let entries = lines
+        .filter_map(|line| {
+            let f: Vec<&str> = line.split('\t').collect();
+            let [path, size, mtime, hybrid, distro, version, arch, label] = f[..] else {
+                return None;
+            };
+            Some(IsoMeta {
+                path: IsoPath::from(PathBuf::from(path)),
+                size: ByteSize::new(size.parse().ok()?),
+                modified: mtime
+                    .parse()
+                    .ok()
+                    .map(|s| UNIX_EPOCH + Duration::from_secs(s)),
+                hybrid: hybrid == "1",
+                distro: DistroInfo {
+                    distro: Distro::parse(distro).unwrap_or_default(),
+                    version: non_empty(version),
+                    arch: Arch::parse(arch),
+                    label: non_empty(label),
+                },
+            })
+        })
+        .collect();
+    Some(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::paths::DirPath;
+
+    fn options(root: &Path) -> ScanOptions {
+        ScanOptions {
+            roots: vec![DirPath::from(root.to_path_buf())],
+            exclude: Vec::new(),
+            max_depth: 3,
+            min_size: 1,
+            extensions: vec!["iso".to_string()],
+        }
+    }
+
+    #[test]
+    fn cache_round_trips_and_checks_options() {
+        let opts = options(Path::new("/srv/images"));
+        let entry = IsoMeta {
+            path: IsoPath::from(PathBuf::from("/srv/images/ubuntu.iso")),
+            size: ByteSize::new(42),
+            modified: Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+            distro: DistroInfo {
+                distro: Distro::Ubuntu,
+                version: Some("24.04".to_string()),
+                arch: Some(Arch::X86_64),
+                label: None,
+            },
+            hybrid: true,
+        };
+        let text = render_cache(&opts, std::slice::from_ref(&entry));
+        let loaded = parse_cache(&text, &opts).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].path, entry.path);
+        assert_eq!(loaded[0].modified, entry.modified);
+        assert_eq!(loaded[0].distro, entry.distro);
+        assert!(loaded[0].hybrid);
+
+        let other = options(Path::new("/mnt/nfs"));
+        assert!(parse_cache(&text, &other).is_none());
+    }
+
+    #[test]
+    fn watcher_sees_added_and_removed_images() {
+        let root = std::env::temp_dir().join(format!("ferric-index-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("old.iso"), b"x").unwrap();
+        let mut watcher = Watcher::new(options(&root), GENERATION.load(Ordering::SeqCst));
+        assert_eq!(watcher.scan_all(&mut |_| {}).unwrap().len(), 1);
+
+        fs::create_dir(root.join("sub")).unwrap();
+        let change = watcher.poll(Duration::from_secs(2)).unwrap();
+        assert!(change.is_empty());
+        fs::write(root.join("sub").join("new.iso"), b"x").unwrap();
+        fs::remove_file(root.join("old.iso")).unwrap();
+
+        let mut index = watcher.entries();
+        let mut added = Vec::new();
+        let mut removed = Vec::new();
+        for _ in 0..10 {
+            let change = watcher.poll(Duration::from_millis(200)).unwrap();
+            change.apply(&mut index);
+            added.extend(change.added);
+            removed.extend(change.removed);
+            if !added.is_empty() && !removed.is_empty() {
+                break;
+            }
+        }
+        assert!(
+            added
+                .iter()
+                .any(|m| m.path.as_path().ends_with("sub/new.iso"))
+        );
+        assert!(removed.iter().any(|p| p.as_path().ends_with("old.iso")));
+        assert_eq!(index.len(), 1);
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,197 @@
+//! Parallel directory walk for the ISO scan.
+//!
+//! A pool of worker threads shares one queue of directories. Each worker
+//! lists a directory, queues its subdirectories and probes its files, so
+//! slow `read_dir` calls (NFS, SSHFS, cold caches) overlap instead of
+//! adding up. Everything found is funnelled back to the calling thread,
+//! which sees it as soon as it is found.
+
+use crate::domain::iso::{self, IsoMeta, ScanOptions};
+use std::{
+    collections::VecDeque,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Condvar, Mutex, mpsc},
+    thread,
+};
+
+/// Upper bound on worker threads; the walk is I/O bound, not CPU bound
+const MAX_WORKERS: usize = 16;
+
+#[derive(Debug, Clone)]
+pub enum WalkItem {
+    /// A directory that was listed, with its depth below the root
+    Dir(PathBuf, usize),
+    Image(IsoMeta),
+}
+
+/// Walk from `starts` (directory, depth) in parallel, handing every item
+/// to `on_item` on the calling thread. Workers stop descending once
+/// `cancelled` returns true; the return value is false in that case.
+pub fn walk(
+    starts: Vec<(PathBuf, usize)>,
+    options: &ScanOptions,
+    cancelled: &(dyn Fn() -> bool + Sync),
+    on_item: &mut dyn FnMut(WalkItem),
+) -> bool {
+    let queue = DirQueue::new(starts);
+    let (tx, rx) = mpsc::channel();
+    thread::scope(|s| {
+        for _ in 0..workers() {
+            let tx = tx.clone();
+            let queue = &queue;
+            s.spawn(move || {
+                while let Some((dir, depth)) = queue.pop() {
+                    if !cancelled() {
+                        visit(&dir, depth, options, queue, &tx);
+                    }
+                    queue.done();
+                }
+            });
+        }
+        drop(tx);
+        for item in rx {
+            on_item(item);
+        }
+    });
+    !cancelled()
+}
+
+fn workers() -> usize {
+    thread::available_parallelism()
+        .map(|n| n.get() * 2)
+        .unwrap_or(4)
+        .min(MAX_WORKERS)
+}
+
+/// List one directory: queue subdirectories, report images
+fn visit(
+    dir: &Path,
+    depth: usize,
+    options: &ScanOptions,
+    queue: &DirQueue,
+    tx: &mpsc::Sender<WalkItem>,
+) {
+    if depth > options.max_depth || options.is_excluded(dir) {
+        return;
+    }
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let _ = tx.send(WalkItem::Dir(dir.to_path_buf(), depth));
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_symlink() {
+            continue;
+        }
+        if file_type.is_dir() {
+            if !iso::is_hidden(&path) {
+                queue.push(path, depth + 1);
+            }
+        } else if file_type.is_file()
+            && let Some(meta) = iso::probe(&path, options)
+        {
+            let _ = tx.send(WalkItem::Image(meta));
+        }
+    }
+}
+
+/// Work queue that knows when the walk is over: empty and nobody busy
+struct DirQueue {
+    state: Mutex<QueueState>,
+    ready: Condvar,
+}
+
+struct QueueState {
+    dirs: VecDeque<(PathBuf, usize)>,
+    busy: usize,
+}
+
+impl DirQueue {
+    fn new(starts: Vec<(PathBuf, usize)>) -> Self {
+        Self {
+            state: Mutex::new(QueueState {
+                dirs: starts.into(),
+                busy: 0,
+            }),
+            ready: Condvar::new(),
+        }
+    }
+
+    fn push(&self, dir: PathBuf, depth: usize) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.dirs.push_back((dir, depth));
+        self.ready.notify_one();
+    }
+
+    /// Next directory, waiting while others may still queue more;
+    /// `None` once the walk is complete
+    fn pop(&self) -> Option<(PathBuf, usize)> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            if let Some(next) = state.dirs.pop_front() {
+                state.busy += 1;
+                return Some(next);
+            }
+            if state.busy == 0 {
+                return None;
+            }
+            state = self.ready.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    fn done(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.busy -= 1;
+        if state.busy == 0 && state.dirs.is_empty() {
+            self.ready.notify_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::paths::DirPath;
+
+    #[test]
+    fn walks_every_level_and_stops_when_cancelled() {
+        let root = std::env::temp_dir().join(format!("ferric-walk-{}", std::process::id()));
+        for i in 0..4 {
+            let dir = root.join(format!("d{}", i)).join("deeper");
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join(format!("img{}.iso", i)), b"x").unwrap();
+        }
+        let options = ScanOptions {
+            roots: vec![DirPath::from(root.clone())],
+            exclude: Vec::new(),
+            max_depth: 3,
+            min_size: 1,
+            extensions: vec!["iso".to_string()],
+        };
+
+        let (mut dirs, mut images) = (0, 0);
+        let complete = walk(
+            vec![(root.clone(), 0)],
+            &options,
+            &|| false,
+            &mut |item| match item {
+                WalkItem::Dir(..) => dirs += 1,
+                WalkItem::Image(_) => images += 1,
+            },
+        );
+        assert!(complete);
+        assert_eq!((dirs, images), (9, 4));
+
+        let mut seen = 0;
+        let complete = walk(vec![(root.clone(), 0)], &options, &|| true, &mut |_| {
+            seen += 1
+        });
+        assert!(!complete);
+        assert_eq!(seen, 0);
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -1,10 +1,28 @@
+pub mod batch;
+pub mod browse;
 pub mod device;
+pub mod distro;
+pub mod fuzzy;
+pub mod history;
+pub mod image_fs;
 pub mod iso;
+pub mod iso9660;
+pub mod iso_index;
+pub mod iso_walk;
+pub mod multiboot;
+pub mod partition;
 pub mod paths;
+pub mod persistence;
+pub mod report;
+pub mod sha256;
+pub mod simulate;
+pub mod udf;
 pub mod units;
+pub mod windows;
 pub mod writer;
 
-pub use device::Device;
+pub use device::{Device, DeviceAccess};
+pub use distro::{Arch, Distro, DistroInfo};
 pub use iso::IsoMeta;
 pub use paths::{DevicePath, IsoPath};
 pub use units::{ByteSize, Percent, Throughput};
@@ -0,0 +1,356 @@
+//! Multi-ISO boot drive mode.
+//!
+//! The stick is prepared once with a large exFAT data partition holding
+//! the images under `/isos` and a small EFI partition with GRUB. Adding
+//! images later only copies files and rewrites the GRUB menu, which
+//! loop-mounts each ISO and hands over to its own `loopback.cfg`.
+//!
+//! The data partition comes first so that systems which only show the
+//! first partition of a removable drive still see the ISOs.
+
+use crate::{
+    adapters::platform,
+    app::{effects::Mounter, msg::Msg},
+    domain::{
+        distro::{self, Distro, DistroInfo},
+        partition::{self, ALIGN, PartKind, PartitionScheme, SECTOR},
+        paths::{DevicePath, IsoPath},
+        writer::{self, MountGuard, Progress},
+    },
+    event::Event,
+};
+use std::{
+    fs,
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+pub const DATA_LABEL: &str = "FERRIC_DATA";
+pub const BOOT_LABEL: &str = "FERRIC_BOOT";
+/// Directory on the data partition that holds the images
+pub const ISO_DIR: &str = "isos";
+/// 128 MiB is plenty for GRUB's EFI and BIOS modules
+const BOOT_SECTORS: u64 = 128 * 1024 * 1024 / SECTOR;
+
+pub fn write_multiboot(
+    isos: Vec<IsoPath>,
+    device_path: DevicePath,
+    mounter: &dyn Mounter,
+    tx: tokio::sync::mpsc::UnboundedSender<Event>,
+) {
+    let result = run(&isos, &device_path, mounter, &tx);
+    let _: Result<_, _> = tx.send(Event::App(Msg::WriteFinished(result)));
+}
+
+fn run(
+    isos: &[IsoPath],
+    device_path: &DevicePath,
+    mounter: &dyn Mounter,
+    tx: &tokio::sync::mpsc::UnboundedSender<Event>,
+) -> Result<(), String> {
+    let mut total = 0u64;
+    for iso in isos {
+        total += fs::metadata(iso.as_path())
+            .map_err(|e| format!("Failed to stat {}: {}", iso, e))?
+            .len();
+    }
+    let _: Result<_, _> = tx.send(Event::App(Msg::WriteStarted { total }));
+    writer::warn(
+        tx,
+        device_path,
+        mounter.unmount_partitions_of(device_path.as_path()),
+    );
+
+    let data_part = platform::partition_path(device_path.as_path(), 1);
+    let boot_part = platform::partition_path(device_path.as_path(), 2);
+    if !is_prepared(device_path.as_path()) {
+        prepare(device_path, &data_part, &boot_part, mounter, tx)?;
+    }
+
+    let data = MountGuard::mount(&data_part, "data")?;
+    let iso_dir = data.path().join(ISO_DIR);
+    fs::create_dir_all(&iso_dir)
+        .map_err(|e| format!("Failed to create {}: {}", iso_dir.display(), e))?;
+    let mut progress = Progress::new(total, tx);
+    for iso in isos {
+        let Some(name) = iso.as_path().file_name() else {
+            continue;
+        };
+        writer::copy_file(iso.as_path(), &iso_dir.join(name), &mut progress)?;
+    }
+
+    let entries = installed_isos(&iso_dir)?;
+    writer::warn(tx, device_path, data.unmount());
+
+    let boot = MountGuard::mount(&boot_part, "boot")?;
+    let cfg_dir = boot.path().join("boot").join("grub");
+    fs::create_dir_all(&cfg_dir)
+        .map_err(|e| format!("Failed to create {}: {}", cfg_dir.display(), e))?;
+    fs::write(cfg_dir.join("grub.cfg"), grub_config(&entries))
+        .map_err(|e| format!("Failed to write grub.cfg: {}", e))?;
+    writer::warn(tx, device_path, boot.unmount());
+
+    writer::warn(tx, device_path, mounter.partprobe(device_path.as_path()));
+    Ok(())
+}
+
+/// True if the drive's own partition table is the one [`prepare`] lays
+/// down, i.e. an earlier run prepared it and it must not be wiped again
+pub fn is_prepared(device_path: &Path) -> bool {
+    fs::File::open(device_path)
+        .and_then(|mut dev| prepared(&mut dev))
+        .unwrap_or(false)
+}
+
+/// An exFAT data partition at the first aligned sector followed by an
+/// EFI partition whose FAT label is [`BOOT_LABEL`], and nothing else
+fn prepared<D: Read + Seek>(dev: &mut D) -> io::Result<bool> {
+    let [Some(data), Some(boot), None, None] = partition::mbr_entries(dev)?[..] else {
+        return Ok(false);
+    };
+    if data.ptype != PartKind::Ntfs.mbr_type() || data.start != ALIGN {
+        return Ok(false);
+    }
+    if boot.ptype != PartKind::EfiSystem.mbr_type() || boot.start < data.start + data.sectors {
+        return Ok(false);
+    }
+    let mut sector = [0u8; SECTOR as usize];
+    dev.seek(SeekFrom::Start(data.start * SECTOR))?;
+    dev.read_exact(&mut sector)?;
+    if &sector[3..11] != b"EXFAT   " {
+        return Ok(false);
+    }
+    // FAT32 keeps the volume label in the boot sector, padded with spaces
+    dev.seek(SeekFrom::Start(boot.start * SECTOR))?;
+    dev.read_exact(&mut sector)?;
+    Ok(sector[71..82] == *format!("{:<11}", BOOT_LABEL).as_bytes())
+}
+
+/// Partition and format a blank drive and install GRUB on it
+fn prepare(
+    device_path: &DevicePath,
+    data_part: &Path,
+    boot_part: &Path,
+    mounter: &dyn Mounter,
+    tx: &tokio::sync::mpsc::UnboundedSender<Event>,
+) -> Result<(), String> {
+    let grub = ["grub-install", "grub2-install"]
+        .into_iter()
+        .find(|c| platform::has_command(c))
+        .ok_or_else(|| "grub-install is required to prepare a multi-ISO drive".to_string())?;
+
+    let disk_bytes = platform::device_size(device_path.as_path())
+        .map_err(|e| format!("Failed to size device {}: {}", device_path, e))?;
+    let disk_sectors = disk_bytes / SECTOR;
+    let data_sectors = disk_sectors.saturating_sub(3 * ALIGN + BOOT_SECTORS) / ALIGN * ALIGN;
+    let parts = partition::layout(
+        PartitionScheme::Mbr,
+        disk_sectors,
+        &[
+            (PartKind::Ntfs, Some(data_sectors), DATA_LABEL),
+            (PartKind::EfiSystem, None, BOOT_LABEL),
+        ],
+    );
+    let mut dev = fs::OpenOptions::new()
+        .write(true)
+        .open(device_path.as_path())
+        .map_err(|e| format!("Failed to open device {}: {}", device_path, e))?;
+    partition::write_table(&mut dev, PartitionScheme::Mbr, disk_sectors, &parts)
+        .map_err(|e| format!("Failed to write partition table: {}", e))?;
+    dev.sync_all()
+        .map_err(|e| format!("sync_all error: {}", e))?;
+    drop(dev);
+    writer::warn(tx, device_path, mounter.partprobe(device_path.as_path()));
+
+    for part in [data_part, boot_part] {
+        if !platform::wait_for_node(part, Duration::from_secs(10)) {
+            return Err(format!("Partition {} did not appear", part.display()));
+        }
+    }
+    platform::run_tool(
+        "mkfs.exfat",
+        &["-n", DATA_LABEL, &data_part.to_string_lossy()],
+    )?;
+    platform::run_tool(
+        "mkfs.vfat",
+        &["-F", "32", "-n", BOOT_LABEL, &boot_part.to_string_lossy()],
+    )?;
+
+    let boot = MountGuard::mount(boot_part, "boot")?;
+    let efi_dir = boot.path().to_string_lossy().to_string();
+    let boot_dir = boot.path().join("boot").to_string_lossy().to_string();
+    platform::run_tool(
+        grub,
+        &[
+            "--target=x86_64-efi",
+            "--removable",
+            "--no-nvram",
+            &format!("--efi-directory={}", efi_dir),
+            &format!("--boot-directory={}", boot_dir),
+        ],
+    )?;
+    // Legacy BIOS support only when the host has the i386-pc modules
+    if Path::new("/usr/lib/grub/i386-pc").is_dir() {
+        platform::run_tool(
+            grub,
+            &[
+                "--target=i386-pc",
+                &format!("--boot-directory={}", boot_dir),
+                &device_path.to_string(),
+            ],
+        )?;
+    }
+    writer::warn(tx, device_path, boot.unmount());
+    Ok(())
+}
+
+/// Images currently on the drive, sorted by file name
+fn installed_isos(iso_dir: &Path) -> Result<Vec<(String, DistroInfo)>, String> {
+    let mut out: Vec<(String, DistroInfo)> = fs::read_dir(iso_dir)
+        .map_err(|e| format!("Failed to list {}: {}", iso_dir.display(), e))?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| {
+            p.extension()
+                .and_then(|x| x.to_str())
+                .is_some_and(|x| x.eq_ignore_ascii_case("iso"))
+        })
+        .filter_map(|p: PathBuf| {
+            let name = p.file_name()?.to_str()?.to_string();
+            Some((name, distro::recognize(&p)))
+        })
+        .collect();
+    out.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(out)
+}
+
+/// Render the GRUB menu for the images in `/isos`.
+///
+/// Each entry loop-mounts the ISO and defers to the image's own
+/// `loopback.cfg`, falling back to its regular `grub.cfg`.
+pub fn grub_config(entries: &[(String, DistroInfo)]) -> String {
+    let mut out = String::new();
+    out.push_str("# Generated by ferric; rewritten whenever images are added.\n");
+    out.push_str("insmod part_msdos\ninsmod exfat\ninsmod loopback\ninsmod iso9660\n");
+    out.push_str(&format!(
+        "search --no-floppy --set=isodev --label {}\n",
+        DATA_LABEL
+    ));
+    out.push_str("set timeout=10\n");
+    for (name, info) in entries {
+        if info.distro == Distro::Windows {
+            out.push_str(&format!(
+                "\n# {}: Windows installers cannot boot from a loopback image\n",
+                name
+            ));
+            continue;
+        }
+        let iso_path = format!("/{}/{}", ISO_DIR, name);
+        out.push_str(&format!(
+            "\nmenuentry {} {{\n",
+            quote(&entry_title(name, info))
+        ));
+        out.push_str(&format!("    set iso_path={}\n", quote(&iso_path)));
+        out.push_str("    export iso_path\n");
+        out.push_str("    loopback loop ($isodev)$iso_path\n");
+        out.push_str("    set root=(loop)\n");
+        out.push_str("    if [ -f /boot/grub/loopback.cfg ]; then\n");
+        out.push_str("        configfile /boot/grub/loopback.cfg\n");
+        out.push_str("    else\n");
+        out.push_str("        configfile /boot/grub/grub.cfg\n");
+        out.push_str("    fi\n");
+        out.push_str("}\n");
+    }
+    out
+}
+
+fn entry_title(name: &str, info: &DistroInfo) -> String {
+    if !info.is_known() {
+        return name.to_string();
+    }
+    let mut title = info.distro.to_string();
+    if let Some(ref version) = info.version {
+        title.push(' ');
+        title.push_str(version);
+    }
+    if let Some(arch) = info.arch {
+        title.push_str(&format!(" ({})", arch));
+    }
+    format!("{} - {}", title, name)
+}
+
+/// Single-quote for GRUB's shell-like parser; nothing expands inside
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::distro::Arch;
+    use std::io::{Cursor, Write};
+
+    #[test]
+    fn only_our_own_layout_counts_as_prepared() {
+        const DISK: u64 = 512 * 1024 * 1024 / SECTOR;
+        let parts = partition::layout(
+            PartitionScheme::Mbr,
+            DISK,
+            &[
+                (
+                    PartKind::Ntfs,
+                    Some(DISK - 3 * ALIGN - BOOT_SECTORS),
+                    DATA_LABEL,
+                ),
+                (PartKind::EfiSystem, None, BOOT_LABEL),
+            ],
+        );
+        let mut disk = Cursor::new(vec![0u8; (DISK * SECTOR) as usize]);
+        partition::write_table(&mut disk, PartitionScheme::Mbr, DISK, &parts).unwrap();
+        assert!(!prepared(&mut disk).unwrap(), "not formatted yet");
+
+        disk.seek(SeekFrom::Start(parts[0].start * SECTOR + 3))
+            .unwrap();
+        disk.write_all(b"EXFAT   ").unwrap();
+        disk.seek(SeekFrom::Start(parts[1].start * SECTOR + 71))
+            .unwrap();
+        disk.write_all(b"OTHER_BOOT ").unwrap();
+        assert!(
+            !prepared(&mut disk).unwrap(),
+            "someone else's EFI partition"
+        );
+
+        disk.seek(SeekFrom::Start(parts[1].start * SECTOR + 71))
+            .unwrap();
+        disk.write_all(b"FERRIC_BOOT").unwrap();
+        assert!(prepared(&mut disk).unwrap());
+    }
+
+    #[test]
+    fn menu_has_one_entry_per_linux_image() {
+        let ubuntu = DistroInfo {
+            distro: Distro::Ubuntu,
+            version: Some("24.04".to_string()),
+            arch: Some(Arch::X86_64),
+            label: None,
+        };
+        let windows = DistroInfo {
+            distro: Distro::Windows,
+            ..Default::default()
+        };
+        let cfg = grub_config(&[
+            ("ubuntu-24.04-desktop-amd64.iso".to_string(), ubuntu),
+            ("Win11.iso".to_string(), windows),
+            ("tool's.iso".to_string(), DistroInfo::default()),
+        ]);
+        assert!(cfg.contains("--label FERRIC_DATA"));
+        assert!(
+            cfg.contains("menuentry 'Ubuntu 24.04 (x86_64) - ubuntu-24.04-desktop-amd64.iso' {")
+        );
+        assert!(cfg.contains("set iso_path='/isos/ubuntu-24.04-desktop-amd64.iso'"));
+        assert!(cfg.contains("menuentry 'tool'\\''s.iso' {"));
+        assert_eq!(cfg.matches("menuentry").count(), 2);
+        assert!(cfg.contains("# Win11.iso: Windows installers"));
+    }
+}
@@ -0,0 +1,607 @@
+//! Partition table writer for MBR and GPT.
+//!
+//! Only what ferric needs to lay out a fresh stick: a handful of
+//! partitions aligned to 1 MiB, written over whatever was there before.
+//! Sizes and offsets are in 512-byte logical sectors.
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+pub const SECTOR: u64 = 512;
+/// First usable sector for 1 MiB alignment
+pub const ALIGN: u64 = 2048;
+
+const GPT_ENTRIES: u64 = 128;
+const GPT_ENTRY_SIZE: u64 = 128;
+/// Sectors taken by the partition entry array
+const GPT_ARRAY_SECTORS: u64 = GPT_ENTRIES * GPT_ENTRY_SIZE / SECTOR;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PartitionScheme {
+    #[default]
+    Mbr,
+    Gpt,
+}
+
+impl std::fmt::Display for PartitionScheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PartitionScheme::Mbr => f.write_str("MBR"),
+            PartitionScheme::Gpt => f.write_str("GPT"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartKind {
+    Fat32,
+    Ntfs,
+    EfiSystem,
+    Linux,
+}
+
+impl PartKind {
+    pub fn mbr_type(self) -> u8 {
+        match self {
+            PartKind::Fat32 => 0x0C,
+            PartKind::Ntfs => 0x07,
+            PartKind::EfiSystem => 0xEF,
+            PartKind::Linux => 0x83,
+        }
+    }
+
+    /// Type GUID in on-disk (mixed endian) byte order
+    fn gpt_type(self) -> [u8; 16] {
+        match self {
+            // EBD0A0A2-B9E5-4433-87C0-68B6B72699C7, Microsoft basic data
+            PartKind::Fat32 | PartKind::Ntfs => [
+                0xA2, 0xA0, 0xD0, 0xEB, 0xE5, 0xB9, 0x33, 0x44, 0x87, 0xC0, 0x68, 0xB6, 0xB7, 0x26,
+                0x99, 0xC7,
+            ],
+            // C12A7328-F81F-11D2-BA4B-00A0C93EC93B
+            PartKind::EfiSystem => [
+                0x28, 0x73, 0x2A, 0xC1, 0x1F, 0xF8, 0xD2, 0x11, 0xBA, 0x4B, 0x00, 0xA0, 0xC9, 0x3E,
+                0xC9, 0x3B,
+            ],
+            // 0FC63DAF-8483-4772-8E79-3D69D8477DE4
+            PartKind::Linux => [
+                0xAF, 0x3D, 0xC6, 0x0F, 0x83, 0x84, 0x72, 0x47, 0x8E, 0x79, 0x3D, 0x69, 0xD8, 0x47,
+                0x7D, 0xE4,
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartitionSpec {
+    pub start: u64,
+    pub sectors: u64,
+    pub kind: PartKind,
+    pub bootable: bool,
+    pub name: String,
+}
+
+/// Lay out partitions back to back from the first aligned sector.
+///
+/// A `None` size takes the rest of the disk; only the last entry may be `None`.
+pub fn layout(
+    scheme: PartitionScheme,
+    disk_sectors: u64,
+    parts: &[(PartKind, Option<u64>, &str)],
+) -> Vec<PartitionSpec> {
+    let end = last_usable(scheme, disk_sectors);
+    let mut next = ALIGN;
+    let mut out = Vec::new();
+    for (i, (kind, size, name)) in parts.iter().enumerate() {
+        let sectors = match size {
+            Some(s) => *s,
+            None => (end + 1).saturating_sub(next) / ALIGN * ALIGN,
+        };
+        out.push(PartitionSpec {
+            start: next,
+            sectors,
+            kind: *kind,
+            bootable: i == 0,
+            name: name.to_string(),
+        });
+        next = (next + sectors).div_ceil(ALIGN) * ALIGN;
+    }
+    out
+}
+
+fn last_usable(scheme: PartitionScheme, disk_sectors: u64) -> u64 {
+    match scheme {
+        PartitionScheme::Mbr => disk_sectors.saturating_sub(1),
+        PartitionScheme::Gpt => disk_sectors.saturating_sub(GPT_ARRAY_SECTORS + 2),
+    }
+}
+
+/// Write a fresh partition table, wiping the old one
+pub fn write_table<D: Write + Seek>(
+    dev: &mut D,
+    scheme: PartitionScheme,
+    disk_sectors: u64,
+    parts: &[PartitionSpec],
+) -> io::Result<()> {
+    let end = last_usable(scheme, disk_sectors);
+    if parts.iter().any(|p| p.start + p.sectors > end + 1) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "partition exceeds disk size",
+        ));
+    }
+    match scheme {
+        PartitionScheme::Mbr => {
+            if parts.len() > 4 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "MBR holds at most 4 primary partitions",
+                ));
+            }
+            let entries: Vec<[u8; 16]> = parts
+                .iter()
+                .map(|p| mbr_entry(p.kind.mbr_type(), p.start, p.sectors, p.bootable))
+                .collect();
+            write_mbr(dev, &entries)?;
+            // Clear any stale GPT header so tools don't prefer it.
+            dev.seek(SeekFrom::Start(SECTOR))?;
+            dev.write_all(&[0u8; SECTOR as usize])?;
+        }
+        PartitionScheme::Gpt => write_gpt(dev, disk_sectors, parts)?,
+    }
+    dev.flush()
+}
+
+/// Add a partition to the table of an already written hybrid image,
+/// which may be GPT (e.g. Ubuntu since 20.10) or MBR; returns its number
+pub fn append_partition<D: Read + Write + Seek>(
+    dev: &mut D,
+    disk_sectors: u64,
+    kind: PartKind,
+    start: u64,
+    sectors: u64,
+    name: &str,
+) -> io::Result<usize> {
+    match read_gpt(dev)? {
+        Some(gpt) => append_gpt_partition(dev, gpt, disk_sectors, kind, start, sectors, name),
+        None => append_mbr_partition(dev, kind, start, sectors),
+    }
+}
+
+/// Highest sector in use by any partition, plus one
+pub fn table_end<D: Read + Seek>(dev: &mut D) -> io::Result<u64> {
+    let Some(gpt) = read_gpt(dev)? else {
+        return mbr_end(dev);
+    };
+    Ok(gpt
+        .array
+        .chunks(gpt.entry_size)
+        .filter(|e| e.iter().any(|b| *b != 0))
+        .map(|e| le64(e, 40) + 1)
+        .max()
+        .unwrap_or(0))
+}
+
+/// A primary GPT header and its entry array, both checksums verified
+struct Gpt {
+    header: Vec<u8>,
+    array: Vec<u8>,
+    entry_size: usize,
+}
+
+/// The GPT the kernel would use: a protective `0xEE` MBR entry and a
+/// valid primary header. `None` means the MBR is the table.
+fn read_gpt<D: Read + Seek>(dev: &mut D) -> io::Result<Option<Gpt>> {
+    if !mbr_entries(dev)?.iter().flatten().any(|e| e.ptype == 0xEE) {
+        return Ok(None);
+    }
+    let bad = |what: &str| io::Error::new(io::ErrorKind::InvalidData, what.to_string());
+    let mut sector = [0u8; SECTOR as usize];
+    dev.seek(SeekFrom::Start(SECTOR))?;
+    dev.read_exact(&mut sector)?;
+    let header_size = le32(&sector, 12) as usize;
+    if &sector[0..8] != b"EFI PART" || !(92..=SECTOR as usize).contains(&header_size) {
+        return Err(bad("protective MBR without a GPT header"));
+    }
+    let header = sector[..header_size].to_vec();
+    if header_crc(&header) != le32(&header, 16) {
+        return Err(bad("GPT header checksum mismatch"));
+    }
+    let count = le32(&header, 80) as usize;
+    let entry_size = le32(&header, 84) as usize;
+    if !(128..=1024).contains(&entry_size) || count == 0 || count > 1024 {
+        return Err(bad("unsupported GPT entry array"));
+    }
+    let mut array = vec![0u8; count * entry_size];
+    dev.seek(SeekFrom::Start(le64(&header, 72) * SECTOR))?;
+    dev.read_exact(&mut array)?;
+    if crc32(&array) != le32(&header, 88) {
+        return Err(bad("GPT entry array checksum mismatch"));
+    }
+    Ok(Some(Gpt {
+        header,
+        array,
+        entry_size,
+    }))
+}
+
+/// CRC of a header with its own checksum field taken as zero
+fn header_crc(header: &[u8]) -> u32 {
+    let mut h = header.to_vec();
+    h[16..20].fill(0);
+    crc32(&h)
+}
+
+/// Fill the first all-zero GPT entry and move the backup table to the
+/// end of the disk, which is larger than the image it came with
+fn append_gpt_partition<D: Read + Write + Seek>(
+    dev: &mut D,
+    mut gpt: Gpt,
+    disk_sectors: u64,
+    kind: PartKind,
+    start: u64,
+    sectors: u64,
+    name: &str,
+) -> io::Result<usize> {
+    let size = gpt.entry_size;
+    let slot = gpt
+        .array
+        .chunks(size)
+        .position(|e| e.iter().all(|b| *b == 0))
+        .ok_or_else(|| io::Error::other("no free GPT entry"))?;
+    let last_lba = disk_sectors - 1;
+    let array_sectors = (gpt.array.len() as u64).div_ceil(SECTOR);
+    let backup_array_lba = last_lba - array_sectors;
+    let last_usable = backup_array_lba - 1;
+    if sectors == 0 || start < le64(&gpt.header, 40) || start + sectors - 1 > last_usable {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "partition outside the usable GPT area",
+        ));
+    }
+
+    let mut seed = disk_id_seed();
+    let e = &mut gpt.array[slot * size..(slot + 1) * size];
+    e[0..16].copy_from_slice(&kind.gpt_type());
+    e[16..32].copy_from_slice(&random_guid(&mut seed));
+    e[32..40].copy_from_slice(&start.to_le_bytes());
+    e[40..48].copy_from_slice(&(start + sectors - 1).to_le_bytes());
+    for (j, unit) in name.encode_utf16().take(36).enumerate() {
+        e[56 + j * 2..58 + j * 2].copy_from_slice(&unit.to_le_bytes());
+    }
+    let array_crc = crc32(&gpt.array);
+
+    let mut primary = gpt.header.clone();
+    primary[32..40].copy_from_slice(&last_lba.to_le_bytes());
+    primary[48..56].copy_from_slice(&last_usable.to_le_bytes());
+    primary[88..92].copy_from_slice(&array_crc.to_le_bytes());
+    let mut backup = primary.clone();
+    backup[24..32].copy_from_slice(&last_lba.to_le_bytes());
+    backup[32..40].copy_from_slice(&1u64.to_le_bytes());
+    backup[72..80].copy_from_slice(&backup_array_lba.to_le_bytes());
+    for h in [&mut primary, &mut backup] {
+        let crc = header_crc(h);
+        h[16..20].copy_from_slice(&crc.to_le_bytes());
+    }
+
+    let sector_of = |header: &[u8]| {
+        let mut sector = vec![0u8; SECTOR as usize];
+        sector[..header.len()].copy_from_slice(header);
+        sector
+    };
+    dev.seek(SeekFrom::Start(le64(&primary, 72) * SECTOR))?;
+    dev.write_all(&gpt.array)?;
+    dev.seek(SeekFrom::Start(backup_array_lba * SECTOR))?;
+    dev.write_all(&gpt.array)?;
+    dev.seek(SeekFrom::Start(last_lba * SECTOR))?;
+    dev.write_all(&sector_of(&backup))?;
+    dev.seek(SeekFrom::Start(SECTOR))?;
+    dev.write_all(&sector_of(&primary))?;
+    dev.flush()?;
+    Ok(slot + 1)
+}
+
+/// Add a partition to an existing MBR in the first free slot; a slot is
+/// free only when all of it is zero, since isohybrid images sometimes
+/// leave type 0 on an entry that still has a start and size.
+pub fn append_mbr_partition<D: Read + Write + Seek>(
+    dev: &mut D,
+    kind: PartKind,
+    start: u64,
+    sectors: u64,
+) -> io::Result<usize> {
+    let mut mbr = [0u8; SECTOR as usize];
+    dev.seek(SeekFrom::Start(0))?;
+    dev.read_exact(&mut mbr)?;
+    if mbr[510..512] != [0x55, 0xAA] {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "no MBR signature",
+        ));
+    }
+    let slot = (0..4)
+        .find(|i| {
+            mbr[446 + i * 16..446 + (i + 1) * 16]
+                .iter()
+                .all(|b| *b == 0)
+        })
+        .ok_or_else(|| io::Error::other("no free MBR slot"))?;
+    let entry = mbr_entry(kind.mbr_type(), start, sectors, false);
+    mbr[446 + slot * 16..446 + (slot + 1) * 16].copy_from_slice(&entry);
+    dev.seek(SeekFrom::Start(0))?;
+    dev.write_all(&mbr)?;
+    dev.flush()?;
+    Ok(slot + 1)
+}
+
+/// A used MBR slot: partition type, first sector and length
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MbrEntry {
+    pub ptype: u8,
+    pub start: u64,
+    pub sectors: u64,
+}
+
+/// The four MBR slots, `None` where empty; no slots without a signature
+pub fn mbr_entries<D: Read + Seek>(dev: &mut D) -> io::Result<Vec<Option<MbrEntry>>> {
+    let mut mbr = [0u8; SECTOR as usize];
+    dev.seek(SeekFrom::Start(0))?;
+    dev.read_exact(&mut mbr)?;
+    if mbr[510..512] != [0x55, 0xAA] {
+        return Ok(Vec::new());
+    }
+    Ok((0..4)
+        .map(|i| &mbr[446 + i * 16..446 + (i + 1) * 16])
+        .map(|e| {
+            (e[4] != 0).then(|| MbrEntry {
+                ptype: e[4],
+                start: le32(e, 8) as u64,
+                sectors: le32(e, 12) as u64,
+            })
+        })
+        .collect())
+}
+
+/// Highest sector in use by any MBR partition, plus one
+pub fn mbr_end<D: Read + Seek>(dev: &mut D) -> io::Result<u64> {
+    let mut mbr = [0u8; SECTOR as usize];
+    dev.seek(SeekFrom::Start(0))?;
+    dev.read_exact(&mut mbr)?;
+    if mbr[510..512] != [0x55, 0xAA] {
+        return Ok(0);
+    }
+    Ok((0..4)
+        .map(|i| &mbr[446 + i * 16..446 + (i + 1) * 16])
+        .filter(|e| e[4] != 0)
+        .map(|e| le32(e, 8) as u64 + le32(e, 12) as u64)
+        .max()
+        .unwrap_or(0))
+}
+
+fn mbr_entry(ptype: u8, start: u64, sectors: u64, bootable: bool) -> [u8; 16] {
+    let mut e = [0u8; 16];
+    e[0] = if bootable { 0x80 } else { 0 };
+    // CHS fields are ignored by anything modern; mark them as LBA-only.
+    e[1..4].copy_from_slice(&[0xFE, 0xFF, 0xFF]);
+    e[4] = ptype;
+    e[5..8].copy_from_slice(&[0xFE, 0xFF, 0xFF]);
+    e[8..12].copy_from_slice(&(start.min(u32::MAX as u64) as u32).to_le_bytes());
+    e[12..16].copy_from_slice(&(sectors.min(u32::MAX as u64) as u32).to_le_bytes());
+    e
+}
+
+fn write_mbr<D: Write + Seek>(dev: &mut D, entries: &[[u8; 16]]) -> io::Result<()> {
+    let mut mbr = [0u8; SECTOR as usize];
+    let disk_id = disk_id_seed() as u32;
+    mbr[440..444].copy_from_slice(&disk_id.to_le_bytes());
+    for (i, e) in entries.iter().enumerate() {
+        mbr[446 + i * 16..446 + (i + 1) * 16].copy_from_slice(e);
+    }
+    mbr[510] = 0x55;
+    mbr[511] = 0xAA;
+    dev.seek(SeekFrom::Start(0))?;
+    dev.write_all(&mbr)
+}
+
+fn write_gpt<D: Write + Seek>(
+    dev: &mut D,
+    disk_sectors: u64,
+    parts: &[PartitionSpec],
+) -> io::Result<()> {
+    let last_lba = disk_sectors - 1;
+    let protective = mbr_entry(0xEE, 1, disk_sectors - 1, false);
+    write_mbr(dev, &[protective])?;
+
+    let mut seed = disk_id_seed();
+    let mut array = vec![0u8; (GPT_ENTRIES * GPT_ENTRY_SIZE) as usize];
+    for (i, p) in parts.iter().enumerate() {
+        let e = &mut array[i * GPT_ENTRY_SIZE as usize..(i + 1) * GPT_ENTRY_SIZE as usize];
+        e[0..16].copy_from_slice(&p.kind.gpt_type());
+        e[16..32].copy_from_slice(&random_guid(&mut seed));
+        e[32..40].copy_from_slice(&p.start.to_le_bytes());
+        e[40..48].copy_from_slice(&(p.start + p.sectors - 1).to_le_bytes());
+        for (j, unit) in p.name.encode_utf16().take(36).enumerate() {
+            e[56 + j * 2..58 + j * 2].copy_from_slice(&unit.to_le_bytes());
+        }
+    }
+    let array_crc = crc32(&array);
+    let disk_guid = random_guid(&mut seed);
+    let backup_array_lba = last_lba - GPT_ARRAY_SECTORS;
+
+    let header = |current: u64, backup: u64, array_lba: u64| {
+        let mut h = [0u8; SECTOR as usize];
+        h[0..8].copy_from_slice(b"EFI PART");
+        h[8..12].copy_from_slice(&0x0001_0000u32.to_le_bytes());
+        h[12..16].copy_from_slice(&92u32.to_le_bytes());
+        h[24..32].copy_from_slice(&current.to_le_bytes());
+        h[32..40].copy_from_slice(&backup.to_le_bytes());
+        h[40..48].copy_from_slice(&(2 + GPT_ARRAY_SECTORS).to_le_bytes());
+        h[48..56].copy_from_slice(&(backup_array_lba - 1).to_le_bytes());
+        h[56..72].copy_from_slice(&disk_guid);
+        h[72..80].copy_from_slice(&array_lba.to_le_bytes());
+        h[80..84].copy_from_slice(&(GPT_ENTRIES as u32).to_le_bytes());
+        h[84..88].copy_from_slice(&(GPT_ENTRY_SIZE as u32).to_le_bytes());
+        h[88..92].copy_from_slice(&array_crc.to_le_bytes());
+        let crc = crc32(&h[..92]);
+        h[16..20].copy_from_slice(&crc.to_le_bytes());
+        h
+    };
+
+    dev.seek(SeekFrom::Start(SECTOR))?;
+    dev.write_all(&header(1, last_lba, 2))?;
+    dev.write_all(&array)?;
+    dev.seek(SeekFrom::Start(backup_array_lba * SECTOR))?;
+    dev.write_all(&array)?;
+    dev.write_all(&header(last_lba, 1, backup_array_lba))?;
+    Ok(())
+}
+
+fn disk_id_seed() -> u64 {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+    nanos ^ ((std::process::id() as u64) << 32)
+}
+
+/// Version 4 style GUID from a xorshift stream; uniqueness, not secrecy
+fn random_guid(state: &mut u64) -> [u8; 16] {
+    let mut g = [0u8; 16];
+    for chunk in g.chunks_mut(8) {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        chunk.copy_from_slice(&state.to_le_bytes());
+    }
+    g[7] = (g[7] & 0x0F) | 0x40;
+    g[8] = (g[8] & 0x3F) | 0x80;
+    g
+}
+
+/// CRC-32 (IEEE 802.3), as used by GPT headers
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn le32(d: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([d[at], d[at + 1], d[at + 2], d[at + 3]])
+}
+
+fn le64(d: &[u8], at: usize) -> u64 {
+    let mut b = [0u8; 8];
+    b.copy_from_slice(&d[at..at + 8]);
+    u64::from_le_bytes(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const DISK: u64 = 64 * 1024 * 1024 / SECTOR;
+
+    #[test]
+    fn crc32_matches_reference() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn mbr_layout_round_trips() {
+        let parts = layout(
+            PartitionScheme::Mbr,
+            DISK,
+            &[(PartKind::Fat32, None, "WIN")],
+        );
+        assert_eq!(parts[0].start, ALIGN);
+        let mut disk = Cursor::new(vec![0u8; (DISK * SECTOR) as usize]);
+        write_table(&mut disk, PartitionScheme::Mbr, DISK, &parts).unwrap();
+        let raw = disk.get_ref();
+        assert_eq!(&raw[510..512], &[0x55, 0xAA]);
+        assert_eq!(raw[446 + 4], 0x0C);
+        assert_eq!(
+            mbr_end(&mut disk).unwrap(),
+            parts[0].start + parts[0].sectors
+        );
+    }
+
+    #[test]
+    fn gpt_header_checksums_are_valid() {
+        let parts = layout(
+            PartitionScheme::Gpt,
+            DISK,
+            &[
+                (PartKind::Ntfs, Some(40_000), "data"),
+                (PartKind::EfiSystem, None, "EFI"),
+            ],
+        );
+        let mut disk = Cursor::new(vec![0u8; (DISK * SECTOR) as usize]);
+        write_table(&mut disk, PartitionScheme::Gpt, DISK, &parts).unwrap();
+        let raw = disk.get_ref();
+        let mut h = raw[512..512 + 92].to_vec();
+        let stored = le32(&h, 16);
+        h[16..20].fill(0);
+        assert_eq!(crc32(&h), stored);
+        assert_eq!(le32(&h, 88), crc32(&raw[1024..1024 + 16384]));
+        assert_eq!(raw[446 + 4], 0xEE);
+    }
+
+    #[test]
+    fn append_uses_free_slot() {
+        let parts = layout(
+            PartitionScheme::Mbr,
+            DISK,
+            &[(PartKind::Fat32, Some(8192), "A")],
+        );
+        let mut disk = Cursor::new(vec![0u8; (DISK * SECTOR) as usize]);
+        write_table(&mut disk, PartitionScheme::Mbr, DISK, &parts).unwrap();
+        let n = append_mbr_partition(&mut disk, PartKind::Linux, 20480, 4096).unwrap();
+        assert_eq!(n, 2);
+        assert_eq!(mbr_end(&mut disk).unwrap(), 20480 + 4096);
+
+        // Type 0 with a start and size left in slot 3 is not free
+        disk.get_mut()[446 + 2 * 16 + 8] = 0x40;
+        let n = append_mbr_partition(&mut disk, PartKind::Linux, 30720, 4096).unwrap();
+        assert_eq!(n, 4);
+    }
+
+    #[test]
+    fn append_to_a_gpt_image_moves_the_backup_to_the_disk_end() {
+        // A hybrid image's GPT ends where the image ends, not the stick
+        const IMAGE: u64 = 8 * 1024 * 1024 / SECTOR;
+        let parts = layout(
+            PartitionScheme::Gpt,
+            IMAGE,
+            &[(PartKind::EfiSystem, Some(4096), "ISO")],
+        );
+        let mut disk = Cursor::new(vec![0u8; (IMAGE * SECTOR) as usize]);
+        write_table(&mut disk, PartitionScheme::Gpt, IMAGE, &parts).unwrap();
+        disk.get_mut().resize((DISK * SECTOR) as usize, 0);
+
+        let start = table_end(&mut disk).unwrap().div_ceil(ALIGN) * ALIGN + ALIGN;
+        let n =
+            append_partition(&mut disk, DISK, PartKind::Linux, start, 8192, "casper-rw").unwrap();
+        assert_eq!(n, 2);
+        assert_eq!(table_end(&mut disk).unwrap(), start + 8192);
+
+        let raw = disk.get_ref();
+        let primary = &raw[512..512 + 92];
+        assert_eq!(header_crc(primary), le32(primary, 16));
+        assert_eq!(le32(primary, 88), crc32(&raw[1024..1024 + 16384]));
+        assert_eq!(le64(primary, 32), DISK - 1);
+        let at = ((DISK - 1) * SECTOR) as usize;
+        let backup = &raw[at..at + 92];
+        assert_eq!(&backup[0..8], b"EFI PART");
+        assert_eq!(header_crc(backup), le32(backup, 16));
+        assert_eq!(le64(backup, 24), DISK - 1);
+        assert_eq!(le64(backup, 72), DISK - 1 - 32);
+        // The MBR stays protective only
+        assert_eq!(mbr_entries(&mut disk).unwrap().iter().flatten().count(), 1);
+    }
+}
@@ -0,0 +1,178 @@
+//! Persistence partition for Ubuntu/Debian live images.
+//!
+//! After a raw write the stick holds the hybrid image followed by
+//! unused space. Casper (Ubuntu, Mint) looks for an ext4 filesystem
+//! labelled `casper-rw`; Debian live-boot wants one labelled
+//! `persistence` containing a `persistence.conf`. Either way the user
+//! still adds `persistent` / `persistence` to the kernel command line.
+
+use crate::{
+    adapters::platform,
+    app::{effects::Mounter, msg::Msg},
+    domain::{
+        distro::{Distro, DistroInfo},
+        partition::{self, ALIGN, PartKind, SECTOR},
+        paths::DevicePath,
+        writer::{self, MountGuard},
+    },
+    event::Event,
+};
+use std::{fs, time::Duration};
+
+/// Don't offer a partition smaller than this
+pub const MIN_SPARE: u64 = 1024 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PersistenceKind {
+    /// Ubuntu and derivatives
+    Casper,
+    /// Debian live-boot
+    Debian,
+}
+
+impl PersistenceKind {
+    pub fn for_distro(info: &DistroInfo) -> Option<Self> {
+        match info.distro {
+            Distro::Ubuntu | Distro::Mint => Some(PersistenceKind::Casper),
+            Distro::Debian => Some(PersistenceKind::Debian),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PersistenceKind::Casper => "casper-rw",
+            PersistenceKind::Debian => "persistence",
+        }
+    }
+
+    /// Kernel parameter that turns persistence on at boot
+    pub fn boot_param(self) -> &'static str {
+        match self {
+            PersistenceKind::Casper => "persistent",
+            PersistenceKind::Debian => "persistence",
+        }
+    }
+}
+
+/// The persistence flavour to offer, if the image supports one and the
+/// device has at least [`MIN_SPARE`] left after the image.
+pub fn offer(info: &DistroInfo, image_size: u64, device_size: u64) -> Option<PersistenceKind> {
+    let kind = PersistenceKind::for_distro(info)?;
+    let start = first_free_sector(image_size, 0) * SECTOR;
+    (device_size.saturating_sub(start) >= MIN_SPARE).then_some(kind)
+}
+
+pub fn create_persistence(
+    device_path: DevicePath,
+    image_size: u64,
+    kind: PersistenceKind,
+    mounter: &dyn Mounter,
+    tx: tokio::sync::mpsc::UnboundedSender<Event>,
+) {
+    let result = run(&device_path, image_size, kind, mounter, &tx);
+    let _: Result<_, _> = tx.send(Event::App(Msg::PersistenceFinished(result)));
+}
+
+fn run(
+    device_path: &DevicePath,
+    image_size: u64,
+    kind: PersistenceKind,
+    mounter: &dyn Mounter,
+    tx: &tokio::sync::mpsc::UnboundedSender<Event>,
+) -> Result<(), String> {
+    writer::warn(
+        tx,
+        device_path,
+        mounter.unmount_partitions_of(device_path.as_path()),
+    );
+    let disk_sectors = platform::device_size(device_path.as_path())
+        .map_err(|e| format!("Failed to size device {}: {}", device_path, e))?
+        / SECTOR;
+    let mut dev = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(device_path.as_path())
+        .map_err(|e| format!("Failed to open device {}: {}", device_path, e))?;
+    let used = partition::table_end(&mut dev)
+        .map_err(|e| format!("Failed to read partition table: {}", e))?;
+    let start = first_free_sector(image_size, used);
+    let sectors = disk_sectors.saturating_sub(start) / ALIGN * ALIGN;
+    if sectors * SECTOR < MIN_SPARE {
+        return Err("Not enough space left after the image".to_string());
+    }
+    let number = partition::append_partition(
+        &mut dev,
+        disk_sectors,
+        PartKind::Linux,
+        start,
+        sectors,
+        kind.label(),
+    )
+    .map_err(|e| format!("Failed to add persistence partition: {}", e))?;
+    dev.sync_all()
+        .map_err(|e| format!("sync_all error: {}", e))?;
+    drop(dev);
+    writer::warn(tx, device_path, mounter.partprobe(device_path.as_path()));
+
+    let part = platform::partition_path(device_path.as_path(), number);
+    if !platform::wait_for_node(&part, Duration::from_secs(10)) {
+        return Err(format!("Partition {} did not appear", part.display()));
+    }
+    platform::run_tool(
+        "mkfs.ext4",
+        &["-F", "-L", kind.label(), &part.to_string_lossy()],
+    )?;
+    if kind == PersistenceKind::Debian {
+        let mount = MountGuard::mount(&part, "persist")?;
+        fs::write(mount.path().join("persistence.conf"), "/ union\n")
+            .map_err(|e| format!("Failed to write persistence.conf: {}", e))?;
+        writer::warn(tx, device_path, mount.unmount());
+    }
+    Ok(())
+}
+
+/// First aligned sector after both the image and its own partitions
+fn first_free_sector(image_size: u64, used_sectors: u64) -> u64 {
+    image_size
+        .div_ceil(SECTOR)
+        .max(used_sectors)
+        .div_ceil(ALIGN)
+        * ALIGN
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GIB: u64 = 1024 * 1024 * 1024;
+
+    fn info(distro: Distro) -> DistroInfo {
+        DistroInfo {
+            distro,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn offered_only_for_supported_images_with_room() {
+        let ubuntu = info(Distro::Ubuntu);
+        assert_eq!(
+            offer(&ubuntu, 6 * GIB, 16 * GIB),
+            Some(PersistenceKind::Casper)
+        );
+        assert_eq!(
+            offer(&info(Distro::Debian), GIB, 8 * GIB),
+            Some(PersistenceKind::Debian)
+        );
+        assert_eq!(offer(&info(Distro::Fedora), GIB, 16 * GIB), None);
+        assert_eq!(offer(&ubuntu, 6 * GIB, 6 * GIB + GIB / 2), None);
+    }
+
+    #[test]
+    fn partition_starts_after_image_and_its_partitions() {
+        assert_eq!(first_free_sector(1, 0), ALIGN);
+        assert_eq!(first_free_sector(ALIGN * SECTOR, 0), ALIGN);
+        assert_eq!(first_free_sector(ALIGN * SECTOR, ALIGN * 3 + 1), ALIGN * 4);
+    }
+}
@@ -0,0 +1,295 @@
+//! Audit reports: what was written to which physical device.
+//!
+//! A report is built when a job finishes and saved as a pair of files,
+//! `<stamp>-<device>.txt` for people and `.json` for tools, in
+//! `report_dir` (default `$XDG_STATE_HOME/ferric/reports`). Times are UTC.
+
+use crate::{
+    config,
+    domain::{
+        paths::{DevicePath, IsoPath},
+        sha256,
+        units::{ByteSize, Throughput},
+    },
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// How the written data was checked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyMode {
+    Off,
+    /// Image and device read back and compared byte for byte
+    ByteCompare,
+    /// File-level installs no longer match the image
+    NotApplicable,
+}
+
+impl VerifyMode {
+    pub fn label(self) -> &'static str {
+        match self {
+            VerifyMode::Off => "off",
+            VerifyMode::ByteCompare => "byte-compare",
+            VerifyMode::NotApplicable => "not applicable",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReportTarget {
+    pub device: DevicePath,
+    pub model: Option<String>,
+    pub serial: Option<String>,
+    /// `usb`, `sata`, `nvme`, ... as reported by lsblk
+    pub transport: Option<String>,
+    pub bytes: u64,
+    pub duration: Duration,
+    pub write: Result<(), String>,
+    /// `None` when no verify ran on this device
+    pub verify: Option<Result<(), String>>,
+}
+
+impl ReportTarget {
+    pub fn throughput(&self) -> Throughput {
+        let secs = self.duration.as_secs_f64();
+        Throughput::new(if secs > 0.0 {
+            self.bytes as f64 / secs
+        } else {
+            0.0
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub started: SystemTime,
+    pub finished: SystemTime,
+    pub image: IsoPath,
+    pub image_size: u64,
+    /// Hex SHA-256 of the image; filled in on export when still empty
+    pub sha256: String,
+    /// `raw`, `windows (GPT, FAT32)` or `multiboot`
+    pub write_mode: String,
+    pub verify: VerifyMode,
+    /// Outcome of the whole job, including any persistence step
+    pub result: Result<(), String>,
+    pub targets: Vec<ReportTarget>,
+    /// Unmount and partition re-read problems that did not stop the write
+    pub warnings: Vec<String>,
+}
+
+pub fn default_dir() -> Option<PathBuf> {
+    config::xdg_dir("XDG_STATE_HOME", ".local/state").map(|d| d.join("ferric").join("reports"))
+}
+
+/// Write the text and JSON versions into `dir`, returning their paths
+pub fn export(mut report: Report, dir: &Path) -> Result<Vec<PathBuf>, String> {
+    if report.sha256.is_empty() {
+        report.sha256 = sha256::file(report.image.as_path())
+            .map_err(|e| format!("Failed to hash {}: {}", report.image, e))?;
+    }
+    fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let device = report
+        .targets
+        .first()
+        .and_then(|t| t.device.as_path().file_name())
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "device".to_string());
+    let stem = format!(
+        "{}-{}",
+        utc(report.finished).replace([':', '-'], ""),
+        device
+    );
+    let mut paths = Vec::new();
+    for (ext, text) in [
+        ("txt", render_text(&report)),
+        ("json", render_json(&report)),
+    ] {
+        let path = dir.join(format!("{}.{}", stem, ext));
+        fs::write(&path, text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+pub fn render_text(r: &Report) -> String {
+    let outcome = |res: &Result<(), String>| match res {
+        Ok(()) => "ok".to_string(),
+        Err(e) => format!("failed: {}", e),
+    };
+    let mut out = String::from("ferric flash report\n\n");
+    let mut field = |name: &str, value: &str| out.push_str(&format!("{:<11}{}\n", name, value));
+    field("Started:", &utc(r.started));
+    field("Finished:", &utc(r.finished));
+    field("Result:", &outcome(&r.result));
+    field("Image:", &r.image.to_string());
+    field(
+        "Size:",
+        &format!("{} bytes ({})", r.image_size, ByteSize::new(r.image_size)),
+    );
+    field("SHA-256:", &r.sha256);
+    field("Mode:", &r.write_mode);
+    field("Verify:", r.verify.label());
+    for t in &r.targets {
+        out.push_str(&format!("\nDevice {}\n", t.device));
+        let mut field =
+            |name: &str, value: &str| out.push_str(&format!("  {:<11}{}\n", name, value));
+        field("Model:", t.model.as_deref().unwrap_or("-"));
+        field("Serial:", t.serial.as_deref().unwrap_or("-"));
+        field("Transport:", t.transport.as_deref().unwrap_or("-"));
+        field(
+            "Written:",
+            &format!(
+                "{} bytes in {:.1} s ({})",
+                t.bytes,
+                t.duration.as_secs_f64(),
+                t.throughput()
+            ),
+        );
+        field("Write:", &outcome(&t.write));
+        field(
+            "Verify:",
+            &t.verify.as_ref().map_or("skipped".to_string(), outcome),
+        );
+    }
+    if !r.warnings.is_empty() {
+        out.push_str("\nWarnings\n");
+        for w in &r.warnings {
+            out.push_str(&format!("  {}\n", w));
+        }
+    }
+    out
+}
+
+pub fn render_json(r: &Report) -> String {
+    let result = |res: &Result<(), String>| match res {
+        Ok(()) => "{\"ok\": true}".to_string(),
+        Err(e) => format!("{{\"ok\": false, \"error\": {}}}", json_str(e)),
+    };
+    let opt = |s: &Option<String>| s.as_deref().map_or("null".to_string(), json_str);
+    let targets: Vec<String> = r
+        .targets
+        .iter()
+        .map(|t| {
+            format!(
+                "    {{\n      \"device\": {},\n      \"model\": {},\n      \"serial\": {},\n      \
+                 \"transport\": {},\n      \"bytes\": {},\n      \"duration_ms\": {},\n      \
+                 \"throughput_bps\": {:.0},\n      \"write\": {},\n      \"verify\": {}\n    }}",
+                json_str(&t.device.to_string()),
+                opt(&t.model),
+                opt(&t.serial),
+                opt(&t.transport),
+                t.bytes,
+                t.duration.as_millis(),
+                t.throughput().as_f64(),
+                result(&t.write),
+                t.verify.as_ref().map_or("null".to_string(), result),
+            )
+        })
+        .collect();
+    let warnings: Vec<String> = r.warnings.iter().map(|w| json_str(w)).collect();
+    format!(
+        "{{\n  \"started\": {},\n  \"finished\": {},\n  \"result\": {},\n  \"image\": {{\n    \
+         \"path\": {},\n    \"size\": {},\n    \"sha256\": {}\n  }},\n  \"write_mode\": {},\n  \
+         \"verify\": {},\n  \"targets\": [\n{}\n  ],\n  \"warnings\": [{}]\n}}\n",
+        json_str(&utc(r.started)),
+        json_str(&utc(r.finished)),
+        result(&r.result),
+        json_str(&r.image.to_string()),
+        r.image_size,
+        json_str(&r.sha256),
+        json_str(&r.write_mode),
+        json_str(r.verify.label()),
+        targets.join(",\n"),
+        warnings.join(", "),
+    )
+}
+
+fn json_str(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// `2024-05-01T12:00:00Z`
+pub fn utc(t: SystemTime) -> String {
+    let secs = t.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_text_and_json() {
+        let report = Report {
+            started: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+            finished: UNIX_EPOCH + Duration::from_secs(1_700_000_100),
+            image: IsoPath::from(PathBuf::from("/isos/alpine.iso")),
+            image_size: 4096,
+            sha256: "ab".repeat(32),
+            write_mode: "raw".to_string(),
+            verify: VerifyMode::ByteCompare,
+            result: Err("verify \"failed\"".to_string()),
+            targets: vec![ReportTarget {
+                device: DevicePath::from(PathBuf::from("/dev/sdb")),
+                model: Some("Cruzer Blade".to_string()),
+                serial: None,
+                transport: Some("usb".to_string()),
+                bytes: 4096,
+                duration: Duration::from_secs(2),
+                write: Ok(()),
+                verify: Some(Err("verify \"failed\"".to_string())),
+            }],
+            warnings: vec!["/dev/sdb: partprobe failed".to_string()],
+        };
+        assert_eq!(utc(report.started), "2023-11-14T22:13:20Z");
+        assert_eq!(utc(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+
+        let text = render_text(&report);
+        assert!(text.contains("Started:   2023-11-14T22:13:20Z\n"));
+        assert!(text.contains("  Transport: usb\n"));
+        assert!(text.contains("  Written:   4096 bytes in 2.0 s"));
+        assert!(text.contains("Warnings\n  /dev/sdb: partprobe failed\n"));
+
+        let json = render_json(&report);
+        assert!(json.contains("\"error\": \"verify \\\"failed\\\"\""));
+        assert!(json.contains("\"serial\": null"));
+        assert!(json.contains("\"throughput_bps\": 2048"));
+        assert!(json.contains("\"verify\": \"byte-compare\""));
+    }
+}
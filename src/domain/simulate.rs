@@ -0,0 +1,328 @@
+//! Dry runs.
+//!
+//! With `--dry-run`, image writes, verifies, unmounts and partition
+//! re-reads go to a simulated device instead of a real one: a sparse
+//! scratch file, taking bytes at a chosen speed. Faults can be injected to
+//! rehearse what a failing stick looks like.
+//!
+//! Only raw image writes are simulated. Windows installs, multi-ISO drives
+//! and persistence partition, format and mount a real disk, so a dry run
+//! refuses them at Confirm; the simulated stick is the only device listed.
+//! `effects::Simulated` puts this in place of the real machine.
+
+use crate::domain::{
+    device::{Device, DeviceAccess},
+    paths::DevicePath,
+    units::ByteSize,
+    writer::{ScratchDir, Sink},
+};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    os::unix::fs::FileExt,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Where simulated writes land
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum SimTarget {
+    /// An unlinked temp file that holds the image until the next write and
+    /// goes away with the process
+    #[default]
+    Scratch,
+    /// A regular file, truncated by each write
+    File(PathBuf),
+}
+
+/// A failure to inject
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// I/O error once this many bytes are written
+    WriteAt(u64),
+    /// Read error once verify gets this far
+    VerifyAt(u64),
+    /// The final flush fails
+    Flush,
+    /// Unmounting the partitions fails
+    Unmount,
+    /// Re-reading the partition table fails
+    Partprobe,
+}
+
+impl Fault {
+    /// `write@1G`, `verify@512M`, `flush`, `unmount` or `partprobe`
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let offset = |at: &str| {
+            ByteSize::parse(at)
+                .map(ByteSize::as_u64)
+                .ok_or_else(|| format!("`{}` is not an offset like 512M", at))
+        };
+        match spec.split_once('@') {
+            Some(("write", at)) => Ok(Fault::WriteAt(offset(at)?)),
+            Some(("verify", at)) => Ok(Fault::VerifyAt(offset(at)?)),
+            None if spec == "flush" => Ok(Fault::Flush),
+            None if spec == "unmount" => Ok(Fault::Unmount),
+            None if spec == "partprobe" => Ok(Fault::Partprobe),
+            _ => Err(format!(
+                "`{}` is not one of write@OFFSET, verify@OFFSET, flush, unmount, partprobe",
+                spec
+            )),
+        }
+    }
+}
+
+/// How the simulated device behaves
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Simulation {
+    /// Bytes per second the device takes; `None` for as fast as possible
+    pub speed: Option<u64>,
+    pub target: SimTarget,
+    /// Extra time the final flush takes
+    pub flush_delay: Duration,
+    pub faults: Vec<Fault>,
+}
+
+/// The default device, created on the first write
+static SCRATCH: Mutex<Option<File>> = Mutex::new(None);
+
+/// The stand-in stick listed during a dry run
+pub fn device() -> Device {
+    let path = DevicePath::from(PathBuf::from("/dev/ferric-sim"));
+    Device {
+        name: "ferric-sim".to_string(),
+        path,
+        size: ByteSize::new(16 * 1024 * 1024 * 1024),
+        model: Some("Simulated stick".to_string()),
+        serial: Some("SIM0".to_string()),
+        tran: Some("usb".to_string()),
+        removable: true,
+        hotplug: true,
+        ro: false,
+        mounted: false,
+        labels: vec!["SIMULATION".to_string()],
+        access: DeviceAccess::FULL,
+        udisks: None,
+    }
+}
+
+impl Simulation {
+    fn has(&self, fault: Fault) -> bool {
+        self.faults.contains(&fault)
+    }
+
+    fn offset(&self, pick: impl Fn(&Fault) -> Option<u64>) -> Option<u64> {
+        self.faults.iter().filter_map(pick).min()
+    }
+
+    /// Stands in for unmounting the device's partitions
+    pub fn unmount(&self) -> Result<(), String> {
+        match self.has(Fault::Unmount) {
+            true => Err("simulated unmount failure: target is busy".to_string()),
+            false => Ok(()),
+        }
+    }
+
+    /// Stands in for re-reading the partition table
+    pub fn partprobe(&self) -> Result<(), String> {
+        match self.has(Fault::Partprobe) {
+            true => Err("simulated partition re-read failure: device busy".to_string()),
+            false => Ok(()),
+        }
+    }
+
+    /// The device, emptied, for an image write
+    pub fn open_write(self: &Arc<Self>) -> Result<SimDevice, String> {
+        let file = match &self.target {
+            SimTarget::Scratch => {
+                let file = scratch()?;
+                file.set_len(0)
+                    .map_err(|e| format!("Failed to empty the simulated device: {}", e))?;
+                file
+            }
+            SimTarget::File(path) => File::create(path)
+                .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?,
+        };
+        Ok(SimDevice::new(self, file))
+    }
+
+    /// The device from the start, for a verify
+    pub fn open_read(self: &Arc<Self>) -> Result<SimDevice, String> {
+        let file = match &self.target {
+            SimTarget::Scratch => scratch()?,
+            SimTarget::File(path) => OpenOptions::new()
+                .read(true)
+                .open(path)
+                .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?,
+        };
+        Ok(SimDevice::new(self, file))
+    }
+}
+
+/// A handle on the scratch device. The file is unlinked as soon as it is
+/// made, in a private directory, so nothing outlives the process and the
+/// bytes live on disk, not in memory.
+fn scratch() -> Result<File, String> {
+    let mut scratch = SCRATCH.lock().map_err(|_| "simulated device poisoned")?;
+    if scratch.is_none() {
+        let dir = ScratchDir::new("sim")?;
+        let path = dir.path().join("stick.img");
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .and_then(|file| std::fs::remove_file(&path).map(|()| file))
+            .map_err(|e| format!("Failed to create the simulated device: {}", e))?;
+        *scratch = Some(file);
+    }
+    scratch
+        .as_ref()
+        .expect("just created")
+        .try_clone()
+        .map_err(|e| format!("Failed to open the simulated device: {}", e))
+}
+
+/// The simulated device: paces transfers, injects faults, keeps the bytes.
+/// Reads and writes are positioned, so handles may share one open file.
+pub struct SimDevice {
+    sim: Arc<Simulation>,
+    file: File,
+    pos: u64,
+    start: Instant,
+}
+
+impl SimDevice {
+    fn new(sim: &Arc<Simulation>, file: File) -> Self {
+        Self {
+            sim: Arc::clone(sim),
+            file,
+            pos: 0,
+            start: Instant::now(),
+        }
+    }
+
+    /// Sleep until `pos` bytes are due at the configured speed
+    fn pace(&self) {
+        let Some(speed) = self.sim.speed.filter(|&s| s > 0) else {
+            return;
+        };
+        let due = Duration::from_secs_f64(self.pos as f64 / speed as f64);
+        if let Some(wait) = due.checked_sub(self.start.elapsed()) {
+            std::thread::sleep(wait);
+        }
+    }
+
+    /// How much of `len` fits before `fault`'s offset
+    fn room(&self, len: usize, fault: Option<u64>) -> io::Result<usize> {
+        match fault {
+            Some(at) if self.pos >= at => Err(io::Error::other(format!(
+                "simulated I/O error at byte {}",
+                at
+            ))),
+            Some(at) => Ok(len.min((at - self.pos) as usize)),
+            None => Ok(len),
+        }
+    }
+}
+
+impl Write for SimDevice {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let fault = self.sim.offset(|f| match *f {
+            Fault::WriteAt(at) => Some(at),
+            _ => None,
+        });
+        let n = self.room(buf.len(), fault)?;
+        self.file.write_all_at(&buf[..n], self.pos)?;
+        self.pos += n as u64;
+        self.pace();
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Sink for SimDevice {
+    fn sync(&mut self) -> io::Result<()> {
+        std::thread::sleep(self.sim.flush_delay);
+        if self.sim.has(Fault::Flush) {
+            return Err(io::Error::other("simulated flush failure"));
+        }
+        match self.sim.target {
+            // Nobody reads the scratch file after a crash
+            SimTarget::Scratch => Ok(()),
+            SimTarget::File(_) => self.file.sync_all(),
+        }
+    }
+}
+
+impl Read for SimDevice {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let fault = self.sim.offset(|f| match *f {
+            Fault::VerifyAt(at) => Some(at),
+            _ => None,
+        });
+        let n = self.room(buf.len(), fault)?;
+        let n = self.file.read_at(&mut buf[..n], self.pos)?;
+        self.pos += n as u64;
+        self.pace();
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn faults_parse_from_specs() {
+        assert_eq!(Fault::parse("write@1M"), Ok(Fault::WriteAt(1024 * 1024)));
+        assert_eq!(Fault::parse("verify@4096"), Ok(Fault::VerifyAt(4096)));
+        assert_eq!(Fault::parse("unmount"), Ok(Fault::Unmount));
+        assert!(Fault::parse("write").is_err());
+        assert!(Fault::parse("write@lots").is_err());
+        assert!(Fault::parse("format").is_err());
+    }
+
+    #[test]
+    fn file_target_fails_at_the_offset_and_reads_back() {
+        let path = std::env::temp_dir().join(format!("ferric-sim-{}.img", std::process::id()));
+        let sim = Arc::new(Simulation {
+            speed: Some(1024 * 1024 * 1024),
+            target: SimTarget::File(path.clone()),
+            flush_delay: Duration::ZERO,
+            faults: vec![Fault::WriteAt(6000), Fault::Flush],
+        });
+        let mut dev = sim.open_write().unwrap();
+        assert_eq!(dev.write(&[7u8; 4096]).unwrap(), 4096);
+        assert_eq!(dev.write(&[7u8; 4096]).unwrap(), 6000 - 4096);
+        let err = dev.write(&[7u8; 4096]).unwrap_err();
+        assert!(err.to_string().contains("byte 6000"));
+        assert!(dev.sync().is_err());
+        assert!(sim.unmount().is_ok());
+
+        let mut back = Vec::new();
+        sim.open_read().unwrap().read_to_end(&mut back).unwrap();
+        assert_eq!(back, vec![7u8; 6000]);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn scratch_target_keeps_the_image_on_disk_until_the_next_write() {
+        let sim = Arc::new(Simulation::default());
+        let mut dev = sim.open_write().unwrap();
+        dev.write_all(&[1u8; 3000]).unwrap();
+        dev.sync().unwrap();
+        let mut back = Vec::new();
+        sim.open_read().unwrap().read_to_end(&mut back).unwrap();
+        assert_eq!(back, vec![1u8; 3000]);
+
+        sim.open_write().unwrap().write_all(&[2u8; 100]).unwrap();
+        back.clear();
+        sim.open_read().unwrap().read_to_end(&mut back).unwrap();
+        assert_eq!(back, vec![2u8; 100]);
+    }
+}
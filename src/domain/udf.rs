@@ -0,0 +1,423 @@
+//! Minimal read-only UDF reader.
+//!
+//! Enough of ECMA-167 / OSTA UDF 1.02 to walk the file tree of a Windows
+//! installer ISO: one type 1 partition, File Entries and Extended File
+//! Entries with short, long or embedded allocation descriptors. Windows
+//! images keep their real content only in the UDF tree; the ISO9660 side
+//! just carries a readme.
+
+use crate::domain::image_fs::{ImageEntry, ImageFs};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+const SECTOR_SIZE: u64 = 2048;
+const ANCHOR_SECTOR: u64 = 256;
+
+const TAG_ANCHOR: u16 = 2;
+const TAG_PARTITION: u16 = 5;
+const TAG_LOGICAL_VOLUME: u16 = 6;
+const TAG_TERMINATING: u16 = 8;
+const TAG_FILE_SET: u16 = 256;
+const TAG_FILE_ID: u16 = 257;
+const TAG_FILE_ENTRY: u16 = 261;
+const TAG_EXT_FILE_ENTRY: u16 = 266;
+
+const FILE_TYPE_DIRECTORY: u8 = 4;
+const FID_DIRECTORY: u8 = 0x02;
+const FID_DELETED: u8 = 0x04;
+const FID_PARENT: u8 = 0x08;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Extent {
+    /// Absolute byte offset and length of recorded data
+    Recorded(u64, u64),
+    /// Allocated but unrecorded, or sparse: reads as zeros
+    Zero(u64),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum UdfData {
+    Extents(Vec<Extent>),
+    Inline(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UdfEntry {
+    pub name: String,
+    pub is_dir: bool,
+    pub size: u64,
+    /// Block of the file entry describing it
+    icb: u32,
+    data: UdfData,
+}
+
+impl ImageEntry for UdfEntry {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn location(&self) -> u64 {
+        self.icb as u64
+    }
+}
+
+pub struct UdfImage<R> {
+    reader: R,
+    block_size: u64,
+    partition_start: u64,
+    root: UdfEntry,
+}
+
+impl<R: Read + Seek> UdfImage<R> {
+    pub fn open(mut reader: R) -> io::Result<Self> {
+        let anchor = read_sector(&mut reader, ANCHOR_SECTOR * SECTOR_SIZE)?;
+        if tag_id(&anchor) != TAG_ANCHOR {
+            return Err(invalid("no UDF anchor at sector 256"));
+        }
+        let vds_len = le32(&anchor, 16) as u64;
+        let vds_loc = le32(&anchor, 20) as u64;
+
+        let mut partition_start: Option<u64> = None;
+        let mut block_size = SECTOR_SIZE;
+        let mut fsd: Option<(u32, u16)> = None;
+        for i in 0..(vds_len / SECTOR_SIZE).min(64) {
+            let d = read_sector(&mut reader, (vds_loc + i) * SECTOR_SIZE)?;
+            match tag_id(&d) {
+                TAG_PARTITION if partition_start.is_none() => {
+                    partition_start = Some(le32(&d, 188) as u64);
+                }
+                TAG_LOGICAL_VOLUME => {
+                    block_size = le32(&d, 212) as u64;
+                    fsd = Some((le32(&d, 252), le16(&d, 256)));
+                }
+                TAG_TERMINATING => break,
+                _ => {}
+            }
+        }
+        let partition_start = partition_start.ok_or_else(|| invalid("no partition descriptor"))?;
+        let (fsd_lbn, _part) = fsd.ok_or_else(|| invalid("no logical volume descriptor"))?;
+        if block_size != SECTOR_SIZE {
+            return Err(invalid("unsupported UDF block size"));
+        }
+
+        let mut img = Self {
+            reader,
+            block_size,
+            partition_start,
+            root: UdfEntry {
+                name: String::new(),
+                is_dir: true,
+                size: 0,
+                icb: 0,
+                data: UdfData::Inline(Vec::new()),
+            },
+        };
+        let fsd = img.read_block(fsd_lbn)?;
+        if tag_id(&fsd) != TAG_FILE_SET {
+            return Err(invalid("bad file set descriptor"));
+        }
+        let root_lbn = le32(&fsd, 404);
+        img.root = img.read_file_entry(root_lbn, String::new())?;
+        Ok(img)
+    }
+
+    fn block_offset(&self, lbn: u32) -> u64 {
+        (self.partition_start + lbn as u64) * self.block_size
+    }
+
+    fn read_block(&mut self, lbn: u32) -> io::Result<Vec<u8>> {
+        let off = self.block_offset(lbn);
+        read_sector(&mut self.reader, off)
+    }
+
+    fn read_file_entry(&mut self, lbn: u32, name: String) -> io::Result<UdfEntry> {
+        let fe = self.read_block(lbn)?;
+        let (size_at, ea_at, ad_at) = match tag_id(&fe) {
+            TAG_FILE_ENTRY => (56, 168, 176),
+            TAG_EXT_FILE_ENTRY => (56, 208, 216),
+            _ => return Err(invalid("expected a file entry")),
+        };
+        let is_dir = fe[27] == FILE_TYPE_DIRECTORY;
+        let ad_type = le16(&fe, 34) & 0x7;
+        let size = le64(&fe, size_at);
+        let l_ea = le32(&fe, ea_at) as usize;
+        let l_ad = le32(&fe, ea_at + 4) as usize;
+        let start = ad_at + l_ea;
+        let ads = fe
+            .get(start..start + l_ad)
+            .ok_or_else(|| invalid("allocation descriptors overflow block"))?;
+
+        let data = match ad_type {
+            0 => UdfData::Extents(
+                ads.chunks_exact(8)
+                    .map_while(|ad| self.extent(le32(ad, 0), le32(ad, 4)))
+                    .collect(),
+            ),
+            1 => UdfData::Extents(
+                ads.chunks_exact(16)
+                    .map_while(|ad| self.extent(le32(ad, 0), le32(ad, 4)))
+                    .collect(),
+            ),
+            3 => UdfData::Inline(ads.to_vec()),
+            _ => return Err(invalid("unsupported allocation descriptor type")),
+        };
+        Ok(UdfEntry {
+            name,
+            is_dir,
+            size,
+            icb: lbn,
+            data,
+        })
+    }
+
+    /// Decode one descriptor; `None` ends the list (continuations are not followed)
+    fn extent(&self, raw_len: u32, lbn: u32) -> Option<Extent> {
+        let len = (raw_len & 0x3FFF_FFFF) as u64;
+        match raw_len >> 30 {
+            _ if len == 0 => None,
+            0 => Some(Extent::Recorded(self.block_offset(lbn), len)),
+            1 | 2 => Some(Extent::Zero(len)),
+            _ => None,
+        }
+    }
+}
+
+impl<R: Read + Seek> ImageFs for UdfImage<R> {
+    type Entry = UdfEntry;
+
+    fn root(&self) -> UdfEntry {
+        self.root.clone()
+    }
+
+    fn read_dir(&mut self, dir: &UdfEntry) -> io::Result<Vec<UdfEntry>> {
+        if !dir.is_dir {
+            return Err(invalid("not a directory"));
+        }
+        let data = self.read_all(dir, 16 * 1024 * 1024)?;
+        let mut out = Vec::new();
+        let mut off = 0usize;
+        while off + 38 <= data.len() {
+            let fid = &data[off..];
+            if tag_id(fid) != TAG_FILE_ID {
+                break;
+            }
+            let characteristics = fid[18];
+            let l_fi = fid[19] as usize;
+            let icb_lbn = le32(fid, 24);
+            let l_iu = le16(fid, 36) as usize;
+            let name_at = 38 + l_iu;
+            let total = (name_at + l_fi).div_ceil(4) * 4;
+            if off + name_at + l_fi > data.len() {
+                break;
+            }
+            off += total;
+            if characteristics & (FID_PARENT | FID_DELETED) != 0 || l_fi == 0 {
+                continue;
+            }
+            let name = decode_dstring(&fid[name_at..name_at + l_fi]);
+            let mut entry = self.read_file_entry(icb_lbn, name)?;
+            entry.is_dir |= characteristics & FID_DIRECTORY != 0;
+            out.push(entry);
+        }
+        Ok(out)
+    }
+
+    fn copy_to(
+        &mut self,
+        entry: &UdfEntry,
+        dst: &mut dyn Write,
+        on_progress: &mut dyn FnMut(u64),
+    ) -> io::Result<u64> {
+        let extents = match &entry.data {
+            UdfData::Inline(bytes) => {
+                let n = (entry.size as usize).min(bytes.len());
+                dst.write_all(&bytes[..n])?;
+                on_progress(n as u64);
+                return Ok(n as u64);
+            }
+            UdfData::Extents(extents) => extents.clone(),
+        };
+        let mut buf = vec![0u8; 1024 * 1024];
+        let mut left_total = entry.size;
+        let mut copied = 0u64;
+        for ext in extents {
+            if left_total == 0 {
+                break;
+            }
+            let (offset, len) = match ext {
+                Extent::Recorded(off, len) => (Some(off), len),
+                Extent::Zero(len) => (None, len),
+            };
+            let mut left = len.min(left_total);
+            if let Some(off) = offset {
+                self.reader.seek(SeekFrom::Start(off))?;
+            }
+            while left > 0 {
+                let n = left.min(buf.len() as u64) as usize;
+                match offset {
+                    Some(_) => self.reader.read_exact(&mut buf[..n])?,
+                    None => buf[..n].fill(0),
+                }
+                dst.write_all(&buf[..n])?;
+                left -= n as u64;
+                left_total -= n as u64;
+                copied += n as u64;
+                on_progress(n as u64);
+            }
+        }
+        Ok(copied)
+    }
+}
+
+/// Decode an OSTA compressed unicode string (8-bit or UCS-2 big endian)
+fn decode_dstring(raw: &[u8]) -> String {
+    match raw.first() {
+        Some(8) => raw[1..].iter().map(|&b| b as char).collect(),
+        Some(16) => {
+            let units: Vec<u16> = raw[1..]
+                .chunks_exact(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => String::from_utf8_lossy(raw).to_string(),
+    }
+}
+
+fn read_sector<R: Read + Seek>(reader: &mut R, offset: u64) -> io::Result<Vec<u8>> {
+    let mut buf = vec![0u8; SECTOR_SIZE as usize];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn tag_id(d: &[u8]) -> u16 {
+    le16(d, 0)
+}
+
+fn le16(d: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([d[at], d[at + 1]])
+}
+
+fn le32(d: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([d[at], d[at + 1], d[at + 2], d[at + 3]])
+}
+
+fn le64(d: &[u8], at: usize) -> u64 {
+    let mut b = [0u8; 8];
+    b.copy_from_slice(&d[at..at + 8]);
+    u64::from_le_bytes(b)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const PART: usize = 300;
+
+    fn put16(d: &mut [u8], at: usize, v: u16) {
+        d[at..at + 2].copy_from_slice(&v.to_le_bytes());
+    }
+
+    fn put32(d: &mut [u8], at: usize, v: u32) {
+        d[at..at + 4].copy_from_slice(&v.to_le_bytes());
+    }
+
+    fn sector(img: &mut [u8], n: usize) -> &mut [u8] {
+        &mut img[n * 2048..(n + 1) * 2048]
+    }
+
+    fn fid(name: &str, lbn: u32, characteristics: u8) -> Vec<u8> {
+        let mut ident = vec![8u8];
+        ident.extend(name.bytes());
+        let len = (38 + ident.len()).div_ceil(4) * 4;
+        let mut f = vec![0u8; len];
+        put16(&mut f, 0, TAG_FILE_ID);
+        f[18] = characteristics;
+        f[19] = if name.is_empty() {
+            0
+        } else {
+            ident.len() as u8
+        };
+        put32(&mut f, 24, lbn);
+        if !name.is_empty() {
+            f[38..38 + ident.len()].copy_from_slice(&ident);
+        }
+        f
+    }
+
+    fn file_entry(img: &mut [u8], lbn: usize, dir: bool, size: u64, ads: &[u8], ad_type: u16) {
+        let fe = sector(img, PART + lbn);
+        put16(fe, 0, TAG_FILE_ENTRY);
+        fe[27] = if dir { FILE_TYPE_DIRECTORY } else { 5 };
+        put16(fe, 34, ad_type);
+        fe[56..64].copy_from_slice(&size.to_le_bytes());
+        put32(fe, 172, ads.len() as u32);
+        fe[176..176 + ads.len()].copy_from_slice(ads);
+    }
+
+    /// `/sources/install.wim` (4 bytes, short_ad) under an embedded root
+    fn sample_udf() -> Vec<u8> {
+        let mut img = vec![0u8; 2048 * (PART + 8)];
+        let avdp = sector(&mut img, 256);
+        put16(avdp, 0, TAG_ANCHOR);
+        put32(avdp, 16, 3 * 2048);
+        put32(avdp, 20, 257);
+        let pd = sector(&mut img, 257);
+        put16(pd, 0, TAG_PARTITION);
+        put32(pd, 188, PART as u32);
+        let lvd = sector(&mut img, 258);
+        put16(lvd, 0, TAG_LOGICAL_VOLUME);
+        put32(lvd, 212, 2048);
+        put32(lvd, 252, 0);
+        put16(sector(&mut img, 259), 0, TAG_TERMINATING);
+
+        let fsd = sector(&mut img, PART);
+        put16(fsd, 0, TAG_FILE_SET);
+        put32(fsd, 404, 1);
+
+        let mut root = fid("", 1, FID_DIRECTORY | FID_PARENT);
+        root.extend(fid("sources", 2, FID_DIRECTORY));
+        file_entry(&mut img, 1, true, root.len() as u64, &root, 3);
+
+        let mut sources = fid("", 1, FID_DIRECTORY | FID_PARENT);
+        sources.extend(fid("install.wim", 3, 0));
+        file_entry(&mut img, 2, true, sources.len() as u64, &sources, 3);
+
+        let mut ad = [0u8; 8];
+        put32(&mut ad, 0, 4);
+        put32(&mut ad, 4, 4);
+        file_entry(&mut img, 3, false, 4, &ad, 0);
+        sector(&mut img, PART + 4)[..4].copy_from_slice(b"WIM!");
+        img
+    }
+
+    #[test]
+    fn walks_tree_and_reads_file() {
+        let mut udf = UdfImage::open(Cursor::new(sample_udf())).unwrap();
+        let wim = udf.lookup("/SOURCES/INSTALL.WIM").unwrap().unwrap();
+        assert_eq!(wim.size, 4);
+        assert_eq!(udf.read_all(&wim, 16).unwrap(), b"WIM!");
+
+        let paths: Vec<String> = udf.walk().unwrap().into_iter().map(|(p, _)| p).collect();
+        assert_eq!(paths, vec!["sources", "sources/install.wim"]);
+    }
+
+    #[test]
+    fn rejects_plain_iso9660() {
+        let img = crate::domain::iso9660::tests::sample_image("X");
+        assert!(UdfImage::open(Cursor::new(img)).is_err());
+    }
+}
@@ -15,6 +15,25 @@ impl ByteSize {
     pub fn zero() -> Self {
         Self(0)
     }
+
+    /// Parse `512`, `64K`, `100M`, `1.5G` or `2GiB` (binary units, case-insensitive)
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        let split = s
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(s.len());
+        let (num, unit) = s.split_at(split);
+        let value: f64 = num.parse().ok()?;
+        let mult: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+            "" | "b" => 1,
+            "k" | "kb" | "kib" => 1 << 10,
+            "m" | "mb" | "mib" => 1 << 20,
+            "g" | "gb" | "gib" => 1 << 30,
+            "t" | "tb" | "tib" => 1 << 40,
+            _ => return None,
+        };
+        (value >= 0.0).then_some(Self((value * mult as f64) as u64))
+    }
 }
 
 impl From<u64> for ByteSize {
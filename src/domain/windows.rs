@@ -0,0 +1,384 @@
+//! Windows installer mode.
+//!
+//! Windows ISOs are not hybrid images: copying them block for block
+//! gives a stick no firmware will boot. Instead the stick gets a fresh
+//! partition table and filesystem and the ISO contents are copied file
+//! by file through the UDF (or ISO9660) reader.
+//!
+//! FAT32 boots everywhere but caps files at 4 GiB, so an oversized
+//! `sources/install.wim` is split into `.swm` parts with `wimlib-imagex`.
+//! The NTFS layout avoids the split and adds a small UEFI:NTFS partition
+//! whose image is taken from `$FERRIC_UEFI_NTFS`.
+
+use crate::{
+    adapters::platform,
+    app::{effects::Mounter, msg::Msg},
+    domain::{
+        image_fs::{ImageEntry, ImageFs},
+        iso9660::IsoImage,
+        partition::{self, ALIGN, PartKind, PartitionScheme, SECTOR},
+        paths::{DevicePath, IsoPath},
+        udf::UdfImage,
+        writer::{self, MountGuard, Progress, ScratchDir},
+    },
+    event::Event,
+};
+use std::{
+    ffi::OsStr,
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Largest file FAT32 can hold
+pub const FAT32_MAX_FILE: u64 = 4 * 1024 * 1024 * 1024 - 1;
+/// Size of each split `.swm` part in MiB
+const SWM_PART_MIB: &str = "3800";
+const UEFI_NTFS_ENV: &str = "FERRIC_UEFI_NTFS";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WindowsFs {
+    #[default]
+    Fat32,
+    Ntfs,
+}
+
+impl std::fmt::Display for WindowsFs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WindowsFs::Fat32 => f.write_str("FAT32"),
+            WindowsFs::Ntfs => f.write_str("NTFS + UEFI:NTFS"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct WindowsOptions {
+    pub scheme: PartitionScheme,
+    pub filesystem: WindowsFs,
+}
+
+/// True if the image carries a partition table of its own (isohybrid)
+/// and can therefore be written block for block.
+pub fn is_hybrid(path: &Path) -> bool {
+    let Ok(mut f) = File::open(path) else {
+        return false;
+    };
+    let mut mbr = [0u8; 512];
+    if f.read_exact(&mut mbr).is_err() || mbr[510..512] != [0x55, 0xAA] {
+        return false;
+    }
+    (0..4).any(|i| mbr[446 + i * 16 + 4] != 0)
+}
+
+pub fn write_windows(
+    iso_path: IsoPath,
+    device_path: DevicePath,
+    options: WindowsOptions,
+    mounter: &dyn Mounter,
+    tx: tokio::sync::mpsc::UnboundedSender<Event>,
+) {
+    let result = run(&iso_path, &device_path, options, mounter, &tx);
+    let _: Result<_, _> = tx.send(Event::App(Msg::WriteFinished(result)));
+}
+
+fn run(
+    iso_path: &IsoPath,
+    device_path: &DevicePath,
+    options: WindowsOptions,
+    mounter: &dyn Mounter,
+    tx: &tokio::sync::mpsc::UnboundedSender<Event>,
+) -> Result<(), String> {
+    let label = iso_label(iso_path);
+    let file = File::open(iso_path.as_path()).map_err(|e| format!("Failed to open ISO: {}", e))?;
+    match UdfImage::open(file) {
+        Ok(mut udf) => install(&mut udf, &label, device_path, options, mounter, tx),
+        Err(_) => {
+            let file =
+                File::open(iso_path.as_path()).map_err(|e| format!("Failed to open ISO: {}", e))?;
+            let mut iso = IsoImage::open(file).map_err(|e| format!("Failed to read ISO: {}", e))?;
+            install(&mut iso, &label, device_path, options, mounter, tx)
+        }
+    }
+}
+
+fn install<F: ImageFs>(
+    image: &mut F,
+    label: &str,
+    device_path: &DevicePath,
+    options: WindowsOptions,
+    mounter: &dyn Mounter,
+    tx: &tokio::sync::mpsc::UnboundedSender<Event>,
+) -> Result<(), String> {
+    let entries = contents(image)?;
+    let total: u64 = entries
+        .iter()
+        .filter(|(_, e)| !e.is_dir())
+        .map(|(_, e)| e.size())
+        .sum();
+
+    let split_wim = options.filesystem == WindowsFs::Fat32
+        && preflight_fat32(
+            &entries
+                .iter()
+                .map(|(p, e)| (p.as_str(), e.size()))
+                .collect::<Vec<_>>(),
+        )?;
+    let uefi_ntfs = match options.filesystem {
+        WindowsFs::Ntfs => Some(uefi_ntfs_image()?),
+        WindowsFs::Fat32 => None,
+    };
+
+    let _: Result<_, _> = tx.send(Event::App(Msg::WriteStarted { total }));
+    writer::warn(
+        tx,
+        device_path,
+        mounter.unmount_partitions_of(device_path.as_path()),
+    );
+
+    let disk_bytes = platform::device_size(device_path.as_path())
+        .map_err(|e| format!("Failed to size device {}: {}", device_path, e))?;
+    let disk_sectors = disk_bytes / SECTOR;
+    let parts = match options.filesystem {
+        WindowsFs::Fat32 => partition::layout(
+            options.scheme,
+            disk_sectors,
+            &[(PartKind::Fat32, None, "WINSETUP")],
+        ),
+        WindowsFs::Ntfs => {
+            let uefi_sectors = ALIGN;
+            let data = (disk_sectors.saturating_sub(3 * ALIGN + uefi_sectors)) / ALIGN * ALIGN;
+            partition::layout(
+                options.scheme,
+                disk_sectors,
+                &[
+                    (PartKind::Ntfs, Some(data), "WINSETUP"),
+                    (PartKind::EfiSystem, None, "UEFI_NTFS"),
+                ],
+            )
+        }
+    };
+    let mut dev = fs::OpenOptions::new()
+        .write(true)
+        .open(device_path.as_path())
+        .map_err(|e| format!("Failed to open device {}: {}", device_path, e))?;
+    partition::write_table(&mut dev, options.scheme, disk_sectors, &parts)
+        .map_err(|e| format!("Failed to write partition table: {}", e))?;
+    dev.sync_all()
+        .map_err(|e| format!("sync_all error: {}", e))?;
+    drop(dev);
+    writer::warn(tx, device_path, mounter.partprobe(device_path.as_path()));
+
+    let data_part = platform::partition_path(device_path.as_path(), 1);
+    if !platform::wait_for_node(&data_part, Duration::from_secs(10)) {
+        return Err(format!("Partition {} did not appear", data_part.display()));
+    }
+    match options.filesystem {
+        WindowsFs::Fat32 => {
+            let fat_label: String = label.chars().take(11).collect();
+            platform::run_tool(
+                "mkfs.vfat",
+                &["-F", "32", "-n", &fat_label, &data_part.to_string_lossy()],
+            )?;
+        }
+        WindowsFs::Ntfs => {
+            platform::run_tool(
+                "mkfs.ntfs",
+                &["-Q", "-L", label, &data_part.to_string_lossy()],
+            )?;
+            let uefi_part = platform::partition_path(device_path.as_path(), 2);
+            if !platform::wait_for_node(&uefi_part, Duration::from_secs(10)) {
+                return Err(format!("Partition {} did not appear", uefi_part.display()));
+            }
+            if let Some(image) = &uefi_ntfs {
+                copy_file_into(image, &uefi_part)?;
+            }
+        }
+    }
+
+    let guard = MountGuard::mount(&data_part, "win")?;
+    let mount_dir = guard.path().to_path_buf();
+
+    let mut progress = Progress::new(total, tx);
+    for (path, entry) in &entries {
+        let dst = mount_dir.join(path);
+        if entry.is_dir() {
+            fs::create_dir_all(&dst).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+            continue;
+        }
+        if split_wim && entry.size() > FAT32_MAX_FILE {
+            split_into(image, entry, &dst, &mut progress)?;
+            continue;
+        }
+        let mut out =
+            File::create(&dst).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+        image
+            .copy_to(entry, &mut out, &mut |n| progress.add(n))
+            .map_err(|e| format!("Copy error on {}: {}", path, e))?;
+        out.sync_all()
+            .map_err(|e| format!("sync_all error on {}: {}", path, e))?;
+    }
+
+    writer::warn(tx, device_path, guard.unmount());
+    writer::warn(tx, device_path, mounter.partprobe(device_path.as_path()));
+    Ok(())
+}
+
+/// Everything in the image, refusing names that would land outside the
+/// mount point when joined to it
+fn contents<F: ImageFs>(image: &mut F) -> Result<Vec<(String, F::Entry)>, String> {
+    let entries = image
+        .walk()
+        .map_err(|e| format!("Failed to read ISO contents: {}", e))?;
+    for (path, entry) in &entries {
+        let name = entry.name();
+        if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\0']) {
+            return Err(format!(
+                "The ISO lists an unsafe file name {:?} (in {:?}); refusing to install it",
+                name, path
+            ));
+        }
+    }
+    Ok(entries)
+}
+
+/// Check that everything fits on FAT32. Returns whether `install.wim` needs splitting.
+fn preflight_fat32(files: &[(&str, u64)]) -> Result<bool, String> {
+    let mut split = false;
+    for (path, size) in files {
+        if *size <= FAT32_MAX_FILE {
+            continue;
+        }
+        if !path.eq_ignore_ascii_case("sources/install.wim") {
+            return Err(format!(
+                "{} is larger than 4 GiB and cannot live on FAT32; use the NTFS layout",
+                path
+            ));
+        }
+        split = true;
+    }
+    if split && !platform::has_command("wimlib-imagex") {
+        return Err(
+            "sources/install.wim is larger than 4 GiB and wimlib-imagex is not installed; \
+             install wimlib or switch to the NTFS layout"
+                .to_string(),
+        );
+    }
+    Ok(split)
+}
+
+/// Extract an oversized WIM to scratch space and split it into `install.swm` parts
+fn split_into<F: ImageFs>(
+    image: &mut F,
+    entry: &F::Entry,
+    dst: &Path,
+    progress: &mut Progress,
+) -> Result<(), String> {
+    use std::os::unix::fs::OpenOptionsExt;
+    let dir = ScratchDir::new("wim")?;
+    let scratch = dir.path().join("install.wim");
+    let result = (|| {
+        let mut tmp = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .custom_flags(libc::O_NOFOLLOW)
+            .open(&scratch)
+            .map_err(|e| format!("Failed to create {}: {}", scratch.display(), e))?;
+        image
+            .copy_to(entry, &mut tmp, &mut |n| progress.add(n))
+            .map_err(|e| format!("Failed to extract install.wim: {}", e))?;
+        drop(tmp);
+        let swm = dst.with_extension("swm");
+        platform::run_tool(
+            "wimlib-imagex",
+            &[
+                OsStr::new("split"),
+                scratch.as_os_str(),
+                swm.as_os_str(),
+                OsStr::new(SWM_PART_MIB),
+            ],
+        )
+    })();
+    let _ = fs::remove_file(&scratch);
+    result
+}
+
+fn uefi_ntfs_image() -> Result<PathBuf, String> {
+    let path = std::env::var_os(UEFI_NTFS_ENV)
+        .map(PathBuf::from)
+        .ok_or_else(|| {
+            format!(
+                "NTFS layout needs a UEFI:NTFS image; set {} to its path",
+                UEFI_NTFS_ENV
+            )
+        })?;
+    if !path.is_file() {
+        return Err(format!("UEFI:NTFS image {} not found", path.display()));
+    }
+    Ok(path)
+}
+
+fn copy_file_into(src: &Path, dst: &Path) -> Result<(), String> {
+    let mut input =
+        File::open(src).map_err(|e| format!("Failed to open {}: {}", src.display(), e))?;
+    let mut output = fs::OpenOptions::new()
+        .write(true)
+        .open(dst)
+        .map_err(|e| format!("Failed to open {}: {}", dst.display(), e))?;
+    output
+        .seek(SeekFrom::Start(0))
+        .and_then(|_| std::io::copy(&mut input, &mut output))
+        .and_then(|_| output.sync_all())
+        .map_err(|e| format!("Failed to write {}: {}", dst.display(), e))
+}
+
+/// Volume label of the ISO9660 side, used for the stick's filesystem label
+fn iso_label(iso_path: &IsoPath) -> String {
+    File::open(iso_path.as_path())
+        .ok()
+        .and_then(|f| IsoImage::open(f).ok())
+        .map(|iso| iso.volume_id().to_string())
+        .filter(|l| !l.is_empty())
+        .unwrap_or_else(|| "WINSETUP".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_files_need_no_split() {
+        let files = [("sources/install.wim", 3_000_000_000), ("setup.exe", 100)];
+        assert_eq!(preflight_fat32(&files), Ok(false));
+    }
+
+    #[test]
+    fn oversized_non_wim_file_is_rejected() {
+        let files = [("sources/huge.esd", FAT32_MAX_FILE + 1)];
+        let err = preflight_fat32(&files).unwrap_err();
+        assert!(err.contains("NTFS"));
+    }
+
+    #[test]
+    fn names_escaping_the_stick_fail_the_install() {
+        use crate::domain::image_fs::mem::MemFs;
+        let mut clean = MemFs::new();
+        let sources = clean.dir(0, "sources");
+        clean.file(sources, "boot.wim", b"wim");
+        assert_eq!(contents(&mut clean).unwrap().len(), 2);
+
+        for bad in ["..", ".", "", "../../etc/cron.d/x", "/etc/passwd", "a\0b"] {
+            let mut image = MemFs::new();
+            let sources = image.dir(0, "sources");
+            image.file(sources, bad, b"payload");
+            let err = contents(&mut image).unwrap_err();
+            assert!(err.contains("unsafe file name"), "{}: {}", bad, err);
+        }
+    }
+
+    #[test]
+    fn non_image_is_not_hybrid() {
+        assert!(!is_hybrid(Path::new("/nonexistent/ferric.iso")));
+    }
+}
@@ -1,110 +1,111 @@
 use crate::{
-    app::msg::Msg,
+    app::{effects::Mounter, msg::Msg},
     domain::paths::{DevicePath, IsoPath},
     event::Event,
 };
 use std::{
-    fs::OpenOptions,
-    io::{Read, Write},
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 
 pub fn write_image(
     iso_path: IsoPath,
     device_path: DevicePath,
+    buffer_size: usize,
+    mounter: &dyn Mounter,
     tx: tokio::sync::mpsc::UnboundedSender<Event>,
 ) {
-    let _ = crate::adapters::platform::unmount_partitions_of(device_path.as_path());
-    let mut src = match std::fs::File::open(iso_path.as_path()) {
-        Ok(f) => f,
-        Err(e) => {
-            let _: Result<_, _> = tx.send(Event::App(Msg::WriteFinished(Err(format!(
-                "Failed to open ISO: {}",
-                e
-            )))));
-            return;
-        }
-    };
-    let total = match src.metadata().map(|m| m.len()) {
-        Ok(sz) => sz,
-        Err(e) => {
-            let _: Result<_, _> = tx.send(Event::App(Msg::WriteFinished(Err(format!(
-                "Failed to stat ISO: {}",
-                e
-            )))));
-            return;
-        }
-    };
+    write_onto(&iso_path, &device_path, buffer_size, mounter, &tx, || {
+        OpenOptions::new()
+            .write(true)
+            .open(device_path.as_path())
+            .map_err(|e| format!("Failed to open device {}: {}", device_path, e))
+    });
+}
+
+/// Unmount, copy the image onto what `open_device` hands back, re-read
+/// the partition table and send `WriteFinished`
+pub(crate) fn write_onto<D: Sink>(
+    iso_path: &IsoPath,
+    device_path: &DevicePath,
+    buffer_size: usize,
+    mounter: &dyn Mounter,
+    tx: &tokio::sync::mpsc::UnboundedSender<Event>,
+    open_device: impl FnOnce() -> Result<D, String>,
+) {
+    warn(
+        tx,
+        device_path,
+        mounter.unmount_partitions_of(device_path.as_path()),
+    );
+    let result = copy_image(iso_path, buffer_size, tx, open_device);
+    if result.is_ok() {
+        warn(tx, device_path, mounter.partprobe(device_path.as_path()));
+    }
+    let _: Result<_, _> = tx.send(Event::App(Msg::WriteFinished(result)));
+}
+
+/// What an image is copied onto: a device node, or a simulated one
+pub(crate) trait Sink: Write {
+    /// Wait until the data is on stable storage
+    fn sync(&mut self) -> io::Result<()>;
+}
+
+impl Sink for File {
+    fn sync(&mut self) -> io::Result<()> {
+        self.sync_all()
+    }
+}
+
+/// Stream the image onto the device `open_device` hands back, sending
+/// `WriteStarted` and `WriteProgress` along the way
+pub(crate) fn copy_image<D: Sink>(
+    iso_path: &IsoPath,
+    buffer_size: usize,
+    tx: &tokio::sync::mpsc::UnboundedSender<Event>,
+    open_device: impl FnOnce() -> Result<D, String>,
+) -> Result<(), String> {
+    let mut src =
+        File::open(iso_path.as_path()).map_err(|e| format!("Failed to open ISO: {}", e))?;
+    let total = src
+        .metadata()
+        .map(|m| m.len())
+        .map_err(|e| format!("Failed to stat ISO: {}", e))?;
     let _: Result<_, _> = tx.send(Event::App(Msg::WriteStarted { total }));
-    let mut dst = match OpenOptions::new().write(true).open(device_path.as_path()) {
-        Ok(f) => f,
-        Err(e) => {
-            let _: Result<_, _> = tx.send(Event::App(Msg::WriteFinished(Err(format!(
-                "Failed to open device {}: {}",
-                device_path, e
-            )))));
-            return;
-        }
-    };
+    let mut dst = open_device()?;
 
-    let mut buf = vec![0u8; 4 * 1024 * 1024];
+    let mut buf = vec![0u8; buffer_size];
     let start = std::time::Instant::now();
     let mut written: u64 = 0;
 
     loop {
-        match src.read(&mut buf) {
-            Ok(0) => break,
-            Ok(n) => {
-                let mut off = 0;
-                while off < n {
-                    match dst.write(&buf[off..n]) {
-                        Ok(w) => {
-                            off += w;
-                            written += w as u64;
-                        }
-                        Err(e) => {
-                            let _: Result<_, _> = tx.send(Event::App(Msg::WriteFinished(Err(
-                                format!("Write error: {}", e),
-                            ))));
-                            return;
-                        }
-                    }
-                }
-                let elapsed = start.elapsed().as_secs_f64().max(0.000_001);
-                let bps = (written as f64) / elapsed;
-                let _: Result<_, _> = tx.send(Event::App(Msg::WriteProgress {
-                    written,
-                    total,
-                    bps,
-                }));
-            }
-            Err(e) => {
-                let _: Result<_, _> = tx.send(Event::App(Msg::WriteFinished(Err(format!(
-                    "Read error: {}",
-                    e
-                )))));
-                return;
-            }
+        let n = src
+            .read(&mut buf)
+            .map_err(|e| format!("Read error: {}", e))?;
+        if n == 0 {
+            break;
         }
+        let mut off = 0;
+        while off < n {
+            let w = dst
+                .write(&buf[off..n])
+                .map_err(|e| format!("Write error: {}", e))?;
+            off += w;
+            written += w as u64;
+        }
+        let elapsed = start.elapsed().as_secs_f64().max(0.000_001);
+        let bps = (written as f64) / elapsed;
+        let _: Result<_, _> = tx.send(Event::App(Msg::WriteProgress {
+            written,
+            total,
+            bps,
+        }));
     }
 
-    if let Err(e) = dst.flush() {
-        let _: Result<_, _> = tx.send(Event::App(Msg::WriteFinished(Err(format!(
-            "Flush error: {}",
-            e
-        )))));
-        return;
-    }
-    if let Err(e) = dst.sync_all() {
-        let _: Result<_, _> = tx.send(Event::App(Msg::WriteFinished(Err(format!(
-            "sync_all error: {}",
-            e
-        )))));
-        return;
-    }
-
-    let _: Result<(), String> = crate::adapters::platform::partprobe(device_path.as_path());
-
-    let _: Result<_, _> = tx.send(Event::App(Msg::WriteFinished(Ok(()))));
+    dst.flush().map_err(|e| format!("Flush error: {}", e))?;
+    dst.sync().map_err(|e| format!("sync_all error: {}", e))
 }
 
 pub fn verify_image(
@@ -113,63 +114,257 @@ pub fn verify_image(
     size: u64,
     tx: tokio::sync::mpsc::UnboundedSender<Event>,
 ) {
-    let mut iso = match std::fs::File::open(iso_path.as_path()) {
-        Ok(f) => f,
-        Err(e) => {
-            let _: Result<_, _> = tx.send(Event::App(Msg::VerifyFinished(Err(format!(
-                "Failed to open ISO for verify: {}",
-                e
-            )))));
-            return;
-        }
-    };
-    let mut dev = match std::fs::File::open(device_path.as_path()) {
-        Ok(f) => f,
-        Err(e) => {
-            let _: Result<_, _> = tx.send(Event::App(Msg::VerifyFinished(Err(format!(
-                "Failed to open device for verify: {}",
-                e
-            )))));
-            return;
-        }
-    };
+    verify_against(&iso_path, size, &tx, || {
+        File::open(device_path.as_path())
+            .map_err(|e| format!("Failed to open device for verify: {}", e))
+    });
+}
+
+/// `VerifyStarted`, progress and `VerifyFinished` for a compare against the
+/// device `open_device` hands back
+pub(crate) fn verify_against<D: Read>(
+    iso_path: &IsoPath,
+    size: u64,
+    tx: &tokio::sync::mpsc::UnboundedSender<Event>,
+    open_device: impl FnOnce() -> Result<D, String>,
+) {
     let _: Result<_, _> = tx.send(Event::App(Msg::VerifyStarted { total: size }));
+    let start = std::time::Instant::now();
+    let result = open_device().and_then(|dev| {
+        compare_with(iso_path.as_path(), dev, size, &mut |checked| {
+            let elapsed = start.elapsed().as_secs_f64().max(0.000_001);
+            let bps = (checked as f64) / elapsed;
+            let _: Result<_, _> = tx.send(Event::App(Msg::VerifyProgress {
+                checked,
+                total: size,
+                bps,
+            }));
+        })
+    });
+    let _: Result<_, _> = tx.send(Event::App(Msg::VerifyFinished(result)));
+}
+
+/// Pass on a best-effort step (unmount, partition re-read) that failed
+/// without stopping the write
+pub(crate) fn warn(
+    tx: &tokio::sync::mpsc::UnboundedSender<Event>,
+    device: &DevicePath,
+    result: Result<(), String>,
+) {
+    if let Err(e) = result {
+        let _: Result<_, _> = tx.send(Event::App(Msg::WriteWarning(format!("{}: {}", device, e))));
+    }
+}
+
+/// Byte-compare the first `size` bytes of the image and the device,
+/// reporting the running total after each chunk
+pub(crate) fn compare_image(
+    iso_path: &Path,
+    device_path: &Path,
+    size: u64,
+    on_progress: &mut dyn FnMut(u64),
+) -> Result<(), String> {
+    let dev =
+        File::open(device_path).map_err(|e| format!("Failed to open device for verify: {}", e))?;
+    compare_with(iso_path, dev, size, on_progress)
+}
+
+fn compare_with(
+    iso_path: &Path,
+    mut dev: impl Read,
+    size: u64,
+    on_progress: &mut dyn FnMut(u64),
+) -> Result<(), String> {
+    let mut iso =
+        File::open(iso_path).map_err(|e| format!("Failed to open ISO for verify: {}", e))?;
     let mut left = size;
     let mut checked: u64 = 0;
     let mut buf_iso = vec![0u8; 4 * 1024 * 1024];
     let mut buf_dev = vec![0u8; 4 * 1024 * 1024];
-    let start = std::time::Instant::now();
     while left > 0 {
         let to_read = std::cmp::min(left, buf_iso.len() as u64) as usize;
-        if let Err(e) = iso.read_exact(&mut buf_iso[..to_read]) {
-            let _: Result<_, _> = tx.send(Event::App(Msg::VerifyFinished(Err(format!(
-                "ISO read error during verify: {}",
-                e
-            )))));
-            return;
-        }
-        if let Err(e) = dev.read_exact(&mut buf_dev[..to_read]) {
-            let _: Result<_, _> = tx.send(Event::App(Msg::VerifyFinished(Err(format!(
-                "Device read error during verify: {}",
-                e
-            )))));
-            return;
-        }
+        iso.read_exact(&mut buf_iso[..to_read])
+            .map_err(|e| format!("ISO read error during verify: {}", e))?;
+        dev.read_exact(&mut buf_dev[..to_read])
+            .map_err(|e| format!("Device read error during verify: {}", e))?;
         if buf_iso[..to_read] != buf_dev[..to_read] {
-            let _: Result<_, _> = tx.send(Event::App(Msg::VerifyFinished(Err(
-                "Mismatch between ISO and device".to_string(),
-            ))));
-            return;
+            return Err("Mismatch between ISO and device".to_string());
         }
         left -= to_read as u64;
         checked += to_read as u64;
-        let elapsed = start.elapsed().as_secs_f64().max(0.000_001);
-        let bps = (checked as f64) / elapsed;
-        let _: Result<_, _> = tx.send(Event::App(Msg::VerifyProgress {
-            checked,
-            total: size,
-            bps,
+        on_progress(checked);
+    }
+    Ok(())
+}
+
+/// Copy a regular file onto a mounted filesystem, reporting through `progress`.
+///
+/// The file-level counterpart of [`write_image`], used by the modes that
+/// format the stick themselves.
+pub(crate) fn copy_file(src: &Path, dst: &Path, progress: &mut Progress) -> Result<(), String> {
+    let mut input =
+        File::open(src).map_err(|e| format!("Failed to open {}: {}", src.display(), e))?;
+    let mut output =
+        File::create(dst).map_err(|e| format!("Failed to create {}: {}", dst.display(), e))?;
+    let mut buf = vec![0u8; 4 * 1024 * 1024];
+    loop {
+        let n = input
+            .read(&mut buf)
+            .map_err(|e| format!("Read error on {}: {}", src.display(), e))?;
+        if n == 0 {
+            break;
+        }
+        output
+            .write_all(&buf[..n])
+            .map_err(|e| format!("Write error on {}: {}", dst.display(), e))?;
+        progress.add(n as u64);
+    }
+    output
+        .sync_all()
+        .map_err(|e| format!("sync_all error on {}: {}", dst.display(), e))
+}
+
+/// Throttled `WriteProgress` reporting for multi-file writes
+pub(crate) struct Progress<'a> {
+    total: u64,
+    written: u64,
+    start: Instant,
+    last_sent: Instant,
+    tx: &'a tokio::sync::mpsc::UnboundedSender<Event>,
+}
+
+impl<'a> Progress<'a> {
+    pub(crate) fn new(total: u64, tx: &'a tokio::sync::mpsc::UnboundedSender<Event>) -> Self {
+        let now = Instant::now();
+        Self {
+            total,
+            written: 0,
+            start: now,
+            last_sent: now,
+            tx,
+        }
+    }
+
+    pub(crate) fn add(&mut self, n: u64) {
+        self.written += n;
+        if self.last_sent.elapsed() < Duration::from_millis(100) && self.written < self.total {
+            return;
+        }
+        self.last_sent = Instant::now();
+        let elapsed = self.start.elapsed().as_secs_f64().max(0.000_001);
+        let _: Result<_, _> = self.tx.send(Event::App(Msg::WriteProgress {
+            written: self.written,
+            total: self.total,
+            bps: self.written as f64 / elapsed,
         }));
     }
-    let _: Result<_, _> = tx.send(Event::App(Msg::VerifyFinished(Ok(()))));
+}
+
+/// A new directory under the temp dir that only its owner can enter
+/// (`mkdtemp`, mode 0700), so nobody else can plant files or links in it.
+/// Removed when dropped if it is empty by then.
+pub(crate) struct ScratchDir(PathBuf);
+
+impl ScratchDir {
+    pub(crate) fn new(tag: &str) -> Result<Self, String> {
+        use std::os::unix::ffi::OsStringExt;
+        let template = std::env::temp_dir().join(format!("ferric-{}-XXXXXX", tag));
+        let mut bytes = template.into_os_string().into_vec();
+        bytes.push(0);
+        // SAFETY: `bytes` is a writable, NUL-terminated template; mkdtemp
+        // only replaces the trailing X's in place.
+        if unsafe { libc::mkdtemp(bytes.as_mut_ptr().cast()) }.is_null() {
+            return Err(format!(
+                "Failed to create a scratch directory: {}",
+                io::Error::last_os_error()
+            ));
+        }
+        bytes.pop();
+        Ok(Self(PathBuf::from(std::ffi::OsString::from_vec(bytes))))
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir(&self.0);
+    }
+}
+
+/// A partition mounted on a [`ScratchDir`].
+///
+/// [`unmount`](Self::unmount) reports whether the unmount worked; when
+/// dropped instead (early return) it unmounts and only logs a failure.
+pub(crate) struct MountGuard {
+    dir: ScratchDir,
+    mounted: bool,
+}
+
+impl MountGuard {
+    pub(crate) fn mount(source: &Path, tag: &str) -> Result<Self, String> {
+        let dir = ScratchDir::new(tag)?;
+        crate::adapters::platform::mount(source, dir.path())?;
+        Ok(Self { dir, mounted: true })
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    pub(crate) fn unmount(mut self) -> Result<(), String> {
+        self.mounted = false;
+        crate::adapters::platform::unmount(self.dir.path())
+    }
+}
+
+impl Drop for MountGuard {
+    fn drop(&mut self) {
+        if self.mounted
+            && let Err(e) = crate::adapters::platform::unmount(self.dir.path())
+        {
+            crate::log::warn("mount", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::effects::fake;
+
+    #[test]
+    fn write_unmounts_first_and_rereads_partitions_after() {
+        let dir = std::env::temp_dir().join(format!("ferric-writer-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let iso = dir.join("image.iso");
+        let disk = dir.join("disk.img");
+        fs::write(&iso, vec![0xA5u8; 10_000]).unwrap();
+        File::create(&disk).unwrap();
+
+        let mounter = fake::Mounter::failing(vec!["unmount"]);
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let device = DevicePath::from(disk.clone());
+        write_image(IsoPath::from(iso), device, 4096, &mounter, tx);
+
+        let disk_name = disk.display();
+        assert_eq!(
+            mounter.calls(),
+            vec![
+                format!("unmount {}", disk_name),
+                format!("partprobe {}", disk_name)
+            ]
+        );
+        let mut msgs = Vec::new();
+        while let Ok(Event::App(msg)) = rx.try_recv() {
+            msgs.push(msg);
+        }
+        // A failed unmount warns and the write goes on
+        assert!(matches!(msgs.first(), Some(Msg::WriteWarning(_))));
+        assert!(matches!(msgs.last(), Some(Msg::WriteFinished(Ok(())))));
+        assert_eq!(fs::read(&disk).unwrap(), vec![0xA5u8; 10_000]);
+        let _ = fs::remove_dir_all(&dir);
+    }
 }
@@ -3,7 +3,7 @@ use color_eyre::eyre::OptionExt;
 use futures::{FutureExt, StreamExt};
 use ratatui::crossterm::event::Event as CrosstermEvent;
 use std::time::Duration;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 
 const TICK_FPS: f64 = 30.0;
 
@@ -14,18 +14,51 @@ pub enum Event {
     App(Msg),
 }
 
+/// Hands the terminal to another program and back
+#[derive(Debug)]
+enum Control {
+    /// Stop reading the terminal; acknowledged once the reader is gone
+    Suspend(oneshot::Sender<()>),
+    Resume,
+}
+
 #[derive(Debug)]
 pub struct EventHandler {
     sender: mpsc::UnboundedSender<Event>,
     receiver: mpsc::UnboundedReceiver<Event>,
+    control: mpsc::UnboundedSender<Control>,
+}
+
+impl Default for EventHandler {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl EventHandler {
     pub fn new() -> Self {
         let (sender, receiver) = mpsc::unbounded_channel();
-        let actor = EventTask::new(sender.clone());
+        let (control, control_rx) = mpsc::unbounded_channel();
+        let actor = EventTask::new(sender.clone(), control_rx);
         tokio::spawn(async { actor.run().await });
-        Self { sender, receiver }
+        Self {
+            sender,
+            receiver,
+            control,
+        }
+    }
+
+    /// Events only from [`send`](Self::send) and command results: no
+    /// terminal input and no ticks, for driving the app from a script
+    #[cfg(any(test, feature = "test-support"))]
+    pub fn headless() -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let (control, _) = mpsc::unbounded_channel();
+        Self {
+            sender,
+            receiver,
+            control,
+        }
     }
 
     pub async fn next(&mut self) -> color_eyre::Result<Event> {
@@ -42,37 +75,72 @@ impl EventHandler {
     pub fn sender_clone(&self) -> mpsc::UnboundedSender<Event> {
         self.sender.clone()
     }
+
+    /// Stop reading keys, so a child process (a sudo prompt) gets them
+    pub async fn suspend(&self) {
+        let (ack, done) = oneshot::channel();
+        if self.control.send(Control::Suspend(ack)).is_ok() {
+            let _ = done.await;
+        }
+    }
+
+    pub fn resume(&self) {
+        let _ = self.control.send(Control::Resume);
+    }
 }
 
 struct EventTask {
     sender: mpsc::UnboundedSender<Event>,
+    control: mpsc::UnboundedReceiver<Control>,
 }
 
 impl EventTask {
-    fn new(sender: mpsc::UnboundedSender<Event>) -> Self {
-        Self { sender }
+    fn new(
+        sender: mpsc::UnboundedSender<Event>,
+        control: mpsc::UnboundedReceiver<Control>,
+    ) -> Self {
+        Self { sender, control }
     }
 
-    async fn run(self) -> color_eyre::Result<()> {
+    async fn run(mut self) -> color_eyre::Result<()> {
         let tick_rate = Duration::from_secs_f64(1.0 / TICK_FPS);
-        let mut reader = crossterm::event::EventStream::new();
         let mut tick = tokio::time::interval(tick_rate);
         loop {
-            let tick_delay = tick.tick();
-            let crossterm_event = reader.next().fuse();
-            tokio::select! {
-              _ = self.sender.closed() => {
-                break;
-              }
-              _ = tick_delay => {
-                self.send(Event::Tick);
-              }
-              Some(Ok(evt)) = crossterm_event => {
-                self.send(Event::Crossterm(evt));
-              }
+            // crossterm buffers input from a background thread while a
+            // stream exists, so suspending drops it
+            let mut reader = crossterm::event::EventStream::new();
+            let ack = loop {
+                let tick_delay = tick.tick();
+                let crossterm_event = reader.next().fuse();
+                tokio::select! {
+                  _ = self.sender.closed() => {
+                    return Ok(());
+                  }
+                  _ = tick_delay => {
+                    self.send(Event::Tick);
+                  }
+                  Some(Ok(evt)) = crossterm_event => {
+                    self.send(Event::Crossterm(evt));
+                  }
+                  Some(control) = self.control.recv() => {
+                    if let Control::Suspend(ack) = control {
+                        break ack;
+                    }
+                  }
+                };
             };
+            drop(reader);
+            let _ = ack.send(());
+            loop {
+                match self.control.recv().await {
+                    Some(Control::Resume) => break,
+                    Some(Control::Suspend(ack)) => {
+                        let _ = ack.send(());
+                    }
+                    None => return Ok(()),
+                }
+            }
         }
-        Ok(())
     }
 
     fn send(&self, event: Event) {
@@ -0,0 +1,456 @@
+//! Privileged write helper.
+//!
+//! `ferric helper` is started as root (sudo, doas, ...) by the unprivileged TUI and does
+//! only the parts that need root: open the device, unmount it, write the
+//! image, verify it and eject it. The two sides talk over the helper's
+//! stdin and stdout, one request or event per line, fields separated by tabs.
+//!
+//! Requests: `write ISO DEVICE BUFFER_SIZE`, `verify ISO DEVICE SIZE`,
+//! `eject DEVICE...`.
+//! Events mirror the [`Msg`]s the writer sends, e.g. `write-progress
+//! WRITTEN TOTAL BPS` or `write-finished err MESSAGE`; the helper says
+//! `ready` once it is up.
+
+use crate::{
+    adapters::{elevate::Elevator, platform},
+    app::{effects::System, msg::Msg},
+    domain::{
+        paths::{DevicePath, IsoPath},
+        writer,
+    },
+    event::Event,
+};
+use std::{
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    process::{Child, ChildStdin, Stdio},
+    sync::Mutex,
+};
+
+/// Work the TUI hands to the helper
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    Write {
+        iso: IsoPath,
+        device: DevicePath,
+        buffer_size: usize,
+    },
+    Verify {
+        iso: IsoPath,
+        device: DevicePath,
+        size: u64,
+    },
+    Eject {
+        devices: Vec<DevicePath>,
+    },
+}
+
+impl Request {
+    /// The messages that end this request with `error`
+    pub fn failed(&self, error: String) -> Vec<Msg> {
+        match self {
+            Request::Write { .. } => vec![Msg::WriteFinished(Err(error))],
+            Request::Verify { .. } => vec![Msg::VerifyFinished(Err(error))],
+            Request::Eject { devices } => devices
+                .iter()
+                .map(|device| Msg::Ejected {
+                    device: device.clone(),
+                    result: Err(error.clone()),
+                })
+                .collect(),
+        }
+    }
+}
+
+const READY: &str = "ready";
+
+/// Serve requests from `input` until it closes; returns the exit code
+pub fn serve(input: impl BufRead, mut output: impl Write) -> i32 {
+    if writeln!(output, "{}", READY)
+        .and_then(|_| output.flush())
+        .is_err()
+    {
+        return 1;
+    }
+    for line in input.lines() {
+        let Ok(line) = line else { return 1 };
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        match decode_request(&line) {
+            Ok(request) => {
+                std::thread::spawn(move || run(request, tx));
+            }
+            Err(e) => {
+                let _: Result<_, _> = tx.send(Event::App(Msg::WriteWarning(e)));
+                drop(tx);
+            }
+        }
+        // Ends when the request's sender is dropped
+        while let Some(Event::App(msg)) = rx.blocking_recv() {
+            let Some(event) = encode_msg(&msg) else {
+                continue;
+            };
+            if writeln!(output, "{}", event)
+                .and_then(|_| output.flush())
+                .is_err()
+            {
+                return 1;
+            }
+        }
+    }
+    0
+}
+
+fn run(request: Request, tx: tokio::sync::mpsc::UnboundedSender<Event>) {
+    match request {
+        Request::Write {
+            iso,
+            device,
+            buffer_size,
+        } => match check_device(device.as_path()) {
+            Ok(()) => writer::write_image(iso, device, buffer_size, &System, tx),
+            Err(e) => {
+                let _: Result<_, _> = tx.send(Event::App(Msg::WriteFinished(Err(e))));
+            }
+        },
+        Request::Verify { iso, device, size } => match check_device(device.as_path()) {
+            Ok(()) => writer::verify_image(iso, device, size, tx),
+            Err(e) => {
+                let _: Result<_, _> = tx.send(Event::App(Msg::VerifyFinished(Err(e))));
+            }
+        },
+        Request::Eject { devices } => {
+            for device in devices {
+                let result =
+                    check_device(device.as_path()).and_then(|()| platform::eject(device.as_path()));
+                let _: Result<_, _> = tx.send(Event::App(Msg::Ejected { device, result }));
+            }
+        }
+    }
+}
+
+/// The helper only ever touches whole-disk block devices under `/dev`
+/// that the running system does not live on
+fn check_device(path: &Path) -> Result<(), String> {
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+    let meta = std::fs::metadata(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    if !path.starts_with("/dev") || !meta.file_type().is_block_device() {
+        return Err(format!("{}: not a block device", path.display()));
+    }
+    let sys = format!(
+        "/sys/dev/block/{}:{}",
+        libc::major(meta.rdev()),
+        libc::minor(meta.rdev())
+    );
+    if Path::new(&sys).join("partition").exists() {
+        return Err(format!("{}: a partition, not a whole disk", path.display()));
+    }
+    let used = platform::system_use_of(path)?;
+    if !used.is_empty() {
+        return Err(format!(
+            "{}: holds the running system ({})",
+            path.display(),
+            used.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// The running helper: requests go to its stdin
+struct Client {
+    stdin: ChildStdin,
+    _child: Child,
+}
+
+static CLIENT: Mutex<Option<Client>> = Mutex::new(None);
+
+/// Start `ferric helper` as root and forward its events to `tx`.
+///
+/// Blocks until the helper is up, so the elevation tool can ask for a
+/// password first. When the helper goes away, `Msg::HelperExited` is sent.
+pub fn start(
+    elevator: Elevator,
+    tx: tokio::sync::mpsc::UnboundedSender<Event>,
+) -> Result<(), String> {
+    crate::log::info("helper", format_args!("starting with {}", elevator));
+    let exe = std::env::current_exe()
+        .map_err(|e| format!("Cannot determine current executable: {}", e))?;
+    let mut child = elevator
+        .command(&exe, &["helper"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run {}: {}", elevator, e))?;
+    let stdin = child.stdin.take().ok_or("helper has no stdin")?;
+    let mut stdout = BufReader::new(child.stdout.take().ok_or("helper has no stdout")?);
+    let mut first = String::new();
+    if stdout.read_line(&mut first).is_err() || first.trim_end() != READY {
+        let _ = child.wait();
+        return Err(format!("{}: the privileged helper did not start", elevator));
+    }
+    std::thread::spawn(move || {
+        for line in stdout.lines() {
+            let Ok(line) = line else { break };
+            if let Some(msg) = decode_msg(&line) {
+                let _: Result<_, _> = tx.send(Event::App(msg));
+            }
+        }
+        if let Ok(mut client) = CLIENT.lock() {
+            *client = None;
+        }
+        let _: Result<_, _> = tx.send(Event::App(Msg::HelperExited));
+    });
+    if let Ok(mut client) = CLIENT.lock() {
+        *client = Some(Client {
+            stdin,
+            _child: child,
+        });
+    }
+    Ok(())
+}
+
+/// Hand a request to the running helper
+pub fn send(request: &Request) -> Result<(), String> {
+    let mut client = CLIENT.lock().map_err(|_| "helper state poisoned")?;
+    let client = client
+        .as_mut()
+        .ok_or("the privileged helper is not running")?;
+    crate::log::debug("helper", format_args!("{:?}", request));
+    writeln!(client.stdin, "{}", encode_request(request))
+        .and_then(|_| client.stdin.flush())
+        .map_err(|e| format!("Failed to reach the privileged helper: {}", e))
+}
+
+pub fn encode_request(request: &Request) -> String {
+    match request {
+        Request::Write {
+            iso,
+            device,
+            buffer_size,
+        } => join(&[
+            "write",
+            &iso.to_string(),
+            &device.to_string(),
+            &buffer_size.to_string(),
+        ]),
+        Request::Verify { iso, device, size } => join(&[
+            "verify",
+            &iso.to_string(),
+            &device.to_string(),
+            &size.to_string(),
+        ]),
+        Request::Eject { devices } => {
+            let mut fields = vec!["eject".to_string()];
+            fields.extend(devices.iter().map(DevicePath::to_string));
+            join(&fields)
+        }
+    }
+}
+
+pub fn decode_request(line: &str) -> Result<Request, String> {
+    let fields = split(line);
+    let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+    let number = |s: &str| {
+        s.parse::<u64>()
+            .map_err(|_| format!("helper: `{}` is not a number", s))
+    };
+    match fields.as_slice() {
+        ["write", iso, device, buffer_size] => Ok(Request::Write {
+            iso: IsoPath::from(std::path::PathBuf::from(iso)),
+            device: DevicePath::from(std::path::PathBuf::from(device)),
+            buffer_size: number(buffer_size)? as usize,
+        }),
+        ["verify", iso, device, size] => Ok(Request::Verify {
+            iso: IsoPath::from(std::path::PathBuf::from(iso)),
+            device: DevicePath::from(std::path::PathBuf::from(device)),
+            size: number(size)?,
+        }),
+        ["eject", devices @ ..] if !devices.is_empty() => Ok(Request::Eject {
+            devices: devices
+                .iter()
+                .map(|d| DevicePath::from(std::path::PathBuf::from(d)))
+                .collect(),
+        }),
+        _ => Err(format!("helper: unknown request `{}`", line)),
+    }
+}
+
+/// The line for a writer event; `None` for messages the helper never sends
+pub fn encode_msg(msg: &Msg) -> Option<String> {
+    let result = |name: &str, r: &Result<(), String>| match r {
+        Ok(()) => join(&[name, "ok"]),
+        Err(e) => join(&[name, "err", e]),
+    };
+    Some(match msg {
+        Msg::WriteStarted { total } => join(&["write-started", &total.to_string()]),
+        Msg::WriteProgress {
+            written,
+            total,
+            bps,
+        } => join(&[
+            "write-progress",
+            &written.to_string(),
+            &total.to_string(),
+            &bps.to_string(),
+        ]),
+        Msg::WriteFinished(r) => result("write-finished", r),
+        Msg::WriteWarning(w) => join(&["warning", w]),
+        Msg::VerifyStarted { total } => join(&["verify-started", &total.to_string()]),
+        Msg::VerifyProgress {
+            checked,
+            total,
+            bps,
+        } => join(&[
+            "verify-progress",
+            &checked.to_string(),
+            &total.to_string(),
+            &bps.to_string(),
+        ]),
+        Msg::VerifyFinished(r) => result("verify-finished", r),
+        Msg::Ejected { device, result } => match result {
+            Ok(off) => join(&["ejected", &device.to_string(), "ok", &off.to_string()]),
+            Err(e) => join(&["ejected", &device.to_string(), "err", e]),
+        },
+        _ => return None,
+    })
+}
+
+pub fn decode_msg(line: &str) -> Option<Msg> {
+    let fields = split(line);
+    let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+    let result = |rest: &[&str]| match rest {
+        ["ok"] => Some(Ok(())),
+        ["err", e] => Some(Err(e.to_string())),
+        _ => None,
+    };
+    Some(match fields.as_slice() {
+        ["write-started", total] => Msg::WriteStarted {
+            total: total.parse().ok()?,
+        },
+        ["write-progress", written, total, bps] => Msg::WriteProgress {
+            written: written.parse().ok()?,
+            total: total.parse().ok()?,
+            bps: bps.parse().ok()?,
+        },
+        ["write-finished", rest @ ..] => Msg::WriteFinished(result(rest)?),
+        ["warning", w] => Msg::WriteWarning(w.to_string()),
+        ["verify-started", total] => Msg::VerifyStarted {
+            total: total.parse().ok()?,
+        },
+        ["verify-progress", checked, total, bps] => Msg::VerifyProgress {
+            checked: checked.parse().ok()?,
+            total: total.parse().ok()?,
+            bps: bps.parse().ok()?,
+        },
+        ["verify-finished", rest @ ..] => Msg::VerifyFinished(result(rest)?),
+        ["ejected", device, rest @ ..] => Msg::Ejected {
+            device: DevicePath::from(std::path::PathBuf::from(device)),
+            result: match rest {
+                ["ok", off] => Ok(off.parse().ok()?),
+                ["err", e] => Err(e.to_string()),
+                _ => return None,
+            },
+        },
+        _ => return None,
+    })
+}
+
+/// Tab-join fields, escaping backslashes, tabs and newlines
+pub(crate) fn join<S: AsRef<str>>(fields: &[S]) -> String {
+    fields
+        .iter()
+        .map(|f| {
+            f.as_ref()
+                .replace('\\', "\\\\")
+                .replace('\t', "\\t")
+                .replace('\n', "\\n")
+        })
+        .collect::<Vec<_>>()
+        .join("\t")
+}
+
+pub(crate) fn split(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().expect("never empty");
+        match c {
+            '\t' => fields.push(String::new()),
+            '\\' => match chars.next() {
+                Some('t') => field.push('\t'),
+                Some('n') => field.push('\n'),
+                Some(other) => field.push(other),
+                None => field.push('\\'),
+            },
+            c => field.push(c),
+        }
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_and_events_survive_the_pipe() {
+        let request = Request::Write {
+            iso: IsoPath::from(std::path::PathBuf::from("/isos/my\tdistro\\1.iso")),
+            device: DevicePath::from(std::path::PathBuf::from("/dev/sdb")),
+            buffer_size: 4 << 20,
+        };
+        assert_eq!(decode_request(&encode_request(&request)), Ok(request));
+        assert!(decode_request("format\t/dev/sda").is_err());
+
+        let failed = Msg::WriteFinished(Err("Write error: line one\nline two".to_string()));
+        let line = encode_msg(&failed).unwrap();
+        assert!(!line.contains('\n'));
+        assert!(matches!(
+            decode_msg(&line),
+            Some(Msg::WriteFinished(Err(e))) if e == "Write error: line one\nline two"
+        ));
+        let progress = encode_msg(&Msg::VerifyProgress {
+            checked: 512,
+            total: 1024,
+            bps: 2.5,
+        })
+        .unwrap();
+        assert!(matches!(
+            decode_msg(&progress),
+            Some(Msg::VerifyProgress { checked: 512, total: 1024, bps }) if bps == 2.5
+        ));
+        assert!(encode_msg(&Msg::Tick).is_none());
+
+        let eject = Request::Eject {
+            devices: vec![DevicePath::from(std::path::PathBuf::from("/dev/sdb"))],
+        };
+        assert_eq!(decode_request(&encode_request(&eject)), Ok(eject));
+        assert!(decode_request("eject").is_err());
+        let ejected = encode_msg(&Msg::Ejected {
+            device: DevicePath::from(std::path::PathBuf::from("/dev/sdb")),
+            result: Ok(true),
+        })
+        .unwrap();
+        assert!(matches!(
+            decode_msg(&ejected),
+            Some(Msg::Ejected {
+                result: Ok(true),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn helper_refuses_anything_but_a_block_device() {
+        let input = "write\t/nonexistent.iso\t/etc/passwd\t4096\nformat\t/dev/sda\n";
+        let mut output = Vec::new();
+        assert_eq!(serve(input.as_bytes(), &mut output), 0);
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], READY);
+        assert!(matches!(
+            decode_msg(lines[1]),
+            Some(Msg::WriteFinished(Err(e))) if e.contains("not a block device")
+        ));
+        assert!(matches!(decode_msg(lines[2]), Some(Msg::WriteWarning(_))));
+    }
+}
@@ -1,5 +1,11 @@
+#![cfg_attr(test, allow(clippy::field_reassign_with_default))]
+
 pub mod adapters;
 pub mod app;
+pub mod cli;
+pub mod config;
 pub mod domain;
 pub mod event;
+pub mod helper;
+pub mod log;
 pub mod ui;
@@ -0,0 +1,174 @@
+//! The log file, `$XDG_STATE_HOME/ferric/ferric.log`.
+//!
+//! One line per event: UTC time, level, where it came from and what
+//! happened; continuation lines of multi-line text are indented. Events
+//! below the level from `--log-level` / `log_level` (default `info`) are
+//! dropped, and nothing is written before [`init`] or with `off`. A log
+//! over 1 MiB is moved to `ferric.log.1` at start.
+
+use crate::{config, domain::report};
+use std::{
+    fmt,
+    fs::{self, File},
+    io::Write,
+    path::PathBuf,
+    sync::{Mutex, OnceLock},
+    time::SystemTime,
+};
+
+const ROTATE_AT: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Level {
+    Off,
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    pub const NAMES: [&'static str; 6] = ["off", "error", "warn", "info", "debug", "trace"];
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "off" => Ok(Level::Off),
+            "error" => Ok(Level::Error),
+            "warn" | "warning" => Ok(Level::Warn),
+            "info" => Ok(Level::Info),
+            "debug" => Ok(Level::Debug),
+            "trace" => Ok(Level::Trace),
+            _ => Err(format!(
+                "`{}` is not a log level ({})",
+                s.trim(),
+                Level::NAMES.join(", ")
+            )),
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Level::Off => "OFF",
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        }
+    }
+}
+
+struct Logger {
+    level: Level,
+    file: Mutex<File>,
+}
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+pub fn log_path() -> Option<PathBuf> {
+    config::xdg_dir("XDG_STATE_HOME", ".local/state").map(|d| d.join("ferric").join("ferric.log"))
+}
+
+/// Start logging at `level`; only the first call has an effect
+pub fn init(level: Level) -> Result<(), String> {
+    if level == Level::Off {
+        return Ok(());
+    }
+    let path = log_path().ok_or("no home directory for the log file")?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    }
+    if fs::metadata(&path).is_ok_and(|m| m.len() > ROTATE_AT) {
+        let _ = fs::rename(&path, path.with_extension("log.1"));
+    }
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    let _ = LOGGER.set(Logger {
+        level,
+        file: Mutex::new(file),
+    });
+    Ok(())
+}
+
+/// Whether events at `level` are written; for messages costly to build
+pub fn enabled(level: Level) -> bool {
+    LOGGER
+        .get()
+        .is_some_and(|l| level != Level::Off && level <= l.level)
+}
+
+pub fn log(level: Level, scope: &str, message: impl fmt::Display) {
+    let Some(logger) = LOGGER.get().filter(|_| enabled(level)) else {
+        return;
+    };
+    let line = format_line(SystemTime::now(), level, scope, &message.to_string());
+    if let Ok(mut file) = logger.file.lock() {
+        let _ = file.write_all(line.as_bytes());
+    }
+}
+
+pub fn error(scope: &str, message: impl fmt::Display) {
+    log(Level::Error, scope, message)
+}
+
+pub fn warn(scope: &str, message: impl fmt::Display) {
+    log(Level::Warn, scope, message)
+}
+
+pub fn info(scope: &str, message: impl fmt::Display) {
+    log(Level::Info, scope, message)
+}
+
+pub fn debug(scope: &str, message: impl fmt::Display) {
+    log(Level::Debug, scope, message)
+}
+
+pub fn trace(scope: &str, message: impl fmt::Display) {
+    log(Level::Trace, scope, message)
+}
+
+/// `what` at `Debug` when it worked, at `Warn` with the reason when not
+pub fn outcome<T>(scope: &str, what: impl fmt::Display, result: &Result<T, String>) {
+    match result {
+        Ok(_) => debug(scope, format_args!("{}: ok", what)),
+        Err(e) => warn(scope, format_args!("{}: {}", what, e)),
+    }
+}
+
+fn format_line(at: SystemTime, level: Level, scope: &str, message: &str) -> String {
+    let message = message.trim_end().replace('\n', "\n    ");
+    format!(
+        "{} {:<5} {}: {}\n",
+        report::utc(at),
+        level.label(),
+        scope,
+        message
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn lines_carry_time_level_and_scope() {
+        let at = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        assert_eq!(
+            format_line(at, Level::Warn, "platform", "umount /mnt: busy"),
+            "2023-11-14T22:13:20Z WARN  platform: umount /mnt: busy\n"
+        );
+        assert_eq!(
+            format_line(at, Level::Debug, "tool", "lsblk said:\nsdb\nsdc\n"),
+            "2023-11-14T22:13:20Z DEBUG tool: lsblk said:\n    sdb\n    sdc\n"
+        );
+        assert_eq!(Level::parse("Debug"), Ok(Level::Debug));
+        assert!(Level::parse("loud").is_err());
+        assert!(Level::Error < Level::Trace);
+    }
+}
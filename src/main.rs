@@ -1,10 +1,96 @@
-use ferric::app::App;
+use ferric::{
+    app::App,
+    cli::{self, Command},
+    config::{self, Config},
+    log,
+};
+use std::path::PathBuf;
 
 #[tokio::main]
 async fn main() -> color_eyre::Result<()> {
     color_eyre::install()?;
+    let cli = cli::parse(std::env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("ferric: {}\n\n{}", e, cli::USAGE);
+        std::process::exit(2);
+    });
+    match cli.command {
+        Command::Help => {
+            print!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Command::ConfigCheck(path) => std::process::exit(config_check(path)),
+        Command::Helper => {
+            let code = tokio::task::spawn_blocking(|| {
+                ferric::helper::serve(std::io::stdin().lock(), std::io::stdout().lock())
+            })
+            .await
+            .unwrap_or(1);
+            std::process::exit(code);
+        }
+        Command::Run | Command::Replay(_) => {}
+    }
+    let config = Config::load(&cli.settings).unwrap_or_else(|e| {
+        eprintln!("ferric: {}", e);
+        std::process::exit(2);
+    });
+    if let Err(e) = log::init(config.log_level) {
+        eprintln!("ferric: not logging: {}", e);
+    }
+    log::info(
+        "app",
+        format_args!(
+            "ferric {} started{}",
+            env!("CARGO_PKG_VERSION"),
+            if config.dry_run { " (dry run)" } else { "" }
+        ),
+    );
+    if let Command::Replay(path) = &cli.command {
+        let trace = ferric::app::trace::load(path).unwrap_or_else(|e| {
+            eprintln!("ferric: {}", e);
+            std::process::exit(2);
+        });
+        let terminal = ratatui::init();
+        let result = App::for_replay(&config).replay(terminal, trace).await;
+        ratatui::restore();
+        return result;
+    }
+    let mut app = App::with_config(&config);
+    if let Some(path) = &cli.record {
+        app = app.record_to(path, cli.redact).unwrap_or_else(|e| {
+            eprintln!("ferric: cannot record: {}", e);
+            std::process::exit(2);
+        });
+    }
     let terminal = ratatui::init();
-    let result = App::new().run(terminal).await;
+    let result = app.run(terminal).await;
     ratatui::restore();
     result
 }
+
+/// `ferric config check`: report every problem, exit 1 if there are any
+fn config_check(path: Option<PathBuf>) -> i32 {
+    let Some(path) = path.or_else(config::config_path) else {
+        eprintln!("ferric: cannot locate the config file; set HOME or XDG_CONFIG_HOME");
+        return 2;
+    };
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            println!("{}: not found; using defaults", path.display());
+            return 0;
+        }
+        Err(e) => {
+            eprintln!("{}: {}", path.display(), e);
+            return 2;
+        }
+    };
+    let problems = Config::check_file(&text, &path.to_string_lossy());
+    if problems.is_empty() {
+        println!("{}: ok", path.display());
+        return 0;
+    }
+    for problem in &problems {
+        eprintln!("{}", problem);
+    }
+    1
+}
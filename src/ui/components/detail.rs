@@ -6,7 +6,7 @@ use ratatui::{
     widgets::{Block, BorderType, Paragraph, Widget},
 };
 
-use crate::ui::theme::{colors, styles};
+use crate::ui::theme::{self, styles};
 
 pub struct DetailPanel<'a> {
     pub title: &'a str,
@@ -23,9 +23,9 @@ pub struct DetailItem<'a> {
 impl<'a> Widget for DetailPanel<'a> {
     fn render(self, area: Rect, buf: &mut Buffer) {
         let border_color = if self.synced_focus {
-            colors::BORDER_ACTIVE
+            theme::active().border_active
         } else {
-            colors::BORDER_INACTIVE
+            theme::active().border_inactive
         };
 
         let block = Block::bordered()
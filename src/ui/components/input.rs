@@ -6,7 +6,7 @@ use ratatui::{
     widgets::{Block, BorderType, Paragraph, Widget},
 };
 
-use crate::ui::theme::{colors, styles};
+use crate::ui::theme::{self, styles};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum InputState {
@@ -61,17 +61,25 @@ impl<'a> Widget for Input<'a> {
     fn render(self, area: Rect, buf: &mut Buffer) {
         let (border_color, border_type, text_color) = match self.state {
             InputState::Normal => (
-                colors::BORDER_INACTIVE,
+                theme::active().border_inactive,
                 BorderType::Rounded,
-                colors::TEXT_PRIMARY,
+                theme::active().text_primary,
             ),
             InputState::Focused => (
-                colors::BORDER_FOCUS,
+                theme::active().border_focus,
                 BorderType::Double,
-                colors::TEXT_PRIMARY,
+                theme::active().text_primary,
+            ),
+            InputState::Valid => (
+                theme::active().success,
+                BorderType::Rounded,
+                theme::active().success,
+            ),
+            InputState::Invalid => (
+                theme::active().danger,
+                BorderType::Rounded,
+                theme::active().danger,
             ),
-            InputState::Valid => (colors::SUCCESS, BorderType::Rounded, colors::SUCCESS),
-            InputState::Invalid => (colors::DANGER, BorderType::Rounded, colors::DANGER_BRIGHT),
         };
 
         let block = Block::bordered()
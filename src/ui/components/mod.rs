@@ -8,4 +8,4 @@ pub use detail::{DetailItem, DetailPanel};
 pub use input::{Input, InputState};
 pub use progress::ProgressWidget;
 pub use status::StatusBadge;
-pub use utils::{format_seconds, human_size};
+pub use utils::{format_ago, format_seconds, human_size};
@@ -44,6 +44,22 @@ pub fn format_seconds(secs: u64) -> String {
     }
 }
 
+/// Coarse age of a timestamp: "5 minutes ago", "3 hours ago", "2 days ago".
+///
+/// Timestamps in the future read as "just now".
+pub fn format_ago(time: std::time::SystemTime) -> String {
+    let secs = time.elapsed().map(|e| e.as_secs()).unwrap_or(0);
+    if secs < 60 {
+        "just now".to_string()
+    } else if secs < 3600 {
+        format!("{} minutes ago", secs / 60)
+    } else if secs < 86400 {
+        format!("{} hours ago", secs / 3600)
+    } else {
+        format!("{} days ago", secs / 86400)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,5 +1,5 @@
-use crate::ui::icons::Icons;
-use crate::ui::theme::ThemeVariant;
+use crate::ui::icons::{self, Icons};
+use crate::ui::theme::{self, ThemeVariant};
 
 /// UI context holding shared theme and icons.
 /// Passed to all view render methods.
@@ -11,8 +11,8 @@ pub struct UiCtx {
 impl UiCtx {
     pub fn new() -> Self {
         Self {
-            theme: ThemeVariant::default(),
-            icons: Icons::UNICODE,
+            theme: theme::active(),
+            icons: icons::active(),
         }
     }
 }
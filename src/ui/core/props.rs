@@ -1,5 +1,11 @@
-use crate::app::state::{ActivePanel, Model};
+use crate::app::keymap::{Action, KeyBinding, Keymap};
+use crate::app::state::{
+    ActivePanel, Browser, Elevator, HistoryEntry, JobQueue, Model, PersistenceKind, TargetState,
+    WriteMode, WriteRoute,
+};
+use crate::domain::paths::DirPath;
 use crate::domain::{Device, DevicePath, IsoMeta, IsoPath};
+use std::path::PathBuf;
 
 #[derive(Debug, Clone)]
 pub struct IsoSearchProps<'a> {
@@ -7,15 +13,26 @@ pub struct IsoSearchProps<'a> {
     pub searching: bool,
     pub results: &'a [IsoMeta],
     pub selected: usize,
+    pub marked: &'a [IsoPath],
+    /// Directories the search walks
+    pub roots: &'a [DirPath],
     pub focused: bool,
+    pub keymap: &'a Keymap,
+    /// Shown instead of the results while browsing by path
+    pub browser: Option<&'a Browser>,
+    pub history: &'a [HistoryEntry],
 }
 
 #[derive(Debug, Clone)]
 pub struct DeviceSelectProps<'a> {
     pub devices: &'a [Device],
     pub selected: usize,
+    pub marked: &'a [DevicePath],
     pub refreshing: bool,
     pub focused: bool,
+    pub keymap: &'a Keymap,
+    /// Earlier jobs, to recognize sticks by serial
+    pub history: &'a [HistoryEntry],
 }
 
 #[derive(Debug, Clone)]
@@ -23,8 +40,35 @@ pub struct ConfirmProps<'a> {
     pub iso_path: Option<&'a IsoPath>,
     pub device_path: Option<&'a DevicePath>,
     pub confirm_input: &'a str,
+    /// What must be typed to enable Enter
+    pub confirm_word: &'a str,
     pub verify_after_write: bool,
-    pub is_root: bool,
+    pub write_mode: WriteMode,
+    pub marked: &'a [IsoPath],
+    /// Every target when more than one device is marked
+    pub devices_marked: &'a [DevicePath],
+    pub persistence_offer: Option<PersistenceKind>,
+    pub persistence_enabled: bool,
+    pub eject_after_write: bool,
+    /// Jobs already waiting in the queue
+    pub queued: usize,
+    /// What the process lacks to write the devices itself
+    pub access_missing: Option<String>,
+    /// What a write by this process goes without
+    pub access_caveat: Option<String>,
+    /// The privileged helper's state; see `Model::helper`
+    pub helper: Option<&'a Result<(), String>>,
+    /// Tool Ctrl-S starts the helper with
+    pub elevator: Option<Elevator>,
+    /// UDisks2 opens the device for this write
+    pub via_udisks: bool,
+    /// Why a dry run cannot simulate this job
+    pub simulation_gap: Option<&'static str>,
+    /// Why only ferric run as root can do this job
+    pub needs_root: Option<&'static str>,
+    /// Something other than the confirm word stands in the way
+    pub write_blocked: bool,
+    pub keymap: &'a Keymap,
 }
 
 #[derive(Debug, Clone)]
@@ -39,6 +83,9 @@ pub struct WritingProps {
     pub verified: u64,
     pub verify_total: u64,
     pub verify_speed_bps: f64,
+    pub persisting: bool,
+    /// Per-device rows of a parallel write; empty for a single device
+    pub targets: Vec<TargetState>,
     pub write_result: Option<Result<(), String>>,
 }
 
@@ -48,7 +95,22 @@ pub struct WritingProps {
 #[derive(Debug, Clone)]
 pub struct DoneProps {
     pub result: Option<Result<(), String>>,
-    pub is_root: bool,
+    /// The persistence step's outcome, when one ran
+    pub persistence: Option<(PersistenceKind, Result<(), String>)>,
+    /// Per-device outcome of a parallel write
+    pub targets: Vec<TargetState>,
+    pub queue: JobQueue,
+    /// Unmount and partition re-read problems that did not stop the write
+    pub warnings: Vec<String>,
+    /// A report of the finished job can be exported
+    pub report_ready: bool,
+    pub report_exported: Option<Result<Vec<PathBuf>, String>>,
+    pub export_key: KeyBinding,
+    /// Devices being ejected; `Ok(true)` once detached and powered off
+    pub ejects: Vec<(DevicePath, Option<Result<bool, String>>)>,
+    pub eject_key: KeyBinding,
+    /// The write ran without the access it needed
+    pub lacked_access: bool,
 }
 
 // Projection Functions: Model → Props
@@ -60,7 +122,12 @@ impl<'a> IsoSearchProps<'a> {
             searching: model.iso_searching,
             results: &model.iso_results,
             selected: model.iso_selected,
+            marked: &model.iso_marked,
+            roots: &model.scan.roots,
             focused: model.active_panel == ActivePanel::IsoList,
+            keymap: &model.keymap,
+            browser: model.browser.as_ref(),
+            history: &model.history,
         }
     }
 }
@@ -70,8 +137,11 @@ impl<'a> DeviceSelectProps<'a> {
         Self {
             devices: &model.devices,
             selected: model.device_selected,
+            marked: &model.device_marked,
             refreshing: model.device_refreshing,
             focused: model.active_panel == ActivePanel::DeviceList,
+            keymap: &model.keymap,
+            history: &model.history,
         }
     }
 }
@@ -82,8 +152,24 @@ impl<'a> ConfirmProps<'a> {
             iso_path: model.iso_chosen.as_ref(),
             device_path: model.device_chosen.as_ref(),
             confirm_input: &model.confirm_input,
+            confirm_word: &model.safety.confirm_word,
             verify_after_write: model.verify_after_write,
-            is_root: model.is_root,
+            write_mode: model.write_mode,
+            marked: &model.iso_marked,
+            devices_marked: &model.device_marked,
+            persistence_offer: model.persistence_offer,
+            persistence_enabled: model.persistence_enabled,
+            eject_after_write: model.eject_after_write,
+            queued: model.queue.jobs.len(),
+            access_missing: model.access_missing(),
+            access_caveat: model.access_caveat(),
+            helper: model.helper.as_ref(),
+            elevator: model.elevator,
+            via_udisks: model.write_route() == WriteRoute::Udisks,
+            simulation_gap: model.simulation_gap(),
+            needs_root: model.needs_root(),
+            write_blocked: model.can_write().is_err(),
+            keymap: &model.keymap,
         }
     }
 }
@@ -94,11 +180,13 @@ impl WritingProps {
             written: model.writing_written,
             total: model.writing_total,
             speed_bps: model.writing_speed_bps,
-            verify_after_write: model.verify_after_write,
+            verify_after_write: model.verify_after_write && model.write_mode == WriteMode::Raw,
             verifying: model.verifying,
             verified: model.verifying_checked,
             verify_total: model.verifying_total,
             verify_speed_bps: model.verifying_speed_bps,
+            persisting: model.persisting,
+            targets: model.targets.clone(),
             write_result: model
                 .write_result
                 .clone()
@@ -114,7 +202,20 @@ impl DoneProps {
                 .write_result
                 .clone()
                 .map(|r| r.map_err(|e| e.to_string())),
-            is_root: model.is_root,
+            persistence: model
+                .persistence_offer
+                .zip(model.persistence_result.clone()),
+            targets: model.targets.clone(),
+            queue: model.queue.clone(),
+            warnings: model.write_warnings.clone(),
+            report_ready: model.report.is_some(),
+            report_exported: model.report_exported.clone(),
+            export_key: model.keymap.binding(Action::ExportReport),
+            ejects: model.ejects.clone(),
+            eject_key: model.keymap.binding(Action::Eject),
+            // The helper and udisks open the device, so a failure is not for lack of access
+            lacked_access: model.access_missing().is_some()
+                && model.write_route() == WriteRoute::Direct,
         }
     }
 }
@@ -122,7 +223,25 @@ impl DoneProps {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::path::PathBuf;
+    use crate::domain::DeviceAccess;
+
+    fn test_device(path: &str, access: DeviceAccess) -> Device {
+        Device {
+            name: path.trim_start_matches("/dev/").to_string(),
+            path: DevicePath::new(PathBuf::from(path)),
+            size: crate::domain::ByteSize::from(16_000_000_000),
+            model: None,
+            serial: None,
+            tran: None,
+            removable: true,
+            hotplug: true,
+            ro: false,
+            mounted: false,
+            labels: vec![],
+            access,
+            udisks: None,
+        }
+    }
 
     #[test]
     fn test_iso_search_props_projection() {
@@ -161,7 +280,7 @@ mod tests {
         model.device_chosen = Some(DevicePath::new(PathBuf::from("/dev/sdb")));
         model.confirm_input = "YES".to_string();
         model.verify_after_write = true;
-        model.is_root = false;
+        model.devices = vec![test_device("/dev/sdb", DeviceAccess::default())];
 
         let props = ConfirmProps::from_model(&model);
 
@@ -171,7 +290,10 @@ mod tests {
         assert_eq!(props.device_path.unwrap().to_string(), "/dev/sdb");
         assert_eq!(props.confirm_input, "YES");
         assert!(props.verify_after_write);
-        assert!(!props.is_root);
+        assert_eq!(
+            props.access_missing.as_deref(),
+            Some("cannot write /dev/sdb")
+        );
     }
 
     #[test]
@@ -196,26 +318,28 @@ mod tests {
     fn test_done_props_projection_success() {
         let mut model = Model::default();
         model.write_result = Some(Ok(()));
-        model.is_root = true;
+        model.device_chosen = Some(DevicePath::new(PathBuf::from("/dev/sdb")));
+        model.devices = vec![test_device("/dev/sdb", DeviceAccess::FULL)];
 
         let props = DoneProps::from_model(&model);
 
         assert!(props.result.is_some());
         assert!(props.result.as_ref().unwrap().is_ok());
-        assert!(props.is_root);
+        assert!(!props.lacked_access);
     }
 
     #[test]
     fn test_done_props_projection_error() {
         let mut model = Model::default();
         model.write_result = Some(Err("Write failed".to_string()));
-        model.is_root = false;
+        model.device_chosen = Some(DevicePath::new(PathBuf::from("/dev/sdb")));
+        model.devices = vec![test_device("/dev/sdb", DeviceAccess::default())];
 
         let props = DoneProps::from_model(&model);
 
         assert!(props.result.is_some());
         assert!(props.result.as_ref().unwrap().is_err());
-        assert!(!props.is_root);
+        assert!(props.lacked_access);
     }
 
     #[test]
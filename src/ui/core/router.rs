@@ -26,6 +26,7 @@ impl UiRouter {
         let shell = AppShell {
             title: " ferric ",
             border_color: compute_border_color(model),
+            banner: model.dry_run.then_some(" SIMULATION: no disk is written "),
         };
 
         shell.render(model, self, area, buf);
@@ -108,4 +109,24 @@ mod tests {
         model.screen = Screen::Done;
         router.render(&model, area, &mut buf);
     }
+
+    #[test]
+    fn test_dry_run_banner_in_the_border() {
+        let router = UiRouter::default();
+        let area = Rect::new(0, 0, 100, 30);
+        let row = |buf: &Buffer, y: u16| -> String {
+            (0..area.width).map(|x| buf[(x, y)].symbol()).collect()
+        };
+
+        let mut model = Model::default();
+        let mut buf = Buffer::empty(area);
+        router.render(&model, area, &mut buf);
+        assert!(!row(&buf, 0).contains("SIMULATION"));
+
+        model.dry_run = true;
+        let mut buf = Buffer::empty(area);
+        router.render(&model, area, &mut buf);
+        assert!(row(&buf, 0).contains("SIMULATION"));
+        assert!(row(&buf, area.height - 1).contains("SIMULATION"));
+    }
 }
@@ -1,3 +1,5 @@
+use std::sync::OnceLock;
+
 #[derive(Debug, Clone, Copy)]
 pub struct Icons {
     pub check: &'static str,
@@ -27,6 +29,11 @@ pub struct Icons {
     pub block_filled: &'static str,
 
     pub block_empty: &'static str,
+
+    /// Marks images and sticks seen in the job history
+    pub recent: &'static str,
+
+    pub eject: &'static str,
 }
 
 impl Icons {
@@ -45,7 +52,51 @@ impl Icons {
         page_down: "⇟",
         block_filled: "█",
         block_empty: "░",
+        recent: "↺",
+        eject: "⏏",
+    };
+
+    /// For terminals and fonts without the symbols above
+    pub const ASCII: Self = Self {
+        check: "+",
+        cross: "x",
+        bullet: "*",
+        arrow_right: ">",
+        warning: "!",
+        info: "i",
+        hourglass: "~",
+        search: "/",
+        backspace: "<-",
+        enter: "RET",
+        page_up: "PgUp",
+        page_down: "PgDn",
+        block_filled: "#",
+        block_empty: ".",
+        recent: "r",
+        eject: "^",
     };
+
+    pub const NAMES: [&str; 2] = ["unicode", "ascii"];
+
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name {
+            "unicode" => Some(Self::UNICODE),
+            "ascii" => Some(Self::ASCII),
+            _ => None,
+        }
+    }
+}
+
+static ACTIVE: OnceLock<Icons> = OnceLock::new();
+
+/// Pick the icon set for this run; only the first call has an effect
+pub fn set_active(icons: Icons) {
+    let _ = ACTIVE.set(icons);
+}
+
+/// The icon set picked at startup, Unicode unless configured otherwise
+pub fn active() -> Icons {
+    ACTIVE.get().copied().unwrap_or_default()
 }
 
 impl Default for Icons {
@@ -77,6 +128,13 @@ mod tests {
         assert_eq!(icons.block_empty, "░");
     }
 
+    #[test]
+    fn test_sets_by_name() {
+        assert_eq!(Icons::by_name("ascii").unwrap().check, "+");
+        assert_eq!(Icons::by_name("unicode").unwrap().check, "✓");
+        assert!(Icons::by_name("emoji").is_none());
+    }
+
     #[test]
     fn test_default_is_unicode() {
         let icons = Icons::default();
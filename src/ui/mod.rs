@@ -4,6 +4,8 @@ pub(crate) mod icons;
 pub(crate) mod layout;
 mod screens;
 pub(crate) mod theme;
+// Spacing scale kept for layouts that do not use it yet
+#[allow(dead_code)]
 pub(crate) mod tokens;
 mod widgets;
 
@@ -16,9 +18,21 @@ use ratatui::{
     widgets::{Paragraph, Widget},
 };
 
-use crate::app::state::{ActivePanel, Model, Screen};
+use crate::app::keymap::Action;
+use crate::app::state::{ActivePanel, Model, Screen, WriteRoute};
 use theme::styles;
 
+/// Select the theme and icon set by name before the first frame.
+/// Unknown names are rejected by config validation and ignored here.
+pub fn set_appearance(theme_name: &str, icon_set: &str) {
+    if let Some(variant) = theme::ThemeVariant::by_name(theme_name) {
+        theme::set_active(variant);
+    }
+    if let Some(set) = icons::Icons::by_name(icon_set) {
+        icons::set_active(set);
+    }
+}
+
 impl Widget for &Model {
     fn render(self, area: Rect, buf: &mut Buffer) {
         // Route using View + Props projection
@@ -34,6 +48,7 @@ pub(crate) fn render_footer(model: &Model, area: Rect, buf: &mut Buffer) {
 }
 
 fn nav_cycle_hint(m: &Model) -> String {
+    let quit = m.keymap.binding(Action::Quit);
     match m.screen {
         Screen::IsoSearch => {
             let focus_label = match m.active_panel {
@@ -42,8 +57,8 @@ fn nav_cycle_hint(m: &Model) -> String {
                 ActivePanel::ConfirmInput => "Confirm",
             };
             format!(
-                "Focus: {} | Tab/Shift-Tab: Switch Panels | q: Quit",
-                focus_label
+                "Focus: {} | Tab/Shift-Tab: Switch Panels | {}: Quit",
+                focus_label, quit
             )
         }
         Screen::DeviceSelect => {
@@ -53,14 +68,37 @@ fn nav_cycle_hint(m: &Model) -> String {
                 ActivePanel::ConfirmInput => "Confirm",
             };
             format!(
-                "Focus: {} | Tab/Shift-Tab: Switch Panels | r: Refresh | q: Quit",
-                focus_label
+                "Focus: {} | Tab/Shift-Tab: Switch Panels | {}: Mark | {}: Refresh | {}: Quit",
+                focus_label,
+                m.keymap.binding(Action::MarkDevice),
+                m.keymap.binding(Action::RefreshDevices),
+                quit
             )
         }
-        Screen::Confirm => {
-            format!("Focus: Confirm | Type YES then Enter | Esc: Back | q: Quit")
-        }
+        Screen::Confirm => match m.elevator {
+            Some(elevator)
+                if m.access_missing().is_some()
+                    && m.write_route() == WriteRoute::Direct
+                    && m.needs_root().is_none() =>
+            {
+                format!(
+                    "Focus: Confirm | Type {} then Enter | {}: Elevate ({}) | Esc: Back | {}: Quit",
+                    m.safety.confirm_word,
+                    m.keymap.binding(Action::Sudo),
+                    elevator,
+                    quit
+                )
+            }
+            _ => format!(
+                "Focus: Confirm | Type {} then Enter | Esc: Back | {}: Quit",
+                m.safety.confirm_word, quit
+            ),
+        },
         Screen::Writing => "Writing in progress... Please wait | Do NOT remove device".to_string(),
-        Screen::Done => "Complete | Esc: Back | q: Quit".to_string(),
+        Screen::Done => format!(
+            "Complete | Esc: Back | {}: Log | {}: Quit",
+            m.keymap.binding(Action::OpenLog),
+            quit
+        ),
     }
 }
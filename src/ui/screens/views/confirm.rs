@@ -5,10 +5,12 @@ use ratatui::{
     widgets::{Paragraph, Widget},
 };
 
+use crate::app::keymap::Action;
+use crate::app::state::{Elevator, WriteMode};
 use crate::ui::{
     components::{Input, InputState},
     core::{ConfirmProps, UiCtx, View},
-    theme::{colors, styles},
+    theme::{self, styles},
     widgets::CenteredModal,
 };
 
@@ -17,23 +19,23 @@ pub struct ConfirmScreen;
 impl View for ConfirmScreen {
     type Props<'a> = ConfirmProps<'a>;
 
-    fn render<'a>(&self, props: &Self::Props<'a>, _ctx: &UiCtx, area: Rect, buf: &mut Buffer) {
+    fn render<'a>(&self, props: &Self::Props<'a>, ctx: &UiCtx, area: Rect, buf: &mut Buffer) {
         let modal = CenteredModal {
             horizontal_margin_pct: 20,
             vertical_margin_pct: 15,
         };
         let content_area = modal.compute_area(area);
 
-        let mut lines = vec![];
-
-        lines.push(Line::from(Span::styled(
-            "⚠  Confirm Action",
-            styles::warning(),
-        )));
-        lines.push(Line::from(""));
+        let mut lines = vec![
+            Line::from(Span::styled(
+                format!("{}  Confirm Action", ctx.icons.warning),
+                styles::warning(),
+            )),
+            Line::from(""),
+        ];
 
         lines.push(Line::from(vec![
-            Span::styled("⚠ WARNING: ", styles::danger()),
+            Span::styled(format!("{} WARNING: ", ctx.icons.warning), styles::danger()),
             Span::styled("This will ", styles::text()),
             Span::styled("ERASE ALL DATA", styles::danger()),
             Span::styled(" on the target drive!", styles::text()),
@@ -59,11 +61,21 @@ impl View for ConfirmScreen {
             }
         }
 
-        lines.push(Line::from(vec![Span::styled(
-            "Selected Device: ",
-            styles::text(),
-        )]));
+        let device_label = if props.devices_marked.len() > 1 && props.write_mode == WriteMode::Raw {
+            format!("Selected Devices ({}): ", props.devices_marked.len())
+        } else {
+            "Selected Device: ".to_string()
+        };
+        lines.push(Line::from(vec![Span::styled(device_label, styles::text())]));
         match &props.device_path {
+            Some(_) if props.devices_marked.len() > 1 && props.write_mode == WriteMode::Raw => {
+                for p in props.devices_marked {
+                    lines.push(Line::from(vec![
+                        Span::raw("  "),
+                        Span::styled(format!("{}", p), styles::danger()),
+                    ]));
+                }
+            }
             Some(p) => {
                 lines.push(Line::from(vec![
                     Span::raw("  "),
@@ -79,51 +91,234 @@ impl View for ConfirmScreen {
         }
         lines.push(Line::from(""));
 
-        let verify_status = if props.verify_after_write {
+        match props.write_mode {
+            WriteMode::Raw => {
+                let verify_status = if props.verify_after_write {
+                    Span::styled("ON", styles::success())
+                } else {
+                    Span::styled("OFF", styles::text_dim())
+                };
+                lines.push(Line::from(vec![
+                    Span::styled("Verify after write: ", styles::text()),
+                    verify_status,
+                    Span::styled("  (press ", styles::text_muted()),
+                    Span::styled("'v'", styles::highlight()),
+                    Span::styled(" to toggle)", styles::text_muted()),
+                ]));
+                if let Some(kind) = props.persistence_offer {
+                    let status = if props.persistence_enabled {
+                        Span::styled("ON", styles::success())
+                    } else {
+                        Span::styled("OFF", styles::text_dim())
+                    };
+                    lines.push(Line::from(vec![
+                        Span::styled(
+                            format!("Persistence partition ({}): ", kind.label()),
+                            styles::text(),
+                        ),
+                        status,
+                        Span::styled("  (press ", styles::text_muted()),
+                        Span::styled(
+                            props.keymap.binding(Action::TogglePersistence).to_string(),
+                            styles::highlight(),
+                        ),
+                        Span::styled(" to toggle)", styles::text_muted()),
+                    ]));
+                }
+            }
+            WriteMode::Windows(options) => {
+                lines.push(Line::from(vec![
+                    Span::styled("Mode: ", styles::text()),
+                    Span::styled("Windows installer", styles::warning()),
+                    Span::styled(
+                        format!(" ({}, {})", options.filesystem, options.scheme),
+                        styles::code(),
+                    ),
+                ]));
+                lines.push(Line::from(vec![
+                    Span::styled(
+                        props
+                            .keymap
+                            .binding(Action::TogglePartitionScheme)
+                            .to_string(),
+                        styles::highlight(),
+                    ),
+                    Span::styled(" MBR/GPT  ", styles::text_muted()),
+                    Span::styled(
+                        props.keymap.binding(Action::ToggleFilesystem).to_string(),
+                        styles::highlight(),
+                    ),
+                    Span::styled(" FAT32/NTFS  ", styles::text_muted()),
+                    Span::styled("(files are copied; verify is skipped)", styles::text_dim()),
+                ]));
+                if props.devices_marked.len() > 1 {
+                    lines.push(Line::from(Span::styled(
+                        "Only the first marked device is written in this mode",
+                        styles::warning(),
+                    )));
+                }
+            }
+            WriteMode::Multiboot => {
+                lines.push(Line::from(vec![
+                    Span::styled("Mode: ", styles::text()),
+                    Span::styled("Multi-ISO drive", styles::warning()),
+                    Span::styled(format!(" ({} images)", props.marked.len()), styles::code()),
+                ]));
+                for iso in props.marked {
+                    let name = iso
+                        .as_path()
+                        .file_name()
+                        .and_then(|n| n.to_str())
+                        .unwrap_or("?");
+                    lines.push(Line::from(Span::styled(
+                        format!("  {}", name),
+                        styles::text_muted(),
+                    )));
+                }
+                lines.push(Line::from(Span::styled(
+                    "A drive already prepared by ferric keeps its images; new ones are added",
+                    styles::text_dim(),
+                )));
+                if props.devices_marked.len() > 1 {
+                    lines.push(Line::from(Span::styled(
+                        "Only the first marked device is written in this mode",
+                        styles::warning(),
+                    )));
+                }
+            }
+        }
+        let eject_status = if props.eject_after_write {
             Span::styled("ON", styles::success())
         } else {
             Span::styled("OFF", styles::text_dim())
         };
         lines.push(Line::from(vec![
-            Span::styled("Verify after write: ", styles::text()),
-            verify_status,
+            Span::styled("Eject when done: ", styles::text()),
+            eject_status,
             Span::styled("  (press ", styles::text_muted()),
-            Span::styled("'v'", styles::highlight()),
+            Span::styled(
+                props.keymap.binding(Action::Eject).to_string(),
+                styles::highlight(),
+            ),
             Span::styled(" to toggle)", styles::text_muted()),
         ]));
         lines.push(Line::from(""));
 
         lines.push(Line::from(vec![
             Span::styled("Type ", styles::text_muted()),
-            Span::styled("YES", styles::warning()),
+            Span::styled(props.confirm_word, styles::warning()),
             Span::styled(" then press ", styles::text_muted()),
             Span::styled("Enter", styles::highlight()),
             Span::styled(" to confirm", styles::text_muted()),
         ]));
 
-        if !props.is_root {
+        if props.queued > 0 {
+            lines.push(Line::from(vec![
+                Span::styled(
+                    format!("{} job(s) queued; ", props.queued),
+                    styles::emphasis(),
+                ),
+                Span::styled("Enter", styles::highlight()),
+                Span::styled(" runs them all, this one last", styles::text_muted()),
+            ]));
+        }
+        lines.push(Line::from(vec![
+            Span::styled("Press ", styles::text_muted()),
+            Span::styled(
+                props.keymap.binding(Action::QueueJob).to_string(),
+                styles::highlight(),
+            ),
+            Span::styled(" to queue this job and pick the next", styles::text_muted()),
+        ]));
+
+        let helper_running = matches!(props.helper, Some(Ok(())));
+        if props.access_missing.is_some()
+            && props.needs_root.is_none()
+            && !helper_running
+            && !props.via_udisks
+            && let Some(elevator) = props.elevator
+        {
             lines.push(Line::from(vec![
                 Span::styled("Press ", styles::text_muted()),
-                Span::styled("Ctrl-S", styles::highlight()),
-                Span::styled(" to rerun with sudo", styles::text_muted()),
+                Span::styled(
+                    props.keymap.binding(Action::Sudo).to_string(),
+                    styles::highlight(),
+                ),
+                Span::styled(
+                    " to start the root write helper with ",
+                    styles::text_muted(),
+                ),
+                Span::styled(elevator.name(), styles::code()),
             ]));
         }
 
         lines.push(Line::from(""));
         lines.push(Line::from(""));
-        if !props.is_root {
+        if let Some(gap) = props.simulation_gap {
             lines.push(Line::from(vec![
-                Span::styled("⚠ ", colors::WARNING),
-                Span::styled("Not running as root. ", styles::warning()),
-                Span::styled("Writing typically requires ", styles::text_muted()),
-                Span::styled("sudo", styles::code()),
-                Span::styled(".", styles::text_muted()),
+                Span::styled(format!("{} ", ctx.icons.warning), theme::active().warning),
+                Span::styled(format!("Simulation: {}.", gap), styles::warning()),
+            ]));
+        } else if let (Some(_), Some(why)) = (&props.access_missing, props.needs_root) {
+            lines.push(Line::from(vec![
+                Span::styled(format!("{} ", ctx.icons.warning), theme::active().warning),
+                Span::styled(format!("Needs root: {}.", why), styles::warning()),
+            ]));
+            lines.push(Line::from(vec![
+                Span::raw("  "),
+                Span::styled(
+                    "The root helper and UDisks2 only write, verify and eject single images.",
+                    styles::text_dim(),
+                ),
+            ]));
+        } else if let (Some(_), true) = (&props.access_missing, helper_running) {
+            lines.push(Line::from(vec![
+                Span::styled(format!("{} ", ctx.icons.check), theme::active().success),
+                Span::styled("Root helper running. ", styles::success()),
+                Span::styled(
+                    "It writes, verifies and ejects this image.",
+                    styles::text_muted(),
+                ),
+            ]));
+        } else if props.via_udisks {
+            lines.push(Line::from(vec![
+                Span::styled(format!("{} ", ctx.icons.check), theme::active().success),
+                Span::styled("Writing through UDisks2. ", styles::success()),
+                Span::styled(
+                    "The desktop may ask you to authorize access to the device.",
+                    styles::text_muted(),
+                ),
+            ]));
+        } else if let Some(missing) = &props.access_missing {
+            lines.push(Line::from(vec![
+                Span::styled(format!("{} ", ctx.icons.warning), theme::active().warning),
+                Span::styled(format!("This process {}.", missing), styles::warning()),
+            ]));
+            if props.elevator.is_none() {
+                lines.push(Line::from(vec![
+                    Span::raw("  "),
+                    Span::styled(
+                        format!("None of {} is installed.", Elevator::NAMES.join(", ")),
+                        styles::text_dim(),
+                    ),
+                ]));
+            }
+            if let Some(Err(e)) = props.helper {
+                lines.push(Line::from(vec![
+                    Span::raw("  "),
+                    Span::styled(e.as_str(), styles::text_dim()),
+                ]));
+            }
+        } else if let Some(caveat) = &props.access_caveat {
+            lines.push(Line::from(vec![
+                Span::styled(format!("{} ", ctx.icons.warning), theme::active().warning),
+                Span::styled(format!("This process {}.", caveat), styles::warning()),
             ]));
         }
 
         if props.iso_path.is_none() || props.device_path.is_none() {
             lines.push(Line::from(vec![
-                Span::styled("⚠ ", colors::WARNING),
+                Span::styled(format!("{} ", ctx.icons.warning), theme::active().warning),
                 Span::styled(
                     "Please choose an ISO and a device first.",
                     styles::warning(),
@@ -131,16 +326,27 @@ impl View for ConfirmScreen {
             ]));
         }
 
-        if props.confirm_input == "YES" {
+        if props.write_blocked {
+            lines.push(Line::from(vec![
+                Span::styled(format!("{} ", ctx.icons.bullet), theme::active().text_dim),
+                Span::styled(
+                    "Enter is disabled until this process can run the job.",
+                    styles::text_dim(),
+                ),
+            ]));
+        } else if props.confirm_input == props.confirm_word {
             lines.push(Line::from(vec![
-                Span::styled("✓ ", colors::SUCCESS),
+                Span::styled(format!("{} ", ctx.icons.check), theme::active().success),
                 Span::styled("Enter", styles::highlight()),
                 Span::styled(" will proceed.", styles::success()),
             ]));
         } else {
             lines.push(Line::from(vec![
-                Span::styled("● ", colors::TEXT_DIM),
-                Span::styled("Enter is disabled until you type YES.", styles::text_dim()),
+                Span::styled(format!("{} ", ctx.icons.bullet), theme::active().text_dim),
+                Span::styled(
+                    format!("Enter is disabled until you type {}.", props.confirm_word),
+                    styles::text_dim(),
+                ),
             ]));
         }
 
@@ -148,7 +354,7 @@ impl View for ConfirmScreen {
         paragraph.render(content_area, buf);
 
         // Render Input component centered below the text
-        let input_state = if props.confirm_input == "YES" {
+        let input_state = if props.confirm_input == props.confirm_word {
             InputState::Valid
         } else if !props.confirm_input.is_empty() {
             InputState::Invalid
@@ -173,6 +379,7 @@ impl View for ConfirmScreen {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::app::keymap::Keymap;
 
     #[test]
     fn test_confirm_screen_view_trait() {
@@ -181,8 +388,24 @@ mod tests {
             iso_path: None,
             device_path: None,
             confirm_input: "",
+            confirm_word: "YES",
             verify_after_write: false,
-            is_root: true,
+            write_mode: WriteMode::Windows(Default::default()),
+            marked: &[],
+            devices_marked: &[],
+            persistence_offer: None,
+            persistence_enabled: false,
+            eject_after_write: false,
+            queued: 0,
+            access_missing: None,
+            access_caveat: None,
+            helper: None,
+            via_udisks: false,
+            simulation_gap: None,
+            needs_root: None,
+            write_blocked: false,
+            elevator: None,
+            keymap: &Keymap::default(),
         };
         let ctx = UiCtx::new();
         let area = Rect::new(0, 0, 80, 24);
@@ -5,10 +5,12 @@ use ratatui::{
     widgets::{Cell, Widget},
 };
 
+use crate::app::keymap::Action;
+use crate::domain::{Device, history};
 use crate::ui::{
-    components::{DetailItem, DetailPanel},
+    components::{DetailItem, DetailPanel, format_ago},
     core::{DeviceSelectProps, UiCtx, View},
-    theme::{colors, styles},
+    theme::{self, styles},
     widgets::{DataPanel, InstructionHeader},
 };
 
@@ -17,7 +19,7 @@ pub struct DeviceSelectScreen;
 impl View for DeviceSelectScreen {
     type Props<'a> = DeviceSelectProps<'a>;
 
-    fn render<'a>(&self, props: &Self::Props<'a>, _ctx: &UiCtx, area: Rect, buf: &mut Buffer) {
+    fn render<'a>(&self, props: &Self::Props<'a>, ctx: &UiCtx, area: Rect, buf: &mut Buffer) {
         let columns = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
@@ -38,13 +40,21 @@ impl View for DeviceSelectScreen {
             Span::styled(" to move; ", styles::text_muted()),
             Span::styled("Enter", styles::highlight()),
             Span::styled(" to select; ", styles::text_muted()),
-            Span::styled("r", styles::highlight()),
+            Span::styled(
+                props.keymap.binding(Action::MarkDevice).to_string(),
+                styles::highlight(),
+            ),
+            Span::styled(" to mark several; ", styles::text_muted()),
+            Span::styled(
+                props.keymap.binding(Action::RefreshDevices).to_string(),
+                styles::highlight(),
+            ),
             Span::styled(" to refresh", styles::text_muted()),
         ]));
 
         if props.refreshing {
             header_lines.push(Line::from(vec![
-                Span::styled("● ", colors::PRIMARY),
+                Span::styled(format!("{} ", ctx.icons.bullet), theme::active().primary),
                 Span::styled("Refreshing...", styles::text_muted()),
             ]));
         } else if props.devices.is_empty() {
@@ -65,6 +75,12 @@ impl View for DeviceSelectScreen {
                 ),
                 Span::styled(" found", styles::text_muted()),
             ]));
+            if !props.marked.is_empty() {
+                header_lines.push(Line::from(vec![
+                    Span::styled(format!("{} ", ctx.icons.check), theme::active().success),
+                    Span::styled(format!("{} marked", props.marked.len()), styles::success()),
+                ]));
+            }
         }
 
         header_lines.push(Line::from(""));
@@ -74,11 +90,17 @@ impl View for DeviceSelectScreen {
         };
         instruction_header.render(list_header_layout[0], buf);
 
+        let known = |d: &Device| {
+            d.serial
+                .as_deref()
+                .and_then(|serial| history::device_uses(props.history, serial))
+        };
         let data_panel = DataPanel {
             title: "Available Devices",
             items: props.devices,
             selected: props.selected,
             columns: vec![
+                crate::ui::widgets::ColumnDef::new(Constraint::Length(1)),
                 crate::ui::widgets::ColumnDef::new(Constraint::Percentage(30)),
                 crate::ui::widgets::ColumnDef::new(Constraint::Length(9)),
                 crate::ui::widgets::ColumnDef::new(Constraint::Percentage(20)),
@@ -96,6 +118,15 @@ impl View for DeviceSelectScreen {
                         }
                         spans.push(Span::styled("mounted", styles::warning()));
                     }
+                    if known(d).is_some() {
+                        if !spans.is_empty() {
+                            spans.push(Span::raw(" "));
+                        }
+                        spans.push(Span::styled(
+                            format!("{} known", ctx.icons.recent),
+                            styles::emphasis(),
+                        ));
+                    }
                     if spans.is_empty() {
                         Line::from(Span::styled("-", styles::text_dim()))
                     } else {
@@ -103,7 +134,13 @@ impl View for DeviceSelectScreen {
                     }
                 };
                 let model_name = d.model.clone().unwrap_or_default();
+                let mark = if props.marked.contains(&d.path) {
+                    Span::styled(ctx.icons.check, styles::success())
+                } else {
+                    Span::raw(" ")
+                };
                 vec![
+                    Cell::from(Line::from(mark)),
                     Cell::from(d.name.clone()),
                     Cell::from(d.size.to_string()),
                     Cell::from(flags_line),
@@ -172,6 +209,24 @@ impl View for DeviceSelectScreen {
                     styles::success()
                 },
             });
+
+            if let Some((uses, last)) = known(device) {
+                detail_items.push(DetailItem {
+                    label: "History",
+                    value: format!(
+                        "written {} time{}, last {} with {}",
+                        uses,
+                        if uses == 1 { "" } else { "s" },
+                        format_ago(last.finished),
+                        last.image
+                            .as_path()
+                            .file_name()
+                            .map(|n| n.to_string_lossy().into_owned())
+                            .unwrap_or_default()
+                    ),
+                    style: styles::emphasis(),
+                });
+            }
         } else {
             detail_items.push(DetailItem {
                 label: "Status",
@@ -192,15 +247,19 @@ impl View for DeviceSelectScreen {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::app::keymap::Keymap;
 
     #[test]
     fn test_device_select_screen_empty() {
         let screen = DeviceSelectScreen;
         let props = DeviceSelectProps {
             devices: &[],
+            marked: &[],
             selected: 0,
             refreshing: false,
             focused: true,
+            keymap: &Keymap::default(),
+            history: &[],
         };
         let ctx = UiCtx::new();
         let area = Rect::new(0, 0, 120, 40);
@@ -214,9 +273,12 @@ mod tests {
         let screen = DeviceSelectScreen;
         let props = DeviceSelectProps {
             devices: &[],
+            marked: &[],
             selected: 0,
             refreshing: true,
             focused: true,
+            keymap: &Keymap::default(),
+            history: &[],
         };
         let ctx = UiCtx::new();
         let area = Rect::new(0, 0, 120, 40);
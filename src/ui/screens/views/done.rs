@@ -7,7 +7,7 @@ use ratatui::{
 
 use crate::ui::{
     core::{DoneProps, UiCtx, View},
-    theme::{colors, styles},
+    theme::{self, styles},
     widgets::CenteredModal,
 };
 
@@ -16,7 +16,7 @@ pub struct DoneScreen;
 impl View for DoneScreen {
     type Props<'a> = DoneProps;
 
-    fn render<'a>(&self, props: &Self::Props<'a>, _ctx: &UiCtx, area: Rect, buf: &mut Buffer) {
+    fn render<'a>(&self, props: &Self::Props<'a>, ctx: &UiCtx, area: Rect, buf: &mut Buffer) {
         let modal = CenteredModal::default();
         let content_area = modal.compute_area(area);
 
@@ -24,28 +24,64 @@ impl View for DoneScreen {
 
         match &props.result {
             Some(Ok(())) => {
-                lines.push(Line::from(Span::styled("✓ Done", styles::success())));
+                lines.push(Line::from(Span::styled(
+                    format!("{} Done", ctx.icons.check),
+                    styles::success(),
+                )));
                 lines.push(Line::from(""));
                 lines.push(Line::from(vec![
-                    Span::styled("✓ ", colors::SUCCESS),
+                    Span::styled(format!("{} ", ctx.icons.check), theme::active().success),
                     Span::styled("Write completed successfully!", styles::success()),
                 ]));
-                lines.push(Line::from(""));
-                lines.push(Line::from(vec![
-                    Span::styled("Note: ", styles::text()),
-                    Span::styled(
-                        "You may need to reinsert the drive or run ",
-                        styles::text_muted(),
-                    ),
-                    Span::styled("partprobe", styles::code()),
-                    Span::styled(".", styles::text_muted()),
-                ]));
+                if props.ejects.is_empty() {
+                    lines.push(Line::from(""));
+                    lines.push(Line::from(vec![
+                        Span::styled("Note: ", styles::text()),
+                        Span::styled(
+                            "You may need to reinsert the drive or run ",
+                            styles::text_muted(),
+                        ),
+                        Span::styled("partprobe", styles::code()),
+                        Span::styled(", or press ", styles::text_muted()),
+                        Span::styled(props.eject_key.to_string(), styles::highlight()),
+                        Span::styled(" to eject it.", styles::text_muted()),
+                    ]));
+                }
+                match &props.persistence {
+                    Some((kind, Ok(()))) => {
+                        lines.push(Line::from(""));
+                        lines.push(Line::from(vec![
+                            Span::styled(format!("{} ", ctx.icons.check), theme::active().success),
+                            Span::styled(
+                                format!(
+                                    "Persistence partition '{}' created; boot with ",
+                                    kind.label()
+                                ),
+                                styles::text(),
+                            ),
+                            Span::styled(kind.boot_param(), styles::code()),
+                            Span::styled(".", styles::text()),
+                        ]));
+                    }
+                    Some((_, Err(e))) => {
+                        lines.push(Line::from(""));
+                        lines.push(Line::from(vec![
+                            Span::styled(format!("{} ", ctx.icons.cross), theme::active().danger),
+                            Span::styled("Persistence partition failed: ", styles::danger()),
+                            Span::styled(e.as_str(), styles::text()),
+                        ]));
+                    }
+                    None => {}
+                }
             }
             Some(Err(e)) => {
-                lines.push(Line::from(Span::styled("✗ Failed", styles::danger())));
+                lines.push(Line::from(Span::styled(
+                    format!("{} Failed", ctx.icons.cross),
+                    styles::danger(),
+                )));
                 lines.push(Line::from(""));
                 lines.push(Line::from(vec![
-                    Span::styled("✗ ", colors::DANGER),
+                    Span::styled(format!("{} ", ctx.icons.cross), theme::active().danger),
                     Span::styled("Write failed:", styles::danger()),
                 ]));
                 lines.push(Line::from(""));
@@ -59,7 +95,7 @@ impl View for DoneScreen {
 
                 lines.push(Line::from(""));
 
-                if !props.is_root || contains_perm_denied(e) {
+                if props.lacked_access || contains_perm_denied(e) {
                     lines.push(Line::from(vec![
                         Span::styled("💡 Hint: ", styles::warning()),
                         Span::styled(
@@ -74,12 +110,138 @@ impl View for DoneScreen {
                 }
             }
             None => {
-                lines.push(Line::from(Span::styled("● Done", styles::text_dim())));
+                lines.push(Line::from(Span::styled(
+                    format!("{} Done", ctx.icons.bullet),
+                    styles::text_dim(),
+                )));
                 lines.push(Line::from(""));
                 lines.push(Line::from(Span::styled("No result.", styles::text_dim())));
             }
         }
 
+        if !props.targets.is_empty() {
+            lines.push(Line::from(""));
+            for t in &props.targets {
+                let outcome = match (&t.write_result, &t.verify_result) {
+                    (Some(Err(e)), _) | (_, Some(Err(e))) => Err(e.as_str()),
+                    (_, Some(Ok(()))) => Ok("written and verified"),
+                    _ => Ok("written"),
+                };
+                lines.push(Line::from(match outcome {
+                    Ok(what) => vec![
+                        Span::styled(format!("{} ", ctx.icons.check), theme::active().success),
+                        Span::styled(format!("{} ", t.device), styles::code()),
+                        Span::styled(what, styles::success()),
+                    ],
+                    Err(e) => vec![
+                        Span::styled(format!("{} ", ctx.icons.cross), theme::active().danger),
+                        Span::styled(format!("{} ", t.device), styles::code()),
+                        Span::styled(e, styles::danger()),
+                    ],
+                }));
+            }
+        }
+
+        if !props.queue.is_empty() {
+            lines.push(Line::from(""));
+            for (i, job) in props.queue.jobs.iter().enumerate() {
+                let name = job
+                    .iso
+                    .as_path()
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .unwrap_or("?");
+                let (icon, style) = match &job.result {
+                    Some(Ok(())) => (ctx.icons.check, styles::success()),
+                    Some(Err(_)) => (ctx.icons.cross, styles::danger()),
+                    None => (ctx.icons.bullet, styles::text_dim()),
+                };
+                lines.push(Line::from(vec![
+                    Span::styled(format!("{} {}. ", icon, i + 1), style),
+                    Span::styled(format!("{} → {}", name, job.device), styles::text()),
+                ]));
+            }
+            if let (true, Some(job)) = (props.queue.awaiting_device, props.queue.current_job()) {
+                lines.push(Line::from(""));
+                let step = if props.queue.written_removed {
+                    "Insert the next device ("
+                } else {
+                    "Unplug the written stick, insert the next device ("
+                };
+                lines.push(Line::from(vec![
+                    Span::styled(step, styles::warning()),
+                    Span::styled(job.device.to_string(), styles::code()),
+                    Span::styled(") and press ", styles::warning()),
+                    Span::styled("Enter", styles::highlight()),
+                ]));
+                if props.queue.device_missing {
+                    lines.push(Line::from(Span::styled(
+                        "Device not found yet; press Enter to check again",
+                        styles::text_dim(),
+                    )));
+                }
+            }
+        }
+
+        if !props.ejects.is_empty() {
+            lines.push(Line::from(""));
+            for (device, outcome) in &props.ejects {
+                let (status, style) = match outcome {
+                    None => ("ejecting…".to_string(), styles::text_dim()),
+                    Some(Ok(true)) => (
+                        "ejected and powered off; unplug it".to_string(),
+                        styles::success(),
+                    ),
+                    Some(Ok(false)) => ("ejected; safe to unplug".to_string(), styles::success()),
+                    Some(Err(e)) => (format!("eject failed: {}", e), styles::danger()),
+                };
+                lines.push(Line::from(vec![
+                    Span::styled(format!("{} ", ctx.icons.eject), style),
+                    Span::styled(format!("{} ", device), styles::code()),
+                    Span::styled(status, style),
+                ]));
+            }
+        }
+
+        if !props.warnings.is_empty() {
+            lines.push(Line::from(""));
+            for w in &props.warnings {
+                lines.push(Line::from(vec![
+                    Span::styled("Warning: ", styles::warning()),
+                    Span::styled(w.as_str(), styles::text_muted()),
+                ]));
+            }
+        }
+
+        match &props.report_exported {
+            Some(Ok(paths)) => {
+                lines.push(Line::from(""));
+                lines.push(Line::from(Span::styled("Report saved:", styles::success())));
+                for path in paths {
+                    lines.push(Line::from(Span::styled(
+                        path.display().to_string(),
+                        styles::code(),
+                    )));
+                }
+            }
+            Some(Err(e)) => {
+                lines.push(Line::from(""));
+                lines.push(Line::from(vec![
+                    Span::styled("Report not saved: ", styles::danger()),
+                    Span::styled(e.as_str(), styles::text()),
+                ]));
+            }
+            None if props.report_ready => {
+                lines.push(Line::from(""));
+                lines.push(Line::from(vec![
+                    Span::styled("Press ", styles::text_muted()),
+                    Span::styled(props.export_key.to_string(), styles::highlight()),
+                    Span::styled(" to export a report (text + JSON).", styles::text_muted()),
+                ]));
+            }
+            None => {}
+        }
+
         lines.push(Line::from(""));
         lines.push(Line::from(vec![
             Span::styled("Press ", styles::text_muted()),
@@ -102,13 +264,23 @@ fn contains_perm_denied(s: &str) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::app::keymap::{Action, Keymap};
 
     #[test]
     fn test_done_screen_success() {
         let screen = DoneScreen;
         let props = DoneProps {
             result: Some(Ok(())),
-            is_root: true,
+            persistence: None,
+            targets: Vec::new(),
+            queue: Default::default(),
+            warnings: Vec::new(),
+            report_ready: true,
+            report_exported: None,
+            export_key: Keymap::default().binding(Action::ExportReport),
+            ejects: Vec::new(),
+            eject_key: Keymap::default().binding(Action::Eject),
+            lacked_access: false,
         };
         let ctx = UiCtx::new();
         let area = Rect::new(0, 0, 80, 24);
@@ -122,7 +294,16 @@ mod tests {
         let screen = DoneScreen;
         let props = DoneProps {
             result: Some(Err("Write failed".to_string())),
-            is_root: false,
+            persistence: None,
+            targets: Vec::new(),
+            queue: Default::default(),
+            warnings: Vec::new(),
+            report_ready: true,
+            report_exported: None,
+            export_key: Keymap::default().binding(Action::ExportReport),
+            ejects: Vec::new(),
+            eject_key: Keymap::default().binding(Action::Eject),
+            lacked_access: true,
         };
         let ctx = UiCtx::new();
         let area = Rect::new(0, 0, 80, 24);
@@ -5,10 +5,12 @@ use ratatui::{
     widgets::{Cell, Widget},
 };
 
+use crate::app::{keymap::Action, state::Browser};
+use crate::domain::{history, iso};
 use crate::ui::{
-    components::{DetailItem, DetailPanel},
+    components::{DetailItem, DetailPanel, format_ago},
     core::{IsoSearchProps, UiCtx, View},
-    theme::{colors, styles},
+    theme::{self, styles},
     widgets::{DataPanel, InstructionHeader},
 };
 
@@ -17,7 +19,7 @@ pub struct IsoSearchScreen;
 impl View for IsoSearchScreen {
     type Props<'a> = IsoSearchProps<'a>;
 
-    fn render<'a>(&self, props: &Self::Props<'a>, _ctx: &UiCtx, area: Rect, buf: &mut Buffer) {
+    fn render<'a>(&self, props: &Self::Props<'a>, ctx: &UiCtx, area: Rect, buf: &mut Buffer) {
         let columns = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
@@ -25,28 +27,67 @@ impl View for IsoSearchScreen {
 
         let list_area = columns[0];
         let detail_area = columns[1];
+        if let Some(browser) = props.browser {
+            render_browser(browser, props, ctx, list_area, detail_area, buf);
+            return;
+        }
 
         let list_header_layout = Layout::default()
             .direction(Direction::Vertical)
-            .constraints([Constraint::Length(5), Constraint::Min(1)])
+            .constraints([Constraint::Length(6), Constraint::Min(1)])
             .split(list_area);
 
         let mut header_lines = vec![];
         header_lines.push(Line::from(Span::styled("ISO Search", styles::title())));
         header_lines.push(Line::from(vec![
-            Span::styled("Type to filter; ", styles::text_muted()),
+            Span::styled("Type to fuzzy-filter; ", styles::text_muted()),
             Span::styled("Up/Down", styles::highlight()),
             Span::styled(" to move; ", styles::text_muted()),
             Span::styled("Enter", styles::highlight()),
-            Span::styled(" to select", styles::text_muted()),
+            Span::styled(" to select; ", styles::text_muted()),
+            Span::styled("arch:", styles::highlight()),
+            Span::styled("/", styles::text_muted()),
+            Span::styled("distro:", styles::highlight()),
+            Span::styled(" to filter; ", styles::text_muted()),
+            Span::styled(
+                props.keymap.binding(Action::MarkIso).to_string(),
+                styles::highlight(),
+            ),
+            Span::styled(" to mark for a multi-ISO drive; ", styles::text_muted()),
+            Span::styled(
+                props.keymap.binding(Action::Browse).to_string(),
+                styles::highlight(),
+            ),
+            Span::styled(" to browse by path", styles::text_muted()),
         ]));
         header_lines.push(Line::from(vec![
             Span::styled("Query: ", styles::text()),
             Span::styled(props.query, styles::code()),
         ]));
+        let roots: Vec<String> = props.roots.iter().map(|r| r.to_string()).collect();
+        header_lines.push(Line::from(vec![
+            Span::styled("Roots: ", styles::text()),
+            if roots.is_empty() {
+                Span::styled("none", styles::text_dim())
+            } else {
+                Span::styled(roots.join(", "), styles::text_muted())
+            },
+        ]));
+        if !props.marked.is_empty() {
+            header_lines.push(Line::from(vec![
+                Span::styled(format!("{} ", ctx.icons.check), theme::active().success),
+                Span::styled(
+                    format!(
+                        "{} marked; Enter to build a multi-ISO drive",
+                        props.marked.len()
+                    ),
+                    styles::success(),
+                ),
+            ]));
+        }
         if props.searching {
             header_lines.push(Line::from(vec![
-                Span::styled("● ", colors::PRIMARY),
+                Span::styled(format!("{} ", ctx.icons.bullet), theme::active().primary),
                 Span::styled("Searching...", styles::text_muted()),
             ]));
         } else if props.results.is_empty() {
@@ -58,15 +99,35 @@ impl View for IsoSearchScreen {
         };
         instruction_header.render(list_header_layout[0], buf);
 
+        // Recently written images lead the unfiltered list
+        let recent = if props.query.trim().is_empty() {
+            history::recent_images(props.history, history::RECENT_LIMIT)
+        } else {
+            Vec::new()
+        };
         let data_panel = DataPanel {
-            title: "Results",
+            title: if recent.is_empty() {
+                "Results"
+            } else {
+                "Recent, then results"
+            },
             items: props.results,
             selected: props.selected,
             columns: vec![
-                crate::ui::widgets::ColumnDef::new(Constraint::Percentage(70)),
-                crate::ui::widgets::ColumnDef::new(Constraint::Percentage(30)),
+                crate::ui::widgets::ColumnDef::new(Constraint::Length(1)),
+                crate::ui::widgets::ColumnDef::new(Constraint::Percentage(55)),
+                crate::ui::widgets::ColumnDef::new(Constraint::Length(9)),
+                crate::ui::widgets::ColumnDef::new(Constraint::Length(8)),
+                crate::ui::widgets::ColumnDef::new(Constraint::Percentage(20)),
             ],
             row_mapper: Box::new(|meta| {
+                let mark = if props.marked.contains(&meta.path) {
+                    Span::styled(ctx.icons.check, styles::success())
+                } else if recent.contains(&meta.path) {
+                    Span::styled(ctx.icons.recent, styles::emphasis())
+                } else {
+                    Span::raw(" ")
+                };
                 let name = meta
                     .path
                     .as_path()
@@ -74,7 +135,25 @@ impl View for IsoSearchScreen {
                     .and_then(|n| n.to_str())
                     .unwrap_or("?");
                 let size = meta.size.to_string();
-                vec![Cell::from(name.to_string()), Cell::from(size)]
+                let badge = if meta.distro.is_known() {
+                    Span::styled(meta.distro.distro.badge(), styles::emphasis())
+                } else {
+                    Span::styled("-", styles::text_dim())
+                };
+                let arch = match meta.distro.arch {
+                    Some(a) => Span::styled(a.as_str(), styles::text_muted()),
+                    None => Span::styled("-", styles::text_dim()),
+                };
+                vec![
+                    Cell::from(Line::from(mark)),
+                    Cell::from(highlight_matches(
+                        name,
+                        &iso::match_positions(meta, props.query),
+                    )),
+                    Cell::from(Line::from(badge)),
+                    Cell::from(Line::from(arch)),
+                    Cell::from(size),
+                ]
             }),
             focused: props.focused,
         };
@@ -103,6 +182,35 @@ impl View for IsoSearchScreen {
                 style: styles::emphasis(),
             });
 
+            if iso.distro.is_known() {
+                let mut os = iso.distro.distro.to_string();
+                if let Some(ref version) = iso.distro.version {
+                    os.push(' ');
+                    os.push_str(version);
+                }
+                detail_items.push(DetailItem {
+                    label: "OS",
+                    value: os,
+                    style: styles::text(),
+                });
+            }
+
+            if let Some(arch) = iso.distro.arch {
+                detail_items.push(DetailItem {
+                    label: "Arch",
+                    value: arch.to_string(),
+                    style: styles::text_muted(),
+                });
+            }
+
+            if let Some(ref label) = iso.distro.label {
+                detail_items.push(DetailItem {
+                    label: "Label",
+                    value: label.clone(),
+                    style: styles::code(),
+                });
+            }
+
             detail_items.push(DetailItem {
                 label: "Path",
                 value: iso.path.to_string(),
@@ -110,21 +218,25 @@ impl View for IsoSearchScreen {
             });
 
             if let Some(modified) = iso.modified {
-                if let Ok(elapsed) = modified.elapsed() {
-                    let secs = elapsed.as_secs();
-                    let time_str = if secs < 3600 {
-                        format!("{} minutes ago", secs / 60)
-                    } else if secs < 86400 {
-                        format!("{} hours ago", secs / 3600)
-                    } else {
-                        format!("{} days ago", secs / 86400)
-                    };
-                    detail_items.push(DetailItem {
-                        label: "Modified",
-                        value: time_str,
-                        style: styles::text_muted(),
-                    });
-                }
+                detail_items.push(DetailItem {
+                    label: "Modified",
+                    value: format_ago(modified),
+                    style: styles::text_muted(),
+                });
+            }
+
+            if let Some(last) = history::last_use(props.history, &iso.path) {
+                let stick = last.model.as_deref().unwrap_or("unknown stick");
+                detail_items.push(DetailItem {
+                    label: "Last written",
+                    value: format!(
+                        "{} to {} ({})",
+                        format_ago(last.finished),
+                        stick,
+                        if last.result.is_ok() { "ok" } else { "failed" }
+                    ),
+                    style: styles::text_muted(),
+                });
             }
         } else {
             detail_items.push(DetailItem {
@@ -143,9 +255,141 @@ impl View for IsoSearchScreen {
     }
 }
 
+/// The path browser in place of the result list
+fn render_browser(
+    browser: &Browser,
+    props: &IsoSearchProps,
+    ctx: &UiCtx,
+    list_area: Rect,
+    detail_area: Rect,
+    buf: &mut Buffer,
+) {
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(6), Constraint::Min(1)])
+        .split(list_area);
+
+    let mut header_lines = vec![
+        Line::from(Span::styled("Browse", styles::title())),
+        Line::from(vec![
+            Span::styled("Type a path, ", styles::text_muted()),
+            Span::styled("Tab", styles::highlight()),
+            Span::styled(" to complete; ", styles::text_muted()),
+            Span::styled("Enter", styles::highlight()),
+            Span::styled(" to open; ", styles::text_muted()),
+            Span::styled("Backspace", styles::highlight()),
+            Span::styled(" for the parent; ", styles::text_muted()),
+            Span::styled(
+                props.keymap.binding(Action::Browse).to_string(),
+                styles::highlight(),
+            ),
+            Span::styled(" back to search", styles::text_muted()),
+        ]),
+        Line::from(vec![
+            Span::styled("Path: ", styles::text()),
+            Span::styled(browser.input.as_str(), styles::code()),
+        ]),
+        Line::from(vec![
+            Span::styled("In: ", styles::text()),
+            Span::styled(browser.dir.display().to_string(), styles::text_muted()),
+        ]),
+    ];
+    if let Some(ref err) = browser.error {
+        header_lines.push(Line::from(vec![
+            Span::styled(format!("{} ", ctx.icons.cross), theme::active().danger),
+            Span::styled(err.as_str(), styles::danger()),
+        ]));
+    }
+    InstructionHeader {
+        lines: header_lines,
+    }
+    .render(layout[0], buf);
+
+    DataPanel {
+        title: "Files",
+        items: &browser.entries,
+        selected: browser.selected,
+        columns: vec![
+            crate::ui::widgets::ColumnDef::new(Constraint::Percentage(75)),
+            crate::ui::widgets::ColumnDef::new(Constraint::Percentage(25)),
+        ],
+        row_mapper: Box::new(|entry| {
+            if entry.is_dir {
+                vec![
+                    Cell::from(Span::styled(format!("{}/", entry.name), styles::emphasis())),
+                    Cell::from(""),
+                ]
+            } else {
+                vec![
+                    Cell::from(entry.name.clone()),
+                    Cell::from(entry.size.to_string()),
+                ]
+            }
+        }),
+        focused: props.focused,
+    }
+    .render(layout[1], buf);
+
+    let mut detail_items = vec![];
+    if let Some(entry) = browser.entries.get(browser.selected) {
+        detail_items.push(DetailItem {
+            label: "Path",
+            value: browser.dir.join(&entry.name).display().to_string(),
+            style: styles::code(),
+        });
+        if !entry.is_dir {
+            detail_items.push(DetailItem {
+                label: "Size",
+                value: entry.size.to_string(),
+                style: styles::emphasis(),
+            });
+        }
+    } else {
+        detail_items.push(DetailItem {
+            label: "Status",
+            value: "Empty directory".to_string(),
+            style: styles::text_dim(),
+        });
+    }
+    DetailPanel {
+        title: "Entry",
+        items: detail_items,
+        synced_focus: props.focused,
+    }
+    .render(detail_area, buf);
+}
+
+/// The name with the fuzzy-matched characters picked out
+fn highlight_matches(name: &str, positions: &[usize]) -> Line<'static> {
+    let mut spans: Vec<Span<'static>> = Vec::new();
+    let mut run = String::new();
+    let mut run_matched = false;
+    for (i, c) in name.chars().enumerate() {
+        let matched = positions.contains(&i);
+        if matched != run_matched && !run.is_empty() {
+            spans.push(match_span(std::mem::take(&mut run), run_matched));
+        }
+        run_matched = matched;
+        run.push(c);
+    }
+    if !run.is_empty() {
+        spans.push(match_span(run, run_matched));
+    }
+    Line::from(spans)
+}
+
+fn match_span(text: String, matched: bool) -> Span<'static> {
+    if matched {
+        Span::styled(text, styles::highlight())
+    } else {
+        Span::raw(text)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::app::keymap::Keymap;
 
     #[test]
     fn test_iso_search_screen_empty() {
@@ -155,7 +399,12 @@ mod tests {
             searching: false,
             results: &[],
             selected: 0,
+            marked: &[],
+            roots: &[],
             focused: true,
+            keymap: &Keymap::default(),
+            browser: None,
+            history: &[],
         };
         let ctx = UiCtx::new();
         let area = Rect::new(0, 0, 120, 40);
@@ -164,6 +413,14 @@ mod tests {
         screen.render(&props, &ctx, area, &mut buf);
     }
 
+    #[test]
+    fn matched_chars_are_grouped_into_spans() {
+        let line = highlight_matches("ubuntu-24.iso", &[0, 1, 2, 7, 8]);
+        let parts: Vec<&str> = line.spans.iter().map(|s| s.content.as_ref()).collect();
+        assert_eq!(parts, vec!["ubu", "ntu-", "24", ".iso"]);
+        assert_eq!(line.spans[0].style, styles::highlight());
+    }
+
     #[test]
     fn test_iso_search_screen_searching() {
         let screen = IsoSearchScreen;
@@ -172,7 +429,12 @@ mod tests {
             searching: true,
             results: &[],
             selected: 0,
+            marked: &[],
+            roots: &[],
             focused: true,
+            keymap: &Keymap::default(),
+            browser: None,
+            history: &[],
         };
         let ctx = UiCtx::new();
         let area = Rect::new(0, 0, 120, 40);
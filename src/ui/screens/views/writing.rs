@@ -2,13 +2,15 @@ use ratatui::{
     buffer::Buffer,
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     text::{Line, Span},
-    widgets::{Paragraph, Widget},
+    widgets::{Cell, Paragraph, Widget},
 };
 
 use crate::ui::{
-    components::ProgressWidget,
+    components::{ProgressWidget, human_size},
     core::{UiCtx, View, WritingProps},
-    theme::{colors, styles},
+    icons::Icons,
+    theme::{self, styles},
+    widgets::{ColumnDef, DataPanel, InstructionHeader},
 };
 
 pub struct WritingScreen;
@@ -17,6 +19,10 @@ impl View for WritingScreen {
     type Props<'a> = WritingProps;
 
     fn render<'a>(&self, props: &Self::Props<'a>, ctx: &UiCtx, area: Rect, buf: &mut Buffer) {
+        if !props.targets.is_empty() {
+            render_targets(props, &ctx.icons, area, buf);
+            return;
+        }
         let centered_layout = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([
@@ -52,14 +58,14 @@ impl View for WritingScreen {
             total: props.total,
             speed_bps: props.speed_bps,
             bar_width: 40,
-            color: colors::SUCCESS,
+            color: theme::active().success,
             icons: &ctx.icons,
         };
         write_progress.render(layout[0], buf);
 
         let mut extra_lines = vec![];
         extra_lines.push(Line::from(vec![
-            Span::styled("⚠ ", colors::WARNING),
+            Span::styled(format!("{} ", ctx.icons.warning), theme::active().warning),
             Span::styled("Please wait ", styles::warning()),
             Span::styled("- do not remove the device.", styles::text()),
         ]));
@@ -73,7 +79,7 @@ impl View for WritingScreen {
                     total: props.verify_total,
                     speed_bps: props.verify_speed_bps,
                     bar_width: 40,
-                    color: colors::ACCENT,
+                    color: theme::active().accent,
                     icons: &ctx.icons,
                 };
 
@@ -86,12 +92,20 @@ impl View for WritingScreen {
                 verify_progress.render(verify_area, buf);
             } else if props.write_result == Some(Ok(())) {
                 extra_lines.push(Line::from(vec![
-                    Span::styled("● ", colors::PRIMARY),
+                    Span::styled(format!("{} ", ctx.icons.bullet), theme::active().primary),
                     Span::styled("Verification pending...", styles::text_muted()),
                 ]));
             }
         }
 
+        if props.persisting {
+            extra_lines.push(Line::from(""));
+            extra_lines.push(Line::from(vec![
+                Span::styled(format!("{} ", ctx.icons.bullet), theme::active().primary),
+                Span::styled("Creating persistence partition...", styles::text_muted()),
+            ]));
+        }
+
         let remaining_area = if props.verify_after_write && props.verifying {
             Rect {
                 x: layout[2].x,
@@ -109,6 +123,83 @@ impl View for WritingScreen {
     }
 }
 
+/// One row per device for a parallel write
+fn render_targets(props: &WritingProps, icons: &Icons, area: Rect, buf: &mut Buffer) {
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(1)])
+        .split(area);
+
+    let done = props
+        .targets
+        .iter()
+        .filter(|t| t.is_finished(props.verify_after_write))
+        .count();
+    InstructionHeader {
+        lines: vec![
+            Line::from(Span::styled("⚡ Writing", styles::title())),
+            Line::from(vec![
+                Span::styled(
+                    format!("{} of {} devices finished ", done, props.targets.len()),
+                    styles::text_muted(),
+                ),
+                Span::styled("- do not remove the devices.", styles::warning()),
+            ]),
+        ],
+    }
+    .render(layout[0], buf);
+
+    DataPanel {
+        title: "Devices",
+        items: &props.targets,
+        selected: 0,
+        columns: vec![
+            ColumnDef::new(Constraint::Percentage(20)),
+            ColumnDef::new(Constraint::Length(8)),
+            ColumnDef::new(Constraint::Percentage(20)),
+            ColumnDef::new(Constraint::Percentage(40)),
+        ],
+        row_mapper: Box::new(|t| {
+            let (current, speed) = if t.verifying || t.verify_result.is_some() {
+                (t.verified, t.verify_speed_bps)
+            } else {
+                (t.written, t.speed_bps)
+            };
+            let pct = if t.total > 0 {
+                current as f64 / t.total as f64 * 100.0
+            } else {
+                0.0
+            };
+            let status = match (&t.write_result, &t.verify_result) {
+                (Some(Err(e)), _) => {
+                    Span::styled(format!("{} {}", icons.cross, e), styles::danger())
+                }
+                (_, Some(Err(e))) => {
+                    Span::styled(format!("{} verify: {}", icons.cross, e), styles::danger())
+                }
+                (_, Some(Ok(()))) => {
+                    Span::styled(format!("{} verified", icons.check), styles::success())
+                }
+                (Some(Ok(())), None) if t.verifying => {
+                    Span::styled("verifying...", styles::text_muted())
+                }
+                (Some(Ok(())), None) => {
+                    Span::styled(format!("{} written", icons.check), styles::success())
+                }
+                (None, _) => Span::styled("writing...", styles::text_muted()),
+            };
+            vec![
+                Cell::from(t.device.to_string()),
+                Cell::from(format!("{:>5.1}%", pct)),
+                Cell::from(format!("{}/s", human_size(speed as u64))),
+                Cell::from(Line::from(status)),
+            ]
+        }),
+        focused: true,
+    }
+    .render(layout[1], buf);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -125,6 +216,8 @@ mod tests {
             verified: 0,
             verify_total: 0,
             verify_speed_bps: 0.0,
+            persisting: false,
+            targets: Vec::new(),
             write_result: None,
         };
         let ctx = UiCtx::new();
@@ -146,6 +239,8 @@ mod tests {
             verified: 1024,
             verify_total: 2048,
             verify_speed_bps: 256.0,
+            persisting: false,
+            targets: Vec::new(),
             write_result: Some(Ok(())),
         };
         let ctx = UiCtx::new();
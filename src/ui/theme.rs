@@ -81,27 +81,27 @@ pub mod styles {
 
     pub fn title() -> Style {
         Style::default()
-            .fg(colors::PRIMARY)
+            .fg(super::active().primary)
             .add_modifier(Modifier::BOLD)
     }
 
     pub fn subtitle() -> Style {
         Style::default()
-            .fg(colors::TEXT_SECONDARY)
+            .fg(super::active().text_secondary)
             .add_modifier(Modifier::DIM)
     }
 
     pub fn text() -> Style {
-        Style::default().fg(colors::TEXT_PRIMARY)
+        Style::default().fg(super::active().text_primary)
     }
 
     pub fn text_muted() -> Style {
-        Style::default().fg(colors::TEXT_SECONDARY)
+        Style::default().fg(super::active().text_secondary)
     }
 
     pub fn text_dim() -> Style {
         Style::default()
-            .fg(colors::TEXT_DIM)
+            .fg(super::active().text_dim)
             .add_modifier(Modifier::DIM)
     }
 
@@ -114,31 +114,31 @@ pub mod styles {
 
     pub fn selected_marker() -> Style {
         Style::default()
-            .fg(colors::ACCENT)
+            .fg(super::active().accent)
             .add_modifier(Modifier::BOLD)
     }
 
     pub fn success() -> Style {
         Style::default()
-            .fg(colors::SUCCESS)
+            .fg(super::active().success)
             .add_modifier(Modifier::BOLD)
     }
 
     pub fn warning() -> Style {
         Style::default()
-            .fg(colors::WARNING)
+            .fg(super::active().warning)
             .add_modifier(Modifier::BOLD)
     }
 
     pub fn danger() -> Style {
         Style::default()
-            .fg(colors::DANGER_BRIGHT)
+            .fg(super::active().danger)
             .add_modifier(Modifier::BOLD)
     }
 
     pub fn highlight() -> Style {
         Style::default()
-            .fg(colors::ACCENT)
+            .fg(super::active().accent)
             .add_modifier(Modifier::BOLD)
     }
 
@@ -150,46 +150,49 @@ pub mod styles {
 
     pub fn emphasis() -> Style {
         Style::default()
-            .fg(colors::PRIMARY)
+            .fg(super::active().primary)
             .add_modifier(Modifier::ITALIC)
     }
 
     pub fn border_focused() -> Style {
-        Style::default().fg(colors::BORDER_FOCUS)
+        Style::default().fg(super::active().border_focus)
     }
 
     pub fn border_default() -> Style {
-        Style::default().fg(colors::BORDER_ACTIVE)
+        Style::default().fg(super::active().border_active)
     }
 
     pub fn border_muted() -> Style {
-        Style::default().fg(colors::BORDER_INACTIVE)
+        Style::default().fg(super::active().border_inactive)
     }
 
     pub fn success_bg() -> Style {
         Style::default()
-            .fg(colors::success_fg())
-            .bg(colors::success_bg())
+            .fg(super::active().success)
+            .bg(super::active().bg_tertiary)
     }
 
     pub fn warning_bg() -> Style {
         Style::default()
-            .fg(colors::warning_fg())
+            .fg(super::active().warning)
             .bg(colors::warning_bg())
     }
 
     pub fn danger_bg() -> Style {
         Style::default()
-            .fg(colors::danger_fg())
+            .fg(super::active().danger)
             .bg(colors::danger_bg())
     }
 
     pub fn info_bg() -> Style {
-        Style::default().fg(colors::info_fg()).bg(colors::info_bg())
+        Style::default()
+            .fg(colors::info_fg())
+            .bg(super::active().bg_tertiary)
     }
 }
 
 use ratatui::style::Color;
+use std::sync::OnceLock;
 
 #[derive(Debug, Clone, Copy)]
 pub struct ThemeVariant {
@@ -249,6 +252,30 @@ impl ThemeVariant {
     }
 }
 
+impl ThemeVariant {
+    pub const NAMES: [&str; 2] = ["dark", "high-contrast"];
+
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "high-contrast" => Some(Self::high_contrast()),
+            _ => None,
+        }
+    }
+}
+
+static ACTIVE: OnceLock<ThemeVariant> = OnceLock::new();
+
+/// Pick the theme for this run; only the first call has an effect
+pub fn set_active(theme: ThemeVariant) {
+    let _ = ACTIVE.set(theme);
+}
+
+/// The theme picked at startup, dark unless configured otherwise
+pub fn active() -> ThemeVariant {
+    ACTIVE.get().copied().unwrap_or_default()
+}
+
 impl Default for ThemeVariant {
     fn default() -> Self {
         Self::dark()
@@ -1,36 +1,48 @@
 use ratatui::{
     buffer::Buffer,
     layout::{Alignment, Rect},
-    style::{Color, Style},
+    style::{Color, Modifier, Style},
+    text::Line,
     widgets::{Block, BorderType, Widget},
 };
 
 use crate::app::state::{Model, Screen};
 use crate::ui::core::UiRouter;
 use crate::ui::layout::split_header_body_footer;
-use crate::ui::theme::colors;
+use crate::ui::theme;
 
 use super::step_header::{StepHeader, build_status_line, screen_to_step_index};
 
 pub struct AppShell<'a> {
     pub title: &'a str,
     pub border_color: Color,
+    /// Warning set into the top and bottom border, e.g. during a dry run
+    pub banner: Option<&'a str>,
 }
 
 impl<'a> AppShell<'a> {
     pub fn render(self, model: &Model, router: &UiRouter, area: Rect, buf: &mut Buffer) {
-        let block = Block::bordered()
+        let mut block = Block::bordered()
             .title(self.title)
             .title_alignment(Alignment::Center)
             .border_type(BorderType::Rounded)
             .border_style(Style::default().fg(self.border_color));
+        if let Some(banner) = self.banner {
+            let style = Style::default()
+                .fg(theme::active().bg_primary)
+                .bg(theme::active().warning)
+                .add_modifier(Modifier::BOLD);
+            block = block
+                .title_top(Line::styled(banner, style).right_aligned())
+                .title_bottom(Line::styled(banner, style).centered());
+        }
 
         let inner = block.inner(area);
         block.render(area, buf);
 
         for y in inner.top()..inner.bottom() {
             for x in inner.left()..inner.right() {
-                buf[(x, y)].set_bg(colors::BG_PRIMARY);
+                buf[(x, y)].set_bg(theme::active().bg_primary);
             }
         }
 
@@ -56,16 +68,16 @@ impl<'a> AppShell<'a> {
 
 pub fn compute_border_color(model: &Model) -> Color {
     match model.screen {
-        Screen::IsoSearch | Screen::DeviceSelect => colors::BORDER_ACTIVE,
-        Screen::Confirm => colors::WARNING,
-        Screen::Writing => colors::PRIMARY,
+        Screen::IsoSearch | Screen::DeviceSelect => theme::active().border_active,
+        Screen::Confirm => theme::active().warning,
+        Screen::Writing => theme::active().primary,
         Screen::Done => {
             if matches!(model.write_result, Some(Ok(_))) {
-                colors::SUCCESS
+                theme::active().success
             } else if matches!(model.write_result, Some(Err(_))) {
-                colors::DANGER
+                theme::active().danger
             } else {
-                colors::BORDER_INACTIVE
+                theme::active().border_inactive
             }
         }
     }
@@ -83,21 +95,21 @@ mod tests {
     fn test_compute_border_color_iso_search() {
         let mut model = Model::default();
         model.screen = Screen::IsoSearch;
-        assert_eq!(compute_border_color(&model), colors::BORDER_ACTIVE);
+        assert_eq!(compute_border_color(&model), theme::active().border_active);
     }
 
     #[test]
     fn test_compute_border_color_confirm() {
         let mut model = Model::default();
         model.screen = Screen::Confirm;
-        assert_eq!(compute_border_color(&model), colors::WARNING);
+        assert_eq!(compute_border_color(&model), theme::active().warning);
     }
 
     #[test]
     fn test_compute_border_color_writing() {
         let mut model = Model::default();
         model.screen = Screen::Writing;
-        assert_eq!(compute_border_color(&model), colors::PRIMARY);
+        assert_eq!(compute_border_color(&model), theme::active().primary);
     }
 
     #[test]
@@ -105,7 +117,7 @@ mod tests {
         let mut model = Model::default();
         model.screen = Screen::Done;
         model.write_result = Some(Ok(()));
-        assert_eq!(compute_border_color(&model), colors::SUCCESS);
+        assert_eq!(compute_border_color(&model), theme::active().success);
     }
 
     #[test]
@@ -113,6 +125,6 @@ mod tests {
         let mut model = Model::default();
         model.screen = Screen::Done;
         model.write_result = Some(Err("error".to_string()));
-        assert_eq!(compute_border_color(&model), colors::DANGER);
+        assert_eq!(compute_border_color(&model), theme::active().danger);
     }
 }
@@ -5,14 +5,16 @@ use ratatui::{
     widgets::{Block, BorderType, Cell, Row, StatefulWidget, Table, TableState, Widget},
 };
 
-use crate::ui::theme::colors;
+use crate::ui::{icons, theme};
+
+pub type RowMapper<'a, T> = Box<dyn Fn(&T) -> Vec<Cell<'a>> + 'a>;
 
 pub struct DataPanel<'a, T> {
     pub title: &'a str,
     pub items: &'a [T],
     pub selected: usize,
     pub columns: Vec<ColumnDef>,
-    pub row_mapper: Box<dyn Fn(&T) -> Vec<Cell<'a>> + 'a>,
+    pub row_mapper: RowMapper<'a, T>,
     pub focused: bool,
 }
 
@@ -29,9 +31,9 @@ impl ColumnDef {
 impl<'a, T> Widget for DataPanel<'a, T> {
     fn render(self, area: Rect, buf: &mut Buffer) {
         let (border_color, border_type) = if self.focused {
-            (colors::BORDER_FOCUS, BorderType::Double)
+            (theme::active().border_focus, BorderType::Double)
         } else {
-            (colors::BORDER_INACTIVE, BorderType::Rounded)
+            (theme::active().border_inactive, BorderType::Rounded)
         };
 
         let block = Block::bordered()
@@ -49,7 +51,7 @@ impl<'a, T> Widget for DataPanel<'a, T> {
 
         let table = Table::new(rows, constraints)
             .block(block)
-            .highlight_symbol("▶ ");
+            .highlight_symbol(format!("{} ", icons::active().arrow_right));
 
         let mut table_state = TableState::default();
         let selected = if self.items.is_empty() {
@@ -68,6 +70,7 @@ mod tests {
     use super::*;
 
     #[derive(Debug, Clone)]
+    #[allow(dead_code)]
     struct TestItem {
         name: String,
         value: u32,
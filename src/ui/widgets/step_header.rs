@@ -7,6 +7,7 @@ use ratatui::{
 
 use crate::app::state::{Model, Screen};
 use crate::ui::components::human_size;
+use crate::ui::icons;
 use crate::ui::theme::styles;
 
 pub struct StepHeader<'a> {
@@ -51,11 +52,59 @@ impl<'a> Widget for StepHeader<'a> {
 }
 
 pub fn build_status_line(model: &Model) -> Line<'static> {
+    let mut line = screen_status_line(model);
+    if let Some(queue) = queue_status(model) {
+        line.spans.push(Span::styled("  •  ", styles::text_dim()));
+        line.spans.extend(queue);
+    }
+    line
+}
+
+/// Job queue progress, shown on every screen once a job is queued
+fn queue_status(model: &Model) -> Option<Vec<Span<'static>>> {
+    let queue = &model.queue;
+    if queue.is_empty() {
+        return None;
+    }
+    let done = queue.jobs.iter().filter(|j| j.result.is_some()).count();
+    let failed = queue
+        .jobs
+        .iter()
+        .filter(|j| matches!(j.result, Some(Err(_))))
+        .count();
+    let mut spans = match queue.position() {
+        Some((n, total)) if queue.awaiting_device => vec![
+            Span::styled(format!("Queue {}/{}", n, total), styles::emphasis()),
+            Span::styled(" waiting for device", styles::warning()),
+        ],
+        Some((n, total)) => vec![Span::styled(
+            format!("Queue {}/{}", n, total),
+            styles::emphasis(),
+        )],
+        None if done == queue.jobs.len() => vec![Span::styled(
+            format!("Queue finished ({} jobs)", done),
+            styles::emphasis(),
+        )],
+        None => vec![Span::styled(
+            format!("Queued: {}", queue.jobs.len()),
+            styles::emphasis(),
+        )],
+    };
+    if failed > 0 {
+        spans.push(Span::styled(
+            format!(" ({} failed)", failed),
+            styles::danger(),
+        ));
+    }
+    Some(spans)
+}
+
+fn screen_status_line(model: &Model) -> Line<'static> {
     match model.screen {
         Screen::IsoSearch => {
             if model.iso_searching {
                 Line::from(vec![
-                    Span::styled("● ", styles::emphasis()),
+                    Span::styled(format!("{} ", icons::active().bullet), styles::emphasis()),
                     Span::styled("Searching for ISOs...", styles::text_muted()),
                 ])
             } else if model.iso_results.is_empty() {
@@ -71,7 +120,7 @@ pub fn build_status_line(model: &Model) -> Line<'static> {
         Screen::DeviceSelect => {
             if model.device_refreshing {
                 Line::from(vec![
-                    Span::styled("● ", styles::emphasis()),
+                    Span::styled(format!("{} ", icons::active().bullet), styles::emphasis()),
                     Span::styled("Refreshing devices...", styles::text_muted()),
                 ])
             } else if model.devices.is_empty() {
@@ -93,16 +142,16 @@ pub fn build_status_line(model: &Model) -> Line<'static> {
             } else {
                 "OFF"
             };
-            if model.confirm_input == "YES" {
+            if model.is_confirmation_valid() {
                 Line::from(vec![
-                    Span::styled("✓ ", styles::success()),
+                    Span::styled(format!("{} ", icons::active().check), styles::success()),
                     Span::styled("Ready • Enter to write • Verify: ", styles::text_muted()),
                     Span::styled(verify, styles::emphasis()),
                 ])
             } else {
                 Line::from(vec![
                     Span::styled("Type ", styles::text_muted()),
-                    Span::styled("YES", styles::warning()),
+                    Span::styled(model.safety.confirm_word.clone(), styles::warning()),
                     Span::styled(" to enable Enter • Verify: ", styles::text_muted()),
                     Span::styled(verify, styles::emphasis()),
                 ])
@@ -127,14 +176,14 @@ pub fn build_status_line(model: &Model) -> Line<'static> {
         Screen::Done => {
             if let Some(Ok(())) = model.verify_result.as_ref().or(model.write_result.as_ref()) {
                 Line::from(vec![
-                    Span::styled("✓ ", styles::success()),
+                    Span::styled(format!("{} ", icons::active().check), styles::success()),
                     Span::styled("Completed successfully", styles::success()),
                 ])
             } else if let Some(Err(_)) =
                 model.verify_result.as_ref().or(model.write_result.as_ref())
             {
                 Line::from(vec![
-                    Span::styled("✗ ", styles::danger()),
+                    Span::styled(format!("{} ", icons::active().cross), styles::danger()),
                     Span::styled("Completed with errors", styles::danger()),
                 ])
             } else {
@@ -157,6 +206,34 @@ pub fn screen_to_step_index(screen: &Screen) -> usize {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::app::state::{Job, WriteMode};
+    use crate::domain::{DevicePath, IsoPath};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_status_line_shows_queue_position() {
+        let mut model = Model::default();
+        let job = Job {
+            iso: IsoPath::from(PathBuf::from("/a.iso")),
+            device: DevicePath::from(PathBuf::from("/dev/sdb")),
+            write_mode: WriteMode::Raw,
+            multiboot_isos: Vec::new(),
+            verify: false,
+            persistence: None,
+            eject: false,
+            result: None,
+        };
+        model.queue.jobs = vec![job.clone(), job];
+        model.queue.current = Some(1);
+        model.queue.awaiting_device = true;
+
+        let text: String = build_status_line(&model)
+            .spans
+            .iter()
+            .map(|s| s.content.to_string())
+            .collect();
+        assert!(text.contains("Queue 2/2 waiting for device"));
+    }
 
     #[test]
     fn test_screen_to_step_index() {
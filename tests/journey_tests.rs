@@ -0,0 +1,208 @@
+#![allow(clippy::field_reassign_with_default)]
+
+use ferric::app::cmd::spawn_all;
+use ferric::app::effects::{Effects, fake};
+use ferric::app::msg::{Cmd, Msg};
+use ferric::app::state::{Device, DeviceAccess, IsoMeta, Model, Screen};
+use ferric::app::update::update;
+use ferric::domain::{ByteSize, DevicePath, IsoPath, paths::DirPath};
+use ferric::event::Event;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn make_test_iso(path: &str, size: u64) -> IsoMeta {
+    IsoMeta {
+        path: IsoPath::from(PathBuf::from(path)),
+        size: ByteSize::from(size),
+        modified: None,
+        distro: Default::default(),
+        hybrid: true,
+    }
+}
+
+fn make_test_device(name: &str, path: &str) -> Device {
+    Device {
+        name: name.to_string(),
+        path: DevicePath::from(PathBuf::from(path)),
+        size: ByteSize::from(16_000_000_000),
+        model: Some("Test USB Drive".to_string()),
+        serial: None,
+        tran: Some("usb".to_string()),
+        removable: true,
+        hotplug: true,
+        ro: false,
+        mounted: false,
+        labels: vec![],
+        access: DeviceAccess::FULL,
+        udisks: None,
+    }
+}
+
+/// Run `cmds` through `effects` and feed what comes back into `update`
+/// until nothing more arrives. Reports are left alone: they go to the
+/// real state directory.
+async fn settle(mut model: Model, cmds: Vec<Cmd>, effects: &Effects) -> Model {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    spawn_all(cmds, effects, tx.clone());
+    while let Ok(Some(event)) = tokio::time::timeout(Duration::from_millis(300), rx.recv()).await {
+        let Event::App(msg) = event else { continue };
+        let (next, cmds) = update(model, msg);
+        model = next;
+        let cmds = cmds
+            .into_iter()
+            .filter(|cmd| !matches!(cmd, Cmd::ExportReport { .. }))
+            .collect();
+        spawn_all(cmds, effects, tx.clone());
+    }
+    model
+}
+
+#[tokio::test]
+async fn test_write_verify_and_eject_through_fakes() {
+    let devices = Arc::new(fake::Devices::new(vec![]));
+    let mounter = Arc::new(fake::Mounter::default());
+    let effects = Effects {
+        devices: devices.clone(),
+        mounter: mounter.clone(),
+        writer: Arc::new(fake::Writer::default()),
+        scanner: Arc::new(fake::Scanner(vec![make_test_iso(
+            "/isos/alpine.iso",
+            1024 * 1024,
+        )])),
+        history: Arc::new(fake::History::default()),
+        builder: Arc::new(fake::Builder::default()),
+        privileged: Arc::new(fake::Privileged::default()),
+    };
+
+    let mut model = Model::default();
+    model.screen = Screen::IsoSearch;
+    let scan = Cmd::ScanIso {
+        options: Default::default(),
+    };
+    let model = settle(model, vec![scan], &effects).await;
+    assert_eq!(model.iso_results.len(), 1);
+
+    // The stick shows up between refreshes
+    devices.set(vec![make_test_device("sdb", "/dev/sdb")]);
+    let (model, cmds) = update(model, Msg::IsoConfirmSelect);
+    let mut model = settle(model, cmds, &effects).await;
+    assert_eq!(model.devices.len(), 1);
+
+    let (next, _) = update(model, Msg::DeviceConfirmSelect);
+    model = next;
+    assert!(matches!(model.screen, Screen::Confirm));
+    model.verify_after_write = true;
+    model.eject_after_write = true;
+    model.confirm_input = model.safety.confirm_word.clone();
+    let (model, cmds) = update(
+        model,
+        Msg::Key(ratatui::crossterm::event::KeyEvent::from(
+            ratatui::crossterm::event::KeyCode::Enter,
+        )),
+    );
+    assert!(matches!(cmds.as_slice(), [Cmd::Write { .. }]));
+    let model = settle(model, cmds, &effects).await;
+
+    assert!(matches!(model.screen, Screen::Done));
+    assert_eq!(model.write_result, Some(Ok(())));
+    assert_eq!(model.verify_result, Some(Ok(())));
+    assert_eq!(mounter.calls(), vec!["eject /dev/sdb".to_string()]);
+}
+
+#[tokio::test]
+async fn test_failed_write_never_verifies() {
+    let effects = Effects {
+        writer: Arc::new(fake::Writer {
+            write: Err("Write error: No space left on device".to_string()),
+            ..Default::default()
+        }),
+        history: Arc::new(fake::History::default()),
+        ..Effects::system(false)
+    };
+    let mut model = Model::default();
+    model.screen = Screen::Writing;
+    model.verify_after_write = true;
+    model.iso_chosen = Some(IsoPath::from(PathBuf::from("/isos/alpine.iso")));
+    model.device_chosen = Some(DevicePath::from(PathBuf::from("/dev/sdb")));
+    let write = Cmd::Write {
+        iso: IsoPath::from(PathBuf::from("/isos/alpine.iso")),
+        device: DevicePath::from(PathBuf::from("/dev/sdb")),
+        buffer_size: 4096,
+    };
+    let model = settle(model, vec![write], &effects).await;
+
+    assert!(matches!(model.write_result, Some(Err(_))));
+    assert!(!model.verifying);
+    assert_eq!(model.verify_result, None);
+}
+
+fn snapshot(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(format!("{}.txt", name))
+}
+
+#[tokio::test]
+async fn test_search_select_confirm_write_done_screens() {
+    use ferric::app::headless::{Headless, key, type_text};
+    use ratatui::crossterm::event::KeyCode;
+
+    let history = Arc::new(fake::History::default());
+    let effects = Effects {
+        devices: Arc::new(fake::Devices::new(vec![make_test_device(
+            "sdb", "/dev/sdb",
+        )])),
+        mounter: Arc::new(fake::Mounter::default()),
+        writer: Arc::new(fake::Writer::default()),
+        scanner: Arc::new(fake::Scanner(vec![
+            make_test_iso("/isos/alpine-3.20.iso", 200 * 1024 * 1024),
+            make_test_iso("/isos/debian-12.iso", 600 * 1024 * 1024),
+        ])),
+        history: history.clone(),
+        builder: Arc::new(fake::Builder::default()),
+        privileged: Arc::new(fake::Privileged::default()),
+    };
+    let mut model = Model::default();
+    model.scan.roots = vec![DirPath::from(PathBuf::from("/isos"))];
+    let trace = std::env::temp_dir().join(format!("ferric-journey-{}.trace", std::process::id()));
+    let mut app = Headless::new(model.clone(), effects, 100, 30)
+        .record_to(&trace, false)
+        .unwrap();
+
+    app.send(Event::Tick).await;
+    app.drive(type_text("alp")).await;
+    app.assert_screen(snapshot("journey_1_search"));
+
+    app.send(key(KeyCode::Enter)).await;
+    app.send(Event::Tick).await;
+    assert!(matches!(app.model().screen, Screen::DeviceSelect));
+    app.assert_screen(snapshot("journey_2_device"));
+
+    app.send(key(KeyCode::Enter)).await;
+    app.drive(type_text("YES")).await;
+    assert!(matches!(app.model().screen, Screen::Confirm));
+    app.assert_screen(snapshot("journey_3_confirm"));
+
+    app.send(key(KeyCode::Enter)).await;
+    assert!(matches!(app.model().screen, Screen::Done));
+    app.assert_screen(snapshot("journey_4_done"));
+    assert_eq!(history.entries().len(), 1);
+
+    app.send(key(KeyCode::Char('q'))).await;
+    assert!(!app.running());
+    drop(app);
+
+    // The trace alone brings a fresh model to the same place
+    let mut replayed = model;
+    for (_, msg) in ferric::app::trace::load(&trace).unwrap() {
+        replayed = update(replayed, msg).0;
+    }
+    assert!(matches!(replayed.screen, Screen::Done));
+    assert_eq!(replayed.write_result, Some(Ok(())));
+    assert_eq!(
+        replayed.iso_chosen,
+        Some(IsoPath::from(PathBuf::from("/isos/alpine-3.20.iso")))
+    );
+    let _ = std::fs::remove_file(&trace);
+}
@@ -1,7 +1,13 @@
+#![allow(clippy::field_reassign_with_default)]
+
 use ferric::app::msg::{Cmd, Msg};
-use ferric::app::state::{Device, IsoMeta, Model, Screen};
+use ferric::app::state::{
+    Device, DeviceAccess, Elevator, IsoMeta, Job, JobQueue, Model, PersistenceKind, Screen,
+    WindowsOptions, WriteMode, WriteRoute,
+};
 use ferric::app::update::update;
-use ferric::domain::{ByteSize, DevicePath, IsoPath};
+use ferric::domain::iso_index::IndexChange;
+use ferric::domain::{ByteSize, DevicePath, Distro, IsoPath};
 use std::path::PathBuf;
 
 fn make_test_iso(path: &str, size: u64) -> IsoMeta {
@@ -9,6 +15,8 @@ fn make_test_iso(path: &str, size: u64) -> IsoMeta {
         path: IsoPath::from(PathBuf::from(path)),
         size: ByteSize::from(size),
         modified: None,
+        distro: Default::default(),
+        hybrid: true,
     }
 }
 
@@ -25,6 +33,8 @@ fn make_test_device(name: &str, path: &str) -> Device {
         ro: false,
         mounted: false,
         labels: vec![],
+        access: DeviceAccess::FULL,
+        udisks: None,
     }
 }
 
@@ -78,30 +88,56 @@ fn test_device_selection() {
 fn test_iso_query_change() {
     let mut model = Model::default();
     model.screen = Screen::IsoSearch;
-    model.iso_results = vec![
-        make_test_iso("/home/user/test1.iso", 1_000_000),
-        make_test_iso("/home/user/test2.iso", 2_000_000),
+    model.iso_index = vec![
+        make_test_iso("/home/user/ubuntu-24.04.iso", 1_000_000),
+        make_test_iso("/home/user/debian-12.iso", 2_000_000),
     ];
+    model.iso_indexed = true;
+    model.iso_results = model.iso_index.clone();
     model.iso_selected = 1;
 
-    let (mut new_model, cmds) = update(model, Msg::IsoQueryChanged("ubuntu".to_string()));
+    // Filtering happens against the index, without rescanning
+    let (model, cmds) = update(model, Msg::IsoQueryChanged("ubuntu".to_string()));
+    assert_eq!(model.iso_query, "ubuntu");
+    assert_eq!(model.iso_selected, 0);
+    assert!(!model.iso_searching);
+    assert!(cmds.is_empty());
+    assert_eq!(model.iso_results.len(), 1);
+
+    let (model, cmds) = update(model, Msg::Tick);
+    assert!(cmds.is_empty());
 
-    assert_eq!(new_model.iso_query, "ubuntu");
-    assert_eq!(new_model.iso_selected, 0);
-    assert!(!new_model.iso_searching);
+    // A watch event adds a matching image to the visible list
+    let change = IndexChange {
+        added: vec![make_test_iso("/srv/ubuntu-22.04.iso", 3_000_000)],
+        removed: vec![IsoPath::from(PathBuf::from("/home/user/debian-12.iso"))],
+    };
+    let (model, _) = update(model, Msg::IsoIndexChanged(change));
+    assert_eq!(model.iso_index.len(), 2);
+    assert_eq!(model.iso_results.len(), 2);
+}
 
-    assert_eq!(cmds.len(), 0);
+#[test]
+fn test_iso_index_is_built_once() {
+    let mut model = Model::default();
+    model.screen = Screen::IsoSearch;
+    let (model, cmds) = update(model, Msg::Tick);
+    assert!(model.iso_searching);
+    assert!(matches!(cmds.as_slice(), [Cmd::ScanIso { .. }]));
 
-    new_model.iso_debounce_until =
-        Some(std::time::Instant::now() - std::time::Duration::from_millis(1));
-    let (new_model, cmds) = update(new_model, Msg::Tick);
+    // Partial batches fill the list while the walk is still running
+    let (model, _) = update(
+        model,
+        Msg::IsoResultsPartial(vec![make_test_iso("/srv/a.iso", 1_000_000)]),
+    );
+    assert_eq!(model.iso_results.len(), 1);
+    assert!(model.iso_searching);
 
-    assert!(new_model.iso_searching);
-    assert_eq!(cmds.len(), 1);
-    match &cmds[0] {
-        Cmd::ScanIso { query } => assert_eq!(query, "ubuntu"),
-        _ => panic!("Expected ScanIso command"),
-    }
+    // Nothing found is still a finished index: no rescan on the next tick
+    let (model, _) = update(model, Msg::IsoResults(Vec::new()));
+    assert!(model.iso_indexed);
+    let (_, cmds) = update(model, Msg::Tick);
+    assert!(cmds.is_empty());
 }
 
 #[test]
@@ -165,6 +201,21 @@ fn test_write_lifecycle_with_verify() {
     }
 }
 
+#[test]
+fn test_verify_without_selections_fails_instead_of_hanging() {
+    let mut model = Model::default();
+    model.screen = Screen::Writing;
+    model.verify_after_write = true;
+
+    let (model, _) = update(model, Msg::WriteStarted { total: 1_000_000 });
+    let (model, cmds) = update(model, Msg::WriteFinished(Ok(())));
+
+    assert!(!cmds.iter().any(|c| matches!(c, Cmd::Verify { .. })));
+    assert!(!model.verifying);
+    assert!(matches!(model.verify_result, Some(Err(_))));
+    assert!(matches!(model.screen, Screen::Done));
+}
+
 #[test]
 fn test_iso_move_selection() {
     let mut model = Model::default();
@@ -204,12 +255,8 @@ fn test_predictability() {
     assert_eq!(result1.iso_searching, result2.iso_searching);
     assert_eq!(cmds1.len(), cmds2.len());
 
-    let mut m1 = result1.clone();
-    let mut m2 = result2.clone();
-    m1.iso_debounce_until = Some(std::time::Instant::now() - std::time::Duration::from_millis(1));
-    m2.iso_debounce_until = Some(std::time::Instant::now() - std::time::Duration::from_millis(1));
-    let (_r1, c1) = update(m1, Msg::Tick);
-    let (_r2, c2) = update(m2, Msg::Tick);
+    let (_r1, c1) = update(result1, Msg::Tick);
+    let (_r2, c2) = update(result2, Msg::Tick);
     assert_eq!(c1.len(), c2.len());
 }
 
@@ -344,3 +391,763 @@ fn test_verify_finished_transitions_to_done() {
     assert!(matches!(new_model.screen, Screen::Done));
     assert_eq!(cmds.len(), 0);
 }
+
+#[test]
+fn test_windows_iso_uses_installer_mode() {
+    let mut iso = make_test_iso("/isos/Win11_24H2_English_x64.iso", 5_000_000_000);
+    iso.distro.distro = Distro::Windows;
+    iso.hybrid = false;
+    let mut model = Model::default();
+    model.iso_results = vec![iso];
+
+    let (mut model, _) = update(model, Msg::IsoConfirmSelect);
+    assert_eq!(
+        model.write_mode,
+        WriteMode::Windows(WindowsOptions::default())
+    );
+
+    model.screen = Screen::Confirm;
+    model.device_chosen = Some(DevicePath::from(PathBuf::from("/dev/sdb")));
+    model.confirm_input = "YES".to_string();
+    let (model, cmds) = update(
+        model,
+        Msg::Key(ratatui::crossterm::event::KeyEvent {
+            code: ratatui::crossterm::event::KeyCode::Enter,
+            modifiers: ratatui::crossterm::event::KeyModifiers::empty(),
+            kind: ratatui::crossterm::event::KeyEventKind::Press,
+            state: ratatui::crossterm::event::KeyEventState::empty(),
+        }),
+    );
+    assert!(matches!(cmds[0], Cmd::WriteWindows { .. }));
+
+    // Verification is skipped: the stick no longer mirrors the image
+    let (model, cmds) = update(model, Msg::WriteFinished(Ok(())));
+    assert!(matches!(cmds.as_slice(), [Cmd::RecordHistory(_)]));
+    assert!(matches!(model.screen, Screen::Done));
+}
+
+#[test]
+fn test_marked_isos_build_multiboot_drive() {
+    let ctrl_t = ratatui::crossterm::event::KeyEvent {
+        code: ratatui::crossterm::event::KeyCode::Char('t'),
+        modifiers: ratatui::crossterm::event::KeyModifiers::CONTROL,
+        kind: ratatui::crossterm::event::KeyEventKind::Press,
+        state: ratatui::crossterm::event::KeyEventState::empty(),
+    };
+    let mut model = Model::default();
+    model.iso_results = vec![
+        make_test_iso("/isos/debian.iso", 600_000_000),
+        make_test_iso("/isos/arch.iso", 900_000_000),
+    ];
+
+    let (mut model, _) = update(model, Msg::Key(ctrl_t));
+    model.iso_selected = 1;
+    let (model, _) = update(model, Msg::Key(ctrl_t));
+    assert_eq!(model.iso_marked.len(), 2);
+    assert!(model.iso_query.is_empty());
+
+    // Toggling again unmarks
+    let (model, _) = update(model, Msg::Key(ctrl_t));
+    assert_eq!(model.iso_marked.len(), 1);
+    let (mut model, _) = update(model, Msg::Key(ctrl_t));
+
+    model.iso_selected = 0;
+    let (mut model, _) = update(model, Msg::IsoConfirmSelect);
+    assert_eq!(model.write_mode, WriteMode::Multiboot);
+
+    model.screen = Screen::Confirm;
+    model.device_chosen = Some(DevicePath::from(PathBuf::from("/dev/sdb")));
+    model.confirm_input = "YES".to_string();
+    let (_, cmds) = update(
+        model,
+        Msg::Key(ratatui::crossterm::event::KeyEvent {
+            code: ratatui::crossterm::event::KeyCode::Enter,
+            modifiers: ratatui::crossterm::event::KeyModifiers::empty(),
+            kind: ratatui::crossterm::event::KeyEventKind::Press,
+            state: ratatui::crossterm::event::KeyEventState::empty(),
+        }),
+    );
+    match &cmds[0] {
+        Cmd::WriteMultiboot { isos, .. } => assert_eq!(isos.len(), 2),
+        other => panic!("unexpected command {:?}", other),
+    }
+}
+
+#[test]
+fn test_persistence_runs_after_write_when_enabled() {
+    let mut iso = make_test_iso("/isos/ubuntu-24.04-desktop-amd64.iso", 6_000_000_000);
+    iso.distro.distro = Distro::Ubuntu;
+    let mut model = Model::default();
+    model.iso_results = vec![iso];
+    model.devices = vec![make_test_device("sdb", "/dev/sdb")];
+
+    let (model, _) = update(model, Msg::IsoConfirmSelect);
+    let (model, _) = update(model, Msg::DeviceConfirmSelect);
+    assert_eq!(model.persistence_offer, Some(PersistenceKind::Casper));
+    assert!(!model.persistence_enabled);
+
+    let (mut model, _) = update(
+        model,
+        Msg::Key(ratatui::crossterm::event::KeyEvent {
+            code: ratatui::crossterm::event::KeyCode::Char('p'),
+            modifiers: ratatui::crossterm::event::KeyModifiers::CONTROL,
+            kind: ratatui::crossterm::event::KeyEventKind::Press,
+            state: ratatui::crossterm::event::KeyEventState::empty(),
+        }),
+    );
+    assert!(model.persistence_enabled);
+    assert!(model.confirm_input.is_empty());
+
+    model.screen = Screen::Writing;
+    model.writing_total = 6_000_000_000;
+    let (model, cmds) = update(model, Msg::WriteFinished(Ok(())));
+    assert!(model.persisting);
+    assert!(matches!(
+        cmds[0],
+        Cmd::CreatePersistence {
+            image_size: 6_000_000_000,
+            ..
+        }
+    ));
+
+    let (model, _) = update(model, Msg::PersistenceFinished(Ok(())));
+    assert!(!model.persisting);
+    assert!(matches!(model.screen, Screen::Done));
+}
+
+#[test]
+fn test_parallel_write_summarizes_per_device() {
+    let mut model = Model::default();
+    model.devices = vec![
+        make_test_device("sdb", "/dev/sdb"),
+        make_test_device("sdc", "/dev/sdc"),
+    ];
+    model.screen = Screen::DeviceSelect;
+    model.iso_chosen = Some(IsoPath::from(PathBuf::from("/test.iso")));
+    let space = ratatui::crossterm::event::KeyEvent {
+        code: ratatui::crossterm::event::KeyCode::Char(' '),
+        modifiers: ratatui::crossterm::event::KeyModifiers::empty(),
+        kind: ratatui::crossterm::event::KeyEventKind::Press,
+        state: ratatui::crossterm::event::KeyEventState::empty(),
+    };
+    let (mut model, _) = update(model, Msg::Key(space));
+    model.device_selected = 1;
+    let (model, _) = update(model, Msg::Key(space));
+    assert_eq!(model.device_marked.len(), 2);
+
+    let (mut model, _) = update(model, Msg::DeviceConfirmSelect);
+    model.confirm_input = "YES".to_string();
+    model.verify_after_write = true;
+    let (model, cmds) = update(
+        model,
+        Msg::Key(ratatui::crossterm::event::KeyEvent {
+            code: ratatui::crossterm::event::KeyCode::Enter,
+            modifiers: ratatui::crossterm::event::KeyModifiers::empty(),
+            kind: ratatui::crossterm::event::KeyEventKind::Press,
+            state: ratatui::crossterm::event::KeyEventState::empty(),
+        }),
+    );
+    assert!(
+        matches!(&cmds[0], Cmd::WriteBatch { devices, verify: true, .. } if devices.len() == 2)
+    );
+    assert_eq!(model.targets.len(), 2);
+
+    let sdb = DevicePath::from(PathBuf::from("/dev/sdb"));
+    let sdc = DevicePath::from(PathBuf::from("/dev/sdc"));
+    let (model, _) = update(
+        model,
+        Msg::TargetWriteFinished {
+            device: sdc,
+            result: Err("Write error".to_string()),
+        },
+    );
+    let (model, _) = update(
+        model,
+        Msg::TargetWriteFinished {
+            device: sdb.clone(),
+            result: Ok(()),
+        },
+    );
+    // sdb still has to verify
+    assert!(matches!(model.screen, Screen::Writing));
+
+    let (model, _) = update(
+        model,
+        Msg::TargetVerifyFinished {
+            device: sdb,
+            result: Ok(()),
+        },
+    );
+    assert!(matches!(model.screen, Screen::Done));
+    assert_eq!(
+        model.write_result,
+        Some(Err("1 of 2 devices failed".to_string()))
+    );
+}
+
+#[test]
+fn test_job_queue_runs_sequentially_with_device_prompt() {
+    let enter = ratatui::crossterm::event::KeyEvent {
+        code: ratatui::crossterm::event::KeyCode::Enter,
+        modifiers: ratatui::crossterm::event::KeyModifiers::empty(),
+        kind: ratatui::crossterm::event::KeyEventKind::Press,
+        state: ratatui::crossterm::event::KeyEventState::empty(),
+    };
+    let mut model = Model::default();
+    model.screen = Screen::Confirm;
+    model.iso_chosen = Some(IsoPath::from(PathBuf::from("/installer.iso")));
+    model.device_chosen = Some(DevicePath::from(PathBuf::from("/dev/sdb")));
+    let (mut model, _) = update(
+        model,
+        Msg::Key(ratatui::crossterm::event::KeyEvent {
+            code: ratatui::crossterm::event::KeyCode::Char('a'),
+            modifiers: ratatui::crossterm::event::KeyModifiers::CONTROL,
+            kind: ratatui::crossterm::event::KeyEventKind::Press,
+            state: ratatui::crossterm::event::KeyEventState::empty(),
+        }),
+    );
+    assert_eq!(model.queue.jobs.len(), 1);
+    assert!(matches!(model.screen, Screen::IsoSearch));
+    assert!(model.iso_chosen.is_none());
+
+    model.screen = Screen::Confirm;
+    model.iso_chosen = Some(IsoPath::from(PathBuf::from("/rescue.iso")));
+    model.device_chosen = Some(DevicePath::from(PathBuf::from("/dev/sdb")));
+    model.confirm_input = "YES".to_string();
+    let (model, cmds) = update(model, Msg::Key(enter));
+    assert_eq!(model.queue.jobs.len(), 2);
+    assert_eq!(model.queue.current, Some(0));
+    assert!(matches!(&cmds[0], Cmd::Write { iso, .. } if iso.to_string() == "/installer.iso"));
+
+    let (model, cmds) = update(model, Msg::WriteFinished(Ok(())));
+    match cmds.as_slice() {
+        [Cmd::RecordHistory(entries)] => {
+            assert_eq!(entries[0].image.to_string(), "/installer.iso");
+            assert_eq!(entries[0].result, Ok(()));
+        }
+        _ => panic!("Expected RecordHistory"),
+    }
+    assert!(matches!(model.screen, Screen::Done));
+    assert!(model.queue.awaiting_device);
+    assert_eq!(model.queue.jobs[0].result, Some(Ok(())));
+
+    let (model, cmds) = update(model, Msg::Key(enter));
+    assert!(matches!(cmds[0], Cmd::RefreshDevices));
+    let (model, cmds) = update(model, Msg::DevicesRefreshed(vec![]));
+    assert!(cmds.is_empty());
+    assert!(model.queue.device_missing);
+
+    let (mut model, cmds) = update(
+        model,
+        Msg::DevicesRefreshed(vec![make_test_device("sdb", "/dev/sdb")]),
+    );
+    // A new stick is confirmed like the first, never written straight away
+    assert!(cmds.is_empty());
+    assert!(matches!(model.screen, Screen::Confirm));
+    assert_eq!(
+        model.iso_chosen.as_ref().unwrap().to_string(),
+        "/rescue.iso"
+    );
+    model.confirm_input = "YES".to_string();
+    let (model, cmds) = update(model, Msg::Key(enter));
+    assert!(matches!(&cmds[0], Cmd::Write { iso, .. } if iso.to_string() == "/rescue.iso"));
+    assert_eq!(model.queue.position(), Some((2, 2)));
+
+    let (model, _) = update(model, Msg::WriteFinished(Err("boom".to_string())));
+    assert!(!model.queue.is_running());
+    assert_eq!(model.queue.jobs[1].result, Some(Err("boom".to_string())));
+}
+
+#[test]
+fn test_job_queue_waits_for_the_written_stick_to_leave() {
+    let mut written = make_test_device("sdb", "/dev/sdb");
+    written.serial = Some("OLD".to_string());
+    let job = Job {
+        iso: IsoPath::from(PathBuf::from("/a.iso")),
+        device: DevicePath::from(PathBuf::from("/dev/sdb")),
+        write_mode: WriteMode::Raw,
+        multiboot_isos: Vec::new(),
+        verify: false,
+        persistence: None,
+        eject: false,
+        result: None,
+    };
+    let model = Model {
+        screen: Screen::Writing,
+        iso_chosen: Some(job.iso.clone()),
+        device_chosen: Some(job.device.clone()),
+        devices: vec![written.clone()],
+        queue: JobQueue {
+            jobs: vec![job.clone(), job],
+            current: Some(0),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (model, _) = update(model, Msg::WriteFinished(Ok(())));
+    assert_eq!(model.queue.written_serial.as_deref(), Some("OLD"));
+
+    // The stick just written is still in the slot
+    let (model, _) = update(model, Msg::DevicesRefreshed(vec![written]));
+    assert!(matches!(model.screen, Screen::Done));
+    assert!(!model.queue.written_removed);
+
+    // Swapped between two refreshes: a different serial on the same path
+    let mut fresh = make_test_device("sdb", "/dev/sdb");
+    fresh.serial = Some("NEW".to_string());
+    let (model, cmds) = update(model, Msg::DevicesRefreshed(vec![fresh]));
+    assert!(cmds.is_empty());
+    assert!(matches!(model.screen, Screen::Confirm));
+}
+
+#[test]
+fn test_queueing_with_marked_devices_adds_a_job_for_each() {
+    let model = Model {
+        screen: Screen::Confirm,
+        iso_chosen: Some(IsoPath::from(PathBuf::from("/a.iso"))),
+        device_chosen: Some(DevicePath::from(PathBuf::from("/dev/sdb"))),
+        device_marked: vec![
+            DevicePath::from(PathBuf::from("/dev/sdb")),
+            DevicePath::from(PathBuf::from("/dev/sdc")),
+        ],
+        ..Default::default()
+    };
+    let (model, _) = update(
+        model,
+        Msg::Key(ratatui::crossterm::event::KeyEvent::new(
+            ratatui::crossterm::event::KeyCode::Char('a'),
+            ratatui::crossterm::event::KeyModifiers::CONTROL,
+        )),
+    );
+    let devices: Vec<String> = model
+        .queue
+        .jobs
+        .iter()
+        .map(|j| j.device.to_string())
+        .collect();
+    assert_eq!(devices, ["/dev/sdb", "/dev/sdc"]);
+    assert!(model.device_marked.is_empty());
+}
+
+#[test]
+fn test_config_preferences_reach_the_model() {
+    use ferric::app::keymap::{Action, KeyBinding};
+    use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    let mut config = ferric::config::Config::default();
+    config
+        .apply_file(
+            "verify = on\nbuffer_size = 1M\nkey.mark_iso = f2\n\
+             confirm_word = WIPE\nremovable_only = true\n",
+            "config",
+        )
+        .unwrap();
+    let mut model = Model::from_config(&config);
+    assert!(model.verify_after_write);
+    assert_eq!(
+        model.keymap.binding(Action::MarkIso),
+        KeyBinding::parse("f2").unwrap()
+    );
+
+    model.iso_results = vec![make_test_iso("/ubuntu.iso", 1_000_000)];
+    let (model, _) = update(
+        model,
+        Msg::Key(KeyEvent::new(KeyCode::F(2), KeyModifiers::NONE)),
+    );
+    assert_eq!(model.iso_marked.len(), 1);
+    let (mut model, _) = update(
+        model,
+        Msg::Key(KeyEvent::new(KeyCode::Char('t'), KeyModifiers::CONTROL)),
+    );
+    assert_eq!(model.iso_marked.len(), 1);
+
+    let mut fixed = make_test_device("sda", "/dev/sda");
+    fixed.removable = false;
+    fixed.hotplug = false;
+    model.screen = Screen::DeviceSelect;
+    let (mut model, _) = update(
+        model,
+        Msg::DevicesRefreshed(vec![fixed, make_test_device("sdb", "/dev/sdb")]),
+    );
+    assert_eq!(model.devices.len(), 1);
+
+    model.screen = Screen::Confirm;
+    model.iso_marked.clear();
+    model.iso_chosen = Some(IsoPath::from(PathBuf::from("/ubuntu.iso")));
+    model.device_chosen = Some(DevicePath::from(PathBuf::from("/dev/sdb")));
+    for c in "wipes".chars() {
+        model = update(
+            model,
+            Msg::Key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)),
+        )
+        .0;
+    }
+    assert_eq!(model.confirm_input, "WIPE");
+    let (_, cmds) = update(
+        model,
+        Msg::Key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE)),
+    );
+    assert!(matches!(
+        cmds[0],
+        Cmd::Write {
+            buffer_size: 1_048_576,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn test_browse_picks_an_image_outside_the_roots() {
+    use ferric::domain::browse::{DirEntry, Opened};
+    use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    let key = |code| Msg::Key(KeyEvent::new(code, KeyModifiers::NONE));
+    let model = Model::default();
+    let (model, cmds) = update(
+        model,
+        Msg::Key(KeyEvent::new(KeyCode::Char('o'), KeyModifiers::CONTROL)),
+    );
+    assert!(model.browser.is_some());
+    assert!(matches!(cmds.as_slice(), [Cmd::BrowseOpen { .. }]));
+
+    let entries = vec![DirEntry {
+        name: "alpine.iso".to_string(),
+        is_dir: false,
+        size: ByteSize::from(1_000_000),
+    }];
+    let (model, _) = update(
+        model,
+        Msg::BrowseOpened(Ok(Opened::Dir(PathBuf::from("/mnt/usb"), entries))),
+    );
+    // Typed characters edit the path input
+    let (model, _) = update(model, key(KeyCode::Char('q')));
+    assert_eq!(model.browser.as_ref().unwrap().input, "q");
+    let (model, _) = update(model, key(KeyCode::Backspace));
+    let (model, cmds) = update(model, key(KeyCode::Enter));
+    match cmds.as_slice() {
+        [Cmd::BrowseOpen { path }] => assert_eq!(path, &PathBuf::from("/mnt/usb/alpine.iso")),
+        _ => panic!("Expected BrowseOpen"),
+    }
+
+    let picked = make_test_iso("/mnt/usb/alpine.iso", 1_000_000);
+    let (model, cmds) = update(model, Msg::BrowseOpened(Ok(Opened::Image(picked))));
+    assert!(model.browser.is_none());
+    assert!(matches!(model.screen, Screen::DeviceSelect));
+    assert_eq!(
+        model.chosen_iso_meta().map(|m| m.size),
+        Some(ByteSize::from(1_000_000))
+    );
+    assert!(matches!(cmds.as_slice(), [Cmd::RefreshDevices]));
+}
+
+#[test]
+fn test_recent_images_lead_the_unfiltered_list() {
+    use ferric::domain::history::HistoryEntry;
+    use std::time::{Duration, SystemTime};
+
+    let model = Model::default();
+    let mut old = make_test_iso("/isos/old.iso", 1_000_000);
+    old.modified = Some(SystemTime::UNIX_EPOCH);
+    let mut new = make_test_iso("/isos/new.iso", 1_000_000);
+    new.modified = Some(SystemTime::now());
+    let (model, _) = update(model, Msg::IsoResults(vec![old.clone(), new]));
+    assert_eq!(model.iso_results[0].path.to_string(), "/isos/new.iso");
+
+    let entry = HistoryEntry {
+        finished: SystemTime::now(),
+        image: old.path.clone(),
+        image_size: 1_000_000,
+        image_modified: old.modified,
+        sha256: String::new(),
+        device: DevicePath::from(PathBuf::from("/dev/sdb")),
+        model: None,
+        serial: Some("4C530001".to_string()),
+        result: Ok(()),
+        duration: Duration::from_secs(10),
+        bytes: 1_000_000,
+    };
+    let (model, _) = update(model, Msg::HistoryRecorded(Ok(vec![entry])));
+    assert_eq!(model.iso_results[0].path.to_string(), "/isos/old.iso");
+
+    // A query ranks by match alone
+    let (model, _) = update(model, Msg::IsoQueryChanged("iso".to_string()));
+    assert_eq!(model.iso_results[0].path.to_string(), "/isos/new.iso");
+}
+
+#[test]
+fn test_finished_write_builds_an_exportable_report() {
+    use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyEventState, KeyModifiers};
+
+    let mut model = Model::default();
+    model.iso_index = vec![make_test_iso("/isos/alpine.iso", 1_000_000)];
+    model.iso_chosen = Some(IsoPath::from(PathBuf::from("/isos/alpine.iso")));
+    model.devices = vec![make_test_device("sdb", "/dev/sdb")];
+    model.device_chosen = Some(DevicePath::from(PathBuf::from("/dev/sdb")));
+    model.screen = Screen::Writing;
+    model.report_dir = Some(PathBuf::from("/srv/reports"));
+
+    let (model, _) = update(model, Msg::WriteStarted { total: 1_000_000 });
+    let (model, _) = update(
+        model,
+        Msg::WriteWarning("/dev/sdb: partprobe failed".to_string()),
+    );
+    let (model, cmds) = update(model, Msg::WriteFinished(Ok(())));
+    assert!(matches!(cmds.as_slice(), [Cmd::RecordHistory(_)]));
+    let report = model.report.clone().expect("a report for the finished job");
+    assert_eq!(report.targets[0].transport.as_deref(), Some("usb"));
+    assert_eq!(report.warnings, vec!["/dev/sdb: partprobe failed"]);
+    assert_eq!(report.image_size, 1_000_000);
+
+    // The history step hashes the image; the report picks the hash up
+    let Cmd::RecordHistory(mut entries) = cmds.into_iter().next().unwrap() else {
+        unreachable!()
+    };
+    entries[0].sha256 = "ab".repeat(32);
+    let (model, cmds) = update(model, Msg::HistoryRecorded(Ok(entries)));
+    assert!(cmds.is_empty());
+    assert_eq!(model.report.as_ref().unwrap().sha256, "ab".repeat(32));
+
+    let e = KeyEvent {
+        code: KeyCode::Char('e'),
+        modifiers: KeyModifiers::empty(),
+        kind: KeyEventKind::Press,
+        state: KeyEventState::empty(),
+    };
+    let (model, cmds) = update(model, Msg::Key(e));
+    match cmds.as_slice() {
+        [Cmd::ExportReport { report, dir }] => {
+            assert_eq!(report.sha256, "ab".repeat(32));
+            assert_eq!(
+                dir.as_deref(),
+                Some(PathBuf::from("/srv/reports").as_path())
+            );
+        }
+        other => panic!("expected an export, got {:?}", other),
+    }
+    let saved = vec![PathBuf::from("/srv/reports/x.txt")];
+    let (model, _) = update(model, Msg::ReportExported(Ok(saved.clone())));
+    assert_eq!(model.report_exported, Some(Ok(saved)));
+}
+
+#[test]
+fn test_eject_after_write_removes_the_device() {
+    let mut model = Model::default();
+    model.iso_chosen = Some(IsoPath::from(PathBuf::from("/isos/alpine.iso")));
+    model.devices = vec![
+        make_test_device("sdb", "/dev/sdb"),
+        make_test_device("sdc", "/dev/sdc"),
+    ];
+    model.device_chosen = Some(DevicePath::from(PathBuf::from("/dev/sdb")));
+    model.screen = Screen::Writing;
+    model.eject_after_write = true;
+
+    // A failed write leaves the stick alone
+    let (mut model, cmds) = update(model.clone(), Msg::WriteFinished(Err("io".to_string())));
+    assert!(matches!(cmds.as_slice(), [Cmd::RecordHistory(_)]));
+    assert!(model.ejects.is_empty());
+
+    model.screen = Screen::Writing;
+    model.write_result = None;
+    let (model, cmds) = update(model, Msg::WriteFinished(Ok(())));
+    let sdb = DevicePath::from(PathBuf::from("/dev/sdb"));
+    match cmds.as_slice() {
+        [Cmd::RecordHistory(_), Cmd::Eject { devices }] => assert_eq!(devices, &vec![sdb.clone()]),
+        other => panic!("expected history then eject, got {:?}", other),
+    }
+    assert_eq!(model.ejects, vec![(sdb.clone(), None)]);
+
+    let (model, cmds) = update(
+        model,
+        Msg::Ejected {
+            device: sdb.clone(),
+            result: Ok(true),
+        },
+    );
+    assert!(matches!(cmds.as_slice(), [Cmd::RefreshDevices]));
+    assert_eq!(model.ejects, vec![(sdb, Some(Ok(true)))]);
+    assert_eq!(model.devices.len(), 1);
+    assert_eq!(model.devices[0].name, "sdc");
+}
+
+#[test]
+fn test_root_helper_takes_the_write_without_restarting() {
+    use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    let mut model = Model::default();
+    model.iso_chosen = Some(IsoPath::from(PathBuf::from("/isos/alpine.iso")));
+    model.devices = vec![make_test_device("sdb", "/dev/sdb")];
+    model.devices[0].access = DeviceAccess::default();
+    model.device_chosen = Some(DevicePath::from(PathBuf::from("/dev/sdb")));
+    model.screen = Screen::Confirm;
+    model.confirm_input = "YES".to_string();
+
+    let ctrl_s = KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL);
+    // The default model does not look at the host for sudo and friends
+    assert_eq!(model.elevator, None);
+    let (mut model, cmds) = update(model, Msg::Key(ctrl_s));
+    assert!(cmds.is_empty());
+    model.elevator = Some(Elevator::Doas);
+    let (model, cmds) = update(model, Msg::Key(ctrl_s));
+    assert!(matches!(
+        cmds.as_slice(),
+        [Cmd::StartHelper(Elevator::Doas)]
+    ));
+
+    // Selections survive elevation and the write goes to the helper
+    let (model, _) = update(model, Msg::HelperStarted(Ok(())));
+    assert!(model.uses_helper());
+    assert_eq!(model.confirm_input, "YES");
+    let (model, cmds) = update(model, Msg::Key(KeyEvent::from(KeyCode::Enter)));
+    assert!(matches!(
+        cmds.as_slice(),
+        [Cmd::Helper(ferric::helper::Request::Write { device, .. })] if device.to_string() == "/dev/sdb"
+    ));
+
+    let (model, _) = update(model, Msg::HelperExited);
+    assert!(!model.uses_helper());
+    assert!(matches!(&model.write_result, Some(Err(e)) if e.contains("helper")));
+}
+
+#[test]
+fn test_can_write_names_the_missing_access() {
+    let mut model = Model::default();
+    model.iso_chosen = Some(IsoPath::from(PathBuf::from("/isos/alpine.iso")));
+    assert!(model.can_write().is_err());
+    model.devices = vec![make_test_device("sdb", "/dev/sdb")];
+    model.device_chosen = Some(DevicePath::from(PathBuf::from("/dev/sdb")));
+    assert_eq!(model.can_write(), Ok(()));
+
+    // In the disk group, without CAP_SYS_ADMIN: the write goes ahead,
+    // short of re-reading the partition table
+    model.devices[0].access.admin = false;
+    assert_eq!(model.can_write(), Ok(()));
+    assert_eq!(model.write_route(), WriteRoute::Direct);
+    assert!(
+        model
+            .access_caveat()
+            .unwrap()
+            .contains("re-read its partition table")
+    );
+
+    // Partitions it cannot unmount do stand in the way
+    model.devices[0].mounted = true;
+    assert_eq!(
+        model.can_write(),
+        Err(
+            "can write /dev/sdb but cannot unmount its partitions (needs CAP_SYS_ADMIN)"
+                .to_string()
+        )
+    );
+    assert_eq!(model.access_caveat(), None);
+    model.helper = Some(Ok(()));
+    assert_eq!(model.can_write(), Ok(()));
+    assert!(model.uses_helper());
+
+    // The helper only takes single image writes; the rest waits on Confirm
+    model.persistence_enabled = true;
+    assert_eq!(model.write_route(), WriteRoute::Direct);
+    assert!(
+        matches!(model.can_write(), Err(e) if e.starts_with("persistence needs ferric itself run as root"))
+    );
+    model.screen = Screen::Confirm;
+    model.confirm_input = "YES".to_string();
+    let (mut model, cmds) = update(
+        model,
+        Msg::Key(ratatui::crossterm::event::KeyEvent::from(
+            ratatui::crossterm::event::KeyCode::Enter,
+        )),
+    );
+    assert!(cmds.is_empty());
+    assert!(matches!(model.screen, Screen::Confirm));
+
+    // Ejecting what the helper wrote goes through it too
+    model.persistence_enabled = false;
+    model.screen = Screen::Done;
+    model.write_result = Some(Ok(()));
+    let (_, cmds) = update(
+        model,
+        Msg::Key(ratatui::crossterm::event::KeyEvent::new(
+            ratatui::crossterm::event::KeyCode::Char('e'),
+            ratatui::crossterm::event::KeyModifiers::CONTROL,
+        )),
+    );
+    assert!(matches!(
+        cmds.as_slice(),
+        [Cmd::Helper(ferric::helper::Request::Eject { devices })] if devices.len() == 1
+    ));
+}
+
+#[test]
+fn test_udisks_opens_the_device_we_cannot() {
+    use ratatui::crossterm::event::{KeyCode, KeyEvent};
+    let mut model = Model::default();
+    model.iso_chosen = Some(IsoPath::from(PathBuf::from("/isos/alpine.iso")));
+    model.devices = vec![make_test_device("sdb", "/dev/sdb")];
+    model.devices[0].access = DeviceAccess::default();
+    model.device_chosen = Some(DevicePath::from(PathBuf::from("/dev/sdb")));
+    assert_eq!(model.write_route(), WriteRoute::Direct);
+    assert!(model.can_write().is_err());
+
+    model.devices[0].udisks = Some("/org/freedesktop/UDisks2/block_devices/sdb".to_string());
+    model.verify_after_write = true;
+    assert_eq!(model.write_route(), WriteRoute::Udisks);
+    assert_eq!(model.can_write(), Ok(()));
+    model.screen = Screen::Confirm;
+    model.confirm_input = "YES".to_string();
+    let (model, cmds) = update(model, Msg::Key(KeyEvent::from(KeyCode::Enter)));
+    assert!(matches!(
+        cmds.as_slice(),
+        [Cmd::Udisks(ferric::helper::Request::Write { .. })]
+    ));
+    let (mut model, cmds) = update(model, Msg::WriteFinished(Ok(())));
+    assert!(matches!(
+        cmds.as_slice(),
+        [Cmd::Udisks(ferric::helper::Request::Verify { .. })]
+    ));
+
+    // udisks only takes single raw writes
+    model.write_mode = WriteMode::Multiboot;
+    assert_eq!(model.write_route(), WriteRoute::Direct);
+}
+
+#[test]
+fn test_dry_run_writes_raw_images_only_and_keeps_no_history() {
+    use ratatui::crossterm::event::{KeyCode, KeyEvent};
+    let mut model = Model::default();
+    model.dry_run = true;
+    model.iso_chosen = Some(IsoPath::from(PathBuf::from("/isos/alpine.iso")));
+    model.devices = vec![make_test_device("ferric-sim", "/dev/ferric-sim")];
+    model.devices[0].access = DeviceAccess::default();
+    model.device_chosen = Some(DevicePath::from(PathBuf::from("/dev/ferric-sim")));
+    // No device is opened, so no access is missing
+    assert_eq!(model.access_missing(), None);
+    assert_eq!(model.write_route(), WriteRoute::Direct);
+    assert_eq!(model.can_write(), Ok(()));
+
+    model.write_mode = WriteMode::Multiboot;
+    assert!(model.simulation_gap().is_some());
+    assert!(model.can_write().is_err());
+    model.write_mode = WriteMode::Raw;
+
+    model.screen = Screen::Confirm;
+    model.confirm_input = "YES".to_string();
+    let (model, cmds) = update(model, Msg::Key(KeyEvent::from(KeyCode::Enter)));
+    assert!(matches!(cmds.as_slice(), [Cmd::Write { .. }]));
+    let mut model = model;
+    model.auto_report = true;
+    let (_, cmds) = update(model, Msg::WriteFinished(Ok(())));
+    assert!(
+        !cmds.iter().any(|c| matches!(c, Cmd::RecordHistory(_))),
+        "dry runs stay out of the history"
+    );
+    // With no history to wait on, the report goes out as the job ends
+    assert!(cmds.iter().any(|c| matches!(c, Cmd::ExportReport { .. })));
+}
+
+#[test]
+fn test_open_log_is_left_to_the_app() {
+    let mut model = Model::default();
+    model.screen = Screen::Writing;
+    let (model, cmds) = update(model, Msg::OpenLogRequested);
+    assert!(matches!(cmds.as_slice(), [Cmd::OpenLog]));
+    assert!(matches!(model.screen, Screen::Writing));
+}